@@ -0,0 +1,169 @@
+//! Players who stop playing without leaving.
+//!
+//! A player whose inputs stay neutral for the whole [`Rules::afk_secs`] of
+//! fighting gives the match to whoever is still there, so an idle peer can't
+//! keep their opponent waiting out round after round of timeouts. It's decided
+//! in the simulation from the inputs both peers agree on, any input at all,
+//! even during a countdown or on the end screen, counts as being back. A
+//! banner warns the idle player, and their opponent, for the last
+//! [`WARN_SECS`]. The training range has nobody to wait on.
+
+use bevy::prelude::*;
+use bevy_ggrs::{LocalPlayers, PlayerInputs, Session};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bot::Bots,
+    coach::is_coach,
+    round::{RoundPhase, Scores, SimFrame},
+    rules::Rules,
+    Config, GameState,
+};
+
+/// Seconds before the forfeit that the banner comes up
+const WARN_SECS: u32 = 10;
+
+pub struct AfkPlugin;
+
+impl Plugin for AfkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::InGame), spawn_afk_banner)
+            .add_systems(OnExit(GameState::InGame), despawn_afk_banner)
+            .add_systems(
+                Update,
+                update_afk_banner.run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Frames of fighting each player has gone without touching anything, by handle
+#[derive(Resource, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct IdleFrames(pub Vec<u32>);
+
+impl IdleFrames {
+    pub fn get(&self, handle: usize) -> u32 {
+        self.0.get(handle).copied().unwrap_or(0)
+    }
+}
+
+/// Frames of idling it takes to forfeit, none if the rules don't forfeit anyone
+fn afk_frames(rules: &Rules) -> Option<u32> {
+    (rules.afk_secs > 0).then(|| rules.afk_secs * 60)
+}
+
+/// Counts up idle fighting, and hands the match to the most successful of the
+/// players still at their keyboards once somebody has idled too long
+pub fn track_idle_players(
+    inputs: Res<PlayerInputs<Config>>,
+    rules: Res<Rules>,
+    frame: Res<SimFrame>,
+    scores: Res<Scores>,
+    mut idle: ResMut<IdleFrames>,
+    mut phase: ResMut<RoundPhase>,
+) {
+    let Some(limit) = afk_frames(&rules) else {
+        return;
+    };
+    if idle.0.len() != inputs.len() {
+        idle.0.resize(inputs.len(), 0);
+    }
+    let fighting = *phase == RoundPhase::Fighting;
+    for (handle, (input, _)) in inputs.iter().enumerate() {
+        if *input != 0 {
+            idle.0[handle] = 0;
+        } else if fighting {
+            idle.0[handle] = idle.0[handle].saturating_add(1);
+        }
+    }
+
+    if !fighting || idle.0.iter().all(|frames| *frames < limit) {
+        return;
+    }
+    // if every one of them walked off, there's nobody to hand it to
+    let Some(winner) = (0..idle.0.len())
+        .filter(|handle| idle.0[*handle] < limit)
+        .max_by_key(|handle| (scores.get(*handle), std::cmp::Reverse(*handle)))
+    else {
+        return;
+    };
+    info!("player {winner} wins, the others have been idle for {limit} frames");
+    *phase = RoundPhase::MatchOver {
+        winner,
+        frame: frame.0,
+    };
+}
+
+#[derive(Component)]
+struct AfkBanner;
+
+fn spawn_afk_banner(mut commands: Commands) {
+    commands.spawn((
+        AfkBanner,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 32.,
+                color: Color::ORANGE_RED,
+                ..default()
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(200.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        }),
+    ));
+}
+
+fn despawn_afk_banner(mut commands: Commands, banners: Query<Entity, With<AfkBanner>>) {
+    for banner in &banners {
+        commands.entity(banner).despawn_recursive();
+    }
+}
+
+fn update_afk_banner(
+    rules: Res<Rules>,
+    idle: Res<IdleFrames>,
+    phase: Res<RoundPhase>,
+    local_players: Res<LocalPlayers>,
+    bots: Res<Bots>,
+    session: Option<Res<Session<Config>>>,
+    mut banners: Query<&mut Text, With<AfkBanner>>,
+) {
+    let warn_frames = WARN_SECS * 60;
+    // the idlest player close enough to the limit to be warned
+    let idlest = afk_frames(&rules)
+        .filter(|_| *phase == RoundPhase::Fighting)
+        .and_then(|limit| {
+            (0..idle.0.len())
+                .filter(|handle| !bots.contains(*handle))
+                .map(|handle| (handle, limit.saturating_sub(idle.get(handle))))
+                .filter(|(_, frames_left)| *frames_left <= warn_frames)
+                .min_by_key(|(_, frames_left)| *frames_left)
+        });
+
+    let status = match (idlest, session) {
+        (Some((handle, frames_left)), Some(session)) => {
+            let secs_left = (frames_left as f32 / 60.).ceil();
+            if is_coach(&session) {
+                format!(
+                    "Player {} is away\nThe match ends in {secs_left:.0}s",
+                    handle + 1
+                )
+            } else if local_players.0.contains(&handle) {
+                format!("Are you still there?\nMove or you forfeit in {secs_left:.0}s")
+            } else {
+                format!("Your opponent is away\nYou win by forfeit in {secs_left:.0}s")
+            }
+        }
+        _ => String::new(),
+    };
+    for mut text in &mut banners {
+        if text.sections[0].value != status {
+            text.sections[0].value.clone_from(&status);
+        }
+    }
+}
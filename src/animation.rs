@@ -0,0 +1,73 @@
+//! Flip-book animation for sprites cut from a texture atlas, shared by the
+//! arena's props and one-off visual effects.
+
+use bevy::prelude::*;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, animate_sprites);
+    }
+}
+
+/// Steps the entity's `TextureAtlas` through `frames`
+#[derive(Component, Clone, Debug)]
+pub struct SpriteAnimation {
+    /// Atlas indices, in order
+    pub frames: Vec<usize>,
+    /// Seconds each frame stays up
+    pub frame_time: f32,
+    /// Starts over after the last frame, otherwise the entity is despawned
+    looping: bool,
+    elapsed: f32,
+}
+
+impl SpriteAnimation {
+    /// Props loop, effects usually play once and go away
+    pub fn new(frames: Vec<usize>, frame_time: f32, looping: bool) -> Self {
+        Self {
+            frames,
+            frame_time,
+            looping,
+            elapsed: 0.,
+        }
+    }
+
+    /// Starts `elapsed` seconds in, so a row of the same prop doesn't move in lockstep
+    pub fn with_offset(mut self, elapsed: f32) -> Self {
+        self.elapsed = elapsed;
+        self
+    }
+
+    pub fn first_frame(&self) -> usize {
+        self.frames.first().copied().unwrap_or_default()
+    }
+}
+
+fn animate_sprites(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut sprites: Query<(Entity, &mut SpriteAnimation, &mut TextureAtlas)>,
+) {
+    for (entity, mut animation, mut atlas) in &mut sprites {
+        if animation.frames.is_empty() || animation.frame_time <= 0. {
+            continue;
+        }
+        animation.elapsed += time.delta_seconds();
+
+        let mut frame = (animation.elapsed / animation.frame_time) as usize;
+        if frame >= animation.frames.len() {
+            if !animation.looping {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+            frame %= animation.frames.len();
+        }
+
+        let index = animation.frames[frame];
+        if atlas.index != index {
+            atlas.index = index;
+        }
+    }
+}
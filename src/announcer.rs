@@ -0,0 +1,83 @@
+//! The announcer, and the wizards' own voices.
+//!
+//! The simulation announces every round, the one that can decide the match,
+//! the fight starting and sudden death as [`Announcement`]s, which like sound
+//! effects only come out of [`crate::sim_events`] once their frame is
+//! confirmed. The announcer sounds the same wherever the camera is. Wizards
+//! grunt as they cast, each at a pitch of their own, from where they stand.
+//! Both follow the voice volume in the settings.
+
+use bevy::{audio::Volume, prelude::*};
+
+use crate::{
+    assets::VoiceAssets,
+    settings::Settings,
+    sfx::{spatial, Sfx, SfxEvent},
+    sim_events::SimEvents,
+};
+
+/// How fast each wizard's grunt plays, by handle, so they sound like different people
+const VOICE_PITCHES: [f32; 4] = [1., 0.8, 1.25, 0.9];
+
+pub struct AnnouncerPlugin;
+
+impl Plugin for AnnouncerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (announce, grunt_on_cast).run_if(resource_exists::<VoiceAssets>),
+        );
+    }
+}
+
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Announcement {
+    Round,
+    /// Somebody wins the match if they take this round
+    FinalRound,
+    Fight,
+    /// The round ran out on a tie and the walls are gone
+    SuddenDeath,
+}
+
+/// What simulation systems send their announcements to
+pub type AnnouncerQueue = SimEvents<Announcement>;
+
+fn announce(
+    mut commands: Commands,
+    assets: Res<VoiceAssets>,
+    settings: Res<Settings>,
+    mut announcements: EventReader<Announcement>,
+) {
+    for announcement in announcements.read() {
+        let source = match announcement {
+            Announcement::Round => &assets.round,
+            Announcement::FinalRound => &assets.final_round,
+            Announcement::Fight => &assets.fight,
+            Announcement::SuddenDeath => &assets.sudden_death,
+        };
+        commands.spawn(AudioBundle {
+            source: source.clone(),
+            settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(settings.voice_volume)),
+        });
+    }
+}
+
+fn grunt_on_cast(
+    mut commands: Commands,
+    assets: Res<VoiceAssets>,
+    settings: Res<Settings>,
+    mut events: EventReader<SfxEvent>,
+) {
+    for event in events.read().filter(|event| event.sfx == Sfx::Cast) {
+        commands.spawn((
+            AudioBundle {
+                source: assets.cast_grunt.clone(),
+                settings: spatial()
+                    .with_volume(Volume::new(settings.voice_volume))
+                    .with_speed(VOICE_PITCHES[event.source % VOICE_PITCHES.len()]),
+            },
+            TransformBundle::from_transform(Transform::from_translation(event.pos.extend(0.))),
+        ));
+    }
+}
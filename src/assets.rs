@@ -0,0 +1,105 @@
+//! Everything loaded up front, while the loading screen shows. Each kind of
+//! content has its own collection, new files go in the one they belong to and
+//! are ready by the time anything runs in `GameState::Matchmaking`.
+
+use bevy::prelude::*;
+use bevy_asset_loader::prelude::*;
+
+use crate::props::ArenaMap;
+
+/// What wizards look like and what they shoot. The wizards themselves are
+/// still flat colored squares.
+#[derive(AssetCollection, Resource, Default)]
+pub struct WizardSprites {
+    /// Bullets, spells and what they leave behind, all white and tinted when
+    /// spawned. Being one texture, however many are flying draw in one batch.
+    #[asset(path = "projectiles.png")]
+    pub projectiles: Handle<Image>,
+    #[asset(texture_atlas_layout(tile_size_x = 16., tile_size_y = 16., columns = 4, rows = 1))]
+    pub projectile_layout: Handle<TextureAtlasLayout>,
+}
+
+/// Tiles of [`WizardSprites::projectiles`]
+#[derive(Clone, Copy)]
+pub enum ProjectileSprite {
+    Bullet,
+    /// Fireballs and mana burns
+    Orb,
+    Hook,
+    Explosion,
+}
+
+impl WizardSprites {
+    /// The atlas and tile to draw `sprite` with
+    pub fn projectile(&self, sprite: ProjectileSprite) -> (Handle<Image>, TextureAtlas) {
+        let atlas = TextureAtlas {
+            layout: self.projectile_layout.clone(),
+            index: sprite as usize,
+        };
+        (self.projectiles.clone(), atlas)
+    }
+}
+
+/// The arena's tiles and the props placed around it
+#[derive(AssetCollection, Resource, Default)]
+pub struct ArenaAssets {
+    #[asset(path = "Dungeon_Objects.png")]
+    pub tiles: Handle<Image>,
+    #[asset(texture_atlas_layout(tile_size_x = 16., tile_size_y = 16., columns = 11, rows = 11))]
+    pub tile_layout: Handle<TextureAtlasLayout>,
+    #[asset(path = "arena.map")]
+    pub map: Handle<ArenaMap>,
+}
+
+/// One per thing a wizard can cast, for the HUD and menus
+#[derive(AssetCollection, Resource, Default)]
+pub struct SpellIcons {
+    #[asset(path = "icons/bullet.png")]
+    pub bullet: Handle<Image>,
+    #[asset(path = "icons/fireball.png")]
+    pub fireball: Handle<Image>,
+}
+
+#[derive(AssetCollection, Resource, Default)]
+pub struct SfxAssets {
+    #[asset(path = "sfx/fire.wav")]
+    pub fire: Handle<AudioSource>,
+    #[asset(path = "sfx/cast.wav")]
+    pub cast: Handle<AudioSource>,
+    #[asset(path = "sfx/impact.wav")]
+    pub impact: Handle<AudioSource>,
+    #[asset(path = "sfx/block.wav")]
+    pub block: Handle<AudioSource>,
+    #[asset(path = "sfx/explosion.wav")]
+    pub explosion: Handle<AudioSource>,
+    #[asset(path = "sfx/death.wav")]
+    pub death: Handle<AudioSource>,
+}
+
+/// The announcer, and what the wizards say themselves
+#[derive(AssetCollection, Resource, Default)]
+pub struct VoiceAssets {
+    #[asset(path = "announcer/round.wav")]
+    pub round: Handle<AudioSource>,
+    #[asset(path = "announcer/final_round.wav")]
+    pub final_round: Handle<AudioSource>,
+    #[asset(path = "announcer/fight.wav")]
+    pub fight: Handle<AudioSource>,
+    #[asset(path = "announcer/sudden_death.wav")]
+    pub sudden_death: Handle<AudioSource>,
+    /// Every wizard's, pitched differently for each
+    #[asset(path = "voice/cast_grunt.wav")]
+    pub cast_grunt: Handle<AudioSource>,
+}
+
+/// Loops, all of a track's layers are the same length so they stay in time
+#[derive(AssetCollection, Resource, Default)]
+pub struct MusicAssets {
+    #[asset(path = "music/menu.wav")]
+    pub menu: Handle<AudioSource>,
+    #[asset(path = "music/battle.wav")]
+    pub battle: Handle<AudioSource>,
+    /// Played on top of `battle` when things get close
+    #[asset(path = "music/battle_intense.wav")]
+    pub battle_intense: Handle<AudioSource>,
+}
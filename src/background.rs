@@ -0,0 +1,141 @@
+//! Matches in a browser tab that's been switched away from.
+//!
+//! Browsers stop drawing hidden tabs, and on the web Bevy only updates when
+//! it draws, so a hidden tab would stop sending inputs until GGRS gave up on
+//! it. While the tab is hidden the window is marked invisible instead, which
+//! has bevy_winit update the app by itself, and the simulation keeps stepping
+//! as usual. Browsers may still slow a background tab down, so the other
+//! peers are told, and say who's tabbed out rather than just stalling.
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ggrs::Session;
+use bevy_matchbox::matchbox_socket::PeerId;
+#[cfg(target_arch = "wasm32")]
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+
+use crate::{
+    coach::CoachedPlayers,
+    peer_messages::{player_handle, PeerDisconnected, PeerMessage, PeerMessageReceived},
+    Config, GameState,
+};
+
+pub struct BackgroundPlugin;
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HiddenPeers>()
+            .add_systems(OnEnter(GameState::InGame), spawn_hidden_banner)
+            .add_systems(OnExit(GameState::InGame), despawn_hidden_banner)
+            .add_systems(Update, (track_hidden_peers, update_hidden_banner).chain());
+        #[cfg(target_arch = "wasm32")]
+        app.add_systems(Update, keep_running_in_background);
+    }
+}
+
+/// Peers whose tab is in the background
+#[derive(Resource, Default)]
+struct HiddenPeers(HashSet<PeerId>);
+
+/// Hides the window along with the tab, so bevy_winit stops waiting on the
+/// browser to draw it, and lets the peers know
+#[cfg(target_arch = "wasm32")]
+fn keep_running_in_background(
+    mut occlusions: EventReader<bevy::window::WindowOccluded>,
+    mut windows: Query<&mut Window>,
+    socket: Option<ResMut<MatchboxSocket<MultipleChannels>>>,
+) {
+    use crate::peer_messages::send_to_peers;
+
+    let Some(hidden) = occlusions.read().last().map(|occlusion| occlusion.occluded) else {
+        return;
+    };
+    for mut window in &mut windows {
+        window.visible = !hidden;
+    }
+    info!("tab {}", if hidden { "hidden" } else { "shown" });
+    if let Some(mut socket) = socket {
+        send_to_peers(&mut socket, PeerMessage::TabHidden(hidden));
+    }
+}
+
+fn track_hidden_peers(
+    mut hidden: ResMut<HiddenPeers>,
+    mut messages: EventReader<PeerMessageReceived>,
+    mut disconnects: EventReader<PeerDisconnected>,
+) {
+    for received in messages.read() {
+        match received.message {
+            PeerMessage::TabHidden(true) => {
+                hidden.0.insert(received.peer);
+            }
+            PeerMessage::TabHidden(false) => {
+                hidden.0.remove(&received.peer);
+            }
+            _ => {}
+        }
+    }
+    for disconnect in disconnects.read() {
+        hidden.0.remove(&disconnect.peer);
+    }
+}
+
+#[derive(Component)]
+struct HiddenBanner;
+
+fn spawn_hidden_banner(mut commands: Commands) {
+    commands.spawn((
+        HiddenBanner,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 24.,
+                color: Color::ORANGE,
+                ..default()
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(290.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        }),
+    ));
+}
+
+fn despawn_hidden_banner(mut commands: Commands, banners: Query<Entity, With<HiddenBanner>>) {
+    for banner in &banners {
+        commands.entity(banner).despawn_recursive();
+    }
+}
+
+fn update_hidden_banner(
+    hidden: Res<HiddenPeers>,
+    session: Option<Res<Session<Config>>>,
+    coached: Option<Res<CoachedPlayers>>,
+    mut banners: Query<&mut Text, With<HiddenBanner>>,
+) {
+    let mut handles: Vec<usize> = session
+        .as_deref()
+        .map(|session| {
+            hidden
+                .0
+                .iter()
+                .filter_map(|peer| player_handle(session, coached.as_deref(), *peer))
+                .collect()
+        })
+        .unwrap_or_default();
+    handles.sort();
+    let status = handles
+        .iter()
+        .map(|handle| format!("Player {} switched to another tab", handle + 1))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    for mut text in &mut banners {
+        if text.sections[0].value != status {
+            text.sections[0].value.clone_from(&status);
+        }
+    }
+}
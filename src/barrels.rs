@@ -0,0 +1,416 @@
+//! Explosive barrels and wooden crates, placed by the map file.
+//!
+//! Both are walls to everything else in the simulation, they block wizards
+//! and stop projectiles. A bullet that hits a barrel sets it off, and any
+//! explosion catching a barrel sets that one off too, which can chain across
+//! a row of them in a single frame. Explosions smash crates to bits.
+//!
+//! Telekinesis picks up the nearest one and throws it away from the caster,
+//! it stops being a wall and flies like any other projectile until it runs
+//! into something. A flung barrel goes off there, a flung crate breaks over
+//! the head of whoever it hits.
+//!
+//! ```json
+//! { "barrels": [{ "pos": [4, 6] }], "crates": [{ "pos": [5, 6] }] }
+//! ```
+
+use bevy::prelude::*;
+use bevy_ggrs::AddRollbackCommandExtension;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::{ArenaAssets, WizardSprites},
+    broadphase::Broadphase,
+    combat::{apply_damage, resolve_damage, ComboTracker, HitQueue, Target},
+    components::*,
+    fixed::{Fixed, SimVec2},
+    graphics::{YSort, YSORT_Z},
+    hits_wall,
+    mutators::RoundMutator,
+    particles::{Burst, BurstQueue},
+    props::ArenaMap,
+    round::{RoundKills, SimFrame},
+    sfx::{Sfx, SfxQueue},
+    spells::{projectile_hit, spawn_explosion, ProjectileHit, EXPLOSION_FRAMES, EXPLOSION_RADIUS},
+    telemetry::MatchTelemetry,
+    GameState, BULLET_RADIUS,
+};
+
+const BARREL_HALF_SIZE: f32 = 0.35;
+const CRATE_HALF_SIZE: f32 = 0.5;
+/// Furthest a prop can be from the caster and still get shoved
+const TELEKINESIS_RANGE: Fixed = Fixed::from_int(4);
+/// Distance covered each frame
+pub const FLUNG_SPEED: Fixed = Fixed::from_f32(0.3);
+pub const FLUNG_RANGE_FRAMES: u32 = 40;
+/// Damage a flung crate deals, barrels explode instead
+const FLUNG_CRATE_DAMAGE: u32 = 20;
+const BARREL_COLOR: Color = Color::rgb(0.65, 0.22, 0.1);
+const CRATE_COLOR: Color = Color::rgb(0.5, 0.33, 0.17);
+
+pub struct BarrelsPlugin;
+
+impl Plugin for BarrelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnExit(GameState::AssetLoading),
+            // not when loading failed
+            load_arena_layout.run_if(resource_exists::<ArenaAssets>),
+        );
+    }
+}
+
+/// Where the map puts the things the simulation has to know about. Replays
+/// bring their own, the map file could have changed since.
+#[derive(Resource, Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ArenaLayout {
+    pub barrels: Vec<Vec2>,
+    pub crates: Vec<Vec2>,
+}
+
+impl ArenaLayout {
+    /// Same layout, same number, on every platform, so peers can tell whether
+    /// they loaded the same map
+    pub fn fingerprint(&self) -> u64 {
+        // FNV-1a
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut write = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+
+        write(self.barrels.len() as u64);
+        write(self.crates.len() as u64);
+        for pos in self.barrels.iter().chain(&self.crates) {
+            write((pos.x.to_bits() as u64) << 32 | pos.y.to_bits() as u64);
+        }
+        hash
+    }
+}
+
+fn load_arena_layout(mut commands: Commands, arena: Res<ArenaAssets>, maps: Res<Assets<ArenaMap>>) {
+    let Some(map) = maps.get(&arena.map) else {
+        return;
+    };
+    commands.insert_resource(ArenaLayout {
+        barrels: map
+            .barrels
+            .iter()
+            .map(|barrel| Vec2::from(barrel.pos))
+            .collect(),
+        crates: map
+            .crates
+            .iter()
+            .map(|crate_| Vec2::from(crate_.pos))
+            .collect(),
+    });
+}
+
+pub fn spawn_barrel(commands: &mut Commands, pos: SimVec2) -> Entity {
+    spawn_prop(commands, pos, true).insert(Barrel).id()
+}
+
+pub fn spawn_crate(commands: &mut Commands, pos: SimVec2) -> Entity {
+    spawn_prop(commands, pos, false).insert(Destructible).id()
+}
+
+fn spawn_prop<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    pos: SimVec2,
+    barrel: bool,
+) -> bevy::ecs::system::EntityCommands<'a> {
+    let mut entity = commands.spawn((
+        Wall {
+            half_size: SimVec2::splat(flung_radius(barrel)),
+        },
+        Position(pos),
+        prop_look(pos, barrel),
+    ));
+    entity.add_rollback();
+    entity
+}
+
+/// A barrel or a crate, standing or flying
+pub fn prop_look(pos: SimVec2, barrel: bool) -> (YSort, SpriteBundle) {
+    let (half_size, color) = if barrel {
+        (BARREL_HALF_SIZE, BARREL_COLOR)
+    } else {
+        (CRATE_HALF_SIZE, CRATE_COLOR)
+    };
+    (
+        YSort { offset: half_size },
+        SpriteBundle {
+            transform: Transform::from_translation(pos.to_vec2().extend(YSORT_Z)),
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::splat(half_size * 2.)),
+                ..default()
+            },
+            ..default()
+        },
+    )
+}
+
+/// Bullets that hit a barrel set it off, the blast counts as theirs. Runs
+/// before bullets are stopped at walls, which would otherwise just eat them.
+pub fn shoot_barrels(
+    mut commands: Commands,
+    sprites: Res<WizardSprites>,
+    bullets: Query<(Entity, &Position, &Owner, &Team), With<Bullet>>,
+    barrels: Query<(Entity, &Position, &Wall), With<Barrel>>,
+) {
+    let radius = Fixed::from_f32(BULLET_RADIUS);
+    let mut spent_bullets = Vec::new();
+    for (barrel, barrel_pos, wall) in &barrels {
+        let shot_by = bullets
+            .iter()
+            .filter(|(bullet, ..)| !spent_bullets.contains(bullet))
+            .find(|(_, position, ..)| hits_wall(position.0, radius, barrel_pos.0, wall));
+        let Some((bullet, _, owner, team)) = shot_by else {
+            continue;
+        };
+
+        spent_bullets.push(bullet);
+        commands.entity(bullet).despawn_recursive();
+        commands.entity(barrel).despawn_recursive();
+        let pos = barrel_pos.0;
+        spawn_explosion(
+            &mut commands,
+            &sprites,
+            *owner,
+            *team,
+            pos,
+            EXPLOSION_FRAMES,
+        );
+    }
+}
+
+/// Explosions going off this frame smash crates and set off barrels, and the
+/// barrels' own blasts are followed through straight away so a chain of them
+/// all goes up together. Runs before the explosions deal their damage.
+pub fn blast_props(
+    mut commands: Commands,
+    sprites: Res<WizardSprites>,
+    explosions: Query<(&Position, &Explosion, &Owner, &Team)>,
+    props: Query<(Entity, &Position, &Wall, Has<Barrel>), Or<(With<Barrel>, With<Destructible>)>>,
+) {
+    let mut blasts: Vec<(SimVec2, Owner, Team)> = explosions
+        .iter()
+        .filter(|(_, explosion, ..)| explosion.frames_left == EXPLOSION_FRAMES)
+        .map(|(position, _, owner, team)| (position.0, *owner, *team))
+        .collect();
+    let radius = Fixed::from_f32(EXPLOSION_RADIUS);
+    let mut destroyed = Vec::new();
+
+    let mut i = 0;
+    while i < blasts.len() {
+        let (center, owner, team) = blasts[i];
+        i += 1;
+        for (entity, position, wall, barrel) in &props {
+            if destroyed.contains(&entity) || !hits_wall(center, radius, position.0, wall) {
+                continue;
+            }
+            destroyed.push(entity);
+            commands.entity(entity).despawn_recursive();
+            if barrel {
+                let pos = position.0;
+                spawn_explosion(&mut commands, &sprites, owner, team, pos, EXPLOSION_FRAMES);
+                blasts.push((pos, owner, team));
+            }
+        }
+    }
+}
+
+/// Props telekinesis can pick up, ones already in flight aren't walls anymore
+pub type Shovable = (With<Wall>, Or<(With<Barrel>, With<Destructible>)>);
+
+/// The prop closest to `pos` that's in telekinesis range, and where it is
+pub fn nearest_prop<F: bevy::ecs::query::QueryFilter>(
+    pos: SimVec2,
+    props: &Query<(Entity, &Position), F>,
+) -> Option<(Entity, SimVec2)> {
+    props
+        .iter()
+        .map(|(entity, position)| (entity, position.0))
+        .filter(|(_, prop_pos)| {
+            prop_pos.distance_squared(pos) <= TELEKINESIS_RANGE * TELEKINESIS_RANGE
+        })
+        // ties go by position, so which one gets picked doesn't hang on query order
+        .min_by_key(|(_, prop_pos)| (prop_pos.distance_squared(pos), prop_pos.x, prop_pos.y))
+}
+
+/// Turns a barrel or crate into a projectile flying off at `velocity`
+pub fn fling_prop(
+    commands: &mut Commands,
+    prop: Entity,
+    owner: Owner,
+    team: Team,
+    velocity: SimVec2,
+    frames_left: u32,
+) {
+    commands.entity(prop).remove::<Wall>().insert((
+        Flung { frames_left },
+        owner,
+        team,
+        Velocity(velocity),
+    ));
+}
+
+/// Size of a flung prop, as far as hitting things goes
+pub fn flung_radius(barrel: bool) -> Fixed {
+    Fixed::from_f32(if barrel {
+        BARREL_HALF_SIZE
+    } else {
+        CRATE_HALF_SIZE
+    })
+}
+
+/// Flung props fly until they hit something or run out of range. Barrels go
+/// off wherever that is, crates break and hurt the enemy they hit.
+pub fn move_flung_props(
+    mut commands: Commands,
+    sprites: Res<WizardSprites>,
+    mut kills: ResMut<RoundKills>,
+    mut telemetry: ResMut<MatchTelemetry>,
+    mut hits: ResMut<HitQueue>,
+    mut sfx: ResMut<SfxQueue>,
+    mut bursts: ResMut<BurstQueue>,
+    frame: Res<SimFrame>,
+    mutator: Res<RoundMutator>,
+    mut flung: Query<(
+        Entity,
+        &mut Position,
+        &mut Flung,
+        &Velocity,
+        &Owner,
+        &Team,
+        Has<Barrel>,
+    )>,
+    mut players: Query<
+        (
+            Entity,
+            &Position,
+            &Player,
+            &Team,
+            &mut Health,
+            &Armor,
+            &mut DamageHistory,
+            &mut LastCombatFrame,
+            &mut Combo,
+        ),
+        Without<Flung>,
+    >,
+    walls: Query<(&Position, &Wall), Without<Flung>>,
+    broadphase: Res<Broadphase>,
+) {
+    let mut attackers = Vec::new();
+    let mut combos = ComboTracker::new(
+        players
+            .iter()
+            .map(|(_, _, player, .., combo)| (player.handle, *combo)),
+    );
+
+    for (entity, mut position, mut prop, velocity, owner, prop_team, barrel) in &mut flung {
+        position.0 += velocity.0;
+        let pos = position.0;
+        prop.frames_left = prop.frames_left.saturating_sub(1);
+
+        let radius = flung_radius(barrel);
+        let hit = projectile_hit(
+            pos,
+            radius,
+            &mutator,
+            *owner,
+            broadphase
+                .wizards
+                .near(pos, radius, &players)
+                .map(|(_, player_pos, player, ..)| (player_pos, player)),
+            broadphase.walls.near(pos, radius, &walls),
+        );
+        if hit.is_none() && prop.frames_left > 0 {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+        if barrel {
+            spawn_explosion(
+                &mut commands,
+                &sprites,
+                *owner,
+                *prop_team,
+                pos,
+                EXPLOSION_FRAMES,
+            );
+            continue;
+        }
+        sfx.play(frame.0, Sfx::Block, owner.0, pos.to_vec2());
+
+        let Some(ProjectileHit::Wizard(handle)) = hit else {
+            continue;
+        };
+        // allies just get bonked
+        let target = players
+            .iter_mut()
+            .find(|(_, _, player, team, ..)| player.handle == handle && *team != prop_team);
+        let Some((
+            player_entity,
+            player_pos,
+            player,
+            _,
+            mut health,
+            armor,
+            mut history,
+            mut last_combat,
+            _,
+        )) = target
+        else {
+            continue;
+        };
+
+        attackers.push(owner.0);
+        last_combat.0 = frame.0;
+        let modifiers = [combos.bonus_percent(owner.0)];
+        let damage = resolve_damage(FLUNG_CRATE_DAMAGE, *armor, &modifiers);
+        combos.record(owner.0, player.handle, true);
+        let pos = player_pos.0.to_vec2();
+        let target = Target {
+            entity: player_entity,
+            player,
+            health: &mut health,
+            history: &mut history,
+            pos,
+        };
+        sfx.play(frame.0, Sfx::Impact, player.handle, pos);
+        let died = apply_damage(
+            &mut commands,
+            &mut kills,
+            &mut telemetry,
+            &mut hits,
+            frame.0,
+            target,
+            Some(owner.0),
+            damage,
+            false,
+        );
+        if died {
+            sfx.play(frame.0, Sfx::Death, player.handle, pos);
+            bursts.send(
+                frame.0,
+                Burst::Death {
+                    pos,
+                    handle: player.handle,
+                },
+            );
+        }
+    }
+
+    // dealing damage also counts as being in combat
+    for (.., player, _, _, _, _, mut last_combat, mut combo) in &mut players {
+        if attackers.contains(&player.handle) {
+            last_combat.0 = frame.0;
+        }
+        combos.update(player.handle, &mut combo);
+    }
+}
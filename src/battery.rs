@@ -0,0 +1,171 @@
+//! Battery saver: the arena drawn at half the window's resolution and at most
+//! 30 frames a second, for laptops and phones that would rather last the match.
+//!
+//! The camera draws into an image half the window's size, and an image
+//! filling the window stretches it back up. The UI goes on top at full
+//! resolution, through a second camera that has nothing else to draw. The
+//! frame rate cap is [`crate::frame_pacing`]'s, and the simulation steps at
+//! 60 Hz all the same.
+
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        texture::{BevyDefault, ImageSampler},
+    },
+    window::{PrimaryWindow, WindowRef, WindowResized},
+};
+
+use crate::settings::Settings;
+
+/// How much of the window's resolution the arena is drawn at
+pub const RENDER_SCALE: f32 = 0.5;
+/// The most frames a second it's drawn at
+pub const FPS: u32 = 30;
+
+pub struct BatterySaverPlugin;
+
+impl Plugin for BatterySaverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (switch_render_target, update_render_target).chain());
+    }
+}
+
+/// What the arena is drawn to while saving battery
+#[derive(Resource)]
+struct LowResTarget(Handle<Image>);
+
+/// The UI camera and the image of the arena stretched over the window
+#[derive(Component)]
+struct Upscaler;
+
+fn render_size(window: &Window) -> Extent3d {
+    let scaled = |pixels: u32| ((pixels as f32 * RENDER_SCALE) as u32).max(1);
+    Extent3d {
+        width: scaled(window.physical_width()),
+        height: scaled(window.physical_height()),
+        depth_or_array_layers: 1,
+    }
+}
+
+fn switch_render_target(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    target: Option<Res<LowResTarget>>,
+    mut images: ResMut<Assets<Image>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut Camera, With<Camera2d>>,
+    upscalers: Query<Entity, With<Upscaler>>,
+) {
+    if settings.battery_saver == target.is_some() {
+        return;
+    }
+
+    if !settings.battery_saver {
+        for mut camera in &mut cameras {
+            camera.target = RenderTarget::Window(WindowRef::Primary);
+        }
+        for upscaler in &upscalers {
+            commands.entity(upscaler).despawn_recursive();
+        }
+        // the image goes with the last handle to it
+        commands.remove_resource::<LowResTarget>();
+        return;
+    }
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let size = render_size(window);
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("low_res_target"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+
+    for mut camera in &mut cameras {
+        camera.target = RenderTarget::Image(image.clone());
+    }
+    // not a 2d camera, everything looking for the arena's camera would find two
+    commands.spawn((
+        Upscaler,
+        Camera3dBundle {
+            camera: Camera {
+                order: 1,
+                ..default()
+            },
+            tonemapping: Tonemapping::None,
+            ..default()
+        },
+    ));
+    commands.spawn((
+        Upscaler,
+        ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            image: UiImage::new(image.clone()),
+            // under all the rest of the UI
+            z_index: ZIndex::Global(i32::MIN),
+            ..default()
+        },
+    ));
+    commands.insert_resource(LowResTarget(image));
+}
+
+/// Keeps the image half the window's size, and as sharp or smooth as the
+/// pixel-perfect setting wants it stretched
+fn update_render_target(
+    settings: Res<Settings>,
+    target: Option<Res<LowResTarget>>,
+    mut resized: EventReader<WindowResized>,
+    mut images: ResMut<Assets<Image>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let resized = resized.read().count() > 0;
+    let Some(target) = target else {
+        return;
+    };
+    if !resized && !settings.is_changed() && !target.is_added() {
+        return;
+    }
+    let (Ok(window), Some(image)) = (windows.get_single(), images.get_mut(&target.0)) else {
+        return;
+    };
+
+    let size = render_size(window);
+    if image.texture_descriptor.size != size {
+        image.resize(size);
+    }
+    image.sampler = if settings.pixel_perfect {
+        ImageSampler::nearest()
+    } else {
+        ImageSampler::linear()
+    };
+}
+
+/// Where `cursor`, in the window, is in what `camera` draws to, which with the
+/// battery saver on is smaller than the window
+pub fn cursor_in_viewport(camera: &Camera, window: &Window, cursor: Vec2) -> Option<Vec2> {
+    let viewport = camera.logical_viewport_size()?;
+    Some(cursor * viewport / Vec2::new(window.width(), window.height()))
+}
@@ -0,0 +1,53 @@
+//! Plays replays back without a window and checks every match still ends
+//! exactly the way it did when it was recorded.
+//!
+//!     cargo run --release --bin verify_replay -- replays/*.wbr
+//!
+//! Run it on another platform, or after touching the simulation, to catch
+//! anything that would make peers (or old replays) desync.
+
+use std::process::ExitCode;
+
+use bevy_wizard_battles::replay::{play_headless, Replay};
+
+fn main() -> ExitCode {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("usage: verify_replay <replay.wbr>...");
+        return ExitCode::FAILURE;
+    }
+
+    let mut failed = 0;
+    for path in &paths {
+        match verify(path) {
+            Ok(()) => println!("ok        {path}"),
+            Err(err) => {
+                println!("FAILED    {path}: {err}");
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "{} of {} replays verified",
+        paths.len() - failed,
+        paths.len()
+    );
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn verify(path: &str) -> Result<(), String> {
+    let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+    let replay = Replay::from_bytes(&bytes).map_err(|err| err.to_string())?;
+    let expected = replay.end_state;
+    let actual = play_headless(replay).ok_or("replay never finished")?;
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("end state {actual:016x}, recorded {expected:016x}"))
+    }
+}
@@ -0,0 +1,308 @@
+//! Offline practice against a bot.
+//!
+//! Bots play through a local GGRS session like anyone else: every frame they
+//! look at the arena and come up with the same input byte a player would send,
+//! so the simulation (and replays) can't tell them apart from people.
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ggrs::{
+    ggrs::{PlayerType, SessionBuilder},
+    LocalInputs, Session,
+};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+
+use crate::{
+    components::*,
+    input::{encode_input, read_local_inputs, Input},
+    not_typing,
+    rng::MatchRng,
+    round::{RoundPhase, SimFrame},
+    Config, GameState, PLAYER_RADIUS,
+};
+
+/// Distance the bot tries to keep from its target
+const PREFERRED_RANGE: f32 = 4.;
+/// How far off the bot's aim may be and still count as lined up
+const AIM_TOLERANCE: f32 = PLAYER_RADIUS * 0.8;
+const MAX_FIREBALL_RANGE: f32 = 8.;
+/// Frames between changes of strafing direction
+const STRAFE_FRAMES: i32 = 90;
+
+pub struct BotPlugin;
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bots>()
+            .init_resource::<BotDifficulty>()
+            .add_systems(Startup, practice_from_args)
+            .add_systems(
+                OnEnter(GameState::Matchmaking),
+                start_bot_session.run_if(has_bots),
+            )
+            .add_systems(
+                Update,
+                practice_on_key_press
+                    .run_if(in_state(GameState::Matchmaking))
+                    .run_if(not_typing),
+            )
+            .add_systems(
+                bevy_ggrs::ReadInputs,
+                read_bot_inputs.after(read_local_inputs).run_if(has_bots),
+            );
+    }
+}
+
+/// Player handles driven by a bot instead of a person
+#[derive(Resource, Default, Debug)]
+pub struct Bots(pub Vec<usize>);
+
+impl Bots {
+    pub fn contains(&self, handle: usize) -> bool {
+        self.0.contains(&handle)
+    }
+}
+
+/// How well the bots play, picked on the matchmaking screen
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum BotDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl BotDifficulty {
+    pub const ALL: [BotDifficulty; 3] = [Self::Easy, Self::Normal, Self::Hard];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+        }
+    }
+
+    fn profile(self) -> BotProfile {
+        match self {
+            Self::Easy => BotProfile {
+                reaction_frames: 20,
+                aim_error: 1.2,
+                spells: SpellPolicy::Never,
+            },
+            Self::Normal => BotProfile {
+                reaction_frames: 10,
+                aim_error: 0.6,
+                spells: SpellPolicy::SafeRange,
+            },
+            Self::Hard => BotProfile {
+                reaction_frames: 3,
+                aim_error: 0.2,
+                spells: SpellPolicy::Aggressive,
+            },
+        }
+    }
+}
+
+struct BotProfile {
+    /// Frames between decisions, the bot keeps doing the same thing in between
+    reaction_frames: i32,
+    /// How far off the bot's idea of where its target is can be
+    aim_error: f32,
+    spells: SpellPolicy,
+}
+
+enum SpellPolicy {
+    Never,
+    /// Only far enough away that the blast can't reach the bot
+    SafeRange,
+    /// Close enough to get caught in it too
+    Aggressive,
+}
+
+impl SpellPolicy {
+    fn min_range(&self) -> Option<f32> {
+        match self {
+            SpellPolicy::Never => None,
+            SpellPolicy::SafeRange => Some(3.),
+            SpellPolicy::Aggressive => Some(1.5),
+        }
+    }
+}
+
+pub fn has_bots(bots: Res<Bots>) -> bool {
+    !bots.0.is_empty()
+}
+
+/// `--practice` skips matchmaking and goes straight into a match against a bot
+fn practice_from_args(mut bots: ResMut<Bots>) {
+    if std::env::args().any(|arg| arg == "--practice") {
+        bots.0 = vec![1];
+    }
+}
+
+/// Pressing P while waiting for an opponent starts a practice match instead
+fn practice_on_key_press(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut bots: ResMut<Bots>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keys.just_pressed(KeyCode::KeyP) && bots.0.is_empty() {
+        play_vs_bot(&mut commands, &mut bots, &mut next_state);
+    }
+}
+
+/// Gives up on matchmaking and starts a match against a bot right away
+pub fn play_vs_bot(
+    commands: &mut Commands,
+    bots: &mut Bots,
+    next_state: &mut NextState<GameState>,
+) {
+    info!("starting practice match");
+    commands.remove_resource::<MatchboxSocket<MultipleChannels>>();
+    bots.0 = vec![1];
+    start_local_session(commands, 2, next_state);
+}
+
+fn start_bot_session(
+    mut commands: Commands,
+    bots: Res<Bots>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    info!("playing against bots {:?}", bots.0);
+    start_local_session(&mut commands, 2, &mut next_state);
+}
+
+/// Everyone is on this machine, so a synctest session that never rolls back
+/// works as a plain local session
+pub fn start_local_session(
+    commands: &mut Commands,
+    num_players: usize,
+    next_state: &mut NextState<GameState>,
+) {
+    let mut session_builder = SessionBuilder::<Config>::new()
+        .with_num_players(num_players)
+        .with_check_distance(0);
+
+    for handle in 0..num_players {
+        session_builder = session_builder
+            .add_player(PlayerType::Local, handle)
+            .expect("failed to add player");
+    }
+
+    let session = session_builder
+        .start_synctest_session()
+        .expect("failed to start session");
+    commands.insert_resource(Session::SyncTest(session));
+    next_state.set(GameState::InGame);
+}
+
+/// Replaces the keyboard input `read_local_inputs` gave the bots' handles
+pub fn read_bot_inputs(
+    mut local_inputs: ResMut<LocalInputs<Config>>,
+    bots: Res<Bots>,
+    difficulty: Res<BotDifficulty>,
+    phase: Res<RoundPhase>,
+    frame: Res<SimFrame>,
+    rng: Res<MatchRng>,
+    players: Query<(&Player, &Team, &Position, &BulletReady, &SpellCooldown)>,
+    // frame of the last decision and the input it came up with, by handle
+    mut decisions: Local<HashMap<usize, (i32, Input)>>,
+) {
+    let profile = difficulty.profile();
+
+    for &handle in &bots.0 {
+        let input = if matches!(*phase, RoundPhase::MatchOver { .. }) {
+            // always up for another one
+            encode_input(Vec2::ZERO, false, false, true)
+        } else {
+            match decisions.get(&handle) {
+                Some(&(decided_at, input))
+                    if (0..profile.reaction_frames).contains(&(frame.0 - decided_at)) =>
+                {
+                    input
+                }
+                _ => {
+                    // a copy, the bot mustn't change the simulation's own rolls.
+                    // Inputs are all that gets recorded, so this is fine for replays.
+                    let mut rng =
+                        MatchRng::new(rng.state() ^ (frame.0 as u64) << 8 ^ handle as u64);
+                    let input = bot_input(handle, frame.0, &profile, &mut rng, &players);
+                    decisions.insert(handle, (frame.0, input));
+                    input
+                }
+            }
+        };
+        local_inputs.0.insert(handle, input);
+    }
+}
+
+fn bot_input(
+    handle: usize,
+    frame: i32,
+    profile: &BotProfile,
+    rng: &mut MatchRng,
+    players: &Query<(&Player, &Team, &Position, &BulletReady, &SpellCooldown)>,
+) -> Input {
+    let Some((_, team, position, bullet_ready, spell_cooldown)) =
+        players.iter().find(|(player, ..)| player.handle == handle)
+    else {
+        return 0; // dead, nothing to do until the next round
+    };
+    let pos = position.0.to_vec2();
+
+    let target = players
+        .iter()
+        .filter(|(_, other_team, ..)| *other_team != team)
+        .map(|(_, _, other, ..)| other.0.to_vec2())
+        .min_by(|a, b| a.distance_squared(pos).total_cmp(&b.distance_squared(pos)));
+    let Some(target) = target else {
+        return 0;
+    };
+
+    // where the bot thinks the target is
+    let angle = rng.next_u32() as f32 / u32::MAX as f32 * std::f32::consts::TAU;
+    let error = rng.next_u32() as f32 / u32::MAX as f32 * profile.aim_error;
+    let target = target + Vec2::from_angle(angle) * error;
+
+    let offset = target - pos;
+    let distance = offset.length();
+
+    // the bot can only shoot in one of the eight directions it can move in
+    let aim = snap_to_input_direction(offset);
+    let lined_up = offset.dot(aim) > 0. && offset.perp_dot(aim).abs() < AIM_TOLERANCE;
+
+    if lined_up {
+        // turn towards the target and let it have it. Fire has to be released
+        // between shots, so only hold it while a bullet is ready.
+        let cast = spell_cooldown.0 == 0
+            && profile
+                .spells
+                .min_range()
+                .is_some_and(|min_range| (min_range..MAX_FIREBALL_RANGE).contains(&distance));
+        return encode_input(aim, bullet_ready.0, cast, false);
+    }
+
+    let direction = if distance > PREFERRED_RANGE + 1. {
+        offset
+    } else if distance < PREFERRED_RANGE - 1. {
+        -offset
+    } else {
+        // circle around the target, switching sides every now and then
+        let side = if (frame / STRAFE_FRAMES) % 2 == 0 {
+            1.
+        } else {
+            -1.
+        };
+        offset.perp() * side
+    };
+
+    encode_input(direction, false, false, false)
+}
+
+/// The closest of the eight directions a wizard can move (and shoot) in
+fn snap_to_input_direction(direction: Vec2) -> Vec2 {
+    let angle = direction.y.atan2(direction.x);
+    let step = std::f32::consts::FRAC_PI_4;
+    Vec2::from_angle((angle / step).round() * step)
+}
@@ -0,0 +1,134 @@
+//! Spatial hash of the arena, so collision checks only look at what's nearby.
+//!
+//! The arena is cut into square cells and everything that can be run into is
+//! listed in each cell it overlaps. A check for whatever's around a point
+//! then only has to look at a few cells instead of every wall and wizard on
+//! the map. It's rebuilt from scratch every simulation frame, so it never has
+//! to be rolled back: walls before anything moves, wizards once they're done
+//! moving for the frame.
+//!
+//! Entries are handed back in the order they were hashed, walls by position
+//! and wizards by handle, so which of two overlapping wizards a projectile
+//! hits doesn't hang on query order.
+
+use bevy::{
+    ecs::query::{QueryData, QueryFilter, ROQueryItem},
+    prelude::*,
+    utils::HashMap,
+};
+
+use crate::{
+    components::*,
+    fixed::{Fixed, SimVec2},
+    mutators::RoundMutator,
+};
+
+/// Width of a cell, about the reach of the biggest thing checked against it
+const CELL_SIZE: Fixed = Fixed::from_int(2);
+
+/// Walls and wizards as of this frame
+#[derive(Resource, Default)]
+pub struct Broadphase {
+    pub walls: SpatialHash,
+    pub wizards: SpatialHash,
+}
+
+/// Entities by the cells of the arena they overlap
+#[derive(Default)]
+pub struct SpatialHash {
+    entities: Vec<Entity>,
+    /// Indices into `entities`, in the order they were inserted
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    fn clear(&mut self) {
+        self.entities.clear();
+        // the cells keep their allocations, the same ones fill up again next frame
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+    }
+
+    /// Lists `entity` in every cell the box from `min` to `max` overlaps
+    fn insert(&mut self, entity: Entity, min: SimVec2, max: SimVec2) {
+        let index = self.entities.len();
+        self.entities.push(entity);
+        for cell in cells(min, max) {
+            self.cells.entry(cell).or_default().push(index);
+        }
+    }
+
+    /// Everything whose box might be within `reach` of `pos`, each once
+    pub fn entities_near(&self, pos: SimVec2, reach: Fixed) -> Vec<Entity> {
+        let reach = SimVec2::splat(reach);
+        let mut found: Vec<usize> = cells(pos - reach, pos + reach)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+            .into_iter()
+            .map(|index| self.entities[index])
+            .collect()
+    }
+
+    /// What `query` has for everything that might be within `reach` of `pos`
+    pub fn near<'a, D: QueryData, F: QueryFilter>(
+        &self,
+        pos: SimVec2,
+        reach: Fixed,
+        query: &'a Query<D, F>,
+    ) -> impl Iterator<Item = ROQueryItem<'a, D>> {
+        self.entities_near(pos, reach)
+            .into_iter()
+            .filter_map(|entity| query.get(entity).ok())
+    }
+}
+
+/// The cell `pos` is in
+fn cell(pos: SimVec2) -> (i32, i32) {
+    ((pos.x / CELL_SIZE).floor(), (pos.y / CELL_SIZE).floor())
+}
+
+/// Every cell the box from `min` to `max` overlaps
+fn cells(min: SimVec2, max: SimVec2) -> impl Iterator<Item = (i32, i32)> {
+    let (min_x, min_y) = cell(min);
+    let (max_x, max_y) = cell(max);
+    (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+}
+
+/// Runs before anything moves into or bounces off a wall
+pub fn hash_walls(mut broadphase: ResMut<Broadphase>, walls: Query<(Entity, &Position, &Wall)>) {
+    let mut walls: Vec<_> = walls.iter().collect();
+    walls.sort_by_key(|(_, position, _)| (position.0.x, position.0.y));
+
+    broadphase.walls.clear();
+    for (entity, position, wall) in walls {
+        broadphase.walls.insert(
+            entity,
+            position.0 - wall.half_size,
+            position.0 + wall.half_size,
+        );
+    }
+}
+
+/// Runs once the wizards have moved, before anything is checked against them
+pub fn hash_wizards(
+    mut broadphase: ResMut<Broadphase>,
+    mutator: Res<RoundMutator>,
+    wizards: Query<(Entity, &Position, &Player)>,
+) {
+    let mut wizards: Vec<_> = wizards.iter().collect();
+    wizards.sort_by_key(|(_, _, player)| player.handle);
+
+    let radius = SimVec2::splat(mutator.wizard_radius());
+    broadphase.wizards.clear();
+    for (entity, position, _) in wizards {
+        broadphase
+            .wizards
+            .insert(entity, position.0 - radius, position.0 + radius);
+    }
+}
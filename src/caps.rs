@@ -0,0 +1,92 @@
+//! A cap on how many projectiles each wizard can have flying at once.
+//!
+//! Bullets only stop at walls, so a wizard who keeps firing into a corner of
+//! the arena or at a wind pushing them back can fill it up, and every one of
+//! them is another entity to simulate, roll back and snapshot each frame.
+//! Past [`Rules::max_projectiles`] the oldest of the wizard's go first.
+//!
+//! Which ones are oldest has to come out the same for both peers, so each
+//! projectile is stamped with the frame it showed up on, cap or no cap, and
+//! ties between ones fired on the same frame go by who fired them, where they
+//! are and where they're headed. That's [`FireOrder`], which everything that
+//! resolves projectiles one after another goes by too, rather than by query
+//! order, which nothing promises is the same on both peers.
+
+use std::cmp::Reverse;
+
+use bevy::prelude::*;
+
+use crate::{components::*, fixed::Fixed, round::SimFrame, rules::Rules};
+
+/// The simulation frame a projectile was first seen on
+#[derive(Component, Clone, Copy)]
+pub struct Fired(pub i32);
+
+/// Sorts projectiles oldest first, the same way for every peer. Ones that
+/// haven't been stamped yet count as fired this frame, two that come out the
+/// same are in the same place doing the same thing, so which goes first
+/// makes no difference.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FireOrder(i32, usize, Fixed, Fixed, Fixed, Fixed);
+
+impl FireOrder {
+    pub fn of(
+        fired: Option<&Fired>,
+        owner: &Owner,
+        position: &Position,
+        velocity: &Velocity,
+    ) -> Self {
+        let (pos, velocity) = (position.0, velocity.0);
+        Self(
+            fired.map_or(i32::MAX, |fired| fired.0),
+            owner.0,
+            pos.x,
+            pos.y,
+            velocity.x,
+            velocity.y,
+        )
+    }
+}
+
+/// The entities sorted by their keys, smallest first
+pub fn in_order<K: Ord>(keyed: impl Iterator<Item = (K, Entity)>) -> Vec<Entity> {
+    let mut keyed: Vec<_> = keyed.collect();
+    keyed.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    keyed.into_iter().map(|(_, entity)| entity).collect()
+}
+
+type Projectile = Or<(With<Bullet>, With<Fireball>, With<Hook>, With<ManaBurn>)>;
+
+/// Stamps whatever was fired this frame and drops each wizard's oldest past
+/// the cap. Runs once everything fired this frame has spawned, before any of
+/// it moves.
+pub fn cap_projectiles(
+    mut commands: Commands,
+    rules: Res<Rules>,
+    frame: Res<SimFrame>,
+    projectiles: Query<(Entity, &Owner, &Position, &Velocity, Option<&Fired>), Projectile>,
+) {
+    let mut projectiles: Vec<_> = projectiles
+        .iter()
+        .map(|(entity, owner, position, velocity, fired)| {
+            let fired = fired.copied().unwrap_or_else(|| {
+                commands.entity(entity).insert(Fired(frame.0));
+                Fired(frame.0)
+            });
+            let order = FireOrder::of(Some(&fired), owner, position, velocity);
+            ((owner.0, Reverse(order)), entity)
+        })
+        .collect();
+    if rules.max_projectiles == 0 {
+        return;
+    }
+    // each wizard's newest first
+    projectiles.sort_unstable_by_key(|(key, _)| *key);
+
+    let max = rules.max_projectiles as usize;
+    for owned in projectiles.chunk_by(|(a, _), (b, _)| a.0 == b.0) {
+        for (_, entity) in owned.iter().skip(max) {
+            commands.entity(*entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Text chat with the other peers.
+//!
+//! Enter opens the chat box, Enter again sends and Escape gives up. Messages
+//! go over a reliable channel of their own, so they get through while the
+//! handshake or the draft are busy reading theirs, and chat works from the
+//! moment the opponent connects until the match is left. The settings can
+//! mute everyone else. Direct and LAN matches have no matchbox socket, and so
+//! no chat.
+//!
+//! A coach's messages only reach the player they're watching, see
+//! [`crate::coach`].
+
+use std::collections::VecDeque;
+
+use bevy::{input::keyboard::KeyboardInput, prelude::*};
+use bevy_matchbox::{
+    matchbox_socket::{MultipleChannels, PeerId},
+    MatchboxSocket,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    coach::{CoachView, CoachedPlayers},
+    peer_messages::CHAT_CHANNEL,
+    settings::Settings,
+    GameState, TextInputFocus,
+};
+
+const MAX_MESSAGE_LEN: usize = 120;
+const MAX_LINES: usize = 6;
+/// How long a line stays up, unless the chat box is open
+const LINE_SECS: f32 = 10.;
+
+const BACKGROUND_COLOR: Color = Color::rgba(0., 0., 0., 0.6);
+const OWN_COLOR: Color = Color::WHITE;
+const OTHER_COLOR: Color = Color::rgb(0.7, 0.85, 1.);
+const COACH_COLOR: Color = Color::GOLD;
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatLog>()
+            .add_systems(Startup, spawn_chat_box)
+            .add_systems(OnEnter(GameState::Matchmaking), clear_chat)
+            .add_systems(
+                Update,
+                (
+                    receive_chat.run_if(resource_exists::<MatchboxSocket<MultipleChannels>>),
+                    type_chat,
+                    update_chat_box,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// What goes over the wire
+#[derive(Serialize, Deserialize, Debug)]
+struct ChatMessage {
+    text: String,
+    from_coach: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Sender {
+    Local,
+    /// The other player, or to the coach either of them
+    Peer(String),
+    Coach,
+}
+
+struct ChatLine {
+    from: Sender,
+    text: String,
+    /// When it came in, in seconds since startup
+    at: f32,
+}
+
+#[derive(Resource, Default)]
+struct ChatLog {
+    lines: VecDeque<ChatLine>,
+    /// What's been typed so far, `None` while the chat box is closed
+    typing: Option<String>,
+}
+
+impl ChatLog {
+    fn push(&mut self, from: Sender, text: String, now: f32) {
+        self.lines.push_back(ChatLine {
+            from,
+            text,
+            at: now,
+        });
+        if self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+}
+
+#[derive(Component)]
+struct ChatBox;
+
+/// The nth line from the top of the log
+#[derive(Component)]
+struct ChatLineText(usize);
+
+#[derive(Component)]
+struct ChatInputText;
+
+fn chat_state(state: &GameState) -> bool {
+    matches!(
+        state,
+        GameState::Matchmaking | GameState::InGame | GameState::MatchOver
+    )
+}
+
+fn spawn_chat_box(mut commands: Commands) {
+    let style = TextStyle {
+        font_size: 20.,
+        ..default()
+    };
+    commands
+        .spawn((
+            ChatBox,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    // clear of the coach's and the replay's status lines
+                    bottom: Val::Px(70.),
+                    left: Val::Px(10.),
+                    max_width: Val::Px(420.),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(6.)),
+                    row_gap: Val::Px(2.),
+                    ..default()
+                },
+                // over the draft too
+                z_index: ZIndex::Global(20),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for i in 0..MAX_LINES {
+                parent.spawn((ChatLineText(i), TextBundle::from_section("", style.clone())));
+            }
+            parent.spawn((ChatInputText, TextBundle::from_section("", style)));
+        });
+}
+
+fn clear_chat(mut log: ResMut<ChatLog>, mut focus: ResMut<TextInputFocus>) {
+    log.lines.clear();
+    if log.typing.take().is_some() {
+        focus.0 = false;
+    }
+}
+
+fn receive_chat(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    coached: Option<Res<CoachedPlayers>>,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    mut log: ResMut<ChatLog>,
+) {
+    let Ok(channel) = socket.get_channel(CHAT_CHANNEL) else {
+        return;
+    };
+    for (peer, bytes) in channel.receive() {
+        let message: ChatMessage = match bincode::deserialize(&bytes) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("dropping malformed chat message from {peer}: {err}");
+                continue;
+            }
+        };
+        if settings.mute_chat {
+            continue;
+        }
+        let text: String = message.text.chars().take(MAX_MESSAGE_LEN).collect();
+        let from = if message.from_coach {
+            Sender::Coach
+        } else {
+            Sender::Peer(peer_name(coached.as_deref(), peer))
+        };
+        log.push(from, text, time.elapsed_seconds());
+    }
+}
+
+/// Who a message is from, as far as we can tell
+fn peer_name(coached: Option<&CoachedPlayers>, peer: PeerId) -> String {
+    // the coach knows the players apart, to the players there's only the one
+    match coached.and_then(|coached| coached.handle(peer)) {
+        Some(handle) => format!("Player {}", handle + 1),
+        None => "Opponent".into(),
+    }
+}
+
+fn type_chat(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut keyboard: EventReader<KeyboardInput>,
+    mut focus: ResMut<TextInputFocus>,
+    mut log: ResMut<ChatLog>,
+    mut socket: Option<ResMut<MatchboxSocket<MultipleChannels>>>,
+    coached: Option<Res<CoachedPlayers>>,
+    view: Res<CoachView>,
+) {
+    let Some(typing) = log.typing.as_mut() else {
+        characters.clear();
+        keyboard.clear();
+        // somebody else's text field may just have let go of Enter
+        let can_open = socket.is_some() && chat_state(state.get()) && !focus.is_changed();
+        if can_open && !focus.0 && keys.just_pressed(KeyCode::Enter) {
+            log.typing = Some(String::new());
+            focus.0 = true;
+        }
+        return;
+    };
+
+    for event in characters.read() {
+        let room = MAX_MESSAGE_LEN.saturating_sub(typing.chars().count());
+        typing.extend(event.char.chars().filter(|c| !c.is_control()).take(room));
+    }
+
+    let mut done = false;
+    let mut send = false;
+    for event in keyboard.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        match event.key_code {
+            KeyCode::Backspace => {
+                typing.pop();
+            }
+            KeyCode::Escape => done = true,
+            KeyCode::Enter => {
+                done = true;
+                send = true;
+            }
+            _ => {}
+        }
+    }
+    if !done {
+        return;
+    }
+
+    let text = log.typing.take().unwrap_or_default().trim().to_string();
+    focus.0 = false;
+    if !send || text.is_empty() {
+        return;
+    }
+    let Some(socket) = socket.as_mut() else {
+        return;
+    };
+
+    // a coach only talks to the player they're watching
+    let to = coached
+        .as_deref()
+        .and_then(|coached| coached.0.get(view.0).copied());
+    let peers: Vec<PeerId> = match to {
+        Some(peer) => vec![peer],
+        None => socket.connected_peers().collect(),
+    };
+    let message = ChatMessage {
+        text: text.clone(),
+        from_coach: coached.is_some(),
+    };
+    let bytes = bincode::serialize(&message).expect("serializing to memory can't fail");
+    let Ok(channel) = socket.get_channel(CHAT_CHANNEL) else {
+        return;
+    };
+    for peer in peers {
+        channel.send(bytes.clone().into_boxed_slice(), peer);
+    }
+
+    let text = match to {
+        Some(_) => format!("(to Player {}) {text}", view.0 + 1),
+        None => text,
+    };
+    log.push(Sender::Local, text, time.elapsed_seconds());
+}
+
+fn update_chat_box(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    log: Res<ChatLog>,
+    mut boxes: Query<(&mut Visibility, &mut BackgroundColor), With<ChatBox>>,
+    mut lines: Query<(&ChatLineText, &mut Text), Without<ChatInputText>>,
+    mut inputs: Query<&mut Text, With<ChatInputText>>,
+) {
+    let now = time.elapsed_seconds();
+    let open = log.typing.is_some();
+    let shown: Vec<&ChatLine> = log
+        .lines
+        .iter()
+        .filter(|line| open || now - line.at < LINE_SECS)
+        .collect();
+
+    let visible = chat_state(state.get()) && (open || !shown.is_empty());
+    for (mut visibility, mut background) in &mut boxes {
+        let wanted = if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+        background.0 = if open { BACKGROUND_COLOR } else { Color::NONE };
+    }
+
+    for (line, mut text) in &mut lines {
+        let (value, color) = match shown.get(line.0) {
+            Some(line) => match &line.from {
+                Sender::Local => (format!("You: {}", line.text), OWN_COLOR),
+                Sender::Peer(name) => (format!("{name}: {}", line.text), OTHER_COLOR),
+                Sender::Coach => (format!("Coach: {}", line.text), COACH_COLOR),
+            },
+            None => (String::new(), OWN_COLOR),
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+            text.sections[0].style.color = color;
+        }
+    }
+
+    let input = match &log.typing {
+        Some(typing) => format!("> {typing}_"),
+        None => String::new(),
+    };
+    for mut text in &mut inputs {
+        if text.sections[0].value != input {
+            text.sections[0].value.clone_from(&input);
+        }
+    }
+}
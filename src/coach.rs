@@ -0,0 +1,250 @@
+//! A coach watching a match from a third seat.
+//!
+//! Players who leave a coach slot open, and whoever wants to coach them, look
+//! for each other in their own room on the matchbox server, three to a match.
+//! The coach tells the others so over the reliable channel, the two players
+//! then handshake and start their match as usual. The player with handle 0
+//! also adds the coach to its session as a GGRS spectator and sends it the
+//! confirmed inputs, which the coach plays through a spectator session of its
+//! own, started once the host passes on the match settings the players agreed
+//! on. The coach has no wizard and no say in the match, they flick the camera
+//! between the two players instead.
+
+use bevy::prelude::*;
+use bevy_ggrs::{ggrs::SessionBuilder, Session};
+use bevy_matchbox::{
+    matchbox_socket::{MultipleChannels, PeerId, WebRtcChannel},
+    MatchboxSocket,
+};
+
+use crate::{
+    components::*,
+    match_setup::MatchSettings,
+    not_typing,
+    peer_messages::{self, PeerMessage, RELIABLE_CHANNEL},
+    settings::Settings,
+    Config, GameState,
+};
+
+pub struct CoachPlugin;
+
+impl Plugin for CoachPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CoachView>()
+            .add_systems(OnEnter(GameState::Matchmaking), forget_coached_players)
+            .add_systems(
+                OnEnter(GameState::InGame),
+                spawn_coach_status.run_if(coaching),
+            )
+            .add_systems(OnExit(GameState::InGame), despawn_coach_status)
+            .add_systems(
+                Update,
+                (
+                    switch_coach_view.run_if(not_typing),
+                    follow_coached_player,
+                    update_coach_status,
+                )
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(coaching),
+            );
+    }
+}
+
+/// Who's who in a coached room
+#[derive(Clone, Copy)]
+pub enum Lineup {
+    /// Still waiting to hear from the coach
+    Waiting,
+    /// We play `opponent`, `coach` watches
+    Player { opponent: PeerId, coach: PeerId },
+    /// We're the coach, and the other two play
+    Coach,
+    /// Two coaches ended up in the same room, nobody gets a match out of it
+    Mismatched,
+}
+
+/// Works out the coach of a coached room, driven by `wait_for_players` every
+/// frame until the lineup is known
+#[derive(Default)]
+pub struct CoachedLobby {
+    /// Peers we've told we're the coach
+    told: Vec<PeerId>,
+    coaches: Vec<PeerId>,
+    /// Peers that finished their ping handshake before we knew who was who
+    handshakes_done: Vec<PeerId>,
+    /// Once we know, the handshake gets the channel to itself
+    settled: Option<Lineup>,
+    /// What the players agreed on, passed on by the host for a coach to start with
+    settings: Option<MatchSettings>,
+}
+
+impl CoachedLobby {
+    pub fn update(&mut self, socket: &mut MatchboxSocket<MultipleChannels>, coach: bool) -> Lineup {
+        if let Some(lineup) = self.settled {
+            return lineup;
+        }
+        let peers: Vec<PeerId> = socket.connected_peers().collect();
+        let Ok(channel) = socket.get_channel(RELIABLE_CHANNEL) else {
+            return Lineup::Waiting;
+        };
+
+        if coach {
+            for peer in &peers {
+                if !self.told.contains(peer) {
+                    PeerMessage::Coaching.send(channel, *peer);
+                    self.told.push(*peer);
+                }
+            }
+        }
+
+        for (peer, message) in peer_messages::receive(channel) {
+            match message {
+                PeerMessage::Coaching if !self.coaches.contains(&peer) => self.coaches.push(peer),
+                // the opponent may have worked the lineup out first
+                PeerMessage::Ping(seq) => PeerMessage::Pong(seq).send(channel, peer),
+                PeerMessage::HandshakeDone => self.handshakes_done.push(peer),
+                PeerMessage::MatchSettings(settings) if coach => self.settings = Some(settings),
+                _ => {}
+            }
+        }
+
+        let coaches: Vec<PeerId> = peers
+            .iter()
+            .copied()
+            .filter(|peer| self.coaches.contains(peer))
+            .collect();
+        let lineup = match (coach, coaches.as_slice()) {
+            (true, []) => Lineup::Coach,
+            (false, [coach]) => {
+                let opponent = peers.iter().copied().find(|peer| peer != coach);
+                opponent.map_or(Lineup::Waiting, |opponent| Lineup::Player {
+                    opponent,
+                    coach: *coach,
+                })
+            }
+            (false, []) => Lineup::Waiting,
+            _ => Lineup::Mismatched,
+        };
+        if let Lineup::Player { .. } = lineup {
+            self.settled = Some(lineup);
+        }
+        lineup
+    }
+
+    /// Whether `peer` is already done with the handshake we haven't started yet
+    pub fn handshake_done(&self, peer: PeerId) -> bool {
+        self.handshakes_done.contains(&peer)
+    }
+
+    /// The match settings the host sent us, once the players have agreed on them
+    pub fn settings(&self) -> Option<MatchSettings> {
+        self.settings
+    }
+}
+
+/// The players a coach is watching, by handle
+#[derive(Resource)]
+pub struct CoachedPlayers(pub Vec<PeerId>);
+
+impl CoachedPlayers {
+    pub fn handle(&self, peer: PeerId) -> Option<usize> {
+        self.0.iter().position(|player| *player == peer)
+    }
+}
+
+/// Follows the match between `players`, hosted by the one with handle 0
+pub fn start_coach_session(commands: &mut Commands, players: Vec<PeerId>, socket: WebRtcChannel) {
+    let session = SessionBuilder::<Config>::new()
+        .with_num_players(players.len())
+        .start_spectator_session(players[0], socket);
+    commands.insert_resource(Session::Spectator(session));
+    commands.insert_resource(CoachedPlayers(players));
+}
+
+fn forget_coached_players(mut commands: Commands) {
+    commands.remove_resource::<CoachedPlayers>();
+}
+
+/// Whether we're watching the match rather than playing in it
+pub fn is_coach(session: &Session<Config>) -> bool {
+    matches!(session, Session::Spectator(_))
+}
+
+fn coaching(session: Option<Res<Session<Config>>>) -> bool {
+    session.is_some_and(|session| is_coach(&session))
+}
+
+/// Handle of the wizard the coach's camera follows, and who they're talking to
+#[derive(Resource, Default)]
+pub struct CoachView(pub usize);
+
+#[derive(Component)]
+struct CoachStatus;
+
+fn spawn_coach_status(mut commands: Commands) {
+    commands.spawn((
+        CoachStatus,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 22.,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            left: Val::Px(10.),
+            ..default()
+        }),
+    ));
+}
+
+fn despawn_coach_status(mut commands: Commands, texts: Query<Entity, With<CoachStatus>>) {
+    for text in &texts {
+        commands.entity(text).despawn_recursive();
+    }
+}
+
+fn switch_coach_view(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut view: ResMut<CoachView>,
+    players: Query<&Player>,
+) {
+    if keys.just_pressed(KeyCode::Tab) {
+        view.0 = (view.0 + 1) % players.iter().count().max(1);
+    }
+}
+
+fn follow_coached_player(
+    view: Res<CoachView>,
+    players: Query<(&Player, &Position)>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    let Some((_, position)) = players.iter().find(|(player, _)| player.handle == view.0) else {
+        return;
+    };
+
+    let pos = position.0.to_vec2();
+    for mut transform in &mut cameras {
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
+
+fn update_coach_status(
+    view: Res<CoachView>,
+    settings: Res<Settings>,
+    mut texts: Query<&mut Text, With<CoachStatus>>,
+) {
+    let status = format!(
+        "COACHING  watching player {}\ntab: switch player",
+        view.0 + 1
+    );
+    for mut text in &mut texts {
+        if text.sections[0].value != status {
+            text.sections[0].value.clone_from(&status);
+            text.sections[0].style.color = settings.palette.color(view.0);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Damage resolution shared by everything that hurts a wizard.
+//!
+//! Every hit goes through the same stages so items and stats stack predictably:
+//! base damage -> armor reduction -> status modifiers -> final damage.
+//! All math is done on integers to keep the simulation deterministic.
+
+use bevy::prelude::*;
+
+use crate::{
+    components::*,
+    round::{Kill, RoundKills},
+    sim_events::SimEvents,
+    telemetry::{DamageEvent, MatchTelemetry},
+};
+
+/// How far back damage still counts towards a kill or an assist
+const ATTRIBUTION_WINDOW_FRAMES: i32 = 5 * 60;
+/// Extra damage per hit in the attacker's combo, in percent
+const COMBO_BONUS_PERCENT: u32 = 4;
+/// Combo past which hits don't get any stronger
+const MAX_COMBO_BONUS: u32 = 5;
+
+/// Runs a hit through the damage pipeline and returns what should be taken off
+/// the target's health.
+///
+/// `modifiers` are multipliers in percent (150 = +50%), applied in order after armor.
+pub fn resolve_damage(base: u32, armor: Armor, modifiers: &[u32]) -> u32 {
+    // each point of armor is worth a bit less than the previous one, so it never
+    // makes a wizard immune
+    let mut damage = base * 100 / (100 + armor.0);
+
+    for percent in modifiers {
+        damage = damage * percent / 100;
+    }
+
+    damage
+}
+
+/// A wizard taking damage, for whatever outside the simulation reacts to hits
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub victim: usize,
+    /// `None` for damage from the environment
+    pub attacker: Option<usize>,
+    pub amount: u32,
+    pub critical: bool,
+    pub fatal: bool,
+    pub pos: Vec2,
+}
+
+/// What [`apply_damage`] sends its hits to
+pub type HitQueue = SimEvents<Hit>;
+
+/// A wizard getting health back
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct Heal {
+    pub handle: usize,
+    pub amount: u32,
+    pub pos: Vec2,
+}
+
+pub type HealQueue = SimEvents<Heal>;
+
+/// Keeps wizards' [`Combo`]s while a damage system hands out hits.
+///
+/// Hits in the same frame all get the bonus of the combo as it was going into
+/// the frame, so the order wizards are looked at in doesn't matter. Once the
+/// system is done, everyone who was hit loses their combo and everyone else
+/// adds the hits they landed to theirs.
+pub struct ComboTracker {
+    before: Vec<(usize, u32)>,
+    landed: Vec<usize>,
+    taken: Vec<usize>,
+}
+
+impl ComboTracker {
+    pub fn new(combos: impl Iterator<Item = (usize, Combo)>) -> Self {
+        Self {
+            before: combos.map(|(handle, combo)| (handle, combo.0)).collect(),
+            landed: Vec::new(),
+            taken: Vec::new(),
+        }
+    }
+
+    /// Damage modifier for a hit on an enemy by `attacker`, in percent
+    pub fn bonus_percent(&self, attacker: usize) -> u32 {
+        let combo = self
+            .before
+            .iter()
+            .find(|(handle, _)| *handle == attacker)
+            .map_or(0, |(_, combo)| *combo);
+        100 + COMBO_BONUS_PERCENT * combo.min(MAX_COMBO_BONUS)
+    }
+
+    /// Only hits on enemies count towards a combo, but any hit breaks one
+    pub fn record(&mut self, attacker: usize, victim: usize, enemy: bool) {
+        if enemy {
+            self.landed.push(attacker);
+        }
+        self.taken.push(victim);
+    }
+
+    pub fn update(&self, handle: usize, combo: &mut Combo) {
+        if self.taken.contains(&handle) {
+            combo.0 = 0;
+            return;
+        }
+        let landed = self
+            .landed
+            .iter()
+            .filter(|&&attacker| attacker == handle)
+            .count();
+        combo.0 += landed as u32;
+    }
+}
+
+/// The wizard on the receiving end of a hit
+pub struct Target<'a> {
+    pub entity: Entity,
+    pub player: &'a Player,
+    pub health: &'a mut Health,
+    pub history: &'a mut DamageHistory,
+    pub pos: Vec2,
+}
+
+/// Takes already resolved damage off a wizard, killing them if that was the
+/// last of their health. Returns whether they died.
+///
+/// `attacker` is `None` for damage from the environment. If that (or the
+/// wizard's own spell) finishes them off, the kill goes to whoever hurt them last.
+pub fn apply_damage(
+    commands: &mut Commands,
+    kills: &mut RoundKills,
+    telemetry: &mut MatchTelemetry,
+    hits: &mut HitQueue,
+    frame: i32,
+    target: Target,
+    attacker: Option<usize>,
+    damage: u32,
+    critical: bool,
+) -> bool {
+    let victim = target.player.handle;
+    let enemy = attacker.filter(|&attacker| attacker != victim);
+    if let Some(enemy) = enemy {
+        target.history.record(enemy, frame);
+    }
+
+    target.health.0 = target.health.0.saturating_sub(damage);
+    telemetry.record_damage(DamageEvent {
+        frame,
+        attacker,
+        victim,
+        amount: damage,
+        critical,
+        fatal: target.health.0 == 0,
+    });
+    hits.send(
+        frame,
+        Hit {
+            victim,
+            attacker,
+            amount: damage,
+            critical,
+            fatal: target.health.0 == 0,
+            pos: target.pos,
+        },
+    );
+
+    if target.health.0 > 0 {
+        return false;
+    }
+
+    let since = frame - ATTRIBUTION_WINDOW_FRAMES;
+    let killer = enemy
+        .or_else(|| target.history.recent(since).next())
+        .unwrap_or(victim);
+    let mut assists = Vec::new();
+    for attacker in target.history.recent(since) {
+        if attacker != killer && !assists.contains(&attacker) {
+            assists.push(attacker);
+        }
+    }
+
+    commands.entity(target.entity).despawn_recursive();
+    kills.0.push(Kill {
+        killer,
+        victim,
+        assists,
+    });
+    true
+}
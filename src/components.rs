@@ -1,8 +1,9 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::{decoy::DECOY_DELAY_FRAMES, fixed::SimVec2};
 
-
-#[derive(Component)]
+#[derive(Component, Clone, Copy)]
 pub struct Player {
     pub handle: usize,
 }
@@ -10,8 +11,290 @@ pub struct Player {
 #[derive(Component, Clone, Copy)]
 pub struct BulletReady(pub bool);
 
-#[derive(Component)]
+/// Frames until the wizard can fire again
+#[derive(Component, Clone, Copy)]
+pub struct FireCooldown(pub u32);
+
+/// Frames until the wizard can cast their spell again
+#[derive(Component, Clone, Copy)]
+pub struct SpellCooldown(pub u32);
+
+/// The spells a wizard can cast, cycled through with the next spell key
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Hash, Serialize, Deserialize)]
+pub enum Spell {
+    #[default]
+    Fireball,
+    Hook,
+    Decoy,
+    ManaBurn,
+    Telekinesis,
+}
+
+impl Spell {
+    pub const ALL: [Spell; 5] = [
+        Spell::Fireball,
+        Spell::Hook,
+        Spell::Decoy,
+        Spell::ManaBurn,
+        Spell::Telekinesis,
+    ];
+
+    pub fn next(self) -> Self {
+        match self {
+            Spell::Fireball => Spell::Hook,
+            Spell::Hook => Spell::Decoy,
+            Spell::Decoy => Spell::ManaBurn,
+            Spell::ManaBurn => Spell::Telekinesis,
+            Spell::Telekinesis => Spell::Fireball,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Spell::Fireball => "Fireball",
+            Spell::Hook => "Hook",
+            Spell::Decoy => "Mirror Image",
+            Spell::ManaBurn => "Mana Burn",
+            Spell::Telekinesis => "Telekinesis",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// A handful of spells, a bit per [`Spell`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SpellSet(u8);
+
+impl Default for SpellSet {
+    fn default() -> Self {
+        Spell::ALL
+            .into_iter()
+            .fold(Self::NONE, |set, spell| set.with(spell))
+    }
+}
+
+impl SpellSet {
+    pub const NONE: Self = Self(0);
+
+    pub fn with(self, spell: Spell) -> Self {
+        Self(self.0 | spell.bit())
+    }
+
+    pub fn contains(self, spell: Spell) -> bool {
+        self.0 & spell.bit() != 0
+    }
+
+    pub fn first(self) -> Option<Spell> {
+        Spell::ALL.into_iter().find(|spell| self.contains(*spell))
+    }
+
+    /// The first spell in the set that comes after `spell` in the cycle,
+    /// `spell` itself if the set is empty
+    pub fn next_after(self, spell: Spell) -> Spell {
+        let mut next = spell.next();
+        for _ in 0..Spell::ALL.len() {
+            if self.contains(next) {
+                return next;
+            }
+            next = next.next();
+        }
+        spell
+    }
+}
+
+/// What spells are paid with, see [`crate::mana`]
+#[derive(Component, Clone, Copy, Hash, Serialize, Deserialize)]
+pub struct Mana {
+    pub amount: u32,
+    /// Mana doesn't come back before this frame
+    pub regen_paused_until: i32,
+}
+
+/// The super a wizard lets loose with a full meter, see [`crate::ultimate`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Serialize, Deserialize)]
+pub enum UltimateKind {
+    MeteorStorm,
+    TimeFreeze,
+}
+
+/// Meter filled by dealing and taking damage
+#[derive(Component, Clone, Copy, Hash, Serialize, Deserialize)]
+pub struct Ultimate {
+    pub kind: UltimateKind,
+    pub charge: u32,
+}
+
+/// Which spell the cast key throws
+#[derive(Component, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Spellbook {
+    pub selected: Spell,
+    /// Whether the next spell key was down last frame, so holding it only switches once
+    pub next_held: bool,
+}
+
+/// Forced movement towards the wizard with handle `by`, taking over from the
+/// wizard's own movement until `frames_left` runs out, see [`crate::spells::pull_wizards`]
+#[derive(Component, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Pulled {
+    pub by: usize,
+    pub frames_left: u32,
+}
+
+/// Velocity from explosions, added on top of regular movement and decaying each frame
+#[derive(Component, Clone, Copy, Default)]
+pub struct Knockback(pub SimVec2);
+
+/// Wizard standing in for a target in the training range, see [`crate::training`]
+#[derive(Component, Clone, Copy)]
+pub struct Dummy;
+
+#[derive(Component, Clone, Copy, Hash)]
+pub struct Health(pub u32);
+
+/// Hits landed on enemies in a row without taking one, see [`crate::combat::ComboTracker`]
+#[derive(Component, Clone, Copy, Default, Hash)]
+pub struct Combo(pub u32);
+
+/// Inputs are ignored until `frames_left` runs out, see [`crate::stun`]
+#[derive(Component, Clone, Copy, Default, Hash, Serialize, Deserialize)]
+pub struct Stun {
+    pub frames_left: u32,
+    /// Stuns taken within the diminishing returns window
+    pub recent: u32,
+    /// Frame of the last stun that landed
+    pub last_frame: i32,
+}
+
+/// Reduces incoming damage, see [`crate::combat::resolve_damage`]
+#[derive(Component, Clone, Copy, Default)]
+pub struct Armor(pub u32);
+
+/// Someone who hurt a wizard, and when
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct DamageSource {
+    /// Player handle, a byte is plenty and keeps the history small in every
+    /// rollback snapshot
+    pub attacker: u8,
+    pub frame: i32,
+}
+
+/// The last few enemies that damaged a wizard, newest first. Used to hand out
+/// credit for environmental kills and assists.
+#[derive(Component, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DamageHistory(pub [Option<DamageSource>; 4]);
+
+impl DamageHistory {
+    pub fn record(&mut self, attacker: usize, frame: i32) {
+        self.0.rotate_right(1);
+        self.0[0] = Some(DamageSource {
+            attacker: attacker as u8,
+            frame,
+        });
+    }
+
+    /// Attackers that hit the wizard at or after `since`, newest first
+    pub fn recent(&self, since: i32) -> impl Iterator<Item = usize> + '_ {
+        self.0
+            .iter()
+            .flatten()
+            .filter(move |source| source.frame >= since)
+            .map(|source| source.attacker as usize)
+    }
+}
+
+/// Simulation frame the wizard last dealt or took damage, used to gate regeneration
+#[derive(Component, Clone, Copy)]
+pub struct LastCombatFrame(pub i32);
+
+/// Blocks movement and projectiles. `half_size` is the extent of the box around its position.
+#[derive(Component, Clone, Copy)]
+pub struct Wall {
+    pub half_size: SimVec2,
+}
+
+/// Wall that goes off when it's shot or caught in an explosion, see [`crate::barrels`]
+#[derive(Component, Clone, Copy)]
+pub struct Barrel;
+
+/// Wall that explosions smash
+#[derive(Component, Clone, Copy)]
+pub struct Destructible;
+
+/// Barrel or crate thrown by telekinesis, flying instead of being a wall
+#[derive(Component, Clone, Copy)]
+pub struct Flung {
+    pub frames_left: u32,
+}
+
+#[derive(Component, Clone, Copy)]
 pub struct Bullet;
 
+/// Projectile that explodes on impact or when it runs out of range
+#[derive(Component, Clone, Copy)]
+pub struct Fireball {
+    pub frames_left: u32,
+}
+
+/// Projectile that reels in the first enemy it hits
+#[derive(Component, Clone, Copy)]
+pub struct Hook {
+    pub frames_left: u32,
+}
+
+/// Projectile that drains the mana of the first enemy it hits
+#[derive(Component, Clone, Copy)]
+pub struct ManaBurn {
+    pub frames_left: u32,
+}
+
+/// Meteors raining down on the enemies of `Owner` until `frames_left` runs out
+#[derive(Component, Clone, Copy)]
+pub struct MeteorStorm {
+    pub frames_left: u32,
+}
+
+/// Fake wizard walking the steps wizard `owner` took a moment ago, see [`crate::decoy`]
+#[derive(Component, Clone, Copy)]
+pub struct Decoy {
+    pub owner: usize,
+    /// Where the caster was over the last `DECOY_DELAY_FRAMES`, oldest first
+    pub trail: [SimVec2; DECOY_DELAY_FRAMES],
+    /// The position from the trail the decoy last stepped after
+    pub followed: SimVec2,
+    pub frames_left: u32,
+}
+
+/// Area damage and knockback, dealt on the first frame and then left around as a visual
+#[derive(Component, Clone, Copy)]
+pub struct Explosion {
+    pub frames_left: u32,
+}
+
+/// The projectile rolled a critical hit when it was fired
+#[derive(Component, Clone, Copy)]
+pub struct Critical;
+
+/// Which side a wizard (or their projectile) is on. In 2v2, even and odd handles team up.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct Team(pub usize);
+
+/// Handle of the player who fired a projectile
+#[derive(Component, Clone, Copy)]
+pub struct Owner(pub usize);
+
+/// Where something in the arena is. This is what the simulation moves, rolls
+/// back and checksums, the `Transform` is only put here to draw it, see
+/// [`crate::place_in_arena`].
+#[derive(Component, Clone, Copy, Default, Hash)]
+pub struct Position(pub SimVec2);
+
+/// Distance a projectile covers each frame, on top of the wind
+#[derive(Component, Clone, Copy, Default)]
+pub struct Velocity(pub SimVec2);
+
+/// Which way a wizard last moved, and fires and casts in. Always a unit vector.
 #[derive(Component, Clone, Copy)]
-pub struct MoveDir(pub Vec2);
\ No newline at end of file
+pub struct Facing(pub SimVec2);
@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+
+use crate::{
+    in_arena,
+    mutators::RoundMutator,
+    round::{RoundPhase, COUNTDOWN_FRAMES},
+    GameState,
+};
+
+/// How long "FIGHT!" stays up after the countdown ends, in seconds
+const FIGHT_TEXT_DURATION: f32 = 0.6;
+
+pub struct CountdownPlugin;
+
+impl Plugin for CountdownPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::InGame), spawn_countdown_text)
+            .add_systems(OnEnter(GameState::Replay), spawn_countdown_text)
+            .add_systems(OnExit(GameState::InGame), despawn_countdown_text)
+            .add_systems(OnExit(GameState::Replay), despawn_countdown_text)
+            .add_systems(Update, update_countdown_text.run_if(in_arena));
+    }
+}
+
+#[derive(Component)]
+struct CountdownText;
+
+fn spawn_countdown_text(mut commands: Commands) {
+    commands
+        .spawn((
+            CountdownText,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            // the number, and the round's mutator under it
+            parent.spawn(
+                TextBundle::from_sections([
+                    TextSection::from_style(TextStyle {
+                        font_size: 120.,
+                        color: Color::WHITE,
+                        ..default()
+                    }),
+                    TextSection::from_style(TextStyle {
+                        font_size: 40.,
+                        color: Color::rgb(1., 0.8, 0.3),
+                        ..default()
+                    }),
+                ])
+                .with_text_justify(JustifyText::Center),
+            );
+        });
+}
+
+fn despawn_countdown_text(mut commands: Commands, texts: Query<Entity, With<CountdownText>>) {
+    for text in &texts {
+        commands.entity(text).despawn_recursive();
+    }
+}
+
+fn update_countdown_text(
+    phase: Res<RoundPhase>,
+    mutator: Res<RoundMutator>,
+    time: Res<Time>,
+    mut fight_shown_at: Local<Option<f32>>,
+    roots: Query<&Children, With<CountdownText>>,
+    mut texts: Query<&mut Text>,
+) {
+    let label = match *phase {
+        RoundPhase::Countdown { frames_left } => {
+            *fight_shown_at = None;
+            // 180..=121 -> "3", 120..=61 -> "2", 60..=0 -> "1"
+            let seconds_left = frames_left.div_ceil(COUNTDOWN_FRAMES / 3).max(1);
+            seconds_left.to_string()
+        }
+        RoundPhase::Fighting => {
+            let shown_at = *fight_shown_at.get_or_insert(time.elapsed_seconds());
+            if time.elapsed_seconds() - shown_at < FIGHT_TEXT_DURATION {
+                "FIGHT!".to_string()
+            } else {
+                String::new()
+            }
+        }
+        _ => String::new(),
+    };
+    let mutator_label = match (*phase, mutator.0) {
+        (RoundPhase::Countdown { .. }, Some(mutator)) => format!("\n{}", mutator.name()),
+        _ => String::new(),
+    };
+
+    for children in &roots {
+        if let Ok(mut text) = texts.get_mut(children[0]) {
+            if text.sections[0].value != label {
+                text.sections[0].value = label.clone();
+            }
+            if text.sections[1].value != mutator_label {
+                text.sections[1].value = mutator_label.clone();
+            }
+        }
+    }
+}
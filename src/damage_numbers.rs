@@ -0,0 +1,142 @@
+//! Floating numbers over wizards for the damage they take and the health they
+//! get back, which drift up and fade out over a second.
+//!
+//! Driven by the confirmed [`Hit`] and [`Heal`] events, so a hit a rollback
+//! takes back never shows a number. Regeneration comes in a point at a time,
+//! those add up on one number instead of stacking a pile of "+1"s.
+
+use bevy::prelude::*;
+
+use crate::{
+    combat::{Heal, Hit},
+    GameState,
+};
+
+/// Seconds a number stays up for
+const NUMBER_SECS: f32 = 1.;
+/// Heals this soon after a heal number went up add up on it
+const HEAL_MERGE_SECS: f32 = 0.5;
+/// World units per second the numbers drift up by
+const RISE_SPEED: f32 = 1.2;
+/// Above the wizard's head
+const NUMBER_OFFSET: Vec2 = Vec2::new(0., 0.6);
+/// Above everything in the arena
+const NUMBER_Z: f32 = 10.;
+const FONT_SIZE: f32 = 40.;
+const DAMAGE_SCALE: f32 = 0.02;
+const CRITICAL_SCALE: f32 = 0.03;
+const DAMAGE_COLOR: Color = Color::WHITE;
+const CRITICAL_COLOR: Color = Color::ORANGE;
+const HEAL_COLOR: Color = Color::rgb(0.4, 1., 0.4);
+
+pub struct DamageNumbersPlugin;
+
+impl Plugin for DamageNumbersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (spawn_hit_numbers, spawn_heal_numbers, float_numbers)
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(OnExit(GameState::InGame), despawn_numbers);
+    }
+}
+
+#[derive(Component)]
+struct DamageNumber {
+    age: f32,
+}
+
+/// Running total of a wizard's regeneration
+#[derive(Component)]
+struct HealNumber {
+    handle: usize,
+    amount: u32,
+}
+
+fn number_bundle(text: String, color: Color, scale: f32, pos: Vec2) -> Text2dBundle {
+    Text2dBundle {
+        text: Text::from_section(
+            text,
+            TextStyle {
+                font_size: FONT_SIZE,
+                color,
+                ..default()
+            },
+        ),
+        transform: Transform::from_translation((pos + NUMBER_OFFSET).extend(NUMBER_Z))
+            .with_scale(Vec3::splat(scale)),
+        ..default()
+    }
+}
+
+fn spawn_hit_numbers(mut commands: Commands, mut hits: EventReader<Hit>) {
+    for hit in hits.read() {
+        let (text, color, scale) = if hit.critical {
+            (format!("{}!", hit.amount), CRITICAL_COLOR, CRITICAL_SCALE)
+        } else {
+            (hit.amount.to_string(), DAMAGE_COLOR, DAMAGE_SCALE)
+        };
+        commands.spawn((
+            DamageNumber { age: 0. },
+            number_bundle(text, color, scale, hit.pos),
+        ));
+    }
+}
+
+fn spawn_heal_numbers(
+    mut commands: Commands,
+    mut heals: EventReader<Heal>,
+    mut numbers: Query<(&mut HealNumber, &DamageNumber, &mut Text)>,
+) {
+    for heal in heals.read() {
+        let merge_with = numbers.iter_mut().find(|(number, damage_number, _)| {
+            number.handle == heal.handle && damage_number.age < HEAL_MERGE_SECS
+        });
+        if let Some((mut number, _, mut text)) = merge_with {
+            number.amount += heal.amount;
+            text.sections[0].value = format!("+{}", number.amount);
+            continue;
+        }
+
+        commands.spawn((
+            HealNumber {
+                handle: heal.handle,
+                amount: heal.amount,
+            },
+            DamageNumber { age: 0. },
+            number_bundle(
+                format!("+{}", heal.amount),
+                HEAL_COLOR,
+                DAMAGE_SCALE,
+                heal.pos,
+            ),
+        ));
+    }
+}
+
+fn float_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut numbers: Query<(Entity, &mut DamageNumber, &mut Transform, &mut Text)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut number, mut transform, mut text) in &mut numbers {
+        number.age += dt;
+        if number.age >= NUMBER_SECS {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.translation.y += RISE_SPEED * dt;
+        // solid for the first half, fading out over the second
+        let alpha = (2. - 2. * number.age / NUMBER_SECS).min(1.);
+        text.sections[0].style.color.set_a(alpha);
+    }
+}
+
+fn despawn_numbers(mut commands: Commands, numbers: Query<Entity, With<DamageNumber>>) {
+    for entity in &numbers {
+        commands.entity(entity).despawn();
+    }
+}
@@ -0,0 +1,233 @@
+//! Mirror image: the caster splits off two decoys that walk the same steps a
+//! moment later, and pop the first time anything of the other team hits them.
+//!
+//! Decoys are rolled back like everything else in the arena, so both peers
+//! always see the same fakes in the same places. They look like the wizard
+//! down to the health bar, which shows what the caster had when they were cast.
+
+use bevy::prelude::*;
+use bevy_ggrs::AddRollbackCommandExtension;
+
+use crate::{
+    assets::WizardSprites,
+    barrels::flung_radius,
+    broadphase::Broadphase,
+    components::*,
+    fixed::{Fixed, SimVec2},
+    graphics::YSort,
+    hits_wall,
+    mutators::RoundMutator,
+    particles::{Burst, BurstQueue},
+    round::SimFrame,
+    sfx::{Sfx, SfxQueue},
+    spells::{spawn_explosion, EXPLOSION_FRAMES, FIREBALL_RADIUS, HOOK_RADIUS},
+    step_wizard, wizard_look, BULLET_RADIUS, PLAYER_COLORS, PLAYER_RADIUS,
+};
+
+/// How far behind the caster the decoys walk
+pub const DECOY_DELAY_FRAMES: usize = 12;
+/// How long the decoys last if nobody pops them
+const DECOY_FRAMES: u32 = 5 * 60;
+/// Distance to either side of the caster the decoys appear at
+const DECOY_SPREAD: Fixed = Fixed::from_f32(1.2);
+
+/// Two decoys of the caster, one to either side of them
+pub fn spawn_decoys(
+    commands: &mut Commands,
+    owner: Owner,
+    team: Team,
+    pos: SimVec2,
+    facing: SimVec2,
+    health: Health,
+    broadphase: &Broadphase,
+    walls: &Query<(&Position, &Wall), Without<Player>>,
+) {
+    let side = facing.perp();
+    let radius = Fixed::from_f32(PLAYER_RADIUS);
+    for offset in [side * DECOY_SPREAD, -side * DECOY_SPREAD] {
+        let decoy_pos = step_wizard(pos, offset, |pos| {
+            broadphase
+                .walls
+                .near(pos, radius, walls)
+                .any(|(wall_pos, wall)| hits_wall(pos, radius, wall_pos.0, wall))
+        });
+        spawn_decoy(
+            commands,
+            Decoy {
+                owner: owner.0,
+                trail: [pos; DECOY_DELAY_FRAMES],
+                followed: pos,
+                frames_left: DECOY_FRAMES,
+            },
+            team,
+            decoy_pos,
+            health,
+        );
+    }
+}
+
+pub fn spawn_decoy(
+    commands: &mut Commands,
+    decoy: Decoy,
+    team: Team,
+    pos: SimVec2,
+    health: Health,
+) {
+    let color = PLAYER_COLORS[decoy.owner % PLAYER_COLORS.len()];
+    commands
+        .spawn((
+            decoy,
+            Owner(decoy.owner),
+            team,
+            health,
+            Position(pos),
+            decoy_look(pos, color),
+        ))
+        .add_rollback();
+}
+
+/// Drawn just like the wizard it's copying, in its caster's `color`
+pub fn decoy_look(pos: SimVec2, color: Color) -> (YSort, SpriteBundle) {
+    wizard_look(pos, color)
+}
+
+/// Walks each decoy the step its caster took `DECOY_DELAY_FRAMES` ago, and
+/// lets it go when it runs out of time or its caster is gone
+pub fn move_decoys(
+    mut commands: Commands,
+    frame: Res<SimFrame>,
+    mut bursts: ResMut<BurstQueue>,
+    mut decoys: Query<(Entity, &mut Decoy, &mut Position), Without<Player>>,
+    players: Query<(&Player, &Position)>,
+    walls: Query<(&Position, &Wall), (Without<Player>, Without<Decoy>)>,
+    broadphase: Res<Broadphase>,
+) {
+    let radius = Fixed::from_f32(PLAYER_RADIUS);
+    for (entity, mut decoy, mut position) in &mut decoys {
+        let caster = players
+            .iter()
+            .find(|(player, _)| player.handle == decoy.owner);
+        decoy.frames_left = decoy.frames_left.saturating_sub(1);
+        let Some((_, caster_pos)) = caster.filter(|_| decoy.frames_left > 0) else {
+            pop_decoy(
+                &mut commands,
+                &mut bursts,
+                frame.0,
+                entity,
+                &decoy,
+                position.0.to_vec2(),
+            );
+            continue;
+        };
+
+        let delayed = decoy.trail[0];
+        decoy.trail.rotate_left(1);
+        decoy.trail[DECOY_DELAY_FRAMES - 1] = caster_pos.0;
+        let step = delayed - decoy.followed;
+        decoy.followed = delayed;
+        if step == SimVec2::ZERO {
+            continue;
+        }
+
+        position.0 = step_wizard(position.0, step, |pos| {
+            broadphase
+                .walls
+                .near(pos, radius, &walls)
+                .any(|(wall_pos, wall)| hits_wall(pos, radius, wall_pos.0, wall))
+        });
+    }
+}
+
+/// Enemy bullets, hooks, fireballs and flung props that run into a decoy pop
+/// it and are used up, fireballs and barrels still go off. Explosions are handled in
+/// [`crate::spells::resolve_explosions`].
+pub fn pop_decoys(
+    mut commands: Commands,
+    frame: Res<SimFrame>,
+    sprites: Res<WizardSprites>,
+    mut sfx: ResMut<SfxQueue>,
+    mut bursts: ResMut<BurstQueue>,
+    mutator: Res<RoundMutator>,
+    decoys: Query<(Entity, &Decoy, &Position, &Team)>,
+    projectiles: Query<
+        (
+            Entity,
+            &Position,
+            &Owner,
+            &Team,
+            Has<Bullet>,
+            Has<Fireball>,
+            Has<Flung>,
+            Has<Barrel>,
+        ),
+        Or<(With<Bullet>, With<Hook>, With<Fireball>, With<Flung>)>,
+    >,
+) {
+    let mut spent = Vec::new();
+    for (entity, decoy, decoy_pos, decoy_team) in &decoys {
+        let decoy_pos = decoy_pos.0;
+        let hit_by = projectiles
+            .iter()
+            .filter(|(projectile, ..)| !spent.contains(projectile))
+            .find(|(_, position, _, team, bullet, fireball, flung, barrel)| {
+                let radius = if *bullet {
+                    Fixed::from_f32(BULLET_RADIUS)
+                } else if *fireball {
+                    Fixed::from_f32(FIREBALL_RADIUS)
+                } else if *flung {
+                    flung_radius(*barrel)
+                } else {
+                    Fixed::from_f32(HOOK_RADIUS)
+                };
+                *team != decoy_team
+                    && position
+                        .0
+                        .within(decoy_pos, mutator.wizard_radius() + radius)
+            });
+        let Some((projectile, position, owner, team, _, fireball, _, barrel)) = hit_by else {
+            continue;
+        };
+
+        spent.push(projectile);
+        commands.entity(projectile).despawn_recursive();
+        if fireball || barrel {
+            let pos = position.0;
+            spawn_explosion(
+                &mut commands,
+                &sprites,
+                *owner,
+                *team,
+                pos,
+                EXPLOSION_FRAMES,
+            );
+        }
+        let decoy_pos = decoy_pos.to_vec2();
+        sfx.play(frame.0, Sfx::Impact, owner.0, decoy_pos);
+        pop_decoy(
+            &mut commands,
+            &mut bursts,
+            frame.0,
+            entity,
+            decoy,
+            decoy_pos,
+        );
+    }
+}
+
+pub fn pop_decoy(
+    commands: &mut Commands,
+    bursts: &mut BurstQueue,
+    frame: i32,
+    entity: Entity,
+    decoy: &Decoy,
+    pos: Vec2,
+) {
+    commands.entity(entity).despawn_recursive();
+    bursts.send(
+        frame,
+        Burst::Decoy {
+            pos,
+            handle: decoy.owner,
+        },
+    );
+}
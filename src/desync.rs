@@ -0,0 +1,166 @@
+//! Noticing when the peers' simulations stop agreeing.
+//!
+//! GGRS compares checksums of the rollback state (positions, health and the
+//! RNG, see `SimulationPlugin`) with the peer every few confirmed frames. If
+//! they ever differ, the match is over: the session is dropped, the arena is
+//! frozen as it was and the player is told what happened.
+
+use bevy::prelude::*;
+use bevy_ggrs::{ggrs::GgrsEvent, Rollback, Session};
+
+use crate::{
+    input::gamepad_just_pressed,
+    prompts::{Control, InputDevice},
+    round::teardown_match,
+    Config, GameState, SessionEvent,
+};
+
+/// Confirmed frames between checksum comparisons
+pub const DESYNC_CHECK_INTERVAL: u32 = 10;
+
+pub struct DesyncPlugin;
+
+impl Plugin for DesyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, detect_desyncs.run_if(in_state(GameState::InGame)))
+            .add_systems(OnEnter(GameState::Desynced), spawn_desync_screen)
+            .add_systems(OnExit(GameState::Desynced), despawn_desync_screen)
+            .add_systems(
+                Update,
+                (
+                    leave_desynced_match,
+                    update_leave_hint.run_if(resource_changed::<InputDevice>),
+                )
+                    .run_if(in_state(GameState::Desynced)),
+            );
+    }
+}
+
+/// Frame the peers' checksums first differed on
+#[derive(Resource, Clone, Copy, Debug)]
+struct Desync {
+    frame: i32,
+}
+
+#[derive(Component)]
+struct DesyncScreen;
+
+#[derive(Component)]
+struct LeaveHint;
+
+fn detect_desyncs(
+    mut commands: Commands,
+    mut events: EventReader<SessionEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for event in events.read() {
+        if let GgrsEvent::DesyncDetected {
+            frame,
+            local_checksum,
+            remote_checksum,
+            addr,
+        } = &event.0
+        {
+            error!(
+                "desync with {addr} at frame {frame}: local checksum {local_checksum:x}, \
+                 remote {remote_checksum:x}"
+            );
+            commands.insert_resource(Desync { frame: *frame });
+            next_state.set(GameState::Desynced);
+        }
+    }
+}
+
+fn leave_hint(device: InputDevice) -> String {
+    match device {
+        InputDevice::Touch => "Tap to go back to the menu".into(),
+        device => format!(
+            "Press {} to go back to the menu",
+            device.glyph(Control::Leave)
+        ),
+    }
+}
+
+fn spawn_desync_screen(mut commands: Commands, desync: Res<Desync>, device: Res<InputDevice>) {
+    // stop simulating, nothing after this point means anything
+    commands.remove_resource::<Session<Config>>();
+
+    commands
+        .spawn((
+            DesyncScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.7).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                format!("Desync detected at frame {}", desync.frame),
+                TextStyle {
+                    font_size: 48.,
+                    color: Color::ORANGE_RED,
+                    ..default()
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                "Your game and your opponent's no longer agree, so the match can't go on",
+                TextStyle {
+                    font_size: 24.,
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                LeaveHint,
+                TextBundle::from_section(
+                    leave_hint(*device),
+                    TextStyle {
+                        font_size: 22.,
+                        color: Color::rgb(0.8, 0.8, 0.8),
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
+
+fn despawn_desync_screen(mut commands: Commands, screens: Query<Entity, With<DesyncScreen>>) {
+    for screen in &screens {
+        commands.entity(screen).despawn_recursive();
+    }
+    commands.remove_resource::<Desync>();
+}
+
+fn update_leave_hint(device: Res<InputDevice>, mut hints: Query<&mut Text, With<LeaveHint>>) {
+    for mut text in &mut hints {
+        text.sections[0].value = leave_hint(*device);
+    }
+}
+
+fn leave_desynced_match(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    touches: Res<Touches>,
+    rollback_entities: Query<Entity, With<Rollback>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let leave = keys.just_pressed(KeyCode::Escape)
+        || gamepad_just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::Start)
+        || touches.any_just_pressed();
+    if !leave {
+        return;
+    }
+
+    teardown_match(&mut commands, &rollback_entities);
+    next_state.set(GameState::Matchmaking);
+}
@@ -0,0 +1,470 @@
+//! Playing over plain UDP straight to an address, without WebRTC or a
+//! signaling server.
+//!
+//! Native only. One player hosts and listens on `DEFAULT_PORT` (or
+//! `--listen <port>`), the other types the host's `address:port` into the
+//! matchmaking screen (or passes `--connect <address:port>`). The host is
+//! player one and learns the other player's address from their first packet.
+
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use bevy::{input::keyboard::KeyboardInput, prelude::*};
+use bevy_ggrs::ggrs::{Message, NonBlockingSocket, PlayerType, UdpNonBlockingSocket};
+use bevy_matchbox::{
+    matchbox_socket::{MultipleChannels, PeerId},
+    MatchboxSocket,
+};
+use uuid::Uuid;
+
+use crate::{
+    matchmaking::{InputDelay, MatchInputDelay},
+    start_matchbox_socket, start_p2p_session, wants_matchmaking, GameState, TextInputFocus,
+};
+
+const DEFAULT_PORT: u16 = 7000;
+/// Stands in for the other player's address, GGRS only ever talks to the one
+const REMOTE_PEER: PeerId = PeerId(Uuid::from_u128(1));
+/// Input delay when it's left on automatic, there's no handshake to measure the ping with
+const DIRECT_INPUT_DELAY: usize = 2;
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const FIELD_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+const FIELD_FOCUSED_COLOR: Color = Color::rgb(0.2, 0.35, 0.6);
+
+pub struct DirectConnectPlugin;
+
+impl Plugin for DirectConnectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AddressEntry>()
+            .add_systems(Startup, direct_from_args)
+            .add_systems(
+                OnEnter(GameState::Matchmaking),
+                (
+                    spawn_direct_connect_panel.run_if(wants_matchmaking),
+                    connect_from_args
+                        .after(start_matchbox_socket)
+                        .run_if(resource_exists::<DirectFromArgs>),
+                ),
+            )
+            .add_systems(OnExit(GameState::Matchmaking), despawn_direct_connect_panel)
+            .add_systems(
+                Update,
+                (
+                    direct_connect_buttons,
+                    type_address,
+                    wait_for_direct_peer.run_if(resource_exists::<DirectListener>),
+                )
+                    .run_if(in_state(GameState::Matchmaking)),
+            );
+    }
+}
+
+/// `--listen [port]` or `--connect <address:port>` on the command line
+#[derive(Resource)]
+enum DirectFromArgs {
+    Listen(u16),
+    Connect(String),
+}
+
+/// What's been typed into the address field
+#[derive(Resource, Default)]
+struct AddressEntry {
+    text: String,
+    focused: bool,
+}
+
+/// Hosting, waiting for the other player's first packet
+#[derive(Resource)]
+struct DirectListener {
+    /// Handed to GGRS once somebody joins
+    socket: Option<UdpNonBlockingSocket>,
+    port: u16,
+}
+
+#[derive(Component)]
+struct DirectConnectPanel;
+
+#[derive(Component)]
+struct AddressField;
+
+#[derive(Component)]
+struct AddressText;
+
+#[derive(Component)]
+struct DirectStatusText;
+
+#[derive(Component, Clone, Copy)]
+enum DirectButton {
+    Host,
+    Join,
+}
+
+fn direct_from_args(mut commands: Commands) {
+    let args: Vec<String> = std::env::args().collect();
+    let value = |name: &str| args.iter().skip_while(|arg| *arg != name).nth(1).cloned();
+
+    if let Some(address) = value("--connect") {
+        commands.insert_resource(DirectFromArgs::Connect(address));
+    } else if args.iter().any(|arg| arg == "--listen") {
+        let port = value("--listen")
+            .and_then(|port| port.parse().ok())
+            .unwrap_or(DEFAULT_PORT);
+        commands.insert_resource(DirectFromArgs::Listen(port));
+    }
+}
+
+fn connect_from_args(
+    mut commands: Commands,
+    args: Res<DirectFromArgs>,
+    input_delay: Res<InputDelay>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let result = match &*args {
+        DirectFromArgs::Listen(port) => listen(&mut commands, *port),
+        DirectFromArgs::Connect(address) => {
+            connect(&mut commands, address, *input_delay, &mut next_state)
+        }
+    };
+    if let Err(err) = result {
+        error!("{err}");
+    }
+    commands.remove_resource::<DirectFromArgs>();
+}
+
+fn listen(commands: &mut Commands, port: u16) -> Result<String, String> {
+    let socket = UdpNonBlockingSocket::bind_to_port(port)
+        .map_err(|err| format!("can't listen on port {port}: {err}"))?;
+    info!("waiting for a direct connection on port {port}");
+    commands.insert_resource(DirectListener {
+        socket: Some(socket),
+        port,
+    });
+    Ok(format!("Waiting for someone to join on port {port}..."))
+}
+
+fn connect(
+    commands: &mut Commands,
+    address: &str,
+    input_delay: InputDelay,
+    next_state: &mut NextState<GameState>,
+) -> Result<String, String> {
+    let peer = address
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| format!("\"{address}\" isn't an address:port"))?;
+    let socket = UdpNonBlockingSocket::bind_to_port(0)
+        .map_err(|err| format!("can't open a socket: {err}"))?;
+
+    info!("connecting directly to {peer}");
+    start_direct_session(commands, socket, peer, false, input_delay);
+    next_state.set(GameState::InGame);
+    Ok(format!("Connecting to {peer}..."))
+}
+
+/// The host starts once the other player has said something
+fn wait_for_direct_peer(
+    mut commands: Commands,
+    mut listener: ResMut<DirectListener>,
+    input_delay: Res<InputDelay>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let port = listener.port;
+    let Some(socket) = &mut listener.socket else {
+        return;
+    };
+    // GGRS keeps asking until it gets an answer, nothing's lost by dropping these
+    let Some((peer, _)) = socket.receive_all_messages().into_iter().next() else {
+        return;
+    };
+
+    info!("{peer} joined on port {port}");
+    let Some(socket) = listener.socket.take() else {
+        return;
+    };
+    commands.remove_resource::<DirectListener>();
+    start_direct_session(&mut commands, socket, peer, true, *input_delay);
+    next_state.set(GameState::InGame);
+}
+
+fn start_direct_session(
+    commands: &mut Commands,
+    socket: UdpNonBlockingSocket,
+    peer: SocketAddr,
+    hosting: bool,
+    input_delay: InputDelay,
+) {
+    let mut players = vec![PlayerType::Local, PlayerType::Remote(REMOTE_PEER)];
+    if !hosting {
+        players.reverse();
+    }
+
+    let frames = match input_delay {
+        InputDelay::Auto => DIRECT_INPUT_DELAY,
+        InputDelay::Fixed(frames) => frames,
+    };
+    start_p2p_session(
+        commands,
+        players,
+        DirectSocket { socket, peer },
+        MatchInputDelay {
+            frames,
+            round_trip: None,
+        },
+    );
+    commands.remove_resource::<MatchboxSocket<MultipleChannels>>();
+}
+
+/// GGRS's own UDP socket, talking to the one address
+struct DirectSocket {
+    socket: UdpNonBlockingSocket,
+    peer: SocketAddr,
+}
+
+impl NonBlockingSocket<PeerId> for DirectSocket {
+    fn send_to(&mut self, msg: &Message, _addr: &PeerId) {
+        self.socket.send_to(msg, &self.peer);
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(PeerId, Message)> {
+        self.socket
+            .receive_all_messages()
+            .into_iter()
+            .filter(|(addr, _)| *addr == self.peer)
+            .map(|(_, msg)| (REMOTE_PEER, msg))
+            .collect()
+    }
+}
+
+fn spawn_direct_connect_panel(mut commands: Commands, mut entry: ResMut<AddressEntry>) {
+    entry.focused = false;
+
+    commands
+        .spawn((
+            DirectConnectPanel,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(24.),
+                    width: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Direct connect:",
+                        TextStyle {
+                            font_size: 22.,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                            ..default()
+                        },
+                    ));
+                    parent
+                        .spawn((
+                            AddressField,
+                            ButtonBundle {
+                                style: Style {
+                                    min_width: Val::Px(220.),
+                                    padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                                    ..default()
+                                },
+                                background_color: FIELD_COLOR.into(),
+                                ..default()
+                            },
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                AddressText,
+                                TextBundle::from_section(
+                                    address_label(&entry),
+                                    TextStyle {
+                                        font_size: 22.,
+                                        ..default()
+                                    },
+                                ),
+                            ));
+                        });
+                    for (button, label) in [
+                        (DirectButton::Join, "Join".to_string()),
+                        (DirectButton::Host, format!("Host on port {DEFAULT_PORT}")),
+                    ] {
+                        parent
+                            .spawn((
+                                button,
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                                        ..default()
+                                    },
+                                    background_color: BUTTON_COLOR.into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    label,
+                                    TextStyle {
+                                        font_size: 22.,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    }
+                });
+            parent.spawn((
+                DirectStatusText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.,
+                        color: Color::rgb(0.8, 0.8, 0.8),
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
+
+fn despawn_direct_connect_panel(
+    mut commands: Commands,
+    panels: Query<Entity, With<DirectConnectPanel>>,
+    mut entry: ResMut<AddressEntry>,
+    mut focus: ResMut<TextInputFocus>,
+) {
+    for panel in &panels {
+        commands.entity(panel).despawn_recursive();
+    }
+    commands.remove_resource::<DirectListener>();
+    entry.focused = false;
+    focus.0 = false;
+}
+
+fn show_status(
+    statuses: &mut Query<&mut Text, impl bevy::ecs::query::QueryFilter>,
+    result: Result<String, String>,
+) {
+    let status = result.unwrap_or_else(|err| {
+        warn!("{err}");
+        err
+    });
+    for mut text in statuses {
+        text.sections[0].value = status.clone();
+    }
+}
+
+fn address_label(entry: &AddressEntry) -> String {
+    match (entry.text.is_empty(), entry.focused) {
+        (true, false) => "address:port".into(),
+        (_, true) => format!("{}_", entry.text),
+        (false, false) => entry.text.clone(),
+    }
+}
+
+fn direct_connect_buttons(
+    mut commands: Commands,
+    fields: Query<&Interaction, (Changed<Interaction>, With<AddressField>)>,
+    mut buttons: Query<(&Interaction, &DirectButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut statuses: Query<&mut Text, With<DirectStatusText>>,
+    mut entry: ResMut<AddressEntry>,
+    listener: Option<Res<DirectListener>>,
+    input_delay: Res<InputDelay>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if fields
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        entry.focused = true;
+    }
+
+    for (interaction, button, mut background) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => {
+                entry.focused = false;
+                let result = match button {
+                    DirectButton::Join => {
+                        connect(&mut commands, &entry.text, *input_delay, &mut next_state)
+                    }
+                    DirectButton::Host if listener.is_some() => continue,
+                    DirectButton::Host => listen(&mut commands, DEFAULT_PORT),
+                };
+                show_status(&mut statuses, result);
+            }
+            Interaction::Hovered => background.0 = BUTTON_HOVER_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+}
+
+fn type_address(
+    mut commands: Commands,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut keyboard: EventReader<KeyboardInput>,
+    mut entry: ResMut<AddressEntry>,
+    mut focus: ResMut<TextInputFocus>,
+    mut fields: Query<&mut BackgroundColor, With<AddressField>>,
+    mut texts: Query<&mut Text, (With<AddressText>, Without<DirectStatusText>)>,
+    mut statuses: Query<&mut Text, With<DirectStatusText>>,
+    input_delay: Res<InputDelay>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if entry.focused {
+        for event in characters.read() {
+            entry
+                .text
+                .extend(event.char.chars().filter(|c| c.is_ascii_graphic()));
+        }
+        for event in keyboard.read() {
+            if !event.state.is_pressed() {
+                continue;
+            }
+            match event.key_code {
+                KeyCode::Backspace => {
+                    entry.text.pop();
+                }
+                KeyCode::Escape => entry.focused = false,
+                KeyCode::Enter => {
+                    entry.focused = false;
+                    let result = connect(&mut commands, &entry.text, *input_delay, &mut next_state);
+                    show_status(&mut statuses, result);
+                }
+                _ => {}
+            }
+        }
+    } else {
+        characters.clear();
+        keyboard.clear();
+    }
+
+    if !entry.is_changed() {
+        return;
+    }
+    // the chat box has the keyboard the rest of the time
+    focus.0 = entry.focused;
+    for mut background in &mut fields {
+        background.0 = if entry.focused {
+            FIELD_FOCUSED_COLOR
+        } else {
+            FIELD_COLOR
+        };
+    }
+    for mut text in &mut texts {
+        text.sections[0].value = address_label(&entry);
+    }
+}
@@ -0,0 +1,219 @@
+//! Players dropping out of a match.
+//!
+//! GGRS notices a peer that stopped answering once its disconnect timeout runs
+//! out, which gives them time to come back, see [`crate::rejoin`]. The
+//! signaling server usually tells us sooner, and unless the players can rejoin
+//! through it, a banner counts down a short grace period from there. Either
+//! way the player is dropped from the session so it stops waiting on their
+//! inputs, then the session is stopped and the end screen gives the match to
+//! whoever is left, with no rematch on offer. Quitting from the match menu
+//! hands the opponent the match straight away. A coach sees the same when one
+//! of the players leaves, a coach leaving makes no difference to the match.
+
+use bevy::prelude::*;
+use bevy_ggrs::{ggrs::GgrsEvent, Session};
+use bevy_matchbox::matchbox_socket::PeerId;
+
+use crate::{
+    coach::{is_coach, CoachedPlayers},
+    num_players,
+    peer_messages::{player_handle, PeerDisconnected, PeerMessage, PeerMessageReceived},
+    rejoin::Rejoin,
+    round::{RoundPhase, SimFrame},
+    Config, GameState, SessionEvent,
+};
+
+/// Seconds between a player leaving and the match going to the other
+const GRACE_SECS: f32 = 5.;
+
+pub struct DisconnectPlugin;
+
+impl Plugin for DisconnectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::InGame), spawn_disconnect_banner)
+            .add_systems(OnExit(GameState::InGame), despawn_disconnect_banner)
+            .add_systems(OnExit(GameState::MatchOver), forget_forfeit)
+            .add_systems(
+                Update,
+                (detect_disconnects, update_disconnect_banner, forfeit_match)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// A player dropped out, by handle, and when we noticed, in seconds since startup
+#[derive(Resource, Clone, Copy)]
+struct PlayerLeft {
+    handle: usize,
+    since: f32,
+}
+
+/// The match was won by the player who stayed, not played out
+#[derive(Resource)]
+pub struct Forfeit {
+    pub left: usize,
+}
+
+#[derive(Component)]
+struct DisconnectBanner;
+
+fn spawn_disconnect_banner(mut commands: Commands) {
+    commands.spawn((
+        DisconnectBanner,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 32.,
+                color: Color::ORANGE_RED,
+                ..default()
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(80.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        }),
+    ));
+}
+
+fn despawn_disconnect_banner(
+    mut commands: Commands,
+    banners: Query<Entity, With<DisconnectBanner>>,
+) {
+    for banner in &banners {
+        commands.entity(banner).despawn_recursive();
+    }
+    // the match ended on its own during the grace period
+    commands.remove_resource::<PlayerLeft>();
+}
+
+fn forget_forfeit(mut commands: Commands) {
+    commands.remove_resource::<Forfeit>();
+}
+
+fn detect_disconnects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut events: EventReader<SessionEvent>,
+    mut disconnects: EventReader<PeerDisconnected>,
+    mut messages: EventReader<PeerMessageReceived>,
+    session: Option<ResMut<Session<Config>>>,
+    coached: Option<Res<CoachedPlayers>>,
+    rejoin: Option<Res<Rejoin>>,
+    left: Option<Res<PlayerLeft>>,
+) {
+    let now = time.elapsed_seconds();
+    // GGRS waits out the time to reconnect before it calls it, that was their grace period
+    let gone_from_session = events.read().filter_map(|event| match &event.0 {
+        GgrsEvent::Disconnected { addr } => Some((*addr, now - GRACE_SECS)),
+        _ => None,
+    });
+    // the signaling server doesn't, but they may be on their way back
+    let gone_from_signaling = disconnects
+        .read()
+        .filter(|_| rejoin.is_none())
+        .map(|disconnect| (disconnect.peer, now));
+    // quitting is conceding, there's nothing to wait for
+    let quit = messages
+        .read()
+        .filter(|received| received.message == PeerMessage::LeftMatch)
+        .map(|received| (received.peer, now - GRACE_SECS));
+    let gone: Vec<(PeerId, f32)> = gone_from_session
+        .chain(gone_from_signaling)
+        .chain(quit)
+        .collect();
+    let Some(mut session) = session else {
+        return;
+    };
+
+    let mut noticed = left.is_some();
+    for (peer, since) in gone {
+        // the coach doesn't count
+        let Some(handle) = player_handle(&session, coached.as_deref(), peer) else {
+            continue;
+        };
+        if let Session::P2P(session) = session.as_mut() {
+            // already done if GGRS was the one to notice
+            let _ = session.disconnect_player(handle);
+        }
+        if !noticed {
+            info!("player {handle} disconnected");
+            noticed = true;
+            commands.insert_resource(PlayerLeft { handle, since });
+        }
+    }
+}
+
+fn update_disconnect_banner(
+    time: Res<Time>,
+    left: Option<Res<PlayerLeft>>,
+    session: Option<Res<Session<Config>>>,
+    mut banners: Query<&mut Text, With<DisconnectBanner>>,
+) {
+    let status = match (left, session) {
+        (Some(left), Some(session)) => {
+            let secs_left = (GRACE_SECS - (time.elapsed_seconds() - left.since)).max(0.);
+            if is_coach(&session) {
+                format!(
+                    "Player {} disconnected\nThe match ends in {:.0}s",
+                    left.handle + 1,
+                    secs_left.ceil()
+                )
+            } else {
+                format!(
+                    "Your opponent disconnected\nYou win by forfeit in {:.0}s",
+                    secs_left.ceil()
+                )
+            }
+        }
+        _ => String::new(),
+    };
+    for mut text in &mut banners {
+        if text.sections[0].value != status {
+            text.sections[0].value.clone_from(&status);
+        }
+    }
+}
+
+/// Once the grace period is up, stops the session and ends the match in favor
+/// of whoever stayed
+fn forfeit_match(
+    mut commands: Commands,
+    time: Res<Time>,
+    left: Option<Res<PlayerLeft>>,
+    session: Option<Res<Session<Config>>>,
+    frame: Res<SimFrame>,
+    mut phase: ResMut<RoundPhase>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let (Some(left), Some(session)) = (left, session) else {
+        return;
+    };
+    if time.elapsed_seconds() - left.since < GRACE_SECS {
+        return;
+    }
+
+    // a match that was already decided stays that way, it just won't get confirmed now
+    if !matches!(*phase, RoundPhase::MatchOver { .. }) {
+        let Some(winner) = (0..num_players(&session)).find(|handle| *handle != left.handle) else {
+            return;
+        };
+        info!(
+            "player {} left, player {winner} wins by forfeit",
+            left.handle
+        );
+        // nothing rolls this back anymore, the session goes with it
+        *phase = RoundPhase::MatchOver {
+            winner,
+            frame: frame.0,
+        };
+    }
+    commands.remove_resource::<Session<Config>>();
+    commands.remove_resource::<PlayerLeft>();
+    commands.insert_resource(Forfeit { left: left.handle });
+    next_state.set(GameState::MatchOver);
+}
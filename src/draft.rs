@@ -0,0 +1,374 @@
+//! The spell draft before a ranked match.
+//!
+//! Once both players are connected they take turns over the reliable channel:
+//! each bans a spell for the whole match, then they pick two spells each from
+//! what's left, snake order so the second player gets to answer the first
+//! pick. Both peers play the same turns in the same order, so they end up with
+//! the same loadouts without anyone having to be in charge. The loadouts go
+//! into the [`Rules`] before the session starts, the simulation never hears of
+//! the draft itself.
+
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_matchbox::{
+    matchbox_socket::{MultipleChannels, PeerId, WebRtcChannel},
+    MatchboxSocket,
+};
+
+use crate::{
+    components::*,
+    peer_messages::{self, PeerMessage, RELIABLE_CHANNEL},
+    rules::Rules,
+    GameState,
+};
+
+/// A turn that's taking longer than this goes to the first spell on offer
+const TURN_SECS: f32 = 20.;
+
+const BACKGROUND_COLOR: Color = Color::rgba(0., 0., 0., 0.85);
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const BUTTON_DISABLED_COLOR: Color = Color::rgb(0.08, 0.08, 0.08);
+const BUTTON_BANNED_COLOR: Color = Color::rgb(0.4, 0.1, 0.1);
+
+pub struct DraftPlugin;
+
+impl Plugin for DraftPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Matchmaking), reset_spells)
+            .add_systems(OnExit(GameState::Matchmaking), end_draft)
+            .add_systems(
+                Update,
+                (
+                    spawn_draft_screen.run_if(resource_added::<Draft>),
+                    (draft_buttons, update_draft_screen).run_if(resource_exists::<Draft>),
+                    despawn_draft_screen.run_if(resource_removed::<Draft>()),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Matchmaking)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Step {
+    Ban,
+    Pick,
+}
+
+/// Whose turn it is, by handle, and what it's for
+const TURNS: [(usize, Step); 6] = [
+    (0, Step::Ban),
+    (1, Step::Ban),
+    (0, Step::Pick),
+    (1, Step::Pick),
+    (1, Step::Pick),
+    (0, Step::Pick),
+];
+
+/// The draft with one opponent, driven by `wait_for_players` every frame until it's done
+#[derive(Resource)]
+pub struct Draft {
+    peer: PeerId,
+    local_handle: usize,
+    banned: SpellSet,
+    picked: [SpellSet; 2],
+    /// Index into `TURNS`
+    turn: usize,
+    /// When the current turn started, in seconds since startup
+    turn_started: f32,
+}
+
+impl Draft {
+    pub fn new(peer: PeerId, local_handle: usize, now: f32) -> Self {
+        Self {
+            peer,
+            local_handle,
+            banned: SpellSet::NONE,
+            picked: [SpellSet::NONE; 2],
+            turn: 0,
+            turn_started: now,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.turn >= TURNS.len()
+    }
+
+    /// The spells each handle ended up with
+    pub fn loadouts(&self) -> [SpellSet; 2] {
+        self.picked
+    }
+
+    fn local_turn(&self) -> bool {
+        TURNS
+            .get(self.turn)
+            .is_some_and(|(handle, _)| *handle == self.local_handle)
+    }
+
+    fn can_choose(&self, spell: Spell) -> bool {
+        let Some((handle, step)) = TURNS.get(self.turn) else {
+            return false;
+        };
+        match step {
+            Step::Ban => !self.banned.contains(spell),
+            Step::Pick => !self.banned.contains(spell) && !self.picked[*handle].contains(spell),
+        }
+    }
+
+    /// Takes `spell` for the current turn and moves on to the next one
+    fn apply(&mut self, spell: Spell, now: f32) -> bool {
+        if !self.can_choose(spell) {
+            return false;
+        }
+        let (handle, step) = TURNS[self.turn];
+        match step {
+            Step::Ban => self.banned = self.banned.with(spell),
+            Step::Pick => self.picked[handle] = self.picked[handle].with(spell),
+        }
+        self.turn += 1;
+        self.turn_started = now;
+        true
+    }
+
+    /// Our choice for our turn, passed on to the opponent
+    fn choose(&mut self, spell: Spell, channel: &mut WebRtcChannel, now: f32) {
+        if self.local_turn() && self.apply(spell, now) {
+            PeerMessage::DraftChoice(spell).send(channel, self.peer);
+        }
+    }
+
+    fn receive_choice(&mut self, peer: PeerId, spell: Spell, now: f32) {
+        if self.local_turn() || !self.apply(spell, now) {
+            warn!("ignoring draft choice {spell:?} from {peer}, it isn't theirs to make");
+        }
+    }
+
+    /// Takes in the opponent's choices and runs our clock, returns true once
+    /// every turn has been taken
+    pub fn update(&mut self, channel: &mut WebRtcChannel, now: f32) -> bool {
+        for (peer, message) in peer_messages::receive(channel) {
+            match message {
+                // they may still be measuring the ping
+                PeerMessage::Ping(seq) => PeerMessage::Pong(seq).send(channel, peer),
+                PeerMessage::DraftChoice(spell) => self.receive_choice(peer, spell, now),
+                _ => {}
+            }
+        }
+
+        if self.local_turn() && now - self.turn_started > TURN_SECS {
+            if let Some(spell) = Spell::ALL.into_iter().find(|spell| self.can_choose(*spell)) {
+                info!("out of time in the draft, going with {spell:?}");
+                self.choose(spell, channel, now);
+            }
+        }
+
+        self.is_done()
+    }
+
+    fn status(&self, now: f32) -> String {
+        let Some((handle, step)) = TURNS.get(self.turn) else {
+            return "Draft done, starting the match...".into();
+        };
+        let action = match step {
+            Step::Ban => "ban a spell for both of you",
+            Step::Pick => "pick a spell",
+        };
+        if *handle == self.local_handle {
+            let secs_left = (TURN_SECS - (now - self.turn_started)).max(0.).ceil();
+            format!("Your turn: {action} ({secs_left:.0}s)")
+        } else {
+            format!("Waiting for your opponent to {action}...")
+        }
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "Banned: {}\nYour spells: {}\nTheir spells: {}",
+            spell_names(self.banned),
+            spell_names(self.picked[self.local_handle]),
+            spell_names(self.picked[1 - self.local_handle]),
+        )
+    }
+}
+
+fn spell_names(spells: SpellSet) -> String {
+    let names: Vec<&str> = Spell::ALL
+        .into_iter()
+        .filter(|spell| spells.contains(*spell))
+        .map(Spell::name)
+        .collect();
+    if names.is_empty() {
+        "-".into()
+    } else {
+        names.join(", ")
+    }
+}
+
+#[derive(Component)]
+struct DraftScreen;
+
+#[derive(Component)]
+struct DraftStatusText;
+
+#[derive(Component)]
+struct DraftSummaryText;
+
+#[derive(Component)]
+struct DraftButton(Spell);
+
+/// Loadouts from a ranked match don't carry over to whatever is played next
+fn reset_spells(mut rules: ResMut<Rules>) {
+    rules.spells = Rules::default().spells;
+}
+
+fn spawn_draft_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            DraftScreen,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.),
+                    ..default()
+                },
+                background_color: BACKGROUND_COLOR.into(),
+                // over the matchmaking screen, whose buttons mustn't be clicked through this
+                z_index: ZIndex::Global(10),
+                focus_policy: FocusPolicy::Block,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Spell draft",
+                TextStyle {
+                    font_size: 40.,
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                DraftStatusText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 24.,
+                        color: Color::ORANGE,
+                        ..default()
+                    },
+                ),
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(8.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for spell in Spell::ALL {
+                        parent
+                            .spawn((
+                                DraftButton(spell),
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::axes(Val::Px(16.), Val::Px(8.)),
+                                        ..default()
+                                    },
+                                    background_color: BUTTON_COLOR.into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    spell.name(),
+                                    TextStyle {
+                                        font_size: 24.,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    }
+                });
+            parent.spawn((
+                DraftSummaryText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 22.,
+                        color: Color::rgb(0.8, 0.8, 0.8),
+                        ..default()
+                    },
+                )
+                .with_text_justify(JustifyText::Center),
+            ));
+        });
+}
+
+fn draft_buttons(
+    time: Res<Time>,
+    mut draft: ResMut<Draft>,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    buttons: Query<(&Interaction, &DraftButton), Changed<Interaction>>,
+) {
+    let Ok(channel) = socket.get_channel(RELIABLE_CHANNEL) else {
+        return;
+    };
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            draft.choose(button.0, channel, time.elapsed_seconds());
+        }
+    }
+}
+
+fn update_draft_screen(
+    time: Res<Time>,
+    draft: Res<Draft>,
+    mut buttons: Query<(&Interaction, &DraftButton, &mut BackgroundColor)>,
+    mut statuses: Query<&mut Text, (With<DraftStatusText>, Without<DraftSummaryText>)>,
+    mut summaries: Query<&mut Text, (With<DraftSummaryText>, Without<DraftStatusText>)>,
+) {
+    let status = draft.status(time.elapsed_seconds());
+    for mut text in &mut statuses {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+    let summary = draft.summary();
+    for mut text in &mut summaries {
+        if text.sections[0].value != summary {
+            text.sections[0].value = summary.clone();
+        }
+    }
+
+    for (interaction, button, mut background) in &mut buttons {
+        let choosable = draft.local_turn() && draft.can_choose(button.0);
+        background.0 = if draft.banned.contains(button.0) {
+            BUTTON_BANNED_COLOR
+        } else if !choosable {
+            BUTTON_DISABLED_COLOR
+        } else if *interaction == Interaction::Hovered {
+            BUTTON_HOVER_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+    }
+}
+
+fn despawn_draft_screen(mut commands: Commands, screens: Query<Entity, With<DraftScreen>>) {
+    for screen in &screens {
+        commands.entity(screen).despawn_recursive();
+    }
+}
+
+fn end_draft(mut commands: Commands, screens: Query<Entity, With<DraftScreen>>) {
+    commands.remove_resource::<Draft>();
+    for screen in &screens {
+        commands.entity(screen).despawn_recursive();
+    }
+}
@@ -0,0 +1,386 @@
+use bevy::prelude::*;
+use bevy_ggrs::{LocalPlayers, Rollback, Session};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+
+use crate::{
+    bot::Bots,
+    coach::{is_coach, CoachedPlayers},
+    disconnect::Forfeit,
+    num_players,
+    peer_messages::{
+        player_handle, send_to_peers, PeerDisconnected, PeerMessage, PeerMessageReceived,
+    },
+    round::{teardown_match, MatchStats, RoundPhase, Scores},
+    settings::Settings,
+    Config, GameState,
+};
+
+/// Set when the local player clicked "Rematch". The new match starts once
+/// everyone's rematch input bit is set, so it begins on the same frame
+/// everywhere, but the opponent is told right away over the reliable channel.
+#[derive(Resource, Default)]
+pub struct RematchVote(pub bool);
+
+pub struct EndScreenPlugin;
+
+impl Plugin for EndScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RematchVote>()
+            .add_systems(OnEnter(GameState::MatchOver), spawn_end_screen)
+            .add_systems(OnExit(GameState::MatchOver), despawn_end_screen)
+            .add_systems(
+                Update,
+                (animate_end_screen, end_screen_buttons, show_opponent_status)
+                    .run_if(in_state(GameState::MatchOver)),
+            );
+
+        #[cfg(target_arch = "wasm32")]
+        app.add_systems(
+            Update,
+            share_replay_button.run_if(in_state(GameState::MatchOver)),
+        );
+    }
+}
+
+#[derive(Component)]
+struct EndScreen {
+    spawned_at: f32,
+}
+
+#[derive(Component)]
+struct EndScreenTitle;
+
+#[derive(Component)]
+struct WinnerPortrait;
+
+/// Whether the opponent is up for a rematch, blank until they've said
+#[derive(Component)]
+struct OpponentStatusText;
+
+#[derive(Component, Clone, Copy)]
+enum EndScreenButton {
+    Rematch,
+    BackToMenu,
+    /// There's nowhere to save replays to in the browser, so offer a link instead
+    #[cfg(target_arch = "wasm32")]
+    ShareReplay,
+}
+
+const TITLE_SIZE: f32 = 80.;
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+
+fn spawn_end_screen(
+    mut commands: Commands,
+    phase: Res<RoundPhase>,
+    scores: Res<Scores>,
+    stats: Res<MatchStats>,
+    local_players: Res<LocalPlayers>,
+    bots: Res<Bots>,
+    session: Option<Res<Session<Config>>>,
+    forfeit: Option<Res<Forfeit>>,
+    coached: Option<Res<CoachedPlayers>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let RoundPhase::MatchOver { winner, .. } = *phase else {
+        return;
+    };
+
+    // after a forfeit there's no session left to ask
+    let coach = session.as_deref().is_some_and(is_coach) || coached.is_some();
+    let (title, title_color) = if coach {
+        ("MATCH OVER", Color::WHITE)
+    } else if local_players.0.contains(&winner) && !bots.contains(winner) {
+        ("VICTORY!", Color::GOLD)
+    } else {
+        ("DEFEAT", Color::CRIMSON)
+    };
+
+    let num_players = session.map_or(2, |session| num_players(&session));
+    let opponent_status = match &forfeit {
+        Some(forfeit) if coach => format!("Player {} disconnected", forfeit.left + 1),
+        Some(_) => "Your opponent disconnected".into(),
+        None => String::new(),
+    };
+    let score = (0..num_players)
+        .map(|handle| scores.get(handle).to_string())
+        .collect::<Vec<_>>()
+        .join(" - ");
+
+    commands
+        .spawn((
+            EndScreen {
+                spawned_at: time.elapsed_seconds(),
+            },
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(20.),
+                    ..default()
+                },
+                background_color: Color::NONE.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                EndScreenTitle,
+                TextBundle::from_section(
+                    title,
+                    TextStyle {
+                        font_size: 0.,
+                        color: title_color,
+                        ..default()
+                    },
+                ),
+            ));
+
+            parent.spawn((
+                WinnerPortrait,
+                NodeBundle {
+                    style: Style {
+                        width: Val::Px(96.),
+                        height: Val::Px(96.),
+                        ..default()
+                    },
+                    background_color: settings.palette.color(winner).into(),
+                    ..default()
+                },
+            ));
+
+            parent.spawn(TextBundle::from_section(
+                format!("Player {} wins  {score}", winner + 1),
+                TextStyle {
+                    font_size: 40.,
+                    ..default()
+                },
+            ));
+
+            for handle in 0..num_players {
+                let stats = stats.get(handle);
+                parent.spawn(TextBundle::from_section(
+                    format!(
+                        "Player {}   K {}  /  D {}  /  A {}",
+                        handle + 1,
+                        stats.kills,
+                        stats.deaths,
+                        stats.assists
+                    ),
+                    TextStyle {
+                        font_size: 24.,
+                        color: settings.palette.color(handle),
+                        ..default()
+                    },
+                ));
+            }
+
+            parent.spawn((
+                OpponentStatusText,
+                TextBundle::from_section(
+                    opponent_status,
+                    TextStyle {
+                        font_size: 24.,
+                        color: Color::rgb(0.8, 0.8, 0.8),
+                        ..default()
+                    },
+                ),
+            ));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    let buttons = [
+                        (EndScreenButton::Rematch, "Rematch"),
+                        (EndScreenButton::BackToMenu, "Back to Menu"),
+                        #[cfg(target_arch = "wasm32")]
+                        (EndScreenButton::ShareReplay, "Share Replay"),
+                    ];
+                    // the players decide on a rematch between them, if they're both still here
+                    let buttons = buttons.into_iter().filter(|(button, _)| {
+                        !matches!(button, EndScreenButton::Rematch) || !coach && forfeit.is_none()
+                    });
+                    for (button, label) in buttons {
+                        parent
+                            .spawn((
+                                button,
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::axes(Val::Px(24.), Val::Px(12.)),
+                                        ..default()
+                                    },
+                                    background_color: BUTTON_COLOR.into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    label,
+                                    TextStyle {
+                                        font_size: 30.,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    }
+                });
+        });
+}
+
+fn despawn_end_screen(
+    mut commands: Commands,
+    screens: Query<Entity, With<EndScreen>>,
+    mut rematch_vote: ResMut<RematchVote>,
+) {
+    for screen in &screens {
+        commands.entity(screen).despawn_recursive();
+    }
+    rematch_vote.0 = false;
+}
+
+fn animate_end_screen(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut screens: Query<(&EndScreen, &mut BackgroundColor)>,
+    mut titles: Query<&mut Text, With<EndScreenTitle>>,
+    mut portraits: Query<&mut Style, With<WinnerPortrait>>,
+) {
+    for (screen, mut background) in &mut screens {
+        let t = time.elapsed_seconds() - screen.spawned_at;
+
+        // fade in the backdrop
+        background.0 = Color::rgba(0., 0., 0., (t * 2.).min(1.) * 0.7);
+
+        // the title just shows up and the wizard stands still with reduced motion
+        if settings.reduced_motion {
+            for mut text in &mut titles {
+                text.sections[0].style.font_size = TITLE_SIZE;
+            }
+            for mut style in &mut portraits {
+                style.margin.bottom = Val::Px(0.);
+            }
+            continue;
+        }
+
+        // pop the title in with a small overshoot, then let it breathe
+        let pop = (t * 4.).min(1.);
+        let overshoot = (pop * std::f32::consts::PI).sin() * 0.3;
+        let breathe = (t * 3.).sin() * 0.03 * pop;
+        for mut text in &mut titles {
+            text.sections[0].style.font_size = TITLE_SIZE * (pop + overshoot + breathe);
+        }
+
+        // and have the winning wizard hop up and down
+        for mut style in &mut portraits {
+            style.margin.bottom = Val::Px((t * 6.).sin().abs() * 16.);
+        }
+    }
+}
+
+fn end_screen_buttons(
+    mut commands: Commands,
+    mut buttons: Query<
+        (
+            &Interaction,
+            &EndScreenButton,
+            &mut BackgroundColor,
+            &Children,
+        ),
+        Changed<Interaction>,
+    >,
+    mut texts: Query<&mut Text>,
+    mut rematch_vote: ResMut<RematchVote>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut socket: Option<ResMut<MatchboxSocket<MultipleChannels>>>,
+    session: Option<Res<Session<Config>>>,
+    rollback_entities: Query<Entity, With<Rollback>>,
+) {
+    let coach = session.as_deref().is_some_and(is_coach);
+    for (interaction, button, mut background, children) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => match button {
+                EndScreenButton::Rematch => {
+                    if !rematch_vote.0 {
+                        if let Some(socket) = &mut socket {
+                            send_to_peers(socket, PeerMessage::WantsRematch);
+                        }
+                    }
+                    rematch_vote.0 = true;
+                    if let Ok(mut text) = texts.get_mut(children[0]) {
+                        text.sections[0].value = "Waiting for opponent...".into();
+                    }
+                }
+                EndScreenButton::BackToMenu => {
+                    // the players don't have to hear about the coach going
+                    if let Some(socket) = socket.as_mut().filter(|_| !coach) {
+                        send_to_peers(socket, PeerMessage::LeftMatch);
+                    }
+                    // the menu is matchmaking, practice is over too
+                    teardown_match(&mut commands, &rollback_entities);
+                    next_state.set(GameState::Matchmaking);
+                }
+                #[cfg(target_arch = "wasm32")]
+                EndScreenButton::ShareReplay => {} // see share_replay_button
+            },
+            Interaction::Hovered => background.0 = BUTTON_HOVER_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+}
+
+fn show_opponent_status(
+    mut messages: EventReader<PeerMessageReceived>,
+    mut disconnects: EventReader<PeerDisconnected>,
+    session: Option<Res<Session<Config>>>,
+    coached: Option<Res<CoachedPlayers>>,
+    mut texts: Query<&mut Text, With<OpponentStatusText>>,
+) {
+    let mut statuses: Vec<&str> = messages
+        .read()
+        .filter_map(|received| match received.message {
+            PeerMessage::WantsRematch => Some("Your opponent wants a rematch"),
+            PeerMessage::LeftMatch => Some("Your opponent left"),
+            _ => None,
+        })
+        .collect();
+    // a coach going doesn't stop the players having a rematch
+    let player_left = disconnects.read().any(|disconnect| {
+        session.as_deref().is_some_and(|session| {
+            player_handle(session, coached.as_deref(), disconnect.peer).is_some()
+        })
+    });
+    if player_left {
+        statuses.push("Your opponent disconnected");
+    }
+
+    let Some(status) = statuses.last() else {
+        return;
+    };
+    for mut text in &mut texts {
+        text.sections[0].value = (*status).into();
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn share_replay_button(
+    buttons: Query<(&Interaction, &EndScreenButton), Changed<Interaction>>,
+    last_replay: Option<Res<crate::replay::LastReplay>>,
+) {
+    for (interaction, button) in &buttons {
+        if let (Interaction::Pressed, EndScreenButton::ShareReplay) = (interaction, button) {
+            if let Some(last_replay) = &last_replay {
+                crate::share::share_replay(&last_replay.0);
+            }
+        }
+    }
+}
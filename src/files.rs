@@ -0,0 +1,55 @@
+//! Saving match files (telemetry, replays) somewhere the player can get at them
+
+use bevy::prelude::*;
+
+/// Writes `contents` to `<dir>/<name>` natively, or offers it as a download in the browser
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_file(dir: &str, name: &str, contents: &[u8], _mime_type: &str) {
+    let dir = std::path::Path::new(dir);
+    let path = dir.join(name);
+
+    match std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, contents)) {
+        Ok(()) => info!("saved {}", path.display()),
+        Err(err) => error!("failed to save {}: {err}", path.display()),
+    }
+}
+
+/// Writes `contents` to `<dir>/<name>` natively, or offers it as a download in the browser
+#[cfg(target_arch = "wasm32")]
+pub fn save_file(_dir: &str, name: &str, contents: &[u8], mime_type: &str) {
+    use wasm_bindgen::{JsCast, JsValue};
+
+    let download = || -> Result<(), JsValue> {
+        let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(contents));
+        let options = web_sys::BlobPropertyBag::new();
+        options.set_type(mime_type);
+        let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+        let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or("no document")?;
+        let link: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+        link.set_href(&url);
+        link.set_download(name);
+        link.click();
+
+        web_sys::Url::revoke_object_url(&url)
+    };
+
+    if let Err(err) = download() {
+        error!("failed to download {name}: {err:?}");
+    }
+}
+
+/// Unique enough name for a file saved at the end of a match
+pub fn timestamped_name(prefix: &str, extension: &str) -> String {
+    #[cfg(not(target_arch = "wasm32"))]
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    #[cfg(target_arch = "wasm32")]
+    let timestamp = (js_sys::Date::now() / 1000.) as u64;
+
+    format!("{prefix}-{timestamp}.{extension}")
+}
@@ -0,0 +1,306 @@
+//! The match-winning kill, played again in slow motion with the camera zooming
+//! in on it before the end screen comes up.
+//!
+//! The match result is confirmed by the time it plays, so there's nothing left
+//! for the simulation to do: this is a render-side replay. The last couple of
+//! seconds of what was drawn (wizards, bullets and spells) are kept around
+//! every frame, and once the final kill is confirmed the live entities are
+//! hidden and ghosts of them are drawn from that buffer instead. With reduced
+//! motion the camera stays put rather than zooming in.
+
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, sprite::Mesh2dHandle, transform::TransformSystem};
+use bevy_ggrs::Session;
+
+use crate::{
+    combat::Hit,
+    components::*,
+    graphics::clamp_camera_to_arena,
+    round::{frame_confirmed, RoundKills, RoundPhase},
+    settings::Settings,
+    Config, GameState,
+};
+
+/// How much of the match leading up to the kill is kept, in seconds
+const RECORD_SECS: f32 = 1.5;
+/// Speed the kill is played back at
+const SLOW_MOTION: f32 = 0.3;
+/// Camera zoom at its closest, as a fraction of the usual view
+const ZOOM: f32 = 0.6;
+const ZOOM_SECS: f32 = 0.6;
+/// How long the last frame stays up before the end screen
+const HOLD_SECS: f32 = 0.8;
+
+pub struct FinalKillPlugin;
+
+impl Plugin for FinalKillPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FinalKill>()
+            .add_systems(
+                Update,
+                (track_deaths, start_final_kill, play_final_kill)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    zoom_on_kill.before(clamp_camera_to_arena),
+                    record_shots.after(TransformSystem::TransformPropagate),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), reset_final_kill);
+    }
+}
+
+/// Render-side record of the match's last moments, and the replay of them
+#[derive(Resource, Default)]
+pub struct FinalKill {
+    /// What was on screen lately, oldest first
+    shots: VecDeque<Shot>,
+    /// Where the latest confirmed death happened
+    last_death: Option<Vec2>,
+    playback: Option<Playback>,
+    done: bool,
+}
+
+impl FinalKill {
+    /// Whether the replay has played out, and the end screen can come up
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+/// Everything drawn on one render frame
+struct Shot {
+    /// Real seconds since startup
+    time: f32,
+    ghosts: Vec<Ghost>,
+}
+
+/// How one entity was drawn
+struct Ghost {
+    transform: Transform,
+    look: Look,
+}
+
+enum Look {
+    Sprite(Sprite, Handle<Image>, Option<TextureAtlas>),
+    Mesh(Mesh2dHandle, Handle<ColorMaterial>),
+}
+
+struct Playback {
+    /// Real seconds since it started
+    elapsed: f32,
+    focus: Vec2,
+    /// Where the camera was when it started
+    from: Vec2,
+}
+
+/// Stands in for a live entity during the replay
+#[derive(Component)]
+struct KillGhost;
+
+type Drawn = Or<(
+    With<Player>,
+    With<Decoy>,
+    With<Bullet>,
+    With<Fireball>,
+    With<Hook>,
+    With<ManaBurn>,
+    With<Flung>,
+    With<Explosion>,
+)>;
+
+fn track_deaths(
+    phase: Res<RoundPhase>,
+    mut final_kill: ResMut<FinalKill>,
+    mut hits: EventReader<Hit>,
+) {
+    if matches!(*phase, RoundPhase::Countdown { .. }) {
+        final_kill.last_death = None;
+    }
+    for hit in hits.read() {
+        if hit.fatal {
+            final_kill.last_death = Some(hit.pos);
+        }
+    }
+}
+
+fn record_shots(
+    time: Res<Time<Real>>,
+    mut final_kill: ResMut<FinalKill>,
+    drawn: Query<
+        (
+            &GlobalTransform,
+            &ViewVisibility,
+            Option<(&Sprite, &Handle<Image>, Option<&TextureAtlas>)>,
+            Option<(&Mesh2dHandle, &Handle<ColorMaterial>)>,
+        ),
+        (Drawn, Without<KillGhost>),
+    >,
+) {
+    if final_kill.playback.is_some() || final_kill.done {
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    let ghosts = drawn
+        .iter()
+        .filter(|(_, visibility, ..)| visibility.get())
+        .filter_map(|(transform, _, sprite, mesh)| {
+            let look = match (sprite, mesh) {
+                (Some((sprite, image, atlas)), _) => {
+                    Look::Sprite(sprite.clone(), image.clone(), atlas.cloned())
+                }
+                (_, Some((mesh, material))) => Look::Mesh(mesh.clone(), material.clone()),
+                _ => return None,
+            };
+            Some(Ghost {
+                transform: transform.compute_transform(),
+                look,
+            })
+        })
+        .collect();
+
+    final_kill.shots.push_back(Shot { time: now, ghosts });
+    while final_kill
+        .shots
+        .front()
+        .is_some_and(|shot| shot.time < now - RECORD_SECS)
+    {
+        final_kill.shots.pop_front();
+    }
+}
+
+fn start_final_kill(
+    phase: Res<RoundPhase>,
+    session: Option<Res<Session<Config>>>,
+    kills: Res<RoundKills>,
+    mut final_kill: ResMut<FinalKill>,
+    mut drawn: Query<&mut Visibility, (Drawn, Without<KillGhost>)>,
+    cameras: Query<&Transform, With<Camera2d>>,
+) {
+    let RoundPhase::MatchOver { frame, .. } = *phase else {
+        return;
+    };
+    if final_kill.playback.is_some() || final_kill.done {
+        return;
+    }
+    if !session.is_some_and(|session| frame_confirmed(&session, frame)) {
+        return;
+    }
+
+    if kills.0.is_empty() || final_kill.shots.is_empty() {
+        // won on the clock, there's no kill to show
+        final_kill.done = true;
+        return;
+    }
+    let Some(focus) = final_kill.last_death else {
+        return; // the confirmed hit is still on its way
+    };
+    let from = cameras
+        .get_single()
+        .map_or(focus, |transform| transform.translation.xy());
+
+    for mut visibility in &mut drawn {
+        *visibility = Visibility::Hidden;
+    }
+    final_kill.playback = Some(Playback {
+        elapsed: 0.,
+        focus,
+        from,
+    });
+}
+
+fn play_final_kill(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut final_kill: ResMut<FinalKill>,
+    mut drawn: Query<&mut Visibility, (Drawn, Without<KillGhost>)>,
+    ghosts: Query<Entity, With<KillGhost>>,
+) {
+    let final_kill = &mut *final_kill;
+    let Some(playback) = &mut final_kill.playback else {
+        return;
+    };
+    playback.elapsed += time.delta_seconds();
+
+    for ghost in &ghosts {
+        commands.entity(ghost).despawn();
+    }
+
+    let (Some(first), Some(last)) = (final_kill.shots.front(), final_kill.shots.back()) else {
+        return;
+    };
+    let replay_time = first.time + playback.elapsed * SLOW_MOTION;
+    if replay_time > last.time + HOLD_SECS * SLOW_MOTION {
+        for mut visibility in &mut drawn {
+            *visibility = Visibility::Inherited;
+        }
+        final_kill.playback = None;
+        final_kill.done = true;
+        return;
+    }
+
+    let shot = final_kill
+        .shots
+        .iter()
+        .take_while(|shot| shot.time <= replay_time)
+        .last()
+        .unwrap_or(first);
+    for ghost in &shot.ghosts {
+        let mut entity = commands.spawn((KillGhost, SpatialBundle::from(ghost.transform)));
+        match &ghost.look {
+            Look::Sprite(sprite, image, atlas) => {
+                entity.insert((sprite.clone(), image.clone()));
+                if let Some(atlas) = atlas {
+                    entity.insert(atlas.clone());
+                }
+            }
+            Look::Mesh(mesh, material) => {
+                entity.insert((mesh.clone(), material.clone()));
+            }
+        }
+    }
+}
+
+/// Eases the camera in onto the kill while it plays
+fn zoom_on_kill(
+    final_kill: Res<FinalKill>,
+    settings: Res<Settings>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+) {
+    let Some(playback) = &final_kill.playback else {
+        return;
+    };
+    if settings.reduced_motion {
+        return;
+    }
+
+    let t = (playback.elapsed / ZOOM_SECS).min(1.);
+    let t = t * t * (3. - 2. * t);
+    for (mut transform, mut projection) in &mut cameras {
+        let pos = playback.from.lerp(playback.focus, t);
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+        projection.scale = 1. + (ZOOM - 1.) * t;
+    }
+}
+
+fn reset_final_kill(
+    mut commands: Commands,
+    mut final_kill: ResMut<FinalKill>,
+    ghosts: Query<Entity, With<KillGhost>>,
+    mut projections: Query<&mut OrthographicProjection, With<Camera2d>>,
+) {
+    *final_kill = FinalKill::default();
+    for ghost in &ghosts {
+        commands.entity(ghost).despawn();
+    }
+    for mut projection in &mut projections {
+        projection.scale = 1.;
+    }
+}
@@ -0,0 +1,241 @@
+//! Fixed-point numbers for where things are in the simulation and how fast
+//! they're going.
+//!
+//! Float math is only as deterministic as the compiler and CPU doing it, and a
+//! peer on another platform rounding a collision a hair differently is a
+//! desync. Movement and collisions work on integers with 16 fractional bits
+//! instead, which come out the same everywhere.
+//!
+//! What the simulation works out is kept in [`crate::components::Position`]
+//! and friends, and only copied into the `Transform` as floats for drawing,
+//! so nothing that draws the arena can nudge it.
+
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const FRAC_BITS: u32 = 16;
+const ONE_F32: f32 = (1 << FRAC_BITS) as f32;
+
+/// A number in steps of 1/65536
+#[derive(
+    Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize,
+)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1 << FRAC_BITS);
+
+    pub const fn from_int(value: i32) -> Self {
+        Self(value << FRAC_BITS)
+    }
+
+    /// The closest fixed-point number to `value`
+    pub const fn from_f32(value: f32) -> Self {
+        let scaled = value * ONE_F32;
+        // casting cuts towards zero, half a step away from it rounds to the nearest
+        let half = if scaled < 0. { -0.5 } else { 0.5 };
+        Self((scaled + half) as i32)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / ONE_F32
+    }
+
+    /// Rounded down to a whole number
+    pub fn floor(self) -> i32 {
+        self.0 >> FRAC_BITS
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    /// Rounded down, zero for negative numbers
+    pub fn sqrt(self) -> Self {
+        Self((((self.0.max(0) as u64) << FRAC_BITS).isqrt()) as i32)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self(((self.0 as i64 * rhs.0 as i64) >> FRAC_BITS) as i32)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Self((((self.0 as i64) << FRAC_BITS) / rhs.0 as i64) as i32)
+    }
+}
+
+impl Mul<i32> for Fixed {
+    type Output = Self;
+
+    fn mul(self, rhs: i32) -> Self {
+        Self(self.0 * rhs)
+    }
+}
+
+impl Div<i32> for Fixed {
+    type Output = Self;
+
+    fn div(self, rhs: i32) -> Self {
+        Self(self.0 / rhs)
+    }
+}
+
+/// A position or velocity in the simulation, in tiles and tiles per frame
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct SimVec2 {
+    pub x: Fixed,
+    pub y: Fixed,
+}
+
+impl SimVec2 {
+    pub const ZERO: Self = Self::new(Fixed::ZERO, Fixed::ZERO);
+    pub const X: Self = Self::new(Fixed::ONE, Fixed::ZERO);
+
+    pub const fn new(x: Fixed, y: Fixed) -> Self {
+        Self { x, y }
+    }
+
+    pub const fn splat(value: Fixed) -> Self {
+        Self::new(value, value)
+    }
+
+    /// The closest fixed-point vector to `value`
+    pub const fn from_vec2(value: Vec2) -> Self {
+        Self::new(Fixed::from_f32(value.x), Fixed::from_f32(value.y))
+    }
+
+    pub fn to_vec2(self) -> Vec2 {
+        Vec2::new(self.x.to_f32(), self.y.to_f32())
+    }
+
+    pub fn dot(self, rhs: Self) -> Fixed {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    pub fn length_squared(self) -> Fixed {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> Fixed {
+        self.length_squared().sqrt()
+    }
+
+    pub fn distance_squared(self, rhs: Self) -> Fixed {
+        (self - rhs).length_squared()
+    }
+
+    /// Whether `rhs` is less than `reach` away, without taking a square root
+    pub fn within(self, rhs: Self, reach: Fixed) -> bool {
+        self.distance_squared(rhs) < reach * reach
+    }
+
+    /// Same direction, a length of one, or zero if it's too short to have a direction
+    pub fn normalize_or_zero(self) -> Self {
+        let length = self.length();
+        if length == Fixed::ZERO {
+            return Self::ZERO;
+        }
+        Self::new(self.x / length, self.y / length)
+    }
+
+    /// Turned a quarter anticlockwise
+    pub fn perp(self) -> Self {
+        Self::new(-self.y, self.x)
+    }
+
+    pub fn clamp(self, min: Self, max: Self) -> Self {
+        Self::new(self.x.clamp(min.x, max.x), self.y.clamp(min.y, max.y))
+    }
+
+    /// Whether either coordinate is further than `limit` from zero
+    pub fn outside(self, limit: Fixed) -> bool {
+        self.x.abs() > limit || self.y.abs() > limit
+    }
+}
+
+impl From<Vec2> for SimVec2 {
+    fn from(value: Vec2) -> Self {
+        Self::from_vec2(value)
+    }
+}
+
+impl Add for SimVec2 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl AddAssign for SimVec2 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for SimVec2 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Neg for SimVec2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y)
+    }
+}
+
+impl Mul<Fixed> for SimVec2 {
+    type Output = Self;
+
+    fn mul(self, rhs: Fixed) -> Self {
+        Self::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl Div<Fixed> for SimVec2 {
+    type Output = Self;
+
+    fn div(self, rhs: Fixed) -> Self {
+        Self::new(self.x / rhs, self.y / rhs)
+    }
+}
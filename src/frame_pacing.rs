@@ -0,0 +1,123 @@
+//! How often the game is drawn: in time with the display, capped at a frame
+//! rate, or as fast as it'll go.
+//!
+//! None of it touches the simulation. bevy_ggrs steps it 60 times a second
+//! whatever the frame rate, as many frames each render frame as real time has
+//! gone by, so a slow machine runs a couple per frame and a fast one draws the
+//! same frame again. Effects that aren't part of it, like hit flashes, time
+//! themselves in seconds rather than render frames for the same reason.
+//!
+//! Natively the cap sleeps off whatever's left of each frame. Browsers only
+//! draw on their own schedule, so there Bevy is told to update no more often
+//! than the cap instead, and the display's refresh rate is the most it gets.
+
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::utils::Instant;
+use bevy::{prelude::*, window::PresentMode};
+use serde::{Deserialize, Serialize};
+
+use crate::{battery, settings::Settings};
+
+pub struct FramePacingPlugin;
+
+impl Plugin for FramePacingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_present_mode)
+            .add_systems(Last, limit_frame_rate);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrameLimit {
+    /// A frame for every refresh of the display
+    #[default]
+    Vsync,
+    /// At most this many frames a second, without waiting on the display
+    Capped(u32),
+    /// As many frames as it can draw
+    Uncapped,
+}
+
+impl FrameLimit {
+    pub const ALL: [FrameLimit; 6] = [
+        Self::Vsync,
+        Self::Capped(30),
+        Self::Capped(60),
+        Self::Capped(120),
+        Self::Capped(144),
+        Self::Uncapped,
+    ];
+
+    pub fn name(self) -> String {
+        match self {
+            Self::Vsync => "Vsync".into(),
+            Self::Capped(fps) => format!("{fps} FPS"),
+            Self::Uncapped => "Uncapped".into(),
+        }
+    }
+
+    /// What the settings come to, battery saver caps whatever they say
+    pub fn of(settings: &Settings) -> Self {
+        match settings.frame_limit {
+            Self::Capped(fps) if fps <= battery::FPS => Self::Capped(fps),
+            _ if settings.battery_saver => Self::Capped(battery::FPS),
+            limit => limit,
+        }
+    }
+
+    /// The shortest a frame may take, none if it's not up to us
+    fn frame_time(self) -> Option<Duration> {
+        match self {
+            Self::Capped(fps) if fps > 0 => Some(Duration::from_secs_f64(1. / fps as f64)),
+            _ => None,
+        }
+    }
+}
+
+fn update_present_mode(settings: Res<Settings>, mut windows: Query<&mut Window>) {
+    if !settings.is_changed() {
+        return;
+    }
+    let present_mode = match FrameLimit::of(&settings) {
+        FrameLimit::Vsync => PresentMode::AutoVsync,
+        FrameLimit::Capped(_) | FrameLimit::Uncapped => PresentMode::AutoNoVsync,
+    };
+    for mut window in &mut windows {
+        if window.present_mode != present_mode {
+            window.present_mode = present_mode;
+        }
+    }
+}
+
+/// Sleeps until the frame has taken as long as the cap allows
+#[cfg(not(target_arch = "wasm32"))]
+fn limit_frame_rate(settings: Res<Settings>, mut last_frame: Local<Option<Instant>>) {
+    if let (Some(frame_time), Some(last_frame)) =
+        (FrameLimit::of(&settings).frame_time(), *last_frame)
+    {
+        let elapsed = last_frame.elapsed();
+        if elapsed < frame_time {
+            std::thread::sleep(frame_time - elapsed);
+        }
+    }
+    *last_frame = Some(Instant::now());
+}
+
+/// Has bevy_winit wait out the rest of the frame between updates, input
+/// still wakes it early
+#[cfg(target_arch = "wasm32")]
+fn limit_frame_rate(settings: Res<Settings>, mut winit: ResMut<bevy::winit::WinitSettings>) {
+    use bevy::winit::UpdateMode;
+
+    if !settings.is_changed() {
+        return;
+    }
+    let mode = match FrameLimit::of(&settings).frame_time() {
+        Some(wait) => UpdateMode::Reactive { wait },
+        None => UpdateMode::Continuous,
+    };
+    winit.focused_mode = mode;
+    winit.unfocused_mode = mode;
+}
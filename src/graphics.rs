@@ -0,0 +1,209 @@
+//! How the arena is drawn: the camera's zoom and bounds, the optional pixel-perfect
+//! look, where the zoom sticks to whole multiples of the sprites' pixels,
+//! the bloom that makes spells glow and the y-sorting that draws whatever
+//! stands further down the screen in front.
+
+use bevy::{
+    core_pipeline::bloom::{BloomCompositeMode, BloomPrefilterSettings, BloomSettings},
+    prelude::*,
+    render::{
+        camera::ScalingMode,
+        texture::{ImageSampler, ImageSamplerDescriptor},
+    },
+    transform::TransformSystem,
+    window::{PrimaryWindow, WindowResized},
+};
+
+use crate::{assets::WizardSprites, battery, settings::Settings, MAP_SIZE};
+
+/// Base z of everything that's y-sorted, between the floor and the lighting
+pub const YSORT_Z: f32 = 1.;
+/// z per world unit of height, small enough that the whole arena stays
+/// within a fraction of a layer
+const YSORT_Z_PER_UNIT: f32 = 0.005;
+
+/// Texels per world unit in the sprite sheet, a wizard-sized tile is 16px
+const PIXELS_PER_UNIT: f32 = 16.;
+/// World units that always fit on screen both ways
+const MIN_VIEW: f32 = 10.;
+
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PixelScale>()
+            .add_systems(
+                Update,
+                (
+                    update_scaling_mode,
+                    update_bloom,
+                    update_sprite_filtering.run_if(resource_exists::<WizardSprites>),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    y_sort,
+                    (clamp_camera_to_arena, snap_camera_to_pixels).chain(),
+                )
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Drawn in front of or behind other y-sorted sprites by where its bottom
+/// edge is, `offset` below its center, instead of at a fixed z
+#[derive(Component, Clone, Copy)]
+pub struct YSort {
+    pub offset: f32,
+}
+
+/// Physical pixels of what the camera draws to per world unit while
+/// pixel-perfect, `None` otherwise
+#[derive(Resource, Default)]
+pub struct PixelScale(Option<f32>);
+
+fn update_scaling_mode(
+    settings: Res<Settings>,
+    mut resized: EventReader<WindowResized>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut OrthographicProjection, With<Camera>>,
+    new_cameras: Query<(), Added<Camera>>,
+    mut pixel_scale: ResMut<PixelScale>,
+) {
+    let resized = resized.read().count() > 0;
+    if !settings.is_changed() && !resized && new_cameras.is_empty() {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    // the battery saver draws to an image smaller than the window, whose
+    // logical pixels are its physical ones
+    let (render_scale, target_scale_factor) = if settings.battery_saver {
+        (battery::RENDER_SCALE, 1.)
+    } else {
+        (1., window.scale_factor())
+    };
+
+    let scaling_mode = if settings.pixel_perfect {
+        // biggest whole zoom that still fits the arena
+        let shortest_side =
+            window.physical_width().min(window.physical_height()) as f32 * render_scale;
+        let zoom = (shortest_side / (MIN_VIEW * PIXELS_PER_UNIT))
+            .floor()
+            .max(1.);
+        let physical_per_unit = zoom * PIXELS_PER_UNIT;
+        pixel_scale.0 = Some(physical_per_unit);
+        // the projection works in logical pixels
+        ScalingMode::WindowSize(physical_per_unit / target_scale_factor)
+    } else {
+        pixel_scale.0 = None;
+        // at least 10 units both ways, so a tall browser window doesn't crop the sides
+        ScalingMode::AutoMin {
+            min_width: MIN_VIEW,
+            min_height: MIN_VIEW,
+        }
+    };
+
+    for mut projection in &mut cameras {
+        projection.scaling_mode = scaling_mode;
+    }
+}
+
+/// Only what's brighter than white blooms, which is just the spells, see
+/// the glow colors in [`crate::spells`]. Bloom works on WebGL2 too, the
+/// camera's HDR target is the one thing it needs.
+fn update_bloom(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    cameras: Query<Entity, With<Camera2d>>,
+    new_cameras: Query<(), Added<Camera2d>>,
+) {
+    if !settings.is_changed() && new_cameras.is_empty() {
+        return;
+    }
+
+    for camera in &cameras {
+        if settings.bloom_intensity > 0. {
+            commands.entity(camera).insert(BloomSettings {
+                intensity: settings.bloom_intensity,
+                prefilter_settings: BloomPrefilterSettings {
+                    threshold: 1.,
+                    threshold_softness: 0.2,
+                },
+                composite_mode: BloomCompositeMode::Additive,
+                ..BloomSettings::NATURAL
+            });
+        } else {
+            commands.entity(camera).remove::<BloomSettings>();
+        }
+    }
+}
+
+/// Nearest filtering keeps sprite pixels sharp instead of smearing them
+fn update_sprite_filtering(
+    settings: Res<Settings>,
+    sprites: Res<WizardSprites>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !settings.is_changed() && !sprites.is_added() {
+        return;
+    }
+    let Some(image) = images.get_mut(&sprites.projectiles) else {
+        return;
+    };
+    image.sampler = if settings.pixel_perfect {
+        ImageSampler::nearest()
+    } else {
+        ImageSampler::Descriptor(ImageSamplerDescriptor::linear())
+    };
+}
+
+/// Whatever moved the camera, don't let it show the void past the arena's
+/// wall. A view bigger than the arena just stays centered on it.
+pub fn clamp_camera_to_arena(
+    mut cameras: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    // the floor plus the ring of wall around it
+    let arena_half = Vec2::splat(MAP_SIZE as f32 / 2. + 1.);
+
+    for (mut transform, projection) in &mut cameras {
+        let view_half = projection.area.half_size() * transform.scale.xy();
+        let limit = (arena_half - view_half).max(Vec2::ZERO);
+        let clamped = transform.translation.xy().clamp(-limit, limit);
+        if transform.translation.xy() != clamped {
+            transform.translation.x = clamped.x;
+            transform.translation.y = clamped.y;
+        }
+    }
+}
+
+/// Whatever moved the camera, keep it on whole screen pixels so sprites
+/// don't shimmer as it follows a wizard
+pub fn snap_camera_to_pixels(
+    pixel_scale: Res<PixelScale>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    let Some(scale) = pixel_scale.0 else {
+        return;
+    };
+    for mut transform in &mut cameras {
+        let snapped = (transform.translation.xy() * scale).round() / scale;
+        if transform.translation.xy() != snapped {
+            transform.translation.x = snapped.x;
+            transform.translation.y = snapped.y;
+        }
+    }
+}
+
+/// Only z changes, which neither the simulation nor its checksums look at
+fn y_sort(mut sprites: Query<(&YSort, &mut Transform)>) {
+    for (y_sort, mut transform) in &mut sprites {
+        let z = YSORT_Z - (transform.translation.y - y_sort.offset) * YSORT_Z_PER_UNIT;
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! A short ping exchange with the opponent before GGRS takes over the connection.
+//!
+//! Both peers ping each other a few times over the reliable channel to measure
+//! the round trip, which is what the automatic input delay is picked from. A
+//! peer is done once it has enough samples (or gave up) and has heard the same
+//! from its opponent.
+
+use std::time::Duration;
+
+use bevy::utils::Instant;
+use bevy_matchbox::matchbox_socket::{PeerId, WebRtcChannel};
+
+use crate::{
+    matchmaking::MAX_INPUT_DELAY,
+    peer_messages::{self, PeerMessage},
+};
+
+const PING_INTERVAL: Duration = Duration::from_millis(100);
+/// Round trips to measure before settling on an input delay
+const PING_SAMPLES: usize = 8;
+/// Stop measuring after this long, even without enough samples
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+/// Used when not a single ping made it back
+const FALLBACK_INPUT_DELAY: usize = 2;
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Ping exchange with one peer, driven by `wait_for_players` every frame until it's done
+pub struct PingHandshake {
+    peer: PeerId,
+    started: Instant,
+    next_send: Instant,
+    /// When each ping went out, indexed by sequence number
+    sent: Vec<Instant>,
+    round_trips: Vec<Duration>,
+    sent_done: bool,
+    peer_done: bool,
+}
+
+impl PingHandshake {
+    pub fn new(peer: PeerId) -> Self {
+        let now = Instant::now();
+        Self {
+            peer,
+            started: now,
+            next_send: now,
+            sent: Vec::new(),
+            round_trips: Vec::new(),
+            sent_done: false,
+            peer_done: false,
+        }
+    }
+
+    /// The peer said it was done before this handshake got going
+    pub fn peer_done_early(&mut self) {
+        self.peer_done = true;
+    }
+
+    fn measured_enough(&self) -> bool {
+        self.round_trips.len() >= PING_SAMPLES || self.started.elapsed() > HANDSHAKE_TIMEOUT
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.sent_done && self.peer_done
+    }
+
+    /// Answers and sends pings, returns true once both sides are ready for GGRS
+    pub fn update(&mut self, channel: &mut WebRtcChannel) -> bool {
+        for (peer, message) in peer_messages::receive(channel) {
+            match message {
+                PeerMessage::Ping(seq) => PeerMessage::Pong(seq).send(channel, peer),
+                PeerMessage::Pong(seq) => {
+                    if let Some(sent) = self.sent.get(seq as usize) {
+                        self.round_trips.push(sent.elapsed());
+                    }
+                }
+                PeerMessage::HandshakeDone => self.peer_done = true,
+                _ => {}
+            }
+        }
+
+        if self.measured_enough() {
+            // the channel is reliable, once is enough
+            if !self.sent_done {
+                self.sent_done = true;
+                PeerMessage::HandshakeDone.send(channel, self.peer);
+            }
+        } else {
+            let now = Instant::now();
+            if now >= self.next_send {
+                self.next_send = now + PING_INTERVAL;
+                self.sent.push(now);
+                PeerMessage::Ping(self.sent.len() as u32 - 1).send(channel, self.peer);
+            }
+        }
+
+        self.is_done()
+    }
+
+    /// Median round trip to the peer, if any ping made it back
+    pub fn round_trip(&self) -> Option<Duration> {
+        let mut round_trips = self.round_trips.clone();
+        round_trips.sort();
+        round_trips.get(round_trips.len() / 2).copied()
+    }
+}
+
+/// Enough input delay to cover the time inputs take to reach the peer
+pub fn input_delay_for(round_trip: Option<Duration>) -> usize {
+    let Some(round_trip) = round_trip else {
+        return FALLBACK_INPUT_DELAY;
+    };
+    let one_way = round_trip / 2;
+    let frames = one_way.as_nanos().div_ceil(FRAME_DURATION.as_nanos());
+    (frames as usize).min(MAX_INPUT_DELAY)
+}
@@ -0,0 +1,128 @@
+//! Marks the wizard this machine controls, both squares look a lot alike otherwise:
+//! an outline, a "YOU" tag above it and an arrow showing which way it faces.
+//!
+//! Purely visual and outside the simulation: the marker isn't a child of the
+//! wizard, it finds it again every frame. A wizard a rollback respawns is
+//! picked back up once [`crate::redraw`] has given it a `Transform` again.
+
+use bevy::prelude::*;
+use bevy_ggrs::LocalPlayers;
+
+use crate::{bot::Bots, components::*, place_in_arena, GameState};
+
+const OUTLINE_SIZE: f32 = 1.25;
+const OUTLINE_COLOR: Color = Color::rgba(1., 1., 1., 0.8);
+/// How far from the wizard's center the facing arrow sits
+const ARROW_DISTANCE: f32 = 0.85;
+const ARROW_SIZE: f32 = 0.25;
+
+pub struct HighlightPlugin;
+
+impl Plugin for HighlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            follow_local_wizards
+                .after(place_in_arena)
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(OnExit(GameState::InGame), despawn_highlights);
+    }
+}
+
+/// Drawn behind the local wizard with this handle
+#[derive(Component)]
+struct LocalHighlight {
+    handle: usize,
+}
+
+/// Child of a highlight, points the way the wizard faces
+#[derive(Component)]
+struct FacingArrow;
+
+fn follow_local_wizards(
+    mut commands: Commands,
+    local_players: Res<LocalPlayers>,
+    bots: Res<Bots>,
+    wizards: Query<(&Player, &Transform, &Facing, Has<Dummy>)>,
+    mut highlights: Query<
+        (&LocalHighlight, &Children, &mut Transform, &mut Visibility),
+        (Without<Player>, Without<FacingArrow>),
+    >,
+    mut arrows: Query<&mut Transform, (With<FacingArrow>, Without<Player>)>,
+) {
+    for &handle in &local_players.0 {
+        if bots.contains(handle) || highlights.iter().any(|(h, ..)| h.handle == handle) {
+            continue;
+        }
+        commands
+            .spawn((
+                LocalHighlight { handle },
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: OUTLINE_COLOR,
+                        custom_size: Some(Vec2::splat(OUTLINE_SIZE)),
+                        ..default()
+                    },
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        "YOU",
+                        TextStyle {
+                            font_size: 40.,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(0., 0.9, 0.2).with_scale(Vec3::splat(0.012)),
+                    ..default()
+                });
+                parent.spawn((
+                    FacingArrow,
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: OUTLINE_COLOR,
+                            custom_size: Some(Vec2::splat(ARROW_SIZE)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ));
+            });
+    }
+
+    for (highlight, children, mut transform, mut visibility) in &mut highlights {
+        let wizard = wizards
+            .iter()
+            .find(|(player, ..)| player.handle == highlight.handle);
+        match wizard {
+            Some((_, wizard_transform, facing, false)) => {
+                // just underneath the wizard
+                transform.translation = wizard_transform.translation - Vec3::Z * 0.1;
+                *visibility = Visibility::Visible;
+
+                // a square turned on its corner makes do as an arrowhead
+                let facing = facing.0.to_vec2();
+                let mut arrows = arrows.iter_many_mut(children);
+                while let Some(mut arrow) = arrows.fetch_next() {
+                    arrow.translation = (facing * ARROW_DISTANCE).extend(0.2);
+                    arrow.rotation = Quat::from_rotation_z(
+                        facing.y.atan2(facing.x) + std::f32::consts::FRAC_PI_4,
+                    );
+                }
+            }
+            // dead, or a training dummy that happens to be local
+            _ => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+fn despawn_highlights(mut commands: Commands, highlights: Query<Entity, With<LocalHighlight>>) {
+    for highlight in &highlights {
+        commands.entity(highlight).despawn_recursive();
+    }
+}
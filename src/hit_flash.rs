@@ -0,0 +1,104 @@
+//! Hit flashes: a wizard that takes damage flashes white and pops for a few
+//! frames, so hits read clearly even in a busy fight.
+//!
+//! Purely visual and driven by the confirmed [`Hit`] events. The flash is a
+//! white square that follows the wizard around rather than a change to the
+//! wizard's own sprite or transform, which the simulation rolls back. Nothing
+//! flashes with reduced motion on.
+
+use bevy::prelude::*;
+
+use crate::{
+    combat::Hit, components::*, graphics::YSORT_Z, place_in_arena, settings::Settings, GameState,
+};
+
+/// Seconds a flash stays up for, whatever the frame rate
+const FLASH_SECS: f32 = 0.1;
+/// Size of the flash as it starts, it shrinks back to the wizard's own
+const FLASH_POP: f32 = 1.3;
+/// Bright enough to catch a little bloom
+const FLASH_COLOR: Color = Color::rgb(1.6, 1.6, 1.6);
+const CRITICAL_FLASH_COLOR: Color = Color::rgb(2.4, 1.6, 0.6);
+
+pub struct HitFlashPlugin;
+
+impl Plugin for HitFlashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (spawn_flashes, update_flashes)
+                .chain()
+                .after(place_in_arena)
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(OnExit(GameState::InGame), despawn_flashes);
+    }
+}
+
+/// Drawn over the wizard with this handle
+#[derive(Component)]
+struct HitFlash {
+    handle: usize,
+    secs_left: f32,
+}
+
+fn spawn_flashes(mut commands: Commands, settings: Res<Settings>, mut hits: EventReader<Hit>) {
+    if settings.reduced_motion {
+        hits.clear();
+        return;
+    }
+    for hit in hits.read() {
+        commands.spawn((
+            HitFlash {
+                handle: hit.victim,
+                secs_left: FLASH_SECS,
+            },
+            SpriteBundle {
+                sprite: Sprite {
+                    color: if hit.critical {
+                        CRITICAL_FLASH_COLOR
+                    } else {
+                        FLASH_COLOR
+                    },
+                    custom_size: Some(Vec2::ONE),
+                    ..default()
+                },
+                transform: Transform::from_translation(hit.pos.extend(YSORT_Z)),
+                ..default()
+            },
+        ));
+    }
+}
+
+fn update_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    wizards: Query<(&Player, &Transform, &Sprite), Without<HitFlash>>,
+    mut flashes: Query<(Entity, &mut HitFlash, &mut Transform, &mut Sprite)>,
+) {
+    for (entity, mut flash, mut transform, mut sprite) in &mut flashes {
+        if flash.secs_left <= 0. {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        flash.secs_left -= time.delta_seconds();
+
+        // the confirmed hit can come in after a dead wizard is long gone,
+        // it just stays where it was
+        let wizard = wizards
+            .iter()
+            .find(|(player, ..)| player.handle == flash.handle);
+        if let Some((_, wizard_transform, wizard_sprite)) = wizard {
+            // just in front of the wizard
+            transform.translation = wizard_transform.translation + Vec3::Z * 0.001;
+            let pop = 1. + (FLASH_POP - 1.) * flash.secs_left.max(0.) / FLASH_SECS;
+            sprite.custom_size = wizard_sprite.custom_size.map(|size| size * pop);
+        }
+    }
+}
+
+fn despawn_flashes(mut commands: Commands, flashes: Query<Entity, With<HitFlash>>) {
+    for entity in &flashes {
+        commands.entity(entity).despawn();
+    }
+}
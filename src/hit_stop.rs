@@ -0,0 +1,105 @@
+//! Hit-stop: on a heavy hit both wizards involved freeze in place for a
+//! moment, which sells the impact.
+//!
+//! Only what's drawn stops. The simulation keeps running and its transforms
+//! keep moving, the frozen wizards' global transforms are just held where they
+//! were after transform propagation, and catch up when the freeze ends. Off
+//! with reduced motion.
+
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::{combat::Hit, components::*, settings::Settings, GameState};
+
+/// Seconds a heavy hit freezes the wizards for, whatever the frame rate
+const HIT_STOP_SECS: f32 = 0.07;
+/// Hits that do at least this much are heavy, as are crits and kills
+const HEAVY_HIT_DAMAGE: u32 = 30;
+
+pub struct HitStopPlugin;
+
+impl Plugin for HitStopPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HitStop>()
+            .add_systems(Update, start_hit_stop.run_if(in_state(GameState::InGame)))
+            .add_systems(
+                PostUpdate,
+                hold_frozen_wizards.after(TransformSystem::TransformPropagate),
+            )
+            .add_systems(OnExit(GameState::InGame), clear_hit_stop);
+    }
+}
+
+/// Wizards frozen in place right now
+#[derive(Resource, Default)]
+struct HitStop(Vec<Frozen>);
+
+struct Frozen {
+    handle: usize,
+    secs_left: f32,
+    /// Where the wizard is held, taken on the first frozen frame
+    at: Option<GlobalTransform>,
+}
+
+fn start_hit_stop(
+    settings: Res<Settings>,
+    mut hit_stop: ResMut<HitStop>,
+    mut hits: EventReader<Hit>,
+) {
+    if settings.reduced_motion {
+        hits.clear();
+        return;
+    }
+    for hit in hits.read() {
+        if !(hit.critical || hit.fatal || hit.amount >= HEAVY_HIT_DAMAGE) {
+            continue;
+        }
+
+        for handle in std::iter::once(hit.victim).chain(hit.attacker) {
+            match hit_stop.0.iter_mut().find(|frozen| frozen.handle == handle) {
+                // already frozen, hold it a bit longer from where it is
+                Some(frozen) => frozen.secs_left = HIT_STOP_SECS,
+                None => hit_stop.0.push(Frozen {
+                    handle,
+                    secs_left: HIT_STOP_SECS,
+                    at: None,
+                }),
+            }
+        }
+    }
+}
+
+fn hold_frozen_wizards(
+    time: Res<Time>,
+    mut hit_stop: ResMut<HitStop>,
+    mut wizards: Query<(&Player, &mut Transform, &mut GlobalTransform)>,
+) {
+    if hit_stop.0.is_empty() {
+        return;
+    }
+
+    for (player, mut transform, mut global_transform) in &mut wizards {
+        let Some(frozen) = hit_stop
+            .0
+            .iter_mut()
+            .find(|frozen| frozen.handle == player.handle)
+        else {
+            continue;
+        };
+        match frozen.at {
+            Some(at) => *global_transform = at,
+            None => frozen.at = Some(*global_transform),
+        }
+        // propagation skips transforms that didn't change, a wizard that stood
+        // still would otherwise stay where it was frozen
+        transform.set_changed();
+    }
+
+    for frozen in &mut hit_stop.0 {
+        frozen.secs_left -= time.delta_seconds();
+    }
+    hit_stop.0.retain(|frozen| frozen.secs_left > 0.);
+}
+
+fn clear_hit_stop(mut hit_stop: ResMut<HitStop>) {
+    hit_stop.0.clear();
+}
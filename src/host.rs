@@ -0,0 +1,53 @@
+//! Running the matchbox signaling server inside the game, for LAN play
+//! without setting up a server first.
+//!
+//! Only built with the `host` feature, natively. Start one player with
+//! `--host`, it prints the address the other player should point
+//! `MATCHBOX_URL` at. The server only introduces the peers, the match itself
+//! is played peer to peer as always.
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+
+use bevy::prelude::*;
+use bevy_matchbox::{matchbox_signaling::SignalingServer, MatchboxServer};
+
+use crate::{regions::RegionPings, signaling::MatchboxUrl};
+
+const HOST_PORT: u16 = 3536;
+
+pub struct HostPlugin;
+
+impl Plugin for HostPlugin {
+    fn build(&self, app: &mut App) {
+        if std::env::args().any(|arg| arg == "--host") {
+            app.add_systems(Startup, start_signaling_server);
+        }
+    }
+}
+
+fn start_signaling_server(mut commands: Commands, mut url: ResMut<MatchboxUrl>) {
+    commands.insert_resource(MatchboxServer::from(SignalingServer::full_mesh_builder((
+        Ipv4Addr::UNSPECIFIED,
+        HOST_PORT,
+    ))));
+
+    // we're on the same machine as the server, whatever region is closest
+    *url = MatchboxUrl(format!("ws://127.0.0.1:{HOST_PORT}"));
+    commands.remove_resource::<RegionPings>();
+
+    let address = match lan_address() {
+        Some(ip) => format!("ws://{ip}:{HOST_PORT}"),
+        None => format!("ws://<this machine's address>:{HOST_PORT}"),
+    };
+    info!("hosting a signaling server on port {HOST_PORT}");
+    info!("waiting for a friend, have them start the game with MATCHBOX_URL={address}");
+}
+
+/// The address other machines on the network can reach us at. Connecting a
+/// UDP socket doesn't send anything, it only makes the OS pick the interface
+/// it would route through.
+fn lan_address() -> Option<std::net::IpAddr> {
+    let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).ok()?;
+    socket.connect((Ipv4Addr::new(8, 8, 8, 8), 80)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
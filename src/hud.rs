@@ -0,0 +1,439 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::*,
+    in_arena,
+    mana::MAX_MANA,
+    palette::Palette,
+    round::{Kill, RoundClock, RoundKills, RoundPhase},
+    settings::Settings,
+    training::TrainingRange,
+    ultimate::MAX_ULTIMATE_CHARGE,
+    GameState, MAX_HEALTH,
+};
+
+const HEALTH_BAR_WIDTH: f32 = 1.;
+const HEALTH_BAR_HEIGHT: f32 = 0.12;
+const MANA_BAR_HEIGHT: f32 = 0.06;
+const MANA_COLOR: Color = Color::rgb(0.3, 0.5, 1.);
+const ULTIMATE_BAR_HEIGHT: f32 = 0.04;
+const ULTIMATE_COLOR: Color = Color::rgb(1., 0.75, 0.2);
+/// Bright enough to bloom, once the ultimate is ready to go
+const ULTIMATE_READY_COLOR: Color = Color::rgb(2.5, 1.9, 0.5);
+/// Combos only show from this many hits up
+const MIN_SHOWN_COMBO: u32 = 2;
+const COMBO_COLOR: Color = Color::rgb(1., 0.8, 0.2);
+const STUN_COLOR: Color = Color::rgb(0.6, 0.8, 1.);
+const SPELL_COLOR: Color = Color::rgb(0.85, 0.85, 0.85);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::InGame),
+            (spawn_round_timer, spawn_kill_feed),
+        )
+        .add_systems(
+            OnEnter(GameState::Replay),
+            (spawn_round_timer, spawn_kill_feed),
+        )
+        .add_systems(OnExit(GameState::InGame), despawn_hud)
+        .add_systems(OnExit(GameState::Replay), despawn_hud)
+        .add_systems(
+            Update,
+            (
+                update_round_timer,
+                add_health_bars,
+                update_health_bars,
+                update_mana_bars,
+                update_ultimate_bars,
+                update_combo_labels,
+                update_stun_labels,
+                update_spell_labels,
+                update_kill_feed,
+            )
+                .run_if(in_arena),
+        );
+    }
+}
+
+#[derive(Component)]
+struct RoundTimerText;
+
+#[derive(Component)]
+struct HealthBar;
+
+#[derive(Component)]
+struct ManaBar;
+
+#[derive(Component)]
+struct UltimateBar;
+
+#[derive(Component)]
+struct ComboLabel;
+
+#[derive(Component)]
+struct StunLabel;
+
+#[derive(Component)]
+struct SpellLabel;
+
+/// Root of everything the HUD spawns in screen space
+#[derive(Component)]
+struct HudRoot;
+
+#[derive(Component)]
+struct KillFeed;
+
+fn spawn_round_timer(mut commands: Commands) {
+    commands
+        .spawn((
+            HudRoot,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.),
+                    width: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                RoundTimerText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 40.,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
+
+fn spawn_kill_feed(mut commands: Commands) {
+    commands.spawn((
+        HudRoot,
+        KillFeed,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.),
+                right: Val::Px(10.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+fn despawn_hud(mut commands: Commands, roots: Query<Entity, With<HudRoot>>) {
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+}
+
+fn update_round_timer(
+    clock: Res<RoundClock>,
+    phase: Res<RoundPhase>,
+    training: Option<Res<TrainingRange>>,
+    mut texts: Query<&mut Text, With<RoundTimerText>>,
+) {
+    let label = if training.is_some() {
+        String::new() // no time limit on the range
+    } else if clock.overtime {
+        "OVERTIME".to_string()
+    } else if *phase == RoundPhase::Fighting || matches!(*phase, RoundPhase::Countdown { .. }) {
+        let seconds = clock.frames_left.div_ceil(60);
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        String::new()
+    };
+
+    for mut text in &mut texts {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+            text.sections[0].style.color = if clock.overtime {
+                Color::ORANGE_RED
+            } else {
+                Color::WHITE
+            };
+        }
+    }
+}
+
+fn add_health_bars(
+    mut commands: Commands,
+    players: Query<(Entity, Has<Decoy>), Or<(Added<Player>, Added<Decoy>)>>,
+) {
+    for (player, decoy) in &players {
+        commands.entity(player).with_children(|parent| {
+            parent.spawn((
+                HealthBar,
+                SpriteBundle {
+                    transform: Transform::from_xyz(0., 0.7, 0.1),
+                    sprite: Sprite {
+                        color: Color::LIME_GREEN,
+                        custom_size: Some(Vec2::new(HEALTH_BAR_WIDTH, HEALTH_BAR_HEIGHT)),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+            // a decoy's stays full, close enough to fool anyone at a glance
+            parent.spawn((
+                ManaBar,
+                SpriteBundle {
+                    transform: Transform::from_xyz(0., 0.6, 0.1),
+                    sprite: Sprite {
+                        color: MANA_COLOR,
+                        custom_size: Some(Vec2::new(HEALTH_BAR_WIDTH, MANA_BAR_HEIGHT)),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                UltimateBar,
+                SpriteBundle {
+                    transform: Transform::from_xyz(0., 0.52, 0.1),
+                    sprite: Sprite {
+                        color: ULTIMATE_COLOR,
+                        custom_size: Some(Vec2::new(0., ULTIMATE_BAR_HEIGHT)),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+            // decoys only need to pass for the wizard at a glance
+            if decoy {
+                return;
+            }
+            parent.spawn((
+                ComboLabel,
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 40.,
+                            color: COMBO_COLOR,
+                            ..default()
+                        },
+                    ),
+                    // right of the health bar
+                    transform: Transform::from_xyz(HEALTH_BAR_WIDTH * 0.5 + 0.3, 0.7, 0.1)
+                        .with_scale(Vec3::splat(0.012)),
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                StunLabel,
+                Text2dBundle {
+                    text: Text::from_section(
+                        "STUNNED",
+                        TextStyle {
+                            font_size: 40.,
+                            color: STUN_COLOR,
+                            ..default()
+                        },
+                    ),
+                    // above the health bar
+                    transform: Transform::from_xyz(0., 0.95, 0.1).with_scale(Vec3::splat(0.008)),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                SpellLabel,
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 40.,
+                            color: SPELL_COLOR,
+                            ..default()
+                        },
+                    ),
+                    // below the wizard
+                    transform: Transform::from_xyz(0., -0.7, 0.1).with_scale(Vec3::splat(0.007)),
+                    ..default()
+                },
+            ));
+        });
+    }
+}
+
+fn update_health_bars(
+    // children too, a decoy cast at low health needs its bar cut down as soon as it's there
+    players: Query<(&Health, &Children), Or<(Changed<Health>, Changed<Children>)>>,
+    mut bars: Query<(&mut Sprite, &mut Transform), With<HealthBar>>,
+) {
+    for (health, children) in &players {
+        let fraction = health.0 as f32 / MAX_HEALTH as f32;
+        for &child in children {
+            if let Ok((mut sprite, mut transform)) = bars.get_mut(child) {
+                sprite.custom_size =
+                    Some(Vec2::new(HEALTH_BAR_WIDTH * fraction, HEALTH_BAR_HEIGHT));
+                // keep the bar left-aligned as it shrinks
+                transform.translation.x = -HEALTH_BAR_WIDTH * (1. - fraction) / 2.;
+                sprite.color = if fraction > 0.3 {
+                    Color::LIME_GREEN
+                } else {
+                    Color::RED
+                };
+            }
+        }
+    }
+}
+
+fn update_mana_bars(
+    players: Query<(&Mana, &Children), Or<(Changed<Mana>, Changed<Children>)>>,
+    mut bars: Query<(&mut Sprite, &mut Transform), With<ManaBar>>,
+) {
+    for (mana, children) in &players {
+        let fraction = mana.amount as f32 / MAX_MANA as f32;
+        for &child in children {
+            if let Ok((mut sprite, mut transform)) = bars.get_mut(child) {
+                sprite.custom_size = Some(Vec2::new(HEALTH_BAR_WIDTH * fraction, MANA_BAR_HEIGHT));
+                transform.translation.x = -HEALTH_BAR_WIDTH * (1. - fraction) / 2.;
+            }
+        }
+    }
+}
+
+fn update_ultimate_bars(
+    players: Query<(&Ultimate, &Children), Or<(Changed<Ultimate>, Changed<Children>)>>,
+    mut bars: Query<(&mut Sprite, &mut Transform), With<UltimateBar>>,
+) {
+    for (ultimate, children) in &players {
+        let fraction = ultimate.charge as f32 / MAX_ULTIMATE_CHARGE as f32;
+        for &child in children {
+            if let Ok((mut sprite, mut transform)) = bars.get_mut(child) {
+                sprite.custom_size =
+                    Some(Vec2::new(HEALTH_BAR_WIDTH * fraction, ULTIMATE_BAR_HEIGHT));
+                transform.translation.x = -HEALTH_BAR_WIDTH * (1. - fraction) / 2.;
+                sprite.color = if ultimate.ready() {
+                    ULTIMATE_READY_COLOR
+                } else {
+                    ULTIMATE_COLOR
+                };
+            }
+        }
+    }
+}
+
+fn update_combo_labels(
+    players: Query<(&Combo, &Children), Changed<Combo>>,
+    mut labels: Query<&mut Text, With<ComboLabel>>,
+) {
+    for (combo, children) in &players {
+        for &child in children {
+            if let Ok(mut text) = labels.get_mut(child) {
+                text.sections[0].value = if combo.0 >= MIN_SHOWN_COMBO {
+                    format!("x{}", combo.0)
+                } else {
+                    String::new()
+                };
+            }
+        }
+    }
+}
+
+fn update_stun_labels(
+    players: Query<(&Stun, &Children), Changed<Stun>>,
+    mut labels: Query<&mut Visibility, With<StunLabel>>,
+) {
+    for (stun, children) in &players {
+        for &child in children {
+            if let Ok(mut visibility) = labels.get_mut(child) {
+                *visibility = if stun.frames_left > 0 {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
+            }
+        }
+    }
+}
+
+fn update_spell_labels(
+    players: Query<(&Spellbook, &Children), Changed<Spellbook>>,
+    mut labels: Query<&mut Text, With<SpellLabel>>,
+) {
+    for (spellbook, children) in &players {
+        for &child in children {
+            if let Ok(mut text) = labels.get_mut(child) {
+                text.sections[0].value = spellbook.selected.name().to_string();
+            }
+        }
+    }
+}
+
+fn update_kill_feed(
+    mut commands: Commands,
+    kills: Res<RoundKills>,
+    settings: Res<Settings>,
+    feeds: Query<Entity, With<KillFeed>>,
+) {
+    // the kills are rolled back with the rest of the round, so just rebuild the
+    // whole feed whenever they change, or the colors do
+    if !kills.is_changed() && !settings.is_changed() {
+        return;
+    }
+
+    for feed in &feeds {
+        commands.entity(feed).despawn_descendants();
+        commands.entity(feed).with_children(|parent| {
+            for kill in &kills.0 {
+                parent.spawn(TextBundle::from_sections(kill_feed_line(
+                    kill,
+                    settings.palette,
+                )));
+            }
+        });
+    }
+}
+
+fn kill_feed_line(kill: &Kill, palette: Palette) -> Vec<TextSection> {
+    let name = |handle: usize| {
+        TextSection::new(
+            format!("Player {}", handle + 1),
+            TextStyle {
+                font_size: 22.,
+                color: palette.color(handle),
+                ..default()
+            },
+        )
+    };
+    let plain = |value: &str| {
+        TextSection::new(
+            value,
+            TextStyle {
+                font_size: 22.,
+                ..default()
+            },
+        )
+    };
+
+    let mut line = Vec::new();
+    if kill.killer != kill.victim {
+        line.push(name(kill.killer));
+        for &assist in &kill.assists {
+            line.push(plain(" + "));
+            line.push(name(assist));
+        }
+        line.push(plain(" blasted "));
+        line.push(name(kill.victim));
+    } else {
+        line.push(name(kill.victim));
+        line.push(plain(" blew themselves up"));
+    }
+    line
+}
@@ -1,51 +1,297 @@
 use bevy::{prelude::*, utils::warn};
 use bevy_ggrs::{LocalInputs, LocalPlayers, PlayerInputs};
 
-use crate::{Config, Player};
+use crate::{
+    end_screen::RematchVote, input_log::InputPlayback, settings::Settings, touch::TouchControls,
+    Config, Player, TextInputFocus,
+};
 
-const INPUT_UP: u8 = 1 << 0;
-const INPUT_DOWN: u8 = 1 << 1;
-const INPUT_LEFT: u8 = 1 << 2;
-const INPUT_RIGHT: u8 = 1 << 3;
-const INPUT_FIRE: u8 = 1 << 4;
+/// Everything a wizard does on one frame, as bits. Two bytes, the first one
+/// filled up once the spells came along.
+pub type Input = u16;
 
-pub fn fire(input: u8) -> bool {
+const INPUT_UP: Input = 1 << 0;
+const INPUT_DOWN: Input = 1 << 1;
+const INPUT_LEFT: Input = 1 << 2;
+const INPUT_RIGHT: Input = 1 << 3;
+const INPUT_FIRE: Input = 1 << 4;
+const INPUT_REMATCH: Input = 1 << 5;
+const INPUT_CAST: Input = 1 << 6;
+const INPUT_NEXT_SPELL: Input = 1 << 7;
+const INPUT_ULTIMATE: Input = 1 << 8;
+/// How far a stick has to be pushed to move, it rarely rests at exactly zero
+const GAMEPAD_DEADZONE: f32 = 0.3;
+/// Every bit in use, the rest are spare and stay clear
+const INPUT_MASK: Input = INPUT_UP
+    | INPUT_DOWN
+    | INPUT_LEFT
+    | INPUT_RIGHT
+    | INPUT_FIRE
+    | INPUT_REMATCH
+    | INPUT_CAST
+    | INPUT_NEXT_SPELL
+    | INPUT_ULTIMATE;
+
+pub fn fire(input: Input) -> bool {
     input & INPUT_FIRE != 0
 }
 
+pub fn cast(input: Input) -> bool {
+    input & INPUT_CAST != 0
+}
+
+pub fn next_spell(input: Input) -> bool {
+    input & INPUT_NEXT_SPELL != 0
+}
+
+pub fn ultimate(input: Input) -> bool {
+    input & INPUT_ULTIMATE != 0
+}
+
+pub fn rematch(input: Input) -> bool {
+    input & INPUT_REMATCH != 0
+}
+
+/// Whether `input` could have come from an unmodified client
+pub fn valid(input: Input) -> bool {
+    input & !INPUT_MASK == 0
+}
+
+/// Turns malformed inputs into neutral ones before anything in the simulation
+/// reads them, so a modified client can't glitch the match for everyone else.
+/// Our own inputs go through it too, both peers have to simulate the same thing.
+pub fn sanitize_inputs(mut inputs: ResMut<PlayerInputs<Config>>, mut warned: Local<bool>) {
+    for (handle, (input, _)) in inputs.iter_mut().enumerate() {
+        if valid(*input) {
+            continue;
+        }
+        // rollbacks would repeat it every frame
+        if !*warned {
+            warn!("player {handle} sent a malformed input {input:#06x}, ignoring it");
+            *warned = true;
+        }
+        *input = 0;
+    }
+}
+
+/// Builds the input for a wizard moving in `direction` (snapped to the
+/// eight directions the keys allow)
+pub fn encode_input(direction: Vec2, fire: bool, cast: bool, rematch: bool) -> Input {
+    // sin(22.5°), anything less than that is closer to the other axis
+    let threshold = 0.38 * direction.length();
+    let mut input = 0;
+
+    if direction.y > threshold {
+        input |= INPUT_UP;
+    }
+    if direction.y < -threshold {
+        input |= INPUT_DOWN;
+    }
+    if direction.x < -threshold {
+        input |= INPUT_LEFT;
+    }
+    if direction.x > threshold {
+        input |= INPUT_RIGHT;
+    }
+    if fire {
+        input |= INPUT_FIRE;
+    }
+    if cast {
+        input |= INPUT_CAST;
+    }
+    if rematch {
+        input |= INPUT_REMATCH;
+    }
+    input
+}
+
+/// Whether the wizard fires on its own, with toggle fire on in the settings:
+/// tapping fire turns it on and tapping again turns it off, on any device.
+#[derive(Resource, Default)]
+pub struct FireToggle {
+    on: bool,
+    /// Whether fire was held last frame, a tap is when it wasn't
+    held: bool,
+    /// Whether this frame's fire bit is set, see [`FireToggle::apply`]
+    pulse: bool,
+}
+
+impl FireToggle {
+    /// Swaps the fire bit for the toggle's. A shot needs fire let go of since
+    /// the last one, so while the toggle is on the bit goes on and off every
+    /// other frame, as fast as anyone could mash it.
+    fn apply(&mut self, input: Input) -> Input {
+        let held = fire(input);
+        if held && !self.held {
+            self.on = !self.on;
+        }
+        self.held = held;
+        self.pulse = self.on && !self.pulse;
+
+        if self.pulse {
+            input | INPUT_FIRE
+        } else {
+            input & !INPUT_FIRE
+        }
+    }
+}
+
+/// Nobody carries on firing into the next match
+pub fn release_fire_toggle(mut toggle: ResMut<FireToggle>) {
+    *toggle = FireToggle::default();
+}
+
 pub fn read_local_inputs(
     mut commands: Commands,
     keys: Res<ButtonInput<KeyCode>>,
     local_players: Res<LocalPlayers>,
+    rematch_vote: Res<RematchVote>,
+    focus: Res<TextInputFocus>,
+    touch: Res<TouchControls>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    settings: Res<Settings>,
+    mut fire_toggle: ResMut<FireToggle>,
+    playback: Option<ResMut<InputPlayback>>,
 ) {
     let mut local_inputs = bevy::utils::HashMap::new();
 
-    for handle in &local_players.0 {
-        let mut input = 0u8;
+    // once for everyone here, they're all on the same keys
+    let mut live = keyboard_input(&keys, &rematch_vote)
+        | touch_input(&touch)
+        | gamepad_input(&gamepads, &gamepad_buttons, &axes);
+    if settings.toggle_fire && !focus.0 {
+        live = fire_toggle.apply(live);
+    }
+
+    // scripted inputs are listed by handle
+    let mut handles = local_players.0.clone();
+    handles.sort();
+    let scripted = playback.map(|mut playback| playback.next_frame());
+
+    for (i, handle) in handles.into_iter().enumerate() {
+        let input = match &scripted {
+            Some(inputs) => inputs.get(i).copied().unwrap_or(0),
+            // typing in the chat box doesn't steer the wizard
+            None if focus.0 => encode_input(Vec2::ZERO, false, false, rematch_vote.0),
+            None => live,
+        };
+
+        local_inputs.insert(handle, input);
+    }
+
+    commands.insert_resource(LocalInputs::<Config>(local_inputs));
+}
+
+fn keyboard_input(keys: &ButtonInput<KeyCode>, rematch_vote: &RematchVote) -> Input {
+    let mut input = 0;
+
+    if keys.any_pressed([KeyCode::ArrowUp, KeyCode::KeyW]) {
+        input |= INPUT_UP;
+    }
+    if keys.any_pressed([KeyCode::ArrowDown, KeyCode::KeyS]) {
+        input |= INPUT_DOWN;
+    }
+    if keys.any_pressed([KeyCode::ArrowLeft, KeyCode::KeyA]) {
+        input |= INPUT_LEFT
+    }
+    if keys.any_pressed([KeyCode::ArrowRight, KeyCode::KeyD]) {
+        input |= INPUT_RIGHT;
+    }
+    // Enter opens the chat
+    if keys.pressed(KeyCode::Space) {
+        input |= INPUT_FIRE;
+    }
+    if keys.any_pressed([KeyCode::KeyE, KeyCode::ShiftRight]) {
+        input |= INPUT_CAST;
+    }
+    if keys.any_pressed([KeyCode::KeyQ, KeyCode::ControlRight]) {
+        input |= INPUT_NEXT_SPELL;
+    }
+    if keys.any_pressed([KeyCode::KeyF, KeyCode::Slash]) {
+        input |= INPUT_ULTIMATE;
+    }
+    if rematch_vote.0 {
+        input |= INPUT_REMATCH;
+    }
+    input
+}
+
+/// Every connected gamepad steers the same wizard, with the buttons
+/// [`crate::prompts`] tells the player about
+fn gamepad_input(
+    gamepads: &Gamepads,
+    buttons: &ButtonInput<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+) -> Input {
+    let mut input = 0;
 
-        if keys.any_pressed([KeyCode::ArrowUp, KeyCode::KeyW]) {
-            input |= INPUT_UP;
+    for gamepad in gamepads.iter() {
+        let pressed = |button| buttons.pressed(GamepadButton::new(gamepad, button));
+        let axis = |axis| axes.get(GamepadAxis::new(gamepad, axis)).unwrap_or(0.);
+
+        let mut direction = Vec2::new(
+            axis(GamepadAxisType::LeftStickX),
+            axis(GamepadAxisType::LeftStickY),
+        );
+        if direction.length() < GAMEPAD_DEADZONE {
+            direction = Vec2::ZERO;
         }
-        if keys.any_pressed([KeyCode::ArrowDown, KeyCode::KeyS]) {
-            input |= INPUT_DOWN;
+        if pressed(GamepadButtonType::DPadUp) {
+            direction.y += 1.;
+        }
+        if pressed(GamepadButtonType::DPadDown) {
+            direction.y -= 1.;
+        }
+        if pressed(GamepadButtonType::DPadLeft) {
+            direction.x -= 1.;
         }
-        if keys.any_pressed([KeyCode::ArrowLeft, KeyCode::KeyA]) {
-            input |= INPUT_LEFT
+        if pressed(GamepadButtonType::DPadRight) {
+            direction.x += 1.;
         }
-        if keys.any_pressed([KeyCode::ArrowRight, KeyCode::KeyD]) {
-            input |= INPUT_RIGHT;
+
+        input |= encode_input(
+            direction,
+            pressed(GamepadButtonType::South),
+            pressed(GamepadButtonType::West),
+            false,
+        );
+        if pressed(GamepadButtonType::LeftTrigger) {
+            input |= INPUT_NEXT_SPELL;
         }
-        if keys.any_pressed([KeyCode::Space, KeyCode::Enter]) {
-            input |= INPUT_FIRE;
+        if pressed(GamepadButtonType::North) {
+            input |= INPUT_ULTIMATE;
         }
-        
-        local_inputs.insert(*handle, input);
     }
+    input
+}
 
-    commands.insert_resource(LocalInputs::<Config>(local_inputs));
+/// Whether `button` was just pressed on any gamepad
+pub fn gamepad_just_pressed(
+    gamepads: &Gamepads,
+    buttons: &ButtonInput<GamepadButton>,
+    button: GamepadButtonType,
+) -> bool {
+    gamepads
+        .iter()
+        .any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button)))
+}
+
+/// What's held on the on-screen controls, see [`crate::touch`]
+fn touch_input(touch: &TouchControls) -> Input {
+    let mut input = encode_input(touch.direction, touch.fire, touch.cast, false);
+
+    if touch.next_spell {
+        input |= INPUT_NEXT_SPELL;
+    }
+    if touch.ultimate {
+        input |= INPUT_ULTIMATE;
+    }
+    input
 }
 
-pub fn direction(input: u8) -> Vec2 {
+pub fn direction(input: Input) -> Vec2 {
         let mut direction = Vec2::ZERO;
 
         if input & INPUT_UP != 0 {
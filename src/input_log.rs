@@ -0,0 +1,117 @@
+//! Dev facility for driving the game without a keyboard.
+//!
+//! `--record-inputs <file>` writes every local input to a text file as it's
+//! read, and `--play-inputs <file>` feeds such a file back into
+//! `read_local_inputs` in place of the keyboard. Each line is one frame: the
+//! input of every local player in hex, ordered by handle, so scripts for
+//! gameplay regression tests can just as well be written by hand.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{LineWriter, Write},
+};
+
+use bevy::prelude::*;
+use bevy_ggrs::{LocalInputs, ReadInputs};
+
+use crate::{
+    bot::read_bot_inputs,
+    input::{read_local_inputs, Input},
+    Config,
+};
+
+pub struct InputLogPlugin;
+
+impl Plugin for InputLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, open_input_logs).add_systems(
+            ReadInputs,
+            // bots fill in their inputs after the keyboard, the log has to
+            // have theirs for the frame too
+            record_local_inputs
+                .after(read_local_inputs)
+                .after(read_bot_inputs)
+                .run_if(resource_exists::<InputRecorder>),
+        );
+    }
+}
+
+/// Local inputs coming from a file instead of the keyboard
+#[derive(Resource, Debug)]
+pub struct InputPlayback {
+    frames: VecDeque<Vec<Input>>,
+}
+
+impl InputPlayback {
+    fn parse(text: &str) -> Result<Self, String> {
+        let frames = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                line.split_whitespace()
+                    .map(|input| Input::from_str_radix(input, 16))
+                    .collect::<Result<Vec<Input>, _>>()
+                    .map_err(|err| format!("line {}: {err}", i + 1))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { frames })
+    }
+
+    /// Inputs for the next frame, one per local player. Once the file runs
+    /// out, nobody presses anything any more.
+    pub fn next_frame(&mut self) -> Vec<Input> {
+        self.frames.pop_front().unwrap_or_default()
+    }
+}
+
+/// Where the local inputs are being written to
+#[derive(Resource)]
+struct InputRecorder(LineWriter<File>);
+
+fn open_input_logs(mut commands: Commands) {
+    let arg = |name: &str| std::env::args().skip_while(|arg| arg != name).nth(1);
+
+    if let Some(path) = arg("--play-inputs") {
+        match std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| InputPlayback::parse(&text))
+        {
+            Ok(playback) => {
+                info!(
+                    "playing back {} frames of inputs from {path}",
+                    playback.frames.len()
+                );
+                commands.insert_resource(playback);
+            }
+            Err(err) => error!("failed to load inputs from {path}: {err}"),
+        }
+    }
+
+    if let Some(path) = arg("--record-inputs") {
+        match File::create(&path) {
+            Ok(file) => {
+                info!("recording inputs to {path}");
+                commands.insert_resource(InputRecorder(LineWriter::new(file)));
+            }
+            Err(err) => error!("failed to create {path}: {err}"),
+        }
+    }
+}
+
+fn record_local_inputs(
+    mut recorder: ResMut<InputRecorder>,
+    local_inputs: Res<LocalInputs<Config>>,
+) {
+    let mut handles: Vec<&usize> = local_inputs.0.keys().collect();
+    handles.sort();
+    let line: Vec<String> = handles
+        .into_iter()
+        .map(|handle| format!("{:02x}", local_inputs.0[handle]))
+        .collect();
+
+    if let Err(err) = writeln!(recorder.0, "{}", line.join(" ")) {
+        error!("failed to record inputs: {err}");
+    }
+}
@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+
+use crate::{
+    in_arena, num_players,
+    round::{RoundKills, RoundNumber, RoundPhase, Scores},
+    settings::Settings,
+    Config, GameState, MAP_SIZE, SPAWN_POINTS,
+};
+
+/// Size of the next-round map preview, in logical pixels
+const PREVIEW_SIZE: f32 = 164.;
+
+pub struct IntermissionPlugin;
+
+impl Plugin for IntermissionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, toggle_intermission_screen.run_if(in_arena))
+            .add_systems(OnExit(GameState::InGame), despawn_intermission_screen)
+            .add_systems(OnExit(GameState::Replay), despawn_intermission_screen);
+    }
+}
+
+#[derive(Component)]
+struct IntermissionScreen;
+
+fn toggle_intermission_screen(
+    mut commands: Commands,
+    phase: Res<RoundPhase>,
+    scores: Res<Scores>,
+    round: Res<RoundNumber>,
+    kills: Res<RoundKills>,
+    session: Option<Res<Session<Config>>>,
+    settings: Res<Settings>,
+    screens: Query<Entity, With<IntermissionScreen>>,
+) {
+    let in_intermission = matches!(*phase, RoundPhase::Intermission { .. });
+
+    if !in_intermission {
+        for screen in &screens {
+            commands.entity(screen).despawn_recursive();
+        }
+        return;
+    }
+
+    if !screens.is_empty() {
+        return;
+    }
+    let num_players = session.map_or(2, |session| num_players(&session));
+
+    commands
+        .spawn((
+            IntermissionScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(12.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(text(format!("Round {} complete", round.0), 56.));
+
+            // score line, one colored entry per player
+            parent.spawn(TextBundle::from_sections((0..num_players).map(|handle| {
+                TextSection::new(
+                    format!("  Player {}: {}  ", handle + 1, scores.get(handle)),
+                    TextStyle {
+                        font_size: 36.,
+                        color: settings.palette.color(handle),
+                        ..default()
+                    },
+                )
+            })));
+
+            if kills.0.is_empty() {
+                parent.spawn(text("Time ran out".into(), 24.));
+            }
+            for kill in &kills.0 {
+                let mut line = if kill.killer == kill.victim {
+                    format!("Player {} blew themselves up", kill.victim + 1)
+                } else {
+                    format!(
+                        "Player {} blasted Player {}",
+                        kill.killer + 1,
+                        kill.victim + 1
+                    )
+                };
+                for assist in &kill.assists {
+                    line += &format!(" (assist: Player {})", assist + 1);
+                }
+                parent.spawn(text(line, 24.));
+            }
+
+            parent.spawn(text(format!("Next up: round {}", round.0 + 1), 28.));
+
+            // the arena with everyone's spawn point marked
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(PREVIEW_SIZE),
+                        height: Val::Px(PREVIEW_SIZE),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.53, 0.53, 0.53).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    let scale = PREVIEW_SIZE / MAP_SIZE as f32;
+                    let marker = 8.;
+                    for (handle, spawn) in SPAWN_POINTS.iter().take(num_players).enumerate() {
+                        parent.spawn(NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(PREVIEW_SIZE / 2. + spawn.x * scale - marker / 2.),
+                                top: Val::Px(PREVIEW_SIZE / 2. - spawn.y * scale - marker / 2.),
+                                width: Val::Px(marker),
+                                height: Val::Px(marker),
+                                ..default()
+                            },
+                            background_color: settings.palette.color(handle).into(),
+                            ..default()
+                        });
+                    }
+                });
+        });
+}
+
+fn despawn_intermission_screen(
+    mut commands: Commands,
+    screens: Query<Entity, With<IntermissionScreen>>,
+) {
+    for screen in &screens {
+        commands.entity(screen).despawn_recursive();
+    }
+}
+
+fn text(value: String, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size,
+            ..default()
+        },
+    )
+}
@@ -0,0 +1,291 @@
+//! Finding an opponent on the local network, without a signaling server.
+//!
+//! Native only. While matchmaking, every client broadcasts a small beacon on
+//! `DISCOVERY_PORT` once a second. A client that hears someone else's beacon
+//! answers it directly, acknowledging them, and the match starts once both
+//! sides have been acknowledged. The match is then played over the same UDP
+//! socket, with no WebRTC involved. Matchbox keeps looking in the meantime,
+//! whichever finds an opponent first wins.
+//!
+//! Only one client per machine can listen on the port, a second one on the
+//! same machine just won't take part.
+
+use std::{
+    io::ErrorKind,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use bevy::{prelude::*, utils::Instant};
+use bevy_ggrs::ggrs::{Message, NonBlockingSocket, PlayerType};
+use bevy_matchbox::{
+    matchbox_socket::{MultipleChannels, PeerId},
+    MatchboxSocket,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    handshake::input_delay_for,
+    matchmaking::{InputDelay, MatchInputDelay},
+    start_p2p_session, wants_matchmaking, GameState,
+};
+
+const DISCOVERY_PORT: u16 = 3537;
+/// First bytes of every beacon, anything else is meant for GGRS
+const BEACON_MAGIC: [u8; 4] = *b"WBLN";
+/// Bumped whenever the game stops being compatible with older builds
+const PROTOCOL_VERSION: u16 = 1;
+const BEACON_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_PACKET_SIZE: usize = 4096;
+
+pub struct LanPlugin;
+
+impl Plugin for LanPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::Matchmaking),
+            start_lan_discovery.run_if(wants_matchmaking),
+        )
+        .add_systems(OnExit(GameState::Matchmaking), stop_lan_discovery)
+        .add_systems(
+            Update,
+            discover_lan_peers
+                .run_if(in_state(GameState::Matchmaking))
+                .run_if(resource_exists::<LanDiscovery>),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Beacon {
+    magic: [u8; 4],
+    version: u16,
+    /// Random, tells clients apart and decides who's player one
+    nonce: u64,
+    /// Nonce of the client this beacon is answering
+    ack: Option<u64>,
+}
+
+impl Beacon {
+    fn new(nonce: u64, ack: Option<u64>) -> Self {
+        Self {
+            magic: BEACON_MAGIC,
+            version: PROTOCOL_VERSION,
+            nonce,
+            ack,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("serializing to memory can't fail")
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if !bytes.starts_with(&BEACON_MAGIC) {
+            return None;
+        }
+        bincode::deserialize(bytes).ok()
+    }
+}
+
+/// Somebody we've heard a beacon from
+#[derive(Clone, Copy, Debug)]
+struct LanPeer {
+    addr: SocketAddr,
+    nonce: u64,
+}
+
+impl LanPeer {
+    fn id(&self) -> PeerId {
+        PeerId(Uuid::from_u64_pair(0, self.nonce))
+    }
+}
+
+#[derive(Resource)]
+struct LanDiscovery {
+    socket: UdpSocket,
+    nonce: u64,
+    next_beacon: Instant,
+    /// Heard from, and answered, but they haven't acknowledged us yet
+    peer: Option<LanPeer>,
+}
+
+fn start_lan_discovery(mut commands: Commands) {
+    let socket = match bind_discovery_socket() {
+        Ok(socket) => socket,
+        Err(err) => {
+            info!("not looking for players on the local network: {err}");
+            return;
+        }
+    };
+
+    commands.insert_resource(LanDiscovery {
+        socket,
+        nonce: random_nonce(),
+        next_beacon: Instant::now(),
+        peer: None,
+    });
+}
+
+fn bind_discovery_socket() -> std::io::Result<UdpSocket> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))?;
+    socket.set_broadcast(true)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// Doesn't need to be good, only different between two machines
+fn random_nonce() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos()),
+    );
+    hasher.finish()
+}
+
+fn stop_lan_discovery(mut commands: Commands) {
+    commands.remove_resource::<LanDiscovery>();
+}
+
+fn discover_lan_peers(
+    mut commands: Commands,
+    mut discovery: ResMut<LanDiscovery>,
+    input_delay: Res<InputDelay>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let now = Instant::now();
+    if now >= discovery.next_beacon {
+        discovery.next_beacon = now + BEACON_INTERVAL;
+        let beacon = Beacon::new(discovery.nonce, discovery.peer.map(|peer| peer.nonce));
+        let _ = discovery
+            .socket
+            .send_to(&beacon.encode(), (Ipv4Addr::BROADCAST, DISCOVERY_PORT));
+    }
+
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    let mut acknowledged_by = None;
+    loop {
+        let (len, addr) = match discovery.socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            // e.g. an ICMP port unreachable from someone who left, try again next frame
+            Err(_) => break,
+        };
+        let Some(beacon) = Beacon::decode(&buffer[..len]) else {
+            continue;
+        };
+        // our own broadcast coming back, or an incompatible build
+        if beacon.nonce == discovery.nonce || beacon.version != PROTOCOL_VERSION {
+            continue;
+        }
+
+        let peer = LanPeer {
+            addr,
+            nonce: beacon.nonce,
+        };
+        if discovery.peer.is_none() {
+            info!("found {addr} on the local network");
+            discovery.peer = Some(peer);
+        }
+        if discovery
+            .peer
+            .is_some_and(|known| known.nonce != peer.nonce)
+        {
+            continue; // somebody else got here first
+        }
+
+        // answer right away, so they don't have to wait for our next broadcast
+        let answer = Beacon::new(discovery.nonce, Some(peer.nonce));
+        let _ = discovery.socket.send_to(&answer.encode(), addr);
+
+        if beacon.ack == Some(discovery.nonce) {
+            acknowledged_by = Some(peer);
+        }
+    }
+
+    let Some(peer) = acknowledged_by else {
+        return;
+    };
+
+    // the lower nonce is player one, both sides agree on that without talking
+    let mut players = vec![PlayerType::Local, PlayerType::Remote(peer.id())];
+    if peer.nonce < discovery.nonce {
+        players.reverse();
+    }
+
+    // no handshake on the local network, the round trip is next to nothing
+    let frames = match *input_delay {
+        InputDelay::Auto => input_delay_for(Some(Duration::ZERO)),
+        InputDelay::Fixed(frames) => frames,
+    };
+    info!(
+        "playing against {} on the local network, input delay {frames}",
+        peer.addr
+    );
+
+    let Ok(socket) = discovery.socket.try_clone() else {
+        return;
+    };
+    start_p2p_session(
+        &mut commands,
+        players,
+        LanSocket {
+            socket,
+            peer,
+            nonce: discovery.nonce,
+        },
+        MatchInputDelay {
+            frames,
+            round_trip: None,
+        },
+    );
+    commands.remove_resource::<MatchboxSocket<MultipleChannels>>();
+    next_state.set(GameState::InGame);
+}
+
+/// The discovery socket as handed to GGRS once an opponent was found
+struct LanSocket {
+    socket: UdpSocket,
+    peer: LanPeer,
+    nonce: u64,
+}
+
+impl NonBlockingSocket<PeerId> for LanSocket {
+    fn send_to(&mut self, msg: &Message, _addr: &PeerId) {
+        let bytes = bincode::serialize(msg).expect("failed to serialize GGRS message");
+        let _ = self.socket.send_to(&bytes, self.peer.addr);
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(PeerId, Message)> {
+        let mut messages = Vec::new();
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => break,
+            };
+            if addr != self.peer.addr {
+                continue;
+            }
+
+            if Beacon::decode(&buffer[..len]).is_some() {
+                // still looking means they missed our answer, tell them again
+                let answer = Beacon::new(self.nonce, Some(self.peer.nonce));
+                let _ = self.socket.send_to(&answer.encode(), addr);
+                continue;
+            }
+
+            match bincode::deserialize(&buffer[..len]) {
+                Ok(msg) => messages.push((self.peer.id(), msg)),
+                Err(err) => warn!("dropping malformed packet from {addr}: {err}"),
+            }
+        }
+        messages
+    }
+}
@@ -0,0 +1,1499 @@
+// queries and systems get big in bevy, clippy is too strict about it
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+mod afk;
+mod animation;
+mod announcer;
+pub mod assets;
+mod background;
+mod barrels;
+mod battery;
+mod bot;
+mod broadphase;
+mod caps;
+mod chat;
+mod coach;
+mod combat;
+mod components;
+mod countdown;
+mod damage_numbers;
+mod decoy;
+mod desync;
+#[cfg(not(target_arch = "wasm32"))]
+mod direct;
+mod disconnect;
+mod draft;
+mod end_screen;
+mod files;
+mod final_kill;
+mod fixed;
+mod frame_pacing;
+mod graphics;
+mod handshake;
+mod highlight;
+mod hit_flash;
+mod hit_stop;
+#[cfg(all(feature = "host", not(target_arch = "wasm32")))]
+mod host;
+mod hud;
+mod input;
+mod input_log;
+mod intermission;
+#[cfg(not(target_arch = "wasm32"))]
+mod lan;
+mod lighting;
+mod loading;
+mod mana;
+mod match_menu;
+mod match_setup;
+mod matchmaking;
+mod music;
+mod mutators;
+mod net_overlay;
+#[cfg(feature = "netsim")]
+mod netsim;
+#[cfg(all(feature = "overlay", not(target_arch = "wasm32")))]
+mod overlay;
+mod palette;
+mod particles;
+mod peer_messages;
+mod private_room;
+mod prompts;
+mod props;
+mod quick_chat;
+mod ready;
+mod redraw;
+mod regions;
+mod rejoin;
+pub mod replay;
+mod rng;
+mod rollback_size;
+mod round;
+mod rules;
+mod settings;
+mod sfx;
+mod shake;
+#[cfg(target_arch = "wasm32")]
+mod share;
+mod signaling;
+mod sim_events;
+mod snapshot;
+mod spells;
+mod stun;
+mod telemetry;
+mod tilemap;
+mod touch;
+mod training;
+mod transition;
+mod tutorial;
+mod ultimate;
+mod wind;
+
+use std::time::Duration;
+
+use afk::{track_idle_players, AfkPlugin, IdleFrames};
+use animation::AnimationPlugin;
+use announcer::{Announcement, AnnouncerPlugin};
+use assets::{
+    ArenaAssets, MusicAssets, ProjectileSprite, SfxAssets, SpellIcons, VoiceAssets, WizardSprites,
+};
+use background::BackgroundPlugin;
+use barrels::{
+    blast_props, move_flung_props, shoot_barrels, spawn_barrel, spawn_crate, ArenaLayout,
+    BarrelsPlugin,
+};
+use battery::BatterySaverPlugin;
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping, input::InputPlugin, log, prelude::*,
+    time::TimeUpdateStrategy,
+};
+use bevy_asset_loader::prelude::*;
+use bevy_ggrs::{
+    ggrs::{DesyncDetection, GgrsEvent, NonBlockingSocket, PlayerType, SessionBuilder},
+    AddRollbackCommandExtension, GgrsApp, GgrsPlugin, GgrsSchedule, LocalInputs, LocalPlayers,
+    PlayerInputs, ReadInputs, Session,
+};
+use bevy_matchbox::{
+    matchbox_socket::{MultipleChannels, PeerId},
+    MatchboxSocket,
+};
+use bot::{BotPlugin, Bots};
+use broadphase::{hash_walls, hash_wizards, Broadphase};
+use caps::{cap_projectiles, in_order, FireOrder, Fired};
+use chat::ChatPlugin;
+use coach::{start_coach_session, CoachPlugin, CoachedLobby, Lineup};
+use combat::{apply_damage, resolve_damage, ComboTracker, Heal, HealQueue, Hit, HitQueue, Target};
+use components::*;
+use countdown::CountdownPlugin;
+use damage_numbers::DamageNumbersPlugin;
+use decoy::{move_decoys, pop_decoys};
+use desync::{DesyncPlugin, DESYNC_CHECK_INTERVAL};
+use disconnect::DisconnectPlugin;
+use draft::{Draft, DraftPlugin};
+use end_screen::EndScreenPlugin;
+use final_kill::FinalKillPlugin;
+use fixed::{Fixed, SimVec2};
+use frame_pacing::FramePacingPlugin;
+use graphics::{GraphicsPlugin, YSort, YSORT_Z};
+use handshake::{input_delay_for, PingHandshake};
+use highlight::HighlightPlugin;
+use hit_flash::HitFlashPlugin;
+use hit_stop::HitStopPlugin;
+use hud::HudPlugin;
+use input::*;
+use input_log::InputLogPlugin;
+use intermission::IntermissionPlugin;
+use iyes_progress::prelude::*;
+use lighting::LightingPlugin;
+use loading::LoadingPlugin;
+use mana::regenerate_mana;
+use match_menu::MatchMenuPlugin;
+use match_setup::{seed_half, Agreement, MatchSettings, MatchSetup};
+use matchmaking::{InputDelay, MatchInputDelay, MatchmakingPlugin, RoomChoice, SignalingReconnect};
+use music::MusicPlugin;
+use mutators::{MutatorsPlugin, RoundMutator};
+use net_overlay::NetOverlayPlugin;
+use palette::PalettePlugin;
+use particles::{Burst, BurstQueue, ParticlesPlugin};
+use peer_messages::{PeerMessage, PeerMessagesPlugin, GGRS_CHANNEL, RELIABLE_CHANNEL};
+use private_room::PrivateRoomPlugin;
+use prompts::PromptsPlugin;
+use props::PropsPlugin;
+use quick_chat::QuickChatPlugin;
+use ready::{ReadyCheck, ReadyPlugin};
+use redraw::redraw_respawned;
+use regions::RegionsPlugin;
+use rejoin::{Rejoin, RejoinPlugin, RejoinableChannel, INTERRUPTED_NOTIFY_DELAY, RECONNECT_SECS};
+use replay::{read_replay_inputs, record_replay_inputs, ReplayPlayback, ReplayPlugin};
+use rng::MatchRng;
+use rollback_size::MeasuredRollbackApp;
+use round::*;
+use rules::Rules;
+use settings::SettingsPlugin;
+use sfx::{Sfx, SfxEvent, SfxPlugin, SfxQueue};
+use shake::ShakePlugin;
+use signaling::{IceServers, MatchboxUrl, Room};
+use sim_events::SimEventsApp;
+use snapshot::{PendingSnapshot, SnapshotPlugin};
+use spells::*;
+use stun::lock_stunned_inputs;
+use telemetry::{record_inputs, MatchTelemetry, TelemetryPlugin};
+use tilemap::TilemapPlugin;
+use touch::TouchPlugin;
+use training::{run_training_range, TrainingPlugin, TrainingRange};
+use transition::TransitionPlugin;
+use tutorial::TutorialPlugin;
+use ultimate::{charge_ultimates, rain_meteors, use_ultimates};
+use wind::{drift_projectiles, Wind};
+
+// The first generic parameter is the input type, a bit per button, see
+// `input::Input`
+// The second parameter is the address type of peers: Matchbox' WebRtcSocket
+// addresses are called `PeerId`s
+type Config = bevy_ggrs::GgrsConfig<input::Input, PeerId>;
+
+const MAP_SIZE: u32 = 41;
+const PLAYER_RADIUS: f32 = 0.5;
+const BULLET_RADIUS: f32 = 0.025;
+/// Distance a bullet covers each frame
+const BULLET_SPEED: Fixed = Fixed::from_f32(20. / 60.);
+/// Distance a wizard walks each frame
+const MOVE_SPEED: Fixed = Fixed::from_f32(7. / 60.);
+const MAX_HEALTH: u32 = 100;
+const BULLET_DAMAGE: u32 = 25;
+const BASE_ARMOR: u32 = 0;
+/// How much of the knockback velocity is left after each frame
+const KNOCKBACK_DECAY: Fixed = Fixed::from_f32(0.85);
+/// Knockback slower than this, squared, stops altogether
+const KNOCKBACK_REST: Fixed = Fixed::from_f32(0.0001);
+const CRIT_CHANCE_PERCENT: u32 = 10;
+const CRIT_DAMAGE_PERCENT: u32 = 200;
+const FIRE_COOLDOWN_FRAMES: u32 = 15;
+/// Brighter than white, so bullets bloom, see [`graphics`]
+const BULLET_GLOW: Color = Color::rgb(1.8, 1.8, 1.8);
+const CRITICAL_BULLET_GLOW: Color = Color::rgb(3., 1.9, 0.);
+/// Frames without dealing or taking damage before health starts coming back
+const REGEN_DELAY_FRAMES: i32 = 3 * 60;
+/// Frames between each point of regenerated health
+const REGEN_INTERVAL_FRAMES: i32 = 10;
+
+/// What the simulation spawns wizards in, the settings can swap them for
+/// another [`palette::Palette`]
+const PLAYER_COLORS: [Color; 4] = [
+    Color::rgb(0., 0.47, 1.),
+    Color::rgb(0., 0.4, 0.),
+    Color::rgb(0.85, 0.6, 0.),
+    Color::rgb(0.55, 0.2, 0.7),
+];
+/// Where each handle starts a round, even handles on the left and odd ones on the right
+const SPAWN_POINTS: [Vec2; 4] = [
+    Vec2::new(-2., 0.),
+    Vec2::new(2., 0.),
+    Vec2::new(-2., 3.5),
+    Vec2::new(2., -3.5),
+];
+// cover in front of each spawn point, (center, half size). Removed during overtime.
+const SPAWN_WALLS: [(Vec2, Vec2); 2] = [
+    (Vec2::new(-1., 0.), Vec2::new(0.15, 1.5)),
+    (Vec2::new(1., 0.), Vec2::new(0.15, 1.5)),
+];
+
+#[derive(States, Clone, Eq, PartialEq, Debug, Hash, Default)]
+pub enum GameState {
+    #[default]
+    AssetLoading,
+    /// Some asset didn't load, waiting for the player to retry
+    AssetLoadFailed,
+    Matchmaking,
+    InGame,
+    MatchOver,
+    /// Re-simulating a recorded match offline
+    Replay,
+    /// The peers' simulations stopped agreeing, the match was called off
+    Desynced,
+}
+
+/// Whether the arena is on screen, either in a live match or a replay
+fn in_arena(state: Res<State<GameState>>) -> bool {
+    matches!(state.get(), GameState::InGame | GameState::Replay)
+}
+
+/// Players in the session, including bots and training dummies
+pub fn num_players(session: &Session<Config>) -> usize {
+    match session {
+        Session::SyncTest(session) => session.num_players(),
+        Session::P2P(session) => session.num_players(),
+        Session::Spectator(session) => session.num_players(),
+    }
+}
+
+/// Something GGRS had to tell us about the session, passed on to whoever cares
+#[derive(Event)]
+pub struct SessionEvent(pub GgrsEvent<Config>);
+
+fn forward_session_events(
+    session: Option<ResMut<Session<Config>>>,
+    mut events: EventWriter<SessionEvent>,
+) {
+    let Some(mut session) = session else {
+        return;
+    };
+    let drained: Vec<GgrsEvent<Config>> = match session.as_mut() {
+        Session::P2P(session) => session.events().collect(),
+        Session::Spectator(session) => session.events().collect(),
+        Session::SyncTest(_) => return,
+    };
+    events.send_batch(drained.into_iter().map(SessionEvent));
+}
+
+/// Set while a text field has the keyboard, so typing doesn't set off shortcuts
+#[derive(Resource, Default)]
+pub struct TextInputFocus(pub bool);
+
+pub fn not_typing(focus: Res<TextInputFocus>) -> bool {
+    !focus.0
+}
+
+/// Frames synctest mode rolls back and resimulates every frame
+const SYNCTEST_CHECK_DISTANCE: usize = 7;
+
+/// Launched with `--synctest`: both wizards are local and GGRS keeps rolling
+/// back and resimulating to check the simulation is deterministic
+#[derive(Resource)]
+struct SyncTestMode;
+
+/// Starts the game in a window
+pub fn run() {
+    let mut app = App::new();
+    if std::env::args().any(|arg| arg == "--synctest") {
+        app.insert_resource(SyncTestMode);
+    }
+    #[cfg(all(feature = "host", not(target_arch = "wasm32")))]
+    app.add_plugins(host::HostPlugin);
+    #[cfg(all(feature = "overlay", not(target_arch = "wasm32")))]
+    app.add_plugins(overlay::OverlayPlugin);
+    #[cfg(not(target_arch = "wasm32"))]
+    app.add_plugins((direct::DirectConnectPlugin, lan::LanPlugin));
+
+    app.init_state::<GameState>()
+        .add_loading_state(
+            LoadingState::new(GameState::AssetLoading)
+                .load_collection::<WizardSprites>()
+                .load_collection::<ArenaAssets>()
+                .load_collection::<SpellIcons>()
+                .load_collection::<SfxAssets>()
+                .load_collection::<MusicAssets>()
+                .load_collection::<VoiceAssets>()
+                .on_failure_continue_to_state(GameState::AssetLoadFailed),
+        )
+        // the loading screen's progress bar follows this, it's what moves on once everything's in
+        .add_plugins(
+            ProgressPlugin::new(GameState::AssetLoading).continue_to(GameState::Matchmaking),
+        )
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    // the canvas in index.html, which css stretches over the whole page.
+                    // winit keeps the surface matched to its size as the page resizes.
+                    canvas: Some("#bevy".into()),
+                    // don't hijack stuff like F5, Ctrl+R, etc
+                    prevent_default_event_handling: false,
+                    ..default()
+                }),
+                ..default()
+            }),
+            SimulationPlugin,
+            BotPlugin,
+            CountdownPlugin,
+            EndScreenPlugin,
+            HighlightPlugin,
+            HudPlugin,
+            IntermissionPlugin,
+            MatchmakingPlugin,
+            NetOverlayPlugin,
+            SnapshotPlugin,
+            TrainingPlugin,
+            TutorialPlugin,
+        ))
+        .add_plugins((
+            AnimationPlugin,
+            BarrelsPlugin,
+            CoachPlugin,
+            GraphicsPlugin,
+            LightingPlugin,
+            LoadingPlugin,
+            MatchMenuPlugin,
+            MusicPlugin,
+            MutatorsPlugin,
+            PropsPlugin,
+            SettingsPlugin,
+            SfxPlugin,
+            TilemapPlugin,
+            TransitionPlugin,
+        ))
+        // playing with something other than a keyboard
+        .add_plugins((TouchPlugin, PromptsPlugin))
+        // telling the wizards apart without relying on hue
+        .add_plugins(PalettePlugin)
+        // how often and how sharply the screen is drawn, the simulation keeps its own time
+        .add_plugins((FramePacingPlugin, BatterySaverPlugin))
+        // which server, and which room on it, to look for a match in
+        .add_plugins((RegionsPlugin, PrivateRoomPlugin))
+        // between finding an opponent and the match starting
+        .add_plugins((ReadyPlugin, DraftPlugin))
+        // keeping track of the other peers
+        .add_plugins((
+            PeerMessagesPlugin,
+            DesyncPlugin,
+            DisconnectPlugin,
+            RejoinPlugin,
+            AfkPlugin,
+            BackgroundPlugin,
+        ))
+        // talking to the other players without leaving the match
+        .add_plugins((ChatPlugin, QuickChatPlugin))
+        // the announcer and the wizards' voices
+        .add_plugins(AnnouncerPlugin)
+        // hit feedback, everything that makes landing a hit feel like one
+        .add_plugins((
+            DamageNumbersPlugin,
+            FinalKillPlugin,
+            HitFlashPlugin,
+            HitStopPlugin,
+            ParticlesPlugin,
+            ShakePlugin,
+        ))
+        .insert_resource(ClearColor(Color::rgb(0.53, 0.53, 0.53)))
+        .init_resource::<MatchboxUrl>()
+        .init_resource::<IceServers>()
+        .init_resource::<TextInputFocus>()
+        .add_event::<SessionEvent>()
+        .add_systems(Startup, setup)
+        .add_systems(
+            OnEnter(GameState::Matchmaking),
+            (
+                start_matchbox_socket.run_if(wants_matchmaking),
+                start_synctest_session.run_if(resource_exists::<SyncTestMode>),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                wait_for_players
+                    .run_if(in_state(GameState::Matchmaking))
+                    .run_if(resource_exists::<MatchboxSocket<MultipleChannels>>),
+                forward_session_events,
+                place_in_arena,
+                shrink_explosions,
+                redraw_respawned,
+                (camera_follow.after(place_in_arena), enter_match_over)
+                    .run_if(in_state(GameState::InGame)),
+                leave_match_over.run_if(in_state(GameState::MatchOver)),
+            ),
+        )
+        .run();
+}
+
+/// The deterministic part of the game: match state, input handling, every
+/// system that runs in the rollback schedule and what gets rolled back.
+/// Everything on screen is added on top of this.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            GgrsPlugin::<Config>::default(),
+            ReplayPlugin,
+            TelemetryPlugin,
+            InputLogPlugin,
+        ))
+        .init_resource::<SimFrame>()
+        .init_resource::<RoundPhase>()
+        .init_resource::<Scores>()
+        .init_resource::<RoundNumber>()
+        .init_resource::<RoundKills>()
+        .init_resource::<MatchStats>()
+        .init_resource::<RoundClock>()
+        .init_resource::<MatchRng>()
+        .init_resource::<ArenaLayout>()
+        .add_sim_event::<SfxEvent>()
+        .add_sim_event::<Announcement>()
+        .add_sim_event::<Burst>()
+        .add_sim_event::<Hit>()
+        .add_sim_event::<Heal>()
+        .init_resource::<Rules>()
+        .init_resource::<Wind>()
+        .init_resource::<Broadphase>()
+        .init_resource::<RoundMutator>()
+        .init_resource::<IdleFrames>()
+        .init_resource::<FireToggle>()
+        .add_systems(OnExit(GameState::InGame), release_fire_toggle)
+        .add_systems(
+            ReadInputs,
+            (
+                read_local_inputs.run_if(not(in_state(GameState::Replay))),
+                read_replay_inputs.run_if(in_state(GameState::Replay)),
+            ),
+        )
+        .add_systems(
+            GgrsSchedule,
+            (
+                advance_frame,
+                sanitize_inputs.after(advance_frame),
+                record_replay_inputs.after(sanitize_inputs),
+                start_round.after(record_replay_inputs),
+                record_inputs.after(sanitize_inputs),
+                run_training_range
+                    .after(start_round)
+                    .run_if(resource_exists::<TrainingRange>),
+                lock_stunned_inputs
+                    .after(record_replay_inputs)
+                    .after(record_inputs)
+                    .run_if(in_round),
+                (
+                    move_players,
+                    reload_bullet,
+                    fire_bullets.after(move_players).after(reload_bullet),
+                    move_bullet.after(fire_bullets),
+                    stop_bullets_at_walls.after(move_bullet),
+                    damage_players.after(stop_bullets_at_walls),
+                    pull_wizards.before(move_players),
+                    select_spells,
+                    cast_spells.after(move_players).after(select_spells),
+                    move_fireballs.after(cast_spells),
+                    move_hooks.after(cast_spells),
+                    move_mana_burns.after(cast_spells),
+                    move_decoys.after(move_players),
+                    pop_decoys
+                        .after(move_decoys)
+                        .after(stop_bullets_at_walls)
+                        .after(move_fireballs)
+                        .after(move_hooks)
+                        .after(move_mana_burns)
+                        .after(move_flung_props)
+                        .before(damage_players),
+                    resolve_explosions
+                        .after(move_fireballs)
+                        .after(damage_players),
+                    (
+                        hash_walls.before(pull_wizards),
+                        hash_wizards
+                            .after(move_players)
+                            .before(cast_spells)
+                            .before(damage_players),
+                    ),
+                    (
+                        regenerate_health.after(resolve_explosions),
+                        regenerate_mana.after(cast_spells).after(move_mana_burns),
+                    ),
+                    (
+                        shoot_barrels
+                            .after(move_bullet)
+                            .before(stop_bullets_at_walls),
+                        move_flung_props.after(cast_spells),
+                        blast_props
+                            .after(shoot_barrels)
+                            .after(move_flung_props)
+                            .after(move_fireballs)
+                            .after(pop_decoys)
+                            .after(rain_meteors)
+                            .before(resolve_explosions),
+                    ),
+                    (
+                        use_ultimates.after(move_players),
+                        rain_meteors.after(use_ultimates).before(resolve_explosions),
+                        charge_ultimates
+                            .after(damage_players)
+                            .after(resolve_explosions)
+                            .after(move_mana_burns)
+                            .after(move_flung_props),
+                    ),
+                    (
+                        cap_projectiles.after(fire_bullets).after(cast_spells),
+                        drift_projectiles
+                            .after(cap_projectiles)
+                            .before(move_bullet)
+                            .before(move_fireballs)
+                            .before(move_hooks)
+                            .before(move_mana_burns)
+                            .before(move_flung_props),
+                    ),
+                )
+                    .after(start_round)
+                    .after(record_inputs)
+                    .after(run_training_range)
+                    .after(lock_stunned_inputs)
+                    .run_if(in_round),
+                // the training range never ends
+                check_round_end
+                    .after(damage_players)
+                    .after(resolve_explosions)
+                    .run_if(not(resource_exists::<TrainingRange>)),
+                handle_rematch.after(check_round_end),
+                // the inputs as sent, before a stun blanks them
+                track_idle_players
+                    .after(start_round)
+                    .after(record_inputs)
+                    .before(lock_stunned_inputs)
+                    .run_if(not(resource_exists::<TrainingRange>)),
+            ),
+        )
+        // what an entity is and whose side it's on, a rollback that brings back
+        // something despawned brings back nothing that isn't registered
+        .measured_rollback_component_with_copy::<Player>()
+        .measured_rollback_component_with_copy::<Dummy>()
+        .measured_rollback_component_with_copy::<Bullet>()
+        .measured_rollback_component_with_copy::<Critical>()
+        .measured_rollback_component_with_copy::<Team>()
+        .measured_rollback_component_with_copy::<Owner>()
+        .measured_rollback_component_with_copy::<Armor>()
+        .measured_rollback_component_with_copy::<Position>()
+        .measured_rollback_component_with_copy::<Velocity>()
+        .measured_rollback_component_with_copy::<Facing>()
+        .measured_rollback_component_with_copy::<BulletReady>()
+        .measured_rollback_component_with_copy::<FireCooldown>()
+        .measured_rollback_component_with_copy::<Health>()
+        .measured_rollback_component_with_copy::<LastCombatFrame>()
+        .measured_rollback_component_with_copy::<DamageHistory>()
+        .measured_rollback_component_with_copy::<Combo>()
+        .measured_rollback_component_with_copy::<Stun>()
+        .measured_rollback_component_with_copy::<SpellCooldown>()
+        .measured_rollback_component_with_copy::<Knockback>()
+        .measured_rollback_component_with_copy::<Fireball>()
+        .measured_rollback_component_with_copy::<Explosion>()
+        .measured_rollback_component_with_copy::<Hook>()
+        .measured_rollback_component_with_copy::<Decoy>()
+        .measured_rollback_component_with_copy::<ManaBurn>()
+        .measured_rollback_component_with_copy::<Mana>()
+        .measured_rollback_component_with_copy::<Ultimate>()
+        .measured_rollback_component_with_copy::<MeteorStorm>()
+        .measured_rollback_component_with_copy::<Spellbook>()
+        .measured_rollback_component_with_copy::<Pulled>()
+        .measured_rollback_component_with_copy::<Wall>()
+        .measured_rollback_component_with_copy::<Barrel>()
+        .measured_rollback_component_with_copy::<Destructible>()
+        .measured_rollback_component_with_copy::<Flung>()
+        .measured_rollback_component_with_copy::<Fired>()
+        .measured_rollback_resource_with_copy::<SimFrame>()
+        .measured_rollback_resource_with_copy::<RoundPhase>()
+        .measured_rollback_resource_with_clone::<Scores>()
+        .measured_rollback_resource_with_copy::<RoundNumber>()
+        .measured_rollback_resource_with_clone::<RoundKills>()
+        .measured_rollback_resource_with_clone::<MatchStats>()
+        .measured_rollback_resource_with_copy::<RoundClock>()
+        .measured_rollback_resource_with_copy::<MatchRng>()
+        .measured_rollback_resource_with_copy::<Wind>()
+        .measured_rollback_resource_with_copy::<RoundMutator>()
+        .measured_rollback_resource_with_clone::<IdleFrames>()
+        .measured_rollback_resource_with_copy::<TrainingRange>()
+        .checksum_component_with_hash::<Position>()
+        .checksum_component_with_hash::<Health>()
+        .checksum_component_with_hash::<Combo>()
+        .checksum_component_with_hash::<Stun>()
+        .checksum_component_with_hash::<Mana>()
+        .checksum_component_with_hash::<Ultimate>()
+        .checksum_resource_with_hash::<MatchRng>();
+    }
+}
+
+/// Whether to look for an opponent online, as opposed to playing locally
+fn wants_matchmaking(
+    replay: Option<Res<ReplayPlayback>>,
+    synctest: Option<Res<SyncTestMode>>,
+    training: Option<Res<TrainingRange>>,
+    snapshot: Option<Res<PendingSnapshot>>,
+    bots: Res<Bots>,
+) -> bool {
+    replay.is_none()
+        && synctest.is_none()
+        && training.is_none()
+        && snapshot.is_none()
+        && bots.0.is_empty()
+}
+
+/// Runs replays without a window, a GPU or any assets, one simulation frame per
+/// `App::update`. Insert a `ReplayPlayback` before the first update.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((MinimalPlugins, InputPlugin, SimulationPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                1. / 60.,
+            )))
+            // nothing is ever drawn, the handles don't need to point anywhere
+            .init_resource::<WizardSprites>()
+            .insert_state(GameState::Replay);
+    }
+}
+
+fn reload_bullet(
+    inputs: Res<PlayerInputs<Config>>,
+    mut players: Query<(&mut BulletReady, &mut FireCooldown, &Player)>,
+) {
+    for (mut can_fire, mut cooldown, player) in players.iter_mut() {
+        let (input, _) = inputs[player.handle];
+        if !fire(input) {
+            can_fire.0 = true;
+        }
+        cooldown.0 = cooldown.0.saturating_sub(1);
+    }
+}
+
+/// Bullets that make it out of the arena past every wall are let go
+fn move_bullet(
+    mut commands: Commands,
+    mut bullets: Query<(Entity, &mut Position, &Velocity), With<Bullet>>,
+) {
+    for (bullet, mut position, velocity) in &mut bullets {
+        position.0 += velocity.0;
+        if position.0.outside(map_limit()) {
+            commands.entity(bullet).despawn_recursive();
+        }
+    }
+}
+
+/// Where a projectile of `radius` fired by a wizard at `pos` facing `facing`
+/// starts, just outside the wizard so it doesn't hit its own caster
+pub fn muzzle(pos: SimVec2, facing: SimVec2, radius: f32) -> SimVec2 {
+    pos + facing * Fixed::from_f32(PLAYER_RADIUS + radius)
+}
+
+/// Puts everything in the simulation where it is on screen. Systems that
+/// follow a wizard or a projectile around go after this.
+pub fn place_in_arena(mut placed: Query<(&Position, &mut Transform), Changed<Position>>) {
+    for (position, mut transform) in &mut placed {
+        let pos = position.0.to_vec2();
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
+
+fn fire_bullets(
+    mut commands: Commands,
+    inputs: Res<PlayerInputs<Config>>,
+    sprites: Res<WizardSprites>,
+    clock: Res<RoundClock>,
+    mutator: Res<RoundMutator>,
+    frame: Res<SimFrame>,
+    mut rng: ResMut<MatchRng>,
+    mut sfx: ResMut<SfxQueue>,
+    mut bursts: ResMut<BurstQueue>,
+    mut players: Query<(
+        &Position,
+        &Player,
+        &Team,
+        &mut BulletReady,
+        &mut FireCooldown,
+        &Facing,
+    )>,
+) {
+    for (position, player, team, mut bullet_ready, mut cooldown, facing) in &mut players {
+        let (input, _) = inputs[player.handle];
+        if fire(input) && bullet_ready.0 && cooldown.0 == 0 {
+            let pos = muzzle(position.0, facing.0, BULLET_RADIUS);
+            let critical = rng.chance(CRIT_CHANCE_PERCENT);
+            spawn_bullet(
+                &mut commands,
+                &sprites,
+                Owner(player.handle),
+                *team,
+                pos,
+                facing.0 * mutator.projectile_speed(BULLET_SPEED),
+                critical,
+            );
+            sfx.play(frame.0, Sfx::Fire, player.handle, pos.to_vec2());
+            bursts.send(
+                frame.0,
+                Burst::Muzzle {
+                    pos: pos.to_vec2(),
+                    dir: facing.0.to_vec2(),
+                },
+            );
+            bullet_ready.0 = false;
+            cooldown.0 = mutator.cooldown(if clock.overtime {
+                FIRE_COOLDOWN_FRAMES / 2
+            } else {
+                FIRE_COOLDOWN_FRAMES
+            });
+        }
+    }
+}
+
+fn spawn_bullet(
+    commands: &mut Commands,
+    sprites: &WizardSprites,
+    owner: Owner,
+    team: Team,
+    pos: SimVec2,
+    velocity: SimVec2,
+    critical: bool,
+) -> Entity {
+    let mut bullet = commands.spawn((
+        Bullet,
+        owner,
+        team,
+        Position(pos),
+        Velocity(velocity),
+        bullet_look(sprites, pos, velocity, critical),
+    ));
+    bullet.add_rollback();
+    if critical {
+        bullet.insert(Critical);
+    }
+    bullet.id()
+}
+
+/// Pointing the way it's flying
+pub fn bullet_look(
+    sprites: &WizardSprites,
+    pos: SimVec2,
+    velocity: SimVec2,
+    critical: bool,
+) -> SpriteSheetBundle {
+    let direction = velocity.to_vec2().try_normalize().unwrap_or(Vec2::X);
+    let (texture, atlas) = sprites.projectile(ProjectileSprite::Bullet);
+    SpriteSheetBundle {
+        // above the darkness of the dungeon lighting, bullets glow
+        transform: Transform::from_translation(pos.to_vec2().extend(1.8))
+            .with_rotation(Quat::from_rotation_arc_2d(Vec2::X, direction)),
+        texture,
+        atlas,
+        sprite: Sprite {
+            color: if critical {
+                CRITICAL_BULLET_GLOW
+            } else {
+                BULLET_GLOW
+            },
+            custom_size: Some(Vec2::new(0.5, 0.2)),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn stop_bullets_at_walls(
+    mut commands: Commands,
+    frame: Res<SimFrame>,
+    mut sfx: ResMut<SfxQueue>,
+    broadphase: Res<Broadphase>,
+    bullets: Query<(Entity, &Position, &Owner), With<Bullet>>,
+    walls: Query<(&Position, &Wall)>,
+) {
+    let radius = Fixed::from_f32(BULLET_RADIUS);
+    for (bullet, bullet_pos, owner) in &bullets {
+        let pos = bullet_pos.0;
+        if broadphase
+            .walls
+            .near(pos, radius, &walls)
+            .any(|(wall_pos, wall)| hits_wall(pos, radius, wall_pos.0, wall))
+        {
+            commands.entity(bullet).despawn_recursive();
+            sfx.play(frame.0, Sfx::Block, owner.0, pos.to_vec2());
+        }
+    }
+}
+
+fn damage_players(
+    mut commands: Commands,
+    mut kills: ResMut<RoundKills>,
+    mut telemetry: ResMut<MatchTelemetry>,
+    mut hits: ResMut<HitQueue>,
+    mut sfx: ResMut<SfxQueue>,
+    mut bursts: ResMut<BurstQueue>,
+    frame: Res<SimFrame>,
+    rules: Res<Rules>,
+    mutator: Res<RoundMutator>,
+    broadphase: Res<Broadphase>,
+    mut players: Query<(
+        Entity,
+        &Position,
+        &Player,
+        &Team,
+        &mut Health,
+        &Armor,
+        &mut DamageHistory,
+        &mut LastCombatFrame,
+        &mut Combo,
+    )>,
+    bullets: Query<
+        (
+            Entity,
+            &Position,
+            &Owner,
+            &Team,
+            Has<Critical>,
+            &Velocity,
+            Option<&Fired>,
+        ),
+        With<Bullet>,
+    >,
+) {
+    let mut attackers = Vec::new();
+    let mut combos = ComboTracker::new(
+        players
+            .iter()
+            .map(|(_, _, player, .., combo)| (player.handle, *combo)),
+    );
+    let radius = Fixed::from_f32(BULLET_RADIUS);
+    let reach = mutator.wizard_radius() + radius;
+
+    let order = in_order(
+        bullets
+            .iter()
+            .map(|(entity, position, owner, _, _, velocity, fired)| {
+                (FireOrder::of(fired, owner, position, velocity), entity)
+            }),
+    );
+    for (bullet, bullet_pos, owner, bullet_team, critical, ..) in bullets.iter_many(order) {
+        for wizard in broadphase.wizards.entities_near(bullet_pos.0, radius) {
+            let Ok((
+                player_entity,
+                player_pos,
+                player,
+                team,
+                mut health,
+                armor,
+                mut history,
+                mut last_combat,
+                _,
+            )) = players.get_mut(wizard)
+            else {
+                continue;
+            };
+            // died to an earlier bullet this frame
+            if health.0 == 0 {
+                continue;
+            }
+
+            let friendly = bullet_team == team && owner.0 != player.handle;
+            if friendly && !rules.friendly_fire {
+                continue; // passes right through teammates
+            }
+
+            if player_pos.0.within(bullet_pos.0, reach) {
+                commands.entity(bullet).despawn_recursive();
+                attackers.push(owner.0);
+                last_combat.0 = frame.0;
+
+                let mut modifiers = Vec::new();
+                if critical {
+                    modifiers.push(CRIT_DAMAGE_PERCENT);
+                }
+                let enemy = bullet_team != team;
+                if enemy {
+                    modifiers.push(combos.bonus_percent(owner.0));
+                }
+                if friendly {
+                    modifiers.push(rules.friendly_fire_percent);
+                }
+                let damage = resolve_damage(BULLET_DAMAGE, *armor, &modifiers);
+                combos.record(owner.0, player.handle, enemy);
+                let pos = player_pos.0.to_vec2();
+                let target = Target {
+                    entity: player_entity,
+                    player,
+                    health: &mut health,
+                    history: &mut history,
+                    pos,
+                };
+                let attacker = Some(owner.0);
+                sfx.play(frame.0, Sfx::Impact, player.handle, pos);
+                if apply_damage(
+                    &mut commands,
+                    &mut kills,
+                    &mut telemetry,
+                    &mut hits,
+                    frame.0,
+                    target,
+                    attacker,
+                    damage,
+                    critical,
+                ) {
+                    sfx.play(frame.0, Sfx::Death, player.handle, pos);
+                    bursts.send(
+                        frame.0,
+                        Burst::Death {
+                            pos,
+                            handle: player.handle,
+                        },
+                    );
+                }
+                // spent on the first wizard it hits
+                break;
+            }
+        }
+    }
+
+    // dealing damage also counts as being in combat
+    for (.., player, _, _, _, _, mut last_combat, mut combo) in &mut players {
+        if attackers.contains(&player.handle) {
+            last_combat.0 = frame.0;
+        }
+        combos.update(player.handle, &mut combo);
+    }
+}
+
+fn regenerate_health(
+    frame: Res<SimFrame>,
+    mut heals: ResMut<HealQueue>,
+    mut players: Query<(&Player, &Position, &mut Health, &LastCombatFrame)>,
+) {
+    for (player, position, mut health, last_combat) in &mut players {
+        let idle_frames = frame.0 - last_combat.0;
+        if idle_frames >= REGEN_DELAY_FRAMES
+            && idle_frames % REGEN_INTERVAL_FRAMES == 0
+            && health.0 < MAX_HEALTH
+        {
+            health.0 += 1;
+            heals.send(
+                frame.0,
+                Heal {
+                    handle: player.handle,
+                    amount: 1,
+                    pos: position.0.to_vec2(),
+                },
+            );
+        }
+    }
+}
+
+/// Whether a circle at `pos` overlaps the box of a wall at `center`
+pub fn hits_wall(pos: SimVec2, radius: Fixed, center: SimVec2, wall: &Wall) -> bool {
+    let closest = pos.clamp(center - wall.half_size, center + wall.half_size);
+    closest.within(pos, radius)
+}
+
+fn camera_follow(
+    local_players: Res<LocalPlayers>,
+    bots: Res<Bots>,
+    players: Query<(&Player, &Position), Without<Dummy>>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    for (player, position) in &players {
+        // only follow the local player
+        if !local_players.0.contains(&player.handle) || bots.contains(player.handle) {
+            continue;
+        }
+
+        let pos = position.0.to_vec2();
+
+        for mut transform in &mut cameras {
+            transform.translation.x = pos.x;
+            transform.translation.y = pos.y;
+        }
+    }
+}
+
+pub fn move_players(
+    mut players: Query<(&mut Position, &mut Facing, &mut Knockback, &Pulled, &Player)>,
+    walls: Query<(&Position, &Wall), Without<Player>>,
+    broadphase: Res<Broadphase>,
+    inputs: Res<PlayerInputs<Config>>,
+) {
+    let radius = Fixed::from_f32(PLAYER_RADIUS);
+    for (mut position, mut facing, mut knockback, pulled, player) in &mut players {
+        let (input, _) = inputs[player.handle];
+        // a wizard being reeled in has no say in where it goes
+        let direction = if pulled.frames_left > 0 {
+            SimVec2::ZERO
+        } else {
+            SimVec2::from(direction(input)).normalize_or_zero()
+        };
+        if direction == SimVec2::ZERO && knockback.0 == SimVec2::ZERO {
+            continue;
+        }
+
+        if direction != SimVec2::ZERO {
+            facing.0 = direction;
+        }
+
+        let move_delta = direction * MOVE_SPEED + knockback.0;
+
+        knockback.0 = knockback.0 * KNOCKBACK_DECAY;
+        if knockback.0.length_squared() < KNOCKBACK_REST {
+            knockback.0 = SimVec2::ZERO;
+        }
+
+        position.0 = step_wizard(position.0, move_delta, |pos| {
+            broadphase
+                .walls
+                .near(pos, radius, &walls)
+                .any(|(wall_pos, wall)| hits_wall(pos, radius, wall_pos.0, wall))
+        });
+    }
+}
+
+/// Where a wizard at `old_pos` ends up after moving by `delta`, kept inside
+/// the arena and out of anywhere `blocked` says it can't be
+pub fn step_wizard(old_pos: SimVec2, delta: SimVec2, blocked: impl Fn(SimVec2) -> bool) -> SimVec2 {
+    let limit = SimVec2::splat(Fixed::from_f32(MAP_SIZE as f32 / 2. - 0.5));
+    let mut new_pos = (old_pos + delta).clamp(-limit, limit);
+
+    // resolve each axis separately so wizards slide along walls instead of sticking to them
+    if blocked(SimVec2::new(new_pos.x, old_pos.y)) {
+        new_pos.x = old_pos.x;
+    }
+    if blocked(new_pos) {
+        new_pos.y = old_pos.y;
+    }
+    new_pos
+}
+
+fn wait_for_players(
+    mut commands: Commands,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    mut server: ResMut<MatchboxUrl>,
+    input_delay: Res<InputDelay>,
+    room_choice: RoomChoice,
+    mut rules: ResMut<Rules>,
+    mut reconnect: ResMut<SignalingReconnect>,
+    time: Res<Time>,
+    layout: Res<ArenaLayout>,
+    mut handshake: Local<Option<PingHandshake>>,
+    mut lobby: Local<CoachedLobby>,
+    mut ready: Option<ResMut<ReadyCheck>>,
+    mut draft: Option<ResMut<Draft>>,
+    mut setup: Local<Option<MatchSetup>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if socket.get_channel(GGRS_CHANNEL).is_err() {
+        return; // we've already started
+    }
+
+    // check for new connections
+    if socket.try_update_peers().is_err() {
+        // the socket is done for, start over with a new one in a bit
+        commands.remove_resource::<MatchboxSocket<MultipleChannels>>();
+        commands.remove_resource::<ReadyCheck>();
+        commands.remove_resource::<Draft>();
+        *handshake = None;
+        *lobby = CoachedLobby::default();
+        *setup = None;
+        reconnect.connection_lost(time.elapsed_seconds(), &mut server);
+        return;
+    }
+    if socket.id().is_some() {
+        reconnect.connected();
+    }
+    let mut players = socket.players();
+
+    let room = room_choice.room();
+    if players.len() < room.size() {
+        // a draft or lineup with whoever left is no good to the next opponent
+        commands.remove_resource::<ReadyCheck>();
+        commands.remove_resource::<Draft>();
+        *lobby = CoachedLobby::default();
+        *setup = None;
+        return; // wait for more players
+    }
+
+    // in a coached room, find out who's coaching before anything else
+    let mut opponent = socket.connected_peers().next();
+    let mut coach = None;
+    if room == Room::Coached {
+        match lobby.update(&mut socket, room_choice.coaching()) {
+            Lineup::Waiting => return,
+            Lineup::Mismatched => {
+                warn!("ended up in a room with another coach, looking for a new one");
+                *lobby = CoachedLobby::default();
+                connect_to_matchbox(&mut commands, &server, room);
+                return;
+            }
+            Lineup::Coach => {
+                let Some(settings) = lobby.settings() else {
+                    return; // the players are still getting ready
+                };
+                settings.apply(&mut commands, &mut rules);
+                // in handle order, like the players have them
+                let players = players
+                    .iter()
+                    .filter_map(|player| match player {
+                        PlayerType::Remote(peer) => Some(*peer),
+                        _ => None,
+                    })
+                    .collect();
+                info!("Both players have joined, watching the match as their coach");
+                let channel = socket.take_channel(GGRS_CHANNEL).unwrap();
+                start_coach_session(&mut commands, players, channel);
+                next_state.set(GameState::InGame);
+                return;
+            }
+            Lineup::Player {
+                opponent: peer,
+                coach: coach_peer,
+            } => {
+                opponent = Some(peer);
+                players.retain(
+                    |player| !matches!(player, PlayerType::Remote(peer) if *peer == coach_peer),
+                );
+                // only the host has the coach follow along
+                if matches!(players[0], PlayerType::Local) {
+                    players.push(PlayerType::Spectator(coach_peer));
+                    coach = Some(coach_peer);
+                }
+            }
+        }
+    }
+    let Some(opponent) = opponent else {
+        return;
+    };
+    let local_handle = players
+        .iter()
+        .position(|player| matches!(player, PlayerType::Local))
+        .unwrap_or(0);
+
+    // nothing starts until both players say so
+    let Some(ready) = ready.as_deref_mut() else {
+        commands.insert_resource(ReadyCheck::new(opponent, time.elapsed_seconds()));
+        return;
+    };
+    if !ready.is_done() && !ready.update(socket.get_channel(RELIABLE_CHANNEL).unwrap()) {
+        if ready.timed_out(time.elapsed_seconds()) {
+            warn!("nobody readied up in time, looking for another match");
+            commands.remove_resource::<ReadyCheck>();
+            *handshake = None;
+            *lobby = CoachedLobby::default();
+            connect_to_matchbox(&mut commands, &server, room);
+        }
+        return;
+    }
+
+    // measure the ping before committing to an input delay. The draft reads
+    // the channel itself once it has started.
+    if *input_delay == InputDelay::Auto && draft.is_none() {
+        let handshake = handshake.get_or_insert_with(|| {
+            let mut handshake = PingHandshake::new(opponent);
+            if lobby.handshake_done(opponent) {
+                handshake.peer_done_early();
+            }
+            handshake
+        });
+        if !handshake.is_done() && !handshake.update(socket.get_channel(RELIABLE_CHANNEL).unwrap())
+        {
+            return;
+        }
+    }
+
+    // ranked matches draft their spells first
+    if room == Room::Ranked {
+        let Some(draft) = draft.as_deref_mut() else {
+            commands.insert_resource(Draft::new(opponent, local_handle, time.elapsed_seconds()));
+            return;
+        };
+        let channel = socket.get_channel(RELIABLE_CHANNEL).unwrap();
+        if !draft.is_done() && !draft.update(channel, time.elapsed_seconds()) {
+            return;
+        }
+        rules.spells[..2].copy_from_slice(&draft.loadouts());
+    }
+
+    // then agree on everything else the match starts with
+    let seed = seed_half(socket.id(), time.elapsed());
+    let channel = socket.get_channel(RELIABLE_CHANNEL).unwrap();
+    let agreement = setup
+        .get_or_insert_with(|| {
+            MatchSetup::new(
+                opponent,
+                local_handle,
+                MatchSettings::new(seed, &layout, room, *rules),
+            )
+        })
+        .update(channel);
+    let settings = match agreement {
+        Agreement::Waiting => return,
+        Agreement::Incompatible(reason) => {
+            warn!("can't play {opponent}, {reason}. Looking for another match");
+            commands.remove_resource::<ReadyCheck>();
+            commands.remove_resource::<Draft>();
+            *handshake = None;
+            *lobby = CoachedLobby::default();
+            *setup = None;
+            connect_to_matchbox(&mut commands, &server, room);
+            return;
+        }
+        Agreement::Agreed(settings) => settings,
+    };
+    *setup = None;
+    settings.apply(&mut commands, &mut rules);
+    if let Some(coach) = coach {
+        PeerMessage::MatchSettings(settings).send(channel, coach);
+    }
+
+    let round_trip = handshake
+        .take()
+        .and_then(|handshake| handshake.round_trip());
+    *lobby = CoachedLobby::default();
+    let frames = match *input_delay {
+        InputDelay::Auto => input_delay_for(round_trip),
+        InputDelay::Fixed(frames) => frames,
+    };
+
+    info!("All peers have joined, going to the game! Input delay {frames}, ping {round_trip:?}");
+
+    // move the channel out of the socket (required because ggrs takes ownership of it).
+    // The reliable channel stays behind for everything else.
+    let channel = RejoinableChannel::new(socket.take_channel(GGRS_CHANNEL).unwrap());
+    if let Some(local) = socket.id() {
+        commands.insert_resource(Rejoin::new(local, opponent, channel.clone()));
+    }
+    start_p2p_session(
+        &mut commands,
+        players,
+        channel,
+        MatchInputDelay { frames, round_trip },
+    );
+
+    next_state.set(GameState::InGame);
+}
+
+/// Starts the match over `socket`, whichever way we found the other players
+pub fn start_p2p_session(
+    commands: &mut Commands,
+    players: Vec<PlayerType<PeerId>>,
+    socket: impl NonBlockingSocket<PeerId> + 'static,
+    input_delay: MatchInputDelay,
+) {
+    // spectators get handles after the players'
+    let num_players = players
+        .iter()
+        .filter(|player| !matches!(player, PlayerType::Spectator(_)))
+        .count();
+    let mut session_builder: SessionBuilder<Config> = SessionBuilder::new()
+        .with_num_players(num_players)
+        .with_input_delay(input_delay.frames)
+        .with_desync_detection_mode(DesyncDetection::On {
+            interval: DESYNC_CHECK_INTERVAL,
+        })
+        // long enough to get a dropped connection back, see the rejoin module
+        .with_disconnect_timeout(Duration::from_secs(RECONNECT_SECS))
+        .with_disconnect_notify_delay(INTERRUPTED_NOTIFY_DELAY);
+
+    for (i, player) in players.into_iter().enumerate() {
+        session_builder = session_builder
+            .add_player(player, i)
+            .expect("failed to add player")
+    }
+
+    #[cfg(feature = "netsim")]
+    let socket = netsim::SimulatedNetwork::new(socket, netsim::NetworkConditions::from_args());
+
+    let ggrs_session = session_builder
+        .start_p2p_session(socket)
+        .expect("failed to start session");
+
+    commands.insert_resource(bevy_ggrs::Session::P2P(ggrs_session));
+    commands.insert_resource(input_delay);
+}
+
+fn start_synctest_session(mut commands: Commands, mut next_state: ResMut<NextState<GameState>>) {
+    info!("starting synctest session, check distance {SYNCTEST_CHECK_DISTANCE}");
+
+    let num_players = 2;
+    let mut session_builder = SessionBuilder::<Config>::new()
+        .with_num_players(num_players)
+        .with_check_distance(SYNCTEST_CHECK_DISTANCE);
+
+    for i in 0..num_players {
+        session_builder = session_builder
+            .add_player(PlayerType::Local, i)
+            .expect("failed to add player");
+    }
+
+    let ggrs_session = session_builder
+        .start_synctest_session()
+        .expect("failed to start session");
+
+    commands.insert_resource(bevy_ggrs::Session::SyncTest(ggrs_session));
+
+    next_state.set(GameState::InGame);
+}
+
+fn start_matchbox_socket(
+    mut commands: Commands,
+    server: Res<MatchboxUrl>,
+    room_choice: RoomChoice,
+    mut reconnect: ResMut<SignalingReconnect>,
+) {
+    reconnect.restart();
+    connect_to_matchbox(&mut commands, &server, room_choice.room());
+}
+
+pub fn connect_to_matchbox(commands: &mut Commands, server: &MatchboxUrl, room: Room) {
+    let room_url = server.room_url(room);
+    info!("connecting to matchbox server: {}", room_url);
+    commands.add(move |world: &mut World| {
+        let socket = peer_messages::new_socket(&room_url, world.resource::<IceServers>());
+        world.insert_resource(socket);
+    });
+}
+
+fn setup(mut commands: Commands) {
+    // zoom and bloom are up to GraphicsPlugin, which follows the window size and settings
+    commands.spawn(Camera2dBundle {
+        // HDR so spells can be brighter than white and bloom
+        camera: Camera {
+            hdr: true,
+            ..default()
+        },
+        // keeps the arena's colors as they were without HDR
+        tonemapping: Tonemapping::None,
+        ..default()
+    });
+}
+
+/// One wizard for every player in the session, facing the other side of the
+/// arena, with the first of their seat's spells at the ready
+fn spawn_players(commands: &mut Commands, num_players: usize, rules: &Rules) {
+    for handle in 0..num_players {
+        let pos = SPAWN_POINTS[handle % SPAWN_POINTS.len()];
+        let facing = if pos.x < 0. { SimVec2::X } else { -SimVec2::X };
+        let wizard = spawn_wizard(
+            commands,
+            handle,
+            handle % 2,
+            pos.into(),
+            facing,
+            PLAYER_COLORS[handle % PLAYER_COLORS.len()],
+        );
+        let allowed = rules.spells[handle % rules.spells.len()];
+        commands.entity(wizard).insert(Spellbook {
+            selected: allowed.first().unwrap_or_default(),
+            next_held: false,
+        });
+    }
+}
+
+/// A wizard at full health, ready to fight
+fn spawn_wizard(
+    commands: &mut Commands,
+    handle: usize,
+    team: usize,
+    pos: SimVec2,
+    facing: SimVec2,
+    color: Color,
+) -> Entity {
+    commands
+        .spawn((
+            Player { handle },
+            Team(team),
+            BulletReady(true),
+            FireCooldown(0),
+            SpellCooldown(0),
+            Knockback::default(),
+            Health(MAX_HEALTH),
+            Armor(BASE_ARMOR),
+            LastCombatFrame(0),
+            DamageHistory::default(),
+            // bundles top out at fifteen components, the status effects go together
+            (
+                Combo::default(),
+                Stun::default(),
+                Spellbook::default(),
+                Pulled::default(),
+                Mana::full(),
+                Ultimate::new(UltimateKind::for_handle(handle)),
+            ),
+            Position(pos),
+            Facing(facing),
+            wizard_look(pos, color),
+        ))
+        .add_rollback()
+        .id()
+}
+
+pub fn wizard_look(pos: SimVec2, color: Color) -> (YSort, SpriteBundle) {
+    (
+        YSort {
+            offset: PLAYER_RADIUS,
+        },
+        SpriteBundle {
+            transform: Transform::from_translation(pos.to_vec2().extend(YSORT_Z)),
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::new(1., 1.)),
+                ..default()
+            },
+            ..default()
+        },
+    )
+}
+
+fn spawn_walls(commands: &mut Commands, layout: &ArenaLayout) {
+    for (center, half_size) in SPAWN_WALLS {
+        spawn_wall(commands, center.into(), half_size.into());
+    }
+    for &pos in &layout.barrels {
+        spawn_barrel(commands, pos.into());
+    }
+    for &pos in &layout.crates {
+        spawn_crate(commands, pos.into());
+    }
+}
+
+fn spawn_wall(commands: &mut Commands, center: SimVec2, half_size: SimVec2) {
+    commands
+        .spawn((
+            Wall { half_size },
+            Position(center),
+            wall_look(center, half_size),
+        ))
+        .add_rollback();
+}
+
+pub fn wall_look(center: SimVec2, half_size: SimVec2) -> (YSort, SpriteBundle) {
+    (
+        YSort {
+            offset: half_size.y.to_f32(),
+        },
+        SpriteBundle {
+            transform: Transform::from_translation(center.to_vec2().extend(YSORT_Z)),
+            sprite: Sprite {
+                color: Color::rgb(0.2, 0.2, 0.25),
+                custom_size: Some(half_size.to_vec2() * 2.),
+                ..default()
+            },
+            ..default()
+        },
+    )
+}
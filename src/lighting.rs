@@ -0,0 +1,198 @@
+//! Dungeon lighting: the arena sits in ambient darkness and fireballs and
+//! explosions light up their surroundings.
+//!
+//! Purely visual. A dark layer is drawn over the floor, walls and wizards,
+//! with soft glows on top of it wherever the simulation has a spell. Like the
+//! other overlays, lights follow spells around instead of being their children,
+//! and a spell a rollback respawns gets its light back once [`crate::redraw`]
+//! has given it a `Transform` again.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    utils::HashSet,
+};
+
+use crate::{
+    components::*, place_in_arena, settings::Settings, spells::shrink_explosions, GameState,
+};
+
+const DARKNESS_COLOR: Color = Color::rgba(0.02, 0.01, 0.05, 0.55);
+/// Above the walls and wizards, below the spells and bullets
+const DARKNESS_Z: f32 = 1.5;
+const LIGHT_Z: f32 = 1.6;
+/// Big enough to cover the arena and whatever the camera sees past its edges
+const DARKNESS_SIZE: f32 = 200.;
+const LIGHT_TEXTURE_SIZE: u32 = 64;
+const FIREBALL_LIGHT: Color = Color::rgba(1., 0.55, 0.2, 0.35);
+const EXPLOSION_LIGHT: Color = Color::rgba(1., 0.7, 0.3, 0.5);
+/// Light radius as a multiple of the spell's own
+const FIREBALL_LIGHT_SCALE: f32 = 8.;
+const EXPLOSION_LIGHT_SCALE: f32 = 2.;
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_light_texture)
+            .add_systems(OnEnter(GameState::InGame), spawn_darkness)
+            .add_systems(
+                Update,
+                (
+                    toggle_darkness,
+                    follow_spells.after(place_in_arena).after(shrink_explosions),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), despawn_lighting);
+    }
+}
+
+/// Soft round falloff every light is drawn with
+#[derive(Resource)]
+struct LightTexture(Handle<Image>);
+
+#[derive(Component)]
+struct Darkness;
+
+/// Glow around the spell entity `source`
+#[derive(Component)]
+struct SpellLight {
+    source: Entity,
+}
+
+fn setup_light_texture(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let size = LIGHT_TEXTURE_SIZE;
+    let center = (size as f32 - 1.) / 2.;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let distance = Vec2::new(x as f32 - center, y as f32 - center).length() / center;
+            let falloff = (1. - distance).clamp(0., 1.).powi(2);
+            data.extend([255, 255, 255, (falloff * 255.) as u8]);
+        }
+    }
+
+    let image = Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    commands.insert_resource(LightTexture(images.add(image)));
+}
+
+fn spawn_darkness(mut commands: Commands, settings: Res<Settings>) {
+    commands.spawn((
+        Darkness,
+        SpriteBundle {
+            sprite: Sprite {
+                color: DARKNESS_COLOR,
+                custom_size: Some(Vec2::splat(DARKNESS_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., DARKNESS_Z),
+            visibility: darkness_visibility(settings.lighting),
+            ..default()
+        },
+    ));
+}
+
+fn toggle_darkness(settings: Res<Settings>, mut darkness: Query<&mut Visibility, With<Darkness>>) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut visibility in &mut darkness {
+        *visibility = darkness_visibility(settings.lighting);
+    }
+}
+
+fn darkness_visibility(lighting: bool) -> Visibility {
+    if lighting {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    }
+}
+
+fn follow_spells(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    texture: Res<LightTexture>,
+    spells: Query<(Entity, &Transform, Has<Explosion>), Or<(With<Fireball>, With<Explosion>)>>,
+    mut lights: Query<
+        (Entity, &SpellLight, &mut Transform),
+        (Without<Fireball>, Without<Explosion>),
+    >,
+) {
+    let mut lit = HashSet::new();
+    for (light, spell_light, mut transform) in &mut lights {
+        let source = spells
+            .get(spell_light.source)
+            .ok()
+            .filter(|_| settings.lighting);
+        let Some((_, spell_transform, explosion)) = source else {
+            commands.entity(light).despawn();
+            continue;
+        };
+        lit.insert(spell_light.source);
+        *transform = light_transform(spell_transform, explosion);
+    }
+
+    if !settings.lighting {
+        return;
+    }
+
+    for (spell, spell_transform, explosion) in &spells {
+        if lit.contains(&spell) {
+            continue;
+        }
+        commands.spawn((
+            SpellLight { source: spell },
+            SpriteBundle {
+                texture: texture.0.clone(),
+                sprite: Sprite {
+                    color: if explosion {
+                        EXPLOSION_LIGHT
+                    } else {
+                        FIREBALL_LIGHT
+                    },
+                    // the transform's scale sets the size
+                    custom_size: Some(Vec2::splat(2.)),
+                    ..default()
+                },
+                transform: light_transform(spell_transform, explosion),
+                ..default()
+            },
+        ));
+    }
+}
+
+/// Spells are unit circles scaled to their radius, explosions shrinking as
+/// they fade, so their lights just scale along
+fn light_transform(spell: &Transform, explosion: bool) -> Transform {
+    let scale = if explosion {
+        EXPLOSION_LIGHT_SCALE
+    } else {
+        FIREBALL_LIGHT_SCALE
+    };
+    Transform::from_translation(spell.translation.xy().extend(LIGHT_Z))
+        .with_scale(Vec3::splat(spell.scale.x * scale))
+}
+
+fn despawn_lighting(
+    mut commands: Commands,
+    entities: Query<Entity, Or<(With<Darkness>, With<SpellLight>)>>,
+) {
+    for entity in &entities {
+        commands.entity(entity).despawn();
+    }
+}
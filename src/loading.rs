@@ -0,0 +1,245 @@
+//! The loading screen: a progress bar and some tips while the assets load,
+//! and a way to try again if any of them fail to.
+
+use bevy::prelude::*;
+use bevy_asset_loader::prelude::*;
+use iyes_progress::prelude::*;
+
+use crate::GameState;
+
+const TIPS: [&str; 7] = [
+    "Fireballs hurt their caster too, but blasting yourself away is a fine escape",
+    "Health comes back after a few seconds out of combat",
+    "Fire has to be released between shots",
+    "Critical hits glow orange and deal double damage",
+    "Press H on the menu for a quick tutorial",
+    "Q switches spells, hooked enemies are reeled right up to you",
+    "Telekinesis throws the nearest barrel or crate, and barrels go off where they land",
+];
+const TIP_SECS: f32 = 4.;
+const BAR_WIDTH: f32 = 400.;
+const BAR_COLOR: Color = Color::rgb(0.9, 0.6, 0.1);
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::AssetLoading), spawn_loading_screen)
+            .add_systems(OnExit(GameState::AssetLoading), despawn_screen)
+            .add_systems(OnEnter(GameState::AssetLoadFailed), spawn_failure_screen)
+            .add_systems(OnExit(GameState::AssetLoadFailed), despawn_screen)
+            .add_systems(
+                Update,
+                (
+                    (update_progress_bar, rotate_tips)
+                        .run_if(in_state(GameState::AssetLoading))
+                        .after(LoadingStateSet(GameState::AssetLoading)),
+                    retry_button.run_if(in_state(GameState::AssetLoadFailed)),
+                ),
+            );
+    }
+}
+
+/// Either the loading or the failure screen
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct ProgressFill;
+
+#[derive(Component)]
+struct ProgressText;
+
+#[derive(Component)]
+struct TipText;
+
+#[derive(Component)]
+struct RetryButton;
+
+fn screen_root() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            row_gap: Val::Px(16.),
+            ..default()
+        },
+        background_color: Color::rgb(0.08, 0.07, 0.1).into(),
+        ..default()
+    }
+}
+
+fn spawn_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((LoadingScreen, screen_root()))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Wizard Battles",
+                TextStyle {
+                    font_size: 48.,
+                    ..default()
+                },
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(BAR_WIDTH),
+                        height: Val::Px(16.),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        ProgressFill,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.),
+                                height: Val::Percent(100.),
+                                ..default()
+                            },
+                            background_color: BAR_COLOR.into(),
+                            ..default()
+                        },
+                    ));
+                });
+            parent.spawn((
+                ProgressText,
+                TextBundle::from_section(
+                    "Loading...",
+                    TextStyle {
+                        font_size: 20.,
+                        color: Color::rgb(0.8, 0.8, 0.8),
+                        ..default()
+                    },
+                ),
+            ));
+            parent.spawn((
+                TipText,
+                TextBundle::from_section(
+                    TIPS[0],
+                    TextStyle {
+                        font_size: 20.,
+                        color: Color::rgb(0.6, 0.6, 0.6),
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
+
+fn update_progress_bar(
+    progress: Option<Res<ProgressCounter>>,
+    mut fills: Query<&mut Style, With<ProgressFill>>,
+    mut texts: Query<&mut Text, With<ProgressText>>,
+) {
+    let Some(progress) = progress.map(|counter| counter.progress()) else {
+        return;
+    };
+    let fraction = if progress.total > 0 {
+        progress.done as f32 / progress.total as f32
+    } else {
+        0.
+    };
+
+    for mut style in &mut fills {
+        style.width = Val::Percent(fraction * 100.);
+    }
+    for mut text in &mut texts {
+        text.sections[0].value = format!("Loading... {:.0}%", fraction * 100.);
+    }
+}
+
+fn rotate_tips(
+    time: Res<Time<Real>>,
+    mut texts: Query<&mut Text, With<TipText>>,
+    mut shown_for: Local<f32>,
+    mut tip: Local<usize>,
+) {
+    *shown_for += time.delta_seconds();
+    if *shown_for < TIP_SECS {
+        return;
+    }
+    *shown_for = 0.;
+    *tip = (*tip + 1) % TIPS.len();
+
+    for mut text in &mut texts {
+        text.sections[0].value = TIPS[*tip].into();
+    }
+}
+
+fn spawn_failure_screen(mut commands: Commands) {
+    error!("failed to load assets");
+
+    commands
+        .spawn((LoadingScreen, screen_root()))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Couldn't load the game's assets",
+                TextStyle {
+                    font_size: 36.,
+                    color: Color::ORANGE_RED,
+                    ..default()
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                "Check your connection and try again",
+                TextStyle {
+                    font_size: 22.,
+                    color: Color::rgb(0.8, 0.8, 0.8),
+                    ..default()
+                },
+            ));
+            parent
+                .spawn((
+                    RetryButton,
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::axes(Val::Px(24.), Val::Px(8.)),
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Retry",
+                        TextStyle {
+                            font_size: 24.,
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn retry_button(
+    mut buttons: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<RetryButton>),
+    >,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, mut background) in &mut buttons {
+        match *interaction {
+            // the loading state starts over and loads whatever isn't there yet
+            Interaction::Pressed => next_state.set(GameState::AssetLoading),
+            Interaction::Hovered => background.0 = BUTTON_HOVER_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+}
+
+fn despawn_screen(mut commands: Commands, screens: Query<Entity, With<LoadingScreen>>) {
+    for screen in &screens {
+        commands.entity(screen).despawn_recursive();
+    }
+}
@@ -1,306 +1,3 @@
-mod components;
-mod input;
-
-use bevy::{log, prelude::*, render::camera::ScalingMode};
-use bevy_asset_loader::prelude::*;
-use bevy_ggrs::{
-    ggrs::SessionBuilder, AddRollbackCommandExtension, GgrsApp, GgrsPlugin, GgrsSchedule,
-    LocalInputs, LocalPlayers, PlayerInputs, ReadInputs,
-};
-use bevy_matchbox::{
-    matchbox_socket::{PeerId, SingleChannel},
-    MatchboxSocket,
-};
-use components::*;
-use input::*;
-
-// The first generic parameter, u8, is the input type: 4-directions + fire fits
-// easily in a single byte
-// The second parameter is the address type of peers: Matchbox' WebRtcSocket
-// addresses are called `PeerId`s
-type Config = bevy_ggrs::GgrsConfig<u8, PeerId>;
-
-const MAP_SIZE: u32 = 41;
-const GRID_WIDTH: f32 = 0.05;
-
-#[derive(AssetCollection, Resource)]
-struct ImageAssets {
-    #[asset(path = "Dungeon_Objects.png")]
-    bullet: Handle<Image>,
-}
-
-#[derive(States, Clone, Eq, PartialEq, Debug, Hash, Default)]
-enum GameState {
-    #[default]
-    AssetLoading,
-    Matchmaking,
-    InGame,
-}
-
 fn main() {
-    App::new()
-        .init_state::<GameState>()
-        .add_loading_state(
-            LoadingState::new(GameState::AssetLoading)
-                .load_collection::<ImageAssets>()
-                .continue_to_state(GameState::Matchmaking),
-        )
-        .add_plugins((
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    //renable in bevy 0.14
-                    // fit_canvas_to_parent: true
-                    // don't hijack stuff like F5, Ctrl+R, etc
-                    prevent_default_event_handling: false,
-                    ..default()
-                }),
-                ..default()
-            }),
-            GgrsPlugin::<Config>::default(),
-        ))
-        .insert_resource(ClearColor(Color::rgb(0.53, 0.53, 0.53)))
-        .add_systems(
-            OnEnter(GameState::Matchmaking),
-            (setup, start_matchbox_socket),
-        )
-        .add_systems(OnEnter(GameState::InGame), spawn_player)
-        .add_systems(
-            Update,
-            (
-                wait_for_players.run_if(in_state(GameState::Matchmaking)),
-                camera_follow.run_if(in_state(GameState::InGame)),
-            ),
-        )
-        .add_systems(ReadInputs, read_local_inputs)
-        .add_systems(
-            GgrsSchedule,
-            (
-                move_players,
-                reload_bullet,
-                fire_bullets.after(move_players).after(reload_bullet),
-                move_bullet.after(fire_bullets),
-            ),
-        )
-        .rollback_component_with_clone::<Transform>()
-        .rollback_component_with_copy::<BulletReady>()
-        //.rollback_component_with_copy::<MoveDir>() // is this right?
-        .run();
-}
-
-fn reload_bullet(
-    inputs: Res<PlayerInputs<Config>>,
-    mut players: Query<(&mut BulletReady, &Player)>,
-) {
-    for (mut can_fire, player) in players.iter_mut() {
-        let (input, _) = inputs[player.handle];
-        if !fire(input) {
-            can_fire.0 = true;
-        }
-    }
-}
-
-fn move_bullet(mut bullets: Query<(&mut Transform, &MoveDir), With<Bullet>>, time: Res<Time>) {
-    for (mut transform, dir) in &mut bullets {
-        let speed = 20.;
-        let delta = dir.0 * speed * time.delta_seconds();
-        transform.translation += delta.extend(0.);
-    }
-}
-
-fn fire_bullets(
-    mut commands: Commands,
-    inputs: Res<PlayerInputs<Config>>,
-    images: Res<ImageAssets>,
-    mut players: Query<(&Transform, &Player, &mut BulletReady, &MoveDir)>,
-) {
-    for (transform, player, mut bullet_ready, move_dir) in &mut players {
-        let (input, _) = inputs[player.handle];
-        if fire(input) && bullet_ready.0 {
-            commands
-                .spawn((
-                    Bullet,
-                    *move_dir,
-                    SpriteBundle {
-                        transform: Transform::from_translation(transform.translation)
-                            .with_rotation(Quat::from_rotation_arc_2d(Vec2::X, move_dir.0)),
-                        texture: images.bullet.clone(),
-                        sprite: Sprite {
-                            custom_size: Some(Vec2::new(0.5, 0.2)),
-                            ..default()
-                        },
-                        ..default()
-                    },
-                ))
-                .add_rollback();
-            bullet_ready.0 = false;
-        }
-    }
-}
-
-fn camera_follow(
-    local_players: Res<LocalPlayers>,
-    players: Query<(&Player, &Transform)>,
-    mut cameras: Query<&mut Transform, (With<Camera>, Without<Player>)>,
-) {
-    for (player, player_transform) in &players {
-        // only follow the local player
-        if !local_players.0.contains(&player.handle) {
-            continue;
-        }
-
-        let pos = player_transform.translation;
-
-        for mut transform in &mut cameras {
-            transform.translation.x = pos.x;
-            transform.translation.y = pos.y;
-        }
-    }
-}
-
-pub fn move_players(
-    mut players: Query<(&mut Transform, &mut MoveDir, &Player)>,
-    inputs: Res<PlayerInputs<Config>>,
-    time: Res<Time>,
-) {
-    for (mut transform, mut move_dir, player) in &mut players {
-        let (input, _) = inputs[player.handle];
-        let direction = direction(input).normalize_or_zero();
-        if direction == Vec2::ZERO {
-            continue;
-        }
-
-        move_dir.0 = direction;
-
-        let move_speed = 7.;
-        let move_delta = direction * move_speed * time.delta_seconds();
-
-        let old_pos = transform.translation.xy();
-        let limit = Vec2::splat(MAP_SIZE as f32 / 2. - 0.5);
-        let new_pos = (old_pos + move_delta).clamp(-limit, limit);
-
-        transform.translation.x = new_pos.x;
-        transform.translation.y = new_pos.y;
-    }
-}
-
-fn wait_for_players(
-    mut commands: Commands,
-    mut socket: ResMut<MatchboxSocket<SingleChannel>>,
-    mut next_state: ResMut<NextState<GameState>>,
-) {
-    if socket.get_channel(0).is_err() {
-        return; // we've already started
-    }
-
-    // check for new connections
-    socket.update_peers();
-    let players = socket.players();
-
-    let num_players = 2;
-    if players.len() < num_players {
-        return; // wait for more players
-    }
-
-    info!("All peers have joined, going to the game!");
-
-    let mut session_builder: SessionBuilder<Config> = SessionBuilder::new()
-        .with_num_players(num_players)
-        .with_input_delay(2);
-
-    for (i, player) in players.into_iter().enumerate() {
-        session_builder = session_builder
-            .add_player(player, i)
-            .expect("failed to add player")
-    }
-
-    // move the channel out of the socket (required because ggrs takes ownership of it)
-    let channel = socket.take_channel(0).unwrap();
-
-    let ggrs_session = session_builder
-        .start_p2p_session(channel)
-        .expect("failed to start session");
-
-    commands.insert_resource(bevy_ggrs::Session::P2P(ggrs_session));
-
-    next_state.set(GameState::InGame);
-}
-
-fn start_matchbox_socket(mut commands: Commands) {
-    let room_url = "ws://127.0.0.1:3536/my_bevy_wasm_game?next=2";
-    info!("connecting to matchbox server: {}", room_url);
-    commands.insert_resource(MatchboxSocket::new_ggrs(room_url));
-}
-
-fn setup(mut commands: Commands) {
-    let mut camera_bundle = Camera2dBundle::default();
-    camera_bundle.projection.scaling_mode = ScalingMode::FixedVertical(10.);
-    commands.spawn(camera_bundle);
-
-    // spawn grid sprites
-    // Horizontal lines
-    for i in 0..=MAP_SIZE {
-        commands.spawn(SpriteBundle {
-            transform: Transform::from_translation(Vec3::new(
-                0.,
-                i as f32 - MAP_SIZE as f32 / 2.,
-                0.,
-            )),
-            sprite: Sprite {
-                color: Color::rgb(0.27, 0.27, 0.27),
-                custom_size: Some(Vec2::new(MAP_SIZE as f32, GRID_WIDTH)),
-                ..default()
-            },
-            ..default()
-        });
-    }
-    // Vertical lines
-    for i in 0..=MAP_SIZE {
-        commands.spawn(SpriteBundle {
-            transform: Transform::from_translation(Vec3::new(
-                i as f32 - MAP_SIZE as f32 / 2.,
-                0.,
-                0.,
-            )),
-            sprite: Sprite {
-                color: Color::rgb(0.27, 0.27, 0.27),
-                custom_size: Some(Vec2::new(GRID_WIDTH, MAP_SIZE as f32)),
-                ..default()
-            },
-            ..default()
-        });
-    }
-}
-
-fn spawn_player(mut commands: Commands) {
-    commands
-        .spawn((
-            Player { handle: 0 },
-            BulletReady(true),
-            SpriteBundle {
-                transform: Transform::from_translation(Vec3::new(-2., 0.0, 1.0)),
-                sprite: Sprite {
-                    color: Color::rgb(0., 0.47, 1.),
-                    custom_size: Some(Vec2::new(1., 1.)),
-                    ..default()
-                },
-                ..default()
-            },
-        ))
-        .add_rollback();
-
-    commands
-        .spawn((
-            Player { handle: 1 },
-            BulletReady(true),
-            SpriteBundle {
-                transform: Transform::from_translation(Vec3::new(2.0, 0., 1.)),
-                sprite: Sprite {
-                    color: Color::rgb(0., 0.4, 0.),
-                    custom_size: Some(Vec2::new(1., 1.)),
-                    ..default()
-                },
-                ..default()
-            },
-        ))
-        .add_rollback();
+    bevy_wizard_battles::run();
 }
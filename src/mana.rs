@@ -0,0 +1,60 @@
+//! Mana: every spell costs some, and it trickles back over time.
+//!
+//! Mana burns drain it and hold off the trickle for a while, see
+//! [`crate::spells::move_mana_burns`]. Everything lives in the rolled-back
+//! [`Mana`] component.
+
+use bevy::prelude::*;
+
+use crate::{components::*, round::SimFrame};
+
+pub const MAX_MANA: u32 = 100;
+/// One point of mana comes back this often, ten a second
+const MANA_REGEN_INTERVAL_FRAMES: i32 = 6;
+
+impl Spell {
+    pub fn mana_cost(self) -> u32 {
+        match self {
+            Spell::Fireball => 30,
+            Spell::Hook => 20,
+            Spell::Decoy => 40,
+            Spell::ManaBurn => 15,
+            Spell::Telekinesis => 25,
+        }
+    }
+}
+
+impl Mana {
+    pub fn full() -> Self {
+        Self {
+            amount: MAX_MANA,
+            regen_paused_until: 0,
+        }
+    }
+
+    /// Takes `cost` off if there's enough of it, otherwise leaves it be
+    pub fn spend(&mut self, cost: u32) -> bool {
+        if self.amount < cost {
+            return false;
+        }
+        self.amount -= cost;
+        true
+    }
+
+    /// Drains `amount` and stops it coming back for `pause_frames`
+    pub fn burn(&mut self, frame: i32, amount: u32, pause_frames: i32) {
+        self.amount = self.amount.saturating_sub(amount);
+        self.regen_paused_until = self.regen_paused_until.max(frame + pause_frames);
+    }
+}
+
+pub fn regenerate_mana(frame: Res<SimFrame>, mut players: Query<&mut Mana>) {
+    if frame.0 % MANA_REGEN_INTERVAL_FRAMES != 0 {
+        return;
+    }
+    for mut mana in &mut players {
+        if mana.amount < MAX_MANA && frame.0 >= mana.regen_paused_until {
+            mana.amount += 1;
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! The menu Escape brings up during a match.
+//!
+//! The match carries on underneath, there's no pausing an online game. Quitting
+//! asks again first, leaving an online match forfeits it, then tells the other
+//! players and tears the whole match down on the way back to the menu, so a
+//! new one can be found without reloading the page. The training range has its
+//! own way out, Escape there leaves straight away.
+
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_ggrs::{Rollback, Session};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+
+use crate::{
+    coach::is_coach,
+    not_typing,
+    peer_messages::{send_to_peers, PeerMessage},
+    round::teardown_match,
+    training::TrainingRange,
+    Config, GameState, TextInputFocus,
+};
+
+const BACKGROUND_COLOR: Color = Color::rgba(0., 0., 0., 0.6);
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+
+pub struct MatchMenuPlugin;
+
+impl Plugin for MatchMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::InGame), close_match_menu)
+            .add_systems(
+                Update,
+                (
+                    toggle_match_menu
+                        .run_if(not_typing)
+                        .run_if(not(resource_exists::<TrainingRange>)),
+                    match_menu_buttons,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct MatchMenu;
+
+#[derive(Component, Clone, Copy)]
+enum MatchMenuButton {
+    Resume,
+    Quit,
+    ConfirmQuit,
+}
+
+#[derive(Clone, Copy)]
+enum MatchMenuPage {
+    Main,
+    /// Making sure, with a warning if it costs them the match
+    ConfirmQuit {
+        forfeit: bool,
+    },
+}
+
+fn toggle_match_menu(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    focus: Res<TextInputFocus>,
+    menus: Query<Entity, With<MatchMenu>>,
+) {
+    // the chat box may have just let go of the Escape that closed it
+    if !keys.just_pressed(KeyCode::Escape) || focus.is_changed() {
+        return;
+    }
+    if menus.is_empty() {
+        spawn_match_menu(&mut commands, MatchMenuPage::Main);
+    } else {
+        for menu in &menus {
+            commands.entity(menu).despawn_recursive();
+        }
+    }
+}
+
+fn spawn_match_menu(commands: &mut Commands, page: MatchMenuPage) {
+    let (message, buttons) = match page {
+        MatchMenuPage::Main => (
+            None,
+            [
+                (MatchMenuButton::Resume, "Resume"),
+                (MatchMenuButton::Quit, "Quit to Menu"),
+            ],
+        ),
+        MatchMenuPage::ConfirmQuit { forfeit } => (
+            Some(if forfeit {
+                "Leaving counts as a forfeit, your opponent gets the win"
+            } else {
+                "Leave the match?"
+            }),
+            // "Stay" goes where "Quit to Menu" was, so a double click doesn't leave
+            [
+                (MatchMenuButton::ConfirmQuit, "Leave Match"),
+                (MatchMenuButton::Resume, "Stay"),
+            ],
+        ),
+    };
+
+    commands
+        .spawn((
+            MatchMenu,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.),
+                    ..default()
+                },
+                background_color: BACKGROUND_COLOR.into(),
+                z_index: ZIndex::Global(15),
+                focus_policy: FocusPolicy::Block,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            if let Some(message) = message {
+                parent.spawn(TextBundle::from_section(
+                    message,
+                    TextStyle {
+                        font_size: 28.,
+                        color: Color::ORANGE,
+                        ..default()
+                    },
+                ));
+            }
+            for (button, label) in buttons {
+                parent
+                    .spawn((
+                        button,
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(260.),
+                                padding: UiRect::axes(Val::Px(24.), Val::Px(12.)),
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font_size: 30.,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn close_match_menu(mut commands: Commands, menus: Query<Entity, With<MatchMenu>>) {
+    for menu in &menus {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+fn match_menu_buttons(
+    mut commands: Commands,
+    mut buttons: Query<
+        (&Interaction, &MatchMenuButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    menus: Query<Entity, With<MatchMenu>>,
+    mut socket: Option<ResMut<MatchboxSocket<MultipleChannels>>>,
+    session: Option<Res<Session<Config>>>,
+    rollback_entities: Query<Entity, With<Rollback>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let coach = session.as_deref().is_some_and(is_coach);
+    for (interaction, button, mut background) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => match button {
+                MatchMenuButton::Resume => {
+                    for menu in &menus {
+                        commands.entity(menu).despawn_recursive();
+                    }
+                }
+                MatchMenuButton::Quit => {
+                    for menu in &menus {
+                        commands.entity(menu).despawn_recursive();
+                    }
+                    // practice and coaching don't cost anyone anything
+                    let forfeit = socket.is_some() && !coach;
+                    spawn_match_menu(&mut commands, MatchMenuPage::ConfirmQuit { forfeit });
+                }
+                MatchMenuButton::ConfirmQuit => {
+                    // the match is the opponent's now, no need to wait for GGRS to notice
+                    if let Some(socket) = socket.as_mut().filter(|_| !coach) {
+                        send_to_peers(socket, PeerMessage::LeftMatch);
+                    }
+                    info!("quitting the match");
+                    teardown_match(&mut commands, &rollback_entities);
+                    next_state.set(GameState::Matchmaking);
+                }
+            },
+            Interaction::Hovered => background.0 = BUTTON_HOVER_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Agreeing on the match before GGRS starts.
+//!
+//! Once the ping handshake and the draft are done, both players send the
+//! settings they'd start the match with over the reliable channel: their
+//! version, their half of the seed, the arena they loaded, the kind of match
+//! they were looking for and the rules. Peers that couldn't play each other
+//! without desyncing look for someone else instead. The rules are the ones of
+//! the player with handle 0, the seed is both halves together so neither side
+//! picks it alone. The host passes what was agreed on to a coach, whose
+//! simulation has to start out the same.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::Instant};
+use bevy_matchbox::matchbox_socket::{PeerId, WebRtcChannel};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    barrels::ArenaLayout,
+    peer_messages::{self, PeerMessage},
+    rng::MatchRng,
+    rules::Rules,
+    signaling::Room,
+};
+
+/// Bumped whenever peers on either side of it couldn't play each other
+const PROTOCOL_VERSION: u16 = 1;
+/// How often our settings go out until the opponent's arrive, they may have
+/// been too busy with the handshake or the draft to notice the first ones
+const RESEND_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Everything both peers have to start the match with
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct MatchSettings {
+    pub version: u16,
+    /// The sender's half of the seed, or the whole of it once agreed on
+    pub seed: u64,
+    /// [`ArenaLayout::fingerprint`] of the sender's map
+    pub map: u64,
+    pub mode: Room,
+    pub rules: Rules,
+}
+
+impl MatchSettings {
+    pub fn new(seed: u64, layout: &ArenaLayout, mode: Room, rules: Rules) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            seed,
+            map: layout.fingerprint(),
+            mode,
+            rules,
+        }
+    }
+
+    /// Why we can't play against a peer with `other`, if we can't
+    fn incompatibility(&self, other: &Self) -> Option<&'static str> {
+        if self.version != other.version {
+            Some("they're on a different version of the game")
+        } else if self.map != other.map {
+            Some("they loaded a different arena")
+        } else if self.mode != other.mode {
+            Some("they were looking for a different kind of match")
+        } else {
+            None
+        }
+    }
+
+    /// Sets the simulation up to start the match with these
+    pub fn apply(&self, commands: &mut Commands, rules: &mut Rules) {
+        *rules = self.rules;
+        commands.insert_resource(MatchRng::new(self.seed));
+    }
+}
+
+/// How the settings exchange went so far
+#[derive(Clone, Copy, Debug)]
+pub enum Agreement {
+    Waiting,
+    Agreed(MatchSettings),
+    Incompatible(&'static str),
+}
+
+/// Settings exchange with one peer, driven by `wait_for_players` every frame until it's done
+pub struct MatchSetup {
+    peer: PeerId,
+    local_handle: usize,
+    ours: MatchSettings,
+    theirs: Option<MatchSettings>,
+    next_send: Instant,
+}
+
+impl MatchSetup {
+    pub fn new(peer: PeerId, local_handle: usize, ours: MatchSettings) -> Self {
+        Self {
+            peer,
+            local_handle,
+            ours,
+            theirs: None,
+            next_send: Instant::now(),
+        }
+    }
+
+    pub fn update(&mut self, channel: &mut WebRtcChannel) -> Agreement {
+        for (peer, message) in peer_messages::receive(channel) {
+            match message {
+                // they may still be measuring the ping
+                PeerMessage::Ping(seq) => PeerMessage::Pong(seq).send(channel, peer),
+                PeerMessage::MatchSettings(settings) if peer == self.peer => {
+                    // they've only just started listening, make sure they got ours
+                    if self.theirs.is_none() {
+                        PeerMessage::MatchSettings(self.ours).send(channel, peer);
+                    }
+                    self.theirs = Some(settings);
+                }
+                _ => {}
+            }
+        }
+
+        let Some(theirs) = self.theirs else {
+            let now = Instant::now();
+            if now >= self.next_send {
+                self.next_send = now + RESEND_INTERVAL;
+                PeerMessage::MatchSettings(self.ours).send(channel, self.peer);
+            }
+            return Agreement::Waiting;
+        };
+
+        if let Some(reason) = self.ours.incompatibility(&theirs) {
+            return Agreement::Incompatible(reason);
+        }
+        let host = if self.local_handle == 0 {
+            self.ours
+        } else {
+            theirs
+        };
+        Agreement::Agreed(MatchSettings {
+            seed: self.ours.seed ^ theirs.seed,
+            ..host
+        })
+    }
+}
+
+/// Our half of the seed. Doesn't need to be good, only different every match.
+pub fn seed_half(local: Option<PeerId>, since_startup: Duration) -> u64 {
+    let (high, low) = local.map_or((0, 0), |peer| peer.0.as_u64_pair());
+    high ^ low.rotate_left(17) ^ since_startup.as_nanos() as u64
+}
@@ -0,0 +1,729 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+
+use crate::{
+    bot::{play_vs_bot, BotDifficulty, Bots},
+    connect_to_matchbox,
+    private_room::PrivateRoom,
+    signaling::{fallback_urls, MatchboxUrl, Room},
+    GameState,
+};
+
+/// How long to look for an opponent before offering a bot instead, in seconds
+const MATCHMAKING_TIMEOUT: f32 = 20.;
+
+pub const MAX_INPUT_DELAY: usize = 5;
+
+/// Wait before the first attempt to reconnect to the matchbox server, in seconds.
+/// Doubles with every failed attempt.
+const RECONNECT_DELAY: f32 = 1.;
+const MAX_RECONNECT_DELAY: f32 = 30.;
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const BUTTON_SELECTED_COLOR: Color = Color::rgb(0.2, 0.35, 0.6);
+
+pub struct MatchmakingPlugin;
+
+impl Plugin for MatchmakingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputDelay>()
+            .init_resource::<Ranked>()
+            .init_resource::<CoachSlot>()
+            .init_resource::<SignalingReconnect>()
+            .add_systems(OnEnter(GameState::Matchmaking), spawn_matchmaking_screen)
+            .add_systems(OnExit(GameState::Matchmaking), despawn_matchmaking_screen)
+            .add_systems(
+                Update,
+                (
+                    offer_bot_after_timeout,
+                    play_vs_bot_button,
+                    difficulty_buttons,
+                    input_delay_buttons,
+                    ranked_button,
+                    coach_button,
+                    reconnect_to_matchbox,
+                    update_signaling_status,
+                )
+                    .run_if(in_state(GameState::Matchmaking)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct MatchmakingScreen {
+    spawned_at: f32,
+}
+
+#[derive(Component)]
+struct PlayVsBotButton;
+
+#[derive(Component)]
+struct DifficultyButton(BotDifficulty);
+
+/// Frames local inputs are held back before they're simulated. More means
+/// fewer rollbacks on a slow connection, less means snappier controls.
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum InputDelay {
+    /// Picked from the ping to the opponent when the match starts
+    #[default]
+    Auto,
+    Fixed(usize),
+}
+
+/// Whether to look for a ranked match. Ranked players are matched among
+/// themselves, and draft their spells before the match, see [`crate::draft`].
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Ranked(pub bool);
+
+/// Whether to look for a match with a coach watching, or to coach one, see
+/// [`crate::coach`]
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum CoachSlot {
+    #[default]
+    Off,
+    /// Playing, with a coach welcome to watch
+    Open,
+    Coaching,
+}
+
+impl CoachSlot {
+    fn next(self) -> Self {
+        match self {
+            CoachSlot::Off => CoachSlot::Open,
+            CoachSlot::Open => CoachSlot::Coaching,
+            CoachSlot::Coaching => CoachSlot::Off,
+        }
+    }
+}
+
+/// The room to look for a match in, a private one trumps the rest
+pub fn room(ranked: Ranked, coach: CoachSlot, private: PrivateRoom) -> Room {
+    match private.0 {
+        Some(code) => Room::Private(code),
+        None => Room::new(ranked.0, coach != CoachSlot::Off),
+    }
+}
+
+/// Everything that decides the room to look for a match in
+#[derive(SystemParam)]
+pub struct RoomChoice<'w> {
+    ranked: Res<'w, Ranked>,
+    coach: Res<'w, CoachSlot>,
+    private: Res<'w, PrivateRoom>,
+}
+
+impl RoomChoice<'_> {
+    pub fn room(&self) -> Room {
+        room(*self.ranked, *self.coach, *self.private)
+    }
+
+    pub fn coaching(&self) -> bool {
+        *self.coach == CoachSlot::Coaching
+    }
+}
+
+/// Input delay the current match is played with, and the round trip it was
+/// picked from, if it was measured
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct MatchInputDelay {
+    pub frames: usize,
+    pub round_trip: Option<Duration>,
+}
+
+/// Backoff for getting back in touch with the matchbox server after the
+/// connection to it dropped.
+///
+/// A failed connection moves straight on to the next of the backup servers,
+/// and only once they've all failed too is it back to the first server, after
+/// a while, with the player told about it. A server that worked is kept until
+/// it fails in turn. Everybody goes down the same list, so players still end
+/// up on the same server as each other.
+#[derive(Resource, Debug)]
+pub struct SignalingReconnect {
+    /// Failed connections in a row
+    attempts: u32,
+    /// When to try again, in seconds since startup. `None` while connecting.
+    retry_at: Option<f32>,
+    /// Backup servers, see [`fallback_urls`]
+    fallbacks: Vec<String>,
+    /// The backup we're on, and the server we fell back from
+    fallback: Option<(usize, String)>,
+}
+
+impl Default for SignalingReconnect {
+    fn default() -> Self {
+        Self {
+            attempts: 0,
+            retry_at: None,
+            fallbacks: fallback_urls(),
+            fallback: None,
+        }
+    }
+}
+
+impl SignalingReconnect {
+    /// Switches `server` over to the next backup if there is one, or back to
+    /// where we started
+    pub fn connection_lost(&mut self, now: f32, server: &mut MatchboxUrl) {
+        let next = self.fallback.as_ref().map_or(0, |(index, _)| index + 1);
+        if let Some(url) = self.fallbacks.get(next) {
+            warn!(
+                "lost connection to the matchbox server at {}, trying {url}",
+                server.0
+            );
+            let primary = match self.fallback.take() {
+                Some((_, primary)) => primary,
+                None => server.0.clone(),
+            };
+            server.0.clone_from(url);
+            self.fallback = Some((next, primary));
+            self.retry_at = Some(now);
+            return;
+        }
+        if let Some((_, primary)) = self.fallback.take() {
+            server.0 = primary;
+        }
+
+        let delay = (RECONNECT_DELAY * 2_f32.powi(self.attempts as i32)).min(MAX_RECONNECT_DELAY);
+        warn!("lost connection to the matchbox server, retrying in {delay}s");
+        self.attempts += 1;
+        self.retry_at = Some(now + delay);
+    }
+
+    /// Starts looking for a match afresh, on whichever server worked last
+    pub fn restart(&mut self) {
+        self.attempts = 0;
+        self.retry_at = None;
+    }
+
+    /// Whether we're on a backup server, which nothing else should move us off
+    pub fn on_fallback(&self) -> bool {
+        self.fallback.is_some()
+    }
+
+    pub fn connected(&mut self) {
+        if self.attempts > 0 {
+            info!("reconnected to the matchbox server");
+        }
+        self.attempts = 0;
+    }
+}
+
+#[derive(Component)]
+struct SignalingStatusText;
+
+/// Changes the input delay by the given number of frames, or switches it to
+/// automatic if `None`
+#[derive(Component)]
+struct InputDelayButton(Option<isize>);
+
+#[derive(Component)]
+struct InputDelayText;
+
+#[derive(Component)]
+struct RankedButton;
+
+#[derive(Component)]
+struct CoachButton;
+
+fn spawn_matchmaking_screen(
+    mut commands: Commands,
+    time: Res<Time>,
+    difficulty: Res<BotDifficulty>,
+    input_delay: Res<InputDelay>,
+    ranked: Res<Ranked>,
+    coach: Res<CoachSlot>,
+) {
+    commands
+        .spawn((
+            MatchmakingScreen {
+                spawned_at: time.elapsed_seconds(),
+            },
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Looking for an opponent...",
+                TextStyle {
+                    font_size: 40.,
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                SignalingStatusText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 22.,
+                        color: Color::ORANGE,
+                        ..default()
+                    },
+                ),
+            ));
+            parent.spawn(TextBundle::from_section(
+                "Press P to practice against a bot, T for the training range or H for the tutorial.\nF2 opens the settings",
+                TextStyle {
+                    font_size: 22.,
+                    color: Color::rgb(0.8, 0.8, 0.8),
+                    ..default()
+                },
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Bot difficulty:",
+                        TextStyle {
+                            font_size: 22.,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                            ..default()
+                        },
+                    ));
+                    for option in BotDifficulty::ALL {
+                        parent
+                            .spawn((
+                                DifficultyButton(option),
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                                        ..default()
+                                    },
+                                    background_color: difficulty_color(option, *difficulty).into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    option.name(),
+                                    TextStyle {
+                                        font_size: 22.,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    }
+                });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Input delay:",
+                        TextStyle {
+                            font_size: 22.,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                            ..default()
+                        },
+                    ));
+                    for (change, label) in [(Some(-1), "-"), (Some(1), "+"), (None, "Auto")] {
+                        parent
+                            .spawn((
+                                InputDelayButton(change),
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                                        ..default()
+                                    },
+                                    background_color: input_delay_color(change, *input_delay)
+                                        .into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    label,
+                                    TextStyle {
+                                        font_size: 22.,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                        if change == Some(-1) {
+                            parent.spawn((
+                                InputDelayText,
+                                TextBundle::from_section(
+                                    input_delay_label(*input_delay),
+                                    TextStyle {
+                                        font_size: 22.,
+                                        ..default()
+                                    },
+                                ),
+                            ));
+                        }
+                    }
+                });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(8.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn((
+                            RankedButton,
+                            ButtonBundle {
+                                style: Style {
+                                    padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                                    ..default()
+                                },
+                                background_color: ranked_color(*ranked).into(),
+                                ..default()
+                            },
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                ranked_label(*ranked),
+                                TextStyle {
+                                    font_size: 22.,
+                                    ..default()
+                                },
+                            ));
+                        });
+                    parent
+                        .spawn((
+                            CoachButton,
+                            ButtonBundle {
+                                style: Style {
+                                    padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                                    ..default()
+                                },
+                                background_color: coach_color(*coach).into(),
+                                ..default()
+                            },
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                coach_label(*coach),
+                                TextStyle {
+                                    font_size: 22.,
+                                    ..default()
+                                },
+                            ));
+                        });
+                });
+        });
+}
+
+fn coach_label(coach: CoachSlot) -> &'static str {
+    match coach {
+        CoachSlot::Off => "Coach slot: off",
+        CoachSlot::Open => "Coach slot: open",
+        CoachSlot::Coaching => "Joining as coach",
+    }
+}
+
+fn coach_color(coach: CoachSlot) -> Color {
+    match coach {
+        CoachSlot::Off => BUTTON_COLOR,
+        CoachSlot::Open | CoachSlot::Coaching => BUTTON_SELECTED_COLOR,
+    }
+}
+
+fn ranked_label(ranked: Ranked) -> &'static str {
+    if ranked.0 {
+        "Ranked: on"
+    } else {
+        "Ranked: off"
+    }
+}
+
+fn ranked_color(ranked: Ranked) -> Color {
+    if ranked.0 {
+        BUTTON_SELECTED_COLOR
+    } else {
+        BUTTON_COLOR
+    }
+}
+
+fn input_delay_label(input_delay: InputDelay) -> String {
+    match input_delay {
+        InputDelay::Auto => "auto".to_string(),
+        InputDelay::Fixed(1) => "1 frame".to_string(),
+        InputDelay::Fixed(frames) => format!("{frames} frames"),
+    }
+}
+
+fn input_delay_color(change: Option<isize>, input_delay: InputDelay) -> Color {
+    if change.is_none() && input_delay == InputDelay::Auto {
+        BUTTON_SELECTED_COLOR
+    } else {
+        BUTTON_COLOR
+    }
+}
+
+fn difficulty_color(option: BotDifficulty, selected: BotDifficulty) -> Color {
+    if option == selected {
+        BUTTON_SELECTED_COLOR
+    } else {
+        BUTTON_COLOR
+    }
+}
+
+fn despawn_matchmaking_screen(
+    mut commands: Commands,
+    screens: Query<Entity, With<MatchmakingScreen>>,
+) {
+    for screen in &screens {
+        commands.entity(screen).despawn_recursive();
+    }
+}
+
+/// Nobody showed up in time, so offer to play against a bot while we keep looking
+fn offer_bot_after_timeout(
+    mut commands: Commands,
+    time: Res<Time>,
+    screens: Query<(Entity, &MatchmakingScreen)>,
+    buttons: Query<(), With<PlayVsBotButton>>,
+) {
+    if !buttons.is_empty() {
+        return;
+    }
+
+    for (entity, screen) in &screens {
+        if time.elapsed_seconds() - screen.spawned_at < MATCHMAKING_TIMEOUT {
+            continue;
+        }
+
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "No opponent found yet",
+                TextStyle {
+                    font_size: 24.,
+                    color: Color::ORANGE,
+                    ..default()
+                },
+            ));
+            parent
+                .spawn((
+                    PlayVsBotButton,
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::axes(Val::Px(24.), Val::Px(12.)),
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Play vs Bot",
+                        TextStyle {
+                            font_size: 30.,
+                            ..default()
+                        },
+                    ));
+                });
+        });
+    }
+}
+
+fn play_vs_bot_button(
+    mut commands: Commands,
+    mut buttons: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<PlayVsBotButton>),
+    >,
+    mut bots: ResMut<Bots>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, mut background) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => play_vs_bot(&mut commands, &mut bots, &mut next_state),
+            Interaction::Hovered => background.0 = BUTTON_HOVER_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+}
+
+fn difficulty_buttons(
+    interactions: Query<(&Interaction, &DifficultyButton), Changed<Interaction>>,
+    mut buttons: Query<(&Interaction, &DifficultyButton, &mut BackgroundColor)>,
+    mut difficulty: ResMut<BotDifficulty>,
+) {
+    if interactions.is_empty() {
+        return;
+    }
+
+    for (interaction, button) in &interactions {
+        if *interaction == Interaction::Pressed {
+            *difficulty = button.0;
+        }
+    }
+
+    for (interaction, button, mut background) in &mut buttons {
+        background.0 = match interaction {
+            Interaction::Hovered if button.0 != *difficulty => BUTTON_HOVER_COLOR,
+            _ => difficulty_color(button.0, *difficulty),
+        };
+    }
+}
+
+fn input_delay_buttons(
+    interactions: Query<(&Interaction, &InputDelayButton), Changed<Interaction>>,
+    mut buttons: Query<(&Interaction, &InputDelayButton, &mut BackgroundColor)>,
+    mut texts: Query<&mut Text, With<InputDelayText>>,
+    mut input_delay: ResMut<InputDelay>,
+) {
+    if interactions.is_empty() {
+        return;
+    }
+
+    for (interaction, button) in &interactions {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        *input_delay = match (button.0, *input_delay) {
+            (None, _) => InputDelay::Auto,
+            // start adjusting from the old default
+            (Some(change), InputDelay::Auto) => {
+                InputDelay::Fixed(2_usize.saturating_add_signed(change))
+            }
+            (Some(change), InputDelay::Fixed(frames)) => {
+                InputDelay::Fixed(frames.saturating_add_signed(change).min(MAX_INPUT_DELAY))
+            }
+        };
+        for mut text in &mut texts {
+            text.sections[0].value = input_delay_label(*input_delay);
+        }
+    }
+
+    for (interaction, button, mut background) in &mut buttons {
+        background.0 = match interaction {
+            Interaction::Hovered => BUTTON_HOVER_COLOR,
+            _ => input_delay_color(button.0, *input_delay),
+        };
+    }
+}
+
+/// Switches between looking for a ranked and a casual match, which are
+/// different rooms on the matchbox server
+fn ranked_button(
+    mut commands: Commands,
+    server: Res<MatchboxUrl>,
+    socket: Option<Res<MatchboxSocket<MultipleChannels>>>,
+    mut ranked: ResMut<Ranked>,
+    coach: Res<CoachSlot>,
+    private: Res<PrivateRoom>,
+    mut buttons: Query<
+        (&Interaction, &Children, &mut BackgroundColor),
+        (Changed<Interaction>, With<RankedButton>),
+    >,
+    mut texts: Query<&mut Text>,
+) {
+    for (interaction, children, mut background) in &mut buttons {
+        if *interaction == Interaction::Pressed {
+            ranked.0 = !ranked.0;
+            if let Ok(mut text) = texts.get_mut(children[0]) {
+                text.sections[0].value = ranked_label(*ranked).into();
+            }
+            // otherwise it's still coming back on its own, and will pick the right
+            // room. A private room stays put either way.
+            if socket.is_some() && private.0.is_none() {
+                connect_to_matchbox(&mut commands, &server, room(*ranked, *coach, *private));
+            }
+        }
+        background.0 = match interaction {
+            Interaction::Hovered => BUTTON_HOVER_COLOR,
+            _ => ranked_color(*ranked),
+        };
+    }
+}
+
+/// Cycles between no coach, playing with one and coaching, which all but the
+/// first look for in the coached room
+fn coach_button(
+    mut commands: Commands,
+    server: Res<MatchboxUrl>,
+    socket: Option<Res<MatchboxSocket<MultipleChannels>>>,
+    ranked: Res<Ranked>,
+    mut coach: ResMut<CoachSlot>,
+    private: Res<PrivateRoom>,
+    mut buttons: Query<
+        (&Interaction, &Children, &mut BackgroundColor),
+        (Changed<Interaction>, With<CoachButton>),
+    >,
+    mut texts: Query<&mut Text>,
+) {
+    for (interaction, children, mut background) in &mut buttons {
+        if *interaction == Interaction::Pressed {
+            *coach = coach.next();
+            if let Ok(mut text) = texts.get_mut(children[0]) {
+                text.sections[0].value = coach_label(*coach).into();
+            }
+            if socket.is_some() && private.0.is_none() {
+                connect_to_matchbox(&mut commands, &server, room(*ranked, *coach, *private));
+            }
+        }
+        background.0 = match interaction {
+            Interaction::Hovered => BUTTON_HOVER_COLOR,
+            _ => coach_color(*coach),
+        };
+    }
+}
+
+fn reconnect_to_matchbox(
+    mut commands: Commands,
+    time: Res<Time>,
+    server: Res<MatchboxUrl>,
+    room_choice: RoomChoice,
+    mut reconnect: ResMut<SignalingReconnect>,
+) {
+    if reconnect
+        .retry_at
+        .is_some_and(|retry_at| time.elapsed_seconds() >= retry_at)
+    {
+        reconnect.retry_at = None;
+        connect_to_matchbox(&mut commands, &server, room_choice.room());
+    }
+}
+
+fn update_signaling_status(
+    time: Res<Time>,
+    reconnect: Res<SignalingReconnect>,
+    mut texts: Query<&mut Text, With<SignalingStatusText>>,
+) {
+    let status = match reconnect.retry_at {
+        Some(retry_at) => format!(
+            "Lost connection to the matchmaking server, retrying in {:.0}s",
+            (retry_at - time.elapsed_seconds()).max(0.).ceil()
+        ),
+        None if reconnect.attempts > 0 => "Reconnecting to the matchmaking server...".into(),
+        None => String::new(),
+    };
+    for mut text in &mut texts {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+}
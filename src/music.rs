@@ -0,0 +1,86 @@
+//! Background music: a calm loop on the menus, the battle loop in a match and
+//! a heavier layer on top of it once somebody's close to dying. Tracks fade
+//! into each other instead of cutting.
+
+use bevy::{audio::Volume, prelude::*};
+
+use crate::{assets::MusicAssets, components::*, settings::Settings, GameState, MAX_HEALTH};
+
+/// Seconds a track takes to fade all the way in or out
+const CROSSFADE_SECS: f32 = 1.5;
+/// Health below which the intense layer kicks in
+const LOW_HEALTH: u32 = MAX_HEALTH * 3 / 10;
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                start_music.run_if(resource_added::<MusicAssets>),
+                fade_music,
+            ),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Track {
+    Menu,
+    Battle,
+    /// Layered over `Battle`, same length so they stay in time
+    Intense,
+}
+
+#[derive(Component)]
+struct Music {
+    track: Track,
+    /// How far faded in, 0 to 1
+    level: f32,
+}
+
+/// Every track plays all the time, fading is what switches between them
+fn start_music(mut commands: Commands, music: Res<MusicAssets>) {
+    for (track, source) in [
+        (Track::Menu, &music.menu),
+        (Track::Battle, &music.battle),
+        (Track::Intense, &music.battle_intense),
+    ] {
+        commands.spawn((
+            Music { track, level: 0. },
+            AudioBundle {
+                source: source.clone(),
+                settings: PlaybackSettings::LOOP.with_volume(Volume::new(0.)),
+            },
+        ));
+    }
+}
+
+fn fade_music(
+    time: Res<Time<Real>>,
+    state: Res<State<GameState>>,
+    settings: Res<Settings>,
+    wizards: Query<&Health, (With<Player>, Without<Dummy>)>,
+    mut tracks: Query<(&mut Music, &AudioSink)>,
+) {
+    let in_match = matches!(
+        state.get(),
+        GameState::InGame | GameState::MatchOver | GameState::Replay
+    );
+    let close_call = wizards
+        .iter()
+        .any(|health| health.0 > 0 && health.0 < LOW_HEALTH);
+
+    let step = time.delta_seconds() / CROSSFADE_SECS;
+    for (mut music, sink) in &mut tracks {
+        let playing = match music.track {
+            Track::Menu => !in_match,
+            Track::Battle => in_match,
+            Track::Intense => in_match && close_call,
+        };
+        let target = if playing { 1. } else { 0. };
+        music.level += (target - music.level).clamp(-step, step);
+        sink.set_volume(music.level * settings.music_volume);
+    }
+}
@@ -0,0 +1,145 @@
+//! Round mutators: every round is played with one twist, rolled from the
+//! [`MatchRng`] when the round starts and announced under the countdown.
+//!
+//! Which twists can come up is part of the [`Rules`], an empty pool plays
+//! every round straight. The simulation systems ask the rolled-back
+//! [`RoundMutator`] for speeds, ranges, cooldowns and hitboxes, this module
+//! only shrinks the wizards' sprites to match.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{components::*, fixed::Fixed, in_arena, rng::MatchRng, rules::Rules, PLAYER_RADIUS};
+
+/// Wizard size under [`Mutator::TinyWizards`], hitbox and sprite both
+const TINY_WIZARD_SCALE: f32 = 0.5;
+
+pub struct MutatorsPlugin;
+
+impl Plugin for MutatorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, shrink_wizards.run_if(in_arena));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Mutator {
+    /// Spells float along at half speed, for twice as long
+    LowGravity,
+    /// Bullets and spells fly twice as fast
+    DoubleSpeed,
+    /// Fire and cast as fast as the buttons go, mana still runs out
+    NoCooldowns,
+    /// Wizards are half the size, and so much harder to hit
+    TinyWizards,
+}
+
+impl Mutator {
+    pub const ALL: [Mutator; 4] = [
+        Mutator::LowGravity,
+        Mutator::DoubleSpeed,
+        Mutator::NoCooldowns,
+        Mutator::TinyWizards,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mutator::LowGravity => "Low Gravity",
+            Mutator::DoubleSpeed => "Double Speed",
+            Mutator::NoCooldowns => "No Cooldowns",
+            Mutator::TinyWizards => "Tiny Wizards",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The mutators rounds get rolled from, a bit per [`Mutator`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct MutatorPool(u8);
+
+impl MutatorPool {
+    pub const NONE: Self = Self(0);
+
+    pub fn all() -> Self {
+        Mutator::ALL
+            .into_iter()
+            .fold(Self::NONE, |pool, mutator| pool.with(mutator))
+    }
+
+    pub fn with(self, mutator: Mutator) -> Self {
+        Self(self.0 | mutator.bit())
+    }
+
+    pub fn contains(self, mutator: Mutator) -> bool {
+        self.0 & mutator.bit() != 0
+    }
+}
+
+/// This round's twist, if any
+#[derive(Resource, Clone, Copy, Default, Debug, Serialize, Deserialize)]
+pub struct RoundMutator(pub Option<Mutator>);
+
+impl RoundMutator {
+    pub fn roll(rng: &mut MatchRng, rules: &Rules) -> Self {
+        let pool: Vec<Mutator> = Mutator::ALL
+            .into_iter()
+            .filter(|mutator| rules.mutators.contains(*mutator))
+            .collect();
+        if pool.is_empty() {
+            return Self(None);
+        }
+        Self(Some(pool[rng.next_u32() as usize % pool.len()]))
+    }
+
+    /// Distance per frame of a projectile that usually covers `speed`
+    pub fn projectile_speed(&self, speed: Fixed) -> Fixed {
+        match self.0 {
+            Some(Mutator::LowGravity) => speed / 2,
+            Some(Mutator::DoubleSpeed) => speed * 2,
+            _ => speed,
+        }
+    }
+
+    /// Frames a projectile that usually flies for `frames` stays up
+    pub fn projectile_range(&self, frames: u32) -> u32 {
+        match self.0 {
+            Some(Mutator::LowGravity) => frames * 2,
+            _ => frames,
+        }
+    }
+
+    pub fn cooldown(&self, frames: u32) -> u32 {
+        match self.0 {
+            Some(Mutator::NoCooldowns) => 0,
+            _ => frames,
+        }
+    }
+
+    /// How far from its center projectiles and blasts reach a wizard
+    pub fn wizard_radius(&self) -> Fixed {
+        Fixed::from_f32(PLAYER_RADIUS * self.wizard_scale())
+    }
+
+    fn wizard_scale(&self) -> f32 {
+        match self.0 {
+            Some(Mutator::TinyWizards) => TINY_WIZARD_SCALE,
+            _ => 1.,
+        }
+    }
+}
+
+/// Keeps wizards and their decoys drawn at the size they get hit at
+fn shrink_wizards(
+    mutator: Res<RoundMutator>,
+    mut wizards: Query<&mut Sprite, Or<(With<Player>, With<Decoy>)>>,
+) {
+    let size = Some(Vec2::splat(mutator.wizard_scale()));
+    for mut sprite in &mut wizards {
+        if sprite.custom_size != size {
+            sprite.custom_size = size;
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Network info for diagnosing lag, toggled with F3 during online matches,
+//! and a signal icon over remote wizards that's always on. It also shows how
+//! big each frame's rollback snapshot is, see [`crate::rollback_size`].
+
+use bevy::prelude::*;
+use bevy_ggrs::{GgrsSchedule, Session};
+
+use crate::{
+    components::Player,
+    matchmaking::MatchInputDelay,
+    place_in_arena,
+    rollback_size::measure_rollback_size,
+    round::{advance_frame, SimFrame},
+    Config, GameState,
+};
+
+const SIGNAL_BARS: usize = 3;
+const SIGNAL_BAR_WIDTH: f32 = 0.08;
+const SIGNAL_BAR_GAP: f32 = 0.04;
+/// Height of the tallest bar, the others are shorter
+const SIGNAL_BAR_HEIGHT: f32 = 0.3;
+const SIGNAL_OFF_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.6);
+
+pub struct NetOverlayPlugin;
+
+impl Plugin for NetOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetOverlayVisible>()
+            .init_resource::<RollbackCounter>()
+            .init_resource::<SnapshotSize>()
+            .add_systems(
+                OnEnter(GameState::InGame),
+                (spawn_net_overlay, reset_rollback_counter),
+            )
+            .add_systems(OnExit(GameState::InGame), despawn_net_overlay)
+            .add_systems(
+                Update,
+                (
+                    (
+                        toggle_net_overlay,
+                        measure_snapshot.run_if(overlay_visible),
+                        update_net_overlay,
+                    )
+                        .chain(),
+                    update_connection_indicators.after(place_in_arena),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), despawn_connection_indicators)
+            .add_systems(GgrsSchedule, count_rollback_frames.after(advance_frame));
+    }
+}
+
+/// Stays on between matches once turned on
+#[derive(Resource, Default)]
+struct NetOverlayVisible(bool);
+
+#[derive(Component)]
+struct NetOverlay;
+
+/// Simulated frames that were resimulations of frames already seen. Lives
+/// outside the rollback state so it survives the rollbacks it's counting.
+#[derive(Resource, Default)]
+struct RollbackCounter {
+    /// Furthest the simulation has been
+    latest_frame: i32,
+    /// Resimulated frames since `since`
+    count: u32,
+    since: f32,
+    per_second: f32,
+}
+
+/// What a frame's rollback snapshot took when the overlay last looked
+#[derive(Resource, Default)]
+struct SnapshotSize {
+    bytes: usize,
+    /// The type taking up the most of it
+    biggest: Option<(&'static str, usize)>,
+}
+
+/// Signal bars over the remote wizard with this handle
+#[derive(Component)]
+struct ConnectionIndicator {
+    handle: usize,
+}
+
+/// One of an indicator's bars, the first is the shortest
+#[derive(Component)]
+struct SignalBar(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConnectionQuality {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl ConnectionQuality {
+    /// Judged by the ping and how many frames the simulation is running ahead
+    /// of what's confirmed, which is how far back a rollback may have to go
+    fn from_stats(ping: u128, prediction: i32) -> Self {
+        if ping < 80 && prediction <= 3 {
+            Self::Good
+        } else if ping < 150 && prediction <= 6 {
+            Self::Fair
+        } else {
+            Self::Poor
+        }
+    }
+
+    fn bars(self) -> usize {
+        match self {
+            Self::Good => 3,
+            Self::Fair => 2,
+            Self::Poor => 1,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Self::Good => Color::rgb(0.3, 0.9, 0.3),
+            Self::Fair => Color::YELLOW,
+            Self::Poor => Color::RED,
+        }
+    }
+}
+
+fn spawn_net_overlay(mut commands: Commands, visible: Res<NetOverlayVisible>) {
+    commands.spawn((
+        NetOverlay,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 18.,
+                color: Color::rgb(0.9, 0.9, 0.9),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            right: Val::Px(10.),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0., 0., 0., 0.5)),
+        visibility(visible.0),
+    ));
+}
+
+fn despawn_net_overlay(mut commands: Commands, overlays: Query<Entity, With<NetOverlay>>) {
+    for overlay in &overlays {
+        commands.entity(overlay).despawn_recursive();
+    }
+}
+
+fn visibility(visible: bool) -> Visibility {
+    if visible {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    }
+}
+
+fn toggle_net_overlay(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut visible: ResMut<NetOverlayVisible>,
+    mut overlays: Query<&mut Visibility, With<NetOverlay>>,
+) {
+    if !keys.just_pressed(KeyCode::F3) {
+        return;
+    }
+
+    visible.0 = !visible.0;
+    for mut visibility_of in &mut overlays {
+        *visibility_of = visibility(visible.0);
+    }
+}
+
+fn overlay_visible(visible: Res<NetOverlayVisible>) -> bool {
+    visible.0
+}
+
+/// Goes through every rollback entity, so only while anyone's looking
+fn measure_snapshot(world: &mut World) {
+    let size = measure_rollback_size(world);
+    *world.resource_mut::<SnapshotSize>() = SnapshotSize {
+        bytes: size.total(),
+        biggest: size.by_type.first().copied(),
+    };
+}
+
+fn reset_rollback_counter(mut counter: ResMut<RollbackCounter>, time: Res<Time>) {
+    *counter = RollbackCounter {
+        since: time.elapsed_seconds(),
+        ..default()
+    };
+}
+
+fn count_rollback_frames(frame: Res<SimFrame>, mut counter: ResMut<RollbackCounter>) {
+    if frame.0 <= counter.latest_frame {
+        counter.count += 1;
+    } else {
+        counter.latest_frame = frame.0;
+    }
+}
+
+fn update_net_overlay(
+    visible: Res<NetOverlayVisible>,
+    time: Res<Time>,
+    frame: Res<SimFrame>,
+    session: Option<Res<Session<Config>>>,
+    input_delay: Option<Res<MatchInputDelay>>,
+    snapshot: Res<SnapshotSize>,
+    mut counter: ResMut<RollbackCounter>,
+    mut overlays: Query<&mut Text, With<NetOverlay>>,
+) {
+    // keep counting while hidden so the rate is right when it's turned on
+    let elapsed = time.elapsed_seconds() - counter.since;
+    if elapsed >= 1. {
+        counter.per_second = counter.count as f32 / elapsed;
+        counter.count = 0;
+        counter.since = time.elapsed_seconds();
+    }
+
+    if !visible.0 {
+        return;
+    }
+
+    let mut lines = Vec::new();
+    match session.as_deref() {
+        Some(Session::P2P(session)) => {
+            for handle in session.remote_player_handles() {
+                match session.network_stats(handle) {
+                    Ok(stats) => {
+                        lines.push(format!("ping: {} ms", stats.ping));
+                        lines.push(format!(
+                            "frames behind: {} local, {} remote",
+                            stats.local_frames_behind, stats.remote_frames_behind
+                        ));
+                    }
+                    Err(_) => lines.push("synchronizing...".to_string()),
+                }
+            }
+            lines.push(format!(
+                "prediction: {}/{} frames",
+                (frame.0 - session.confirmed_frame()).max(0),
+                session.max_prediction()
+            ));
+            lines.push(format!("rollback frames/s: {:.0}", counter.per_second));
+        }
+        _ => lines.push("offline".to_string()),
+    }
+    if let Some(input_delay) = input_delay {
+        lines.push(format!("input delay: {} frames", input_delay.frames));
+        if let Some(round_trip) = input_delay.round_trip {
+            lines.push(format!("ping at start: {} ms", round_trip.as_millis()));
+        }
+    }
+    lines.push(format!("snapshot: {} B/frame", snapshot.bytes));
+    if let Some((name, bytes)) = snapshot.biggest {
+        lines.push(format!("  most of it: {name}, {bytes} B"));
+    }
+
+    let label = lines.join("\n");
+    for mut text in &mut overlays {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+fn update_connection_indicators(
+    mut commands: Commands,
+    frame: Res<SimFrame>,
+    session: Option<Res<Session<Config>>>,
+    wizards: Query<(&Player, &Transform)>,
+    mut indicators: Query<
+        (
+            &ConnectionIndicator,
+            &Children,
+            &mut Transform,
+            &mut Visibility,
+        ),
+        Without<Player>,
+    >,
+    mut bars: Query<(&SignalBar, &mut Sprite)>,
+) {
+    let Some(Session::P2P(session)) = session.as_deref() else {
+        return;
+    };
+
+    for handle in session.remote_player_handles() {
+        if indicators
+            .iter()
+            .any(|(indicator, ..)| indicator.handle == handle)
+        {
+            continue;
+        }
+        commands
+            .spawn((
+                ConnectionIndicator { handle },
+                SpatialBundle {
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+            ))
+            .with_children(|parent| {
+                let total_width = SIGNAL_BARS as f32 * (SIGNAL_BAR_WIDTH + SIGNAL_BAR_GAP);
+                for bar in 0..SIGNAL_BARS {
+                    let height = SIGNAL_BAR_HEIGHT * (bar + 1) as f32 / SIGNAL_BARS as f32;
+                    let x = bar as f32 * (SIGNAL_BAR_WIDTH + SIGNAL_BAR_GAP) - total_width / 2.;
+                    parent.spawn((
+                        SignalBar(bar),
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: SIGNAL_OFF_COLOR,
+                                custom_size: Some(Vec2::new(SIGNAL_BAR_WIDTH, height)),
+                                // line the bars up at the bottom
+                                anchor: bevy::sprite::Anchor::BottomCenter,
+                                ..default()
+                            },
+                            transform: Transform::from_xyz(x, 0., 0.),
+                            ..default()
+                        },
+                    ));
+                }
+            });
+    }
+
+    let prediction = (frame.0 - session.confirmed_frame()).max(0);
+    for (indicator, children, mut transform, mut visibility) in &mut indicators {
+        let wizard = wizards
+            .iter()
+            .find(|(player, _)| player.handle == indicator.handle);
+        let Some((_, wizard_transform)) = wizard else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        transform.translation = wizard_transform.translation + Vec3::new(0., 0.65, 5.);
+        *visibility = Visibility::Visible;
+
+        // still synchronizing counts as poor, there's no telling yet
+        let quality = session
+            .network_stats(indicator.handle)
+            .map_or(ConnectionQuality::Poor, |stats| {
+                ConnectionQuality::from_stats(stats.ping, prediction)
+            });
+        let mut bars = bars.iter_many_mut(children);
+        while let Some((bar, mut sprite)) = bars.fetch_next() {
+            sprite.color = if bar.0 < quality.bars() {
+                quality.color()
+            } else {
+                SIGNAL_OFF_COLOR
+            };
+        }
+    }
+}
+
+fn despawn_connection_indicators(
+    mut commands: Commands,
+    indicators: Query<Entity, With<ConnectionIndicator>>,
+) {
+    for indicator in &indicators {
+        commands.entity(indicator).despawn_recursive();
+    }
+}
@@ -0,0 +1,115 @@
+//! Bad network conditions on demand, for testing rollback locally.
+//!
+//! Only built with the `netsim` feature. The peer socket is wrapped in a shim
+//! that holds back, shuffles and drops outgoing packets according to
+//! `--latency <ms>`, `--jitter <ms>` and `--packet-loss <percent>`. Only what
+//! this peer sends is affected, so give both peers the same flags for a
+//! symmetric connection.
+
+use std::{hash::Hash, time::Duration};
+
+use bevy::{prelude::*, utils::Instant};
+use bevy_ggrs::ggrs::{Message, NonBlockingSocket};
+
+use crate::rng::MatchRng;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NetworkConditions {
+    /// Delay added to every packet, in milliseconds
+    pub latency: u32,
+    /// Up to this many milliseconds more or less than `latency`
+    pub jitter: u32,
+    /// Share of packets that never arrive, in percent
+    pub packet_loss: u32,
+}
+
+impl NetworkConditions {
+    /// Reads the conditions from the command line, missing flags default to a perfect network
+    pub fn from_args() -> Self {
+        let arg = |name: &str| {
+            std::env::args()
+                .skip_while(|arg| arg != name)
+                .nth(1)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(0)
+        };
+
+        Self {
+            latency: arg("--latency"),
+            jitter: arg("--jitter"),
+            packet_loss: arg("--packet-loss").min(100),
+        }
+    }
+}
+
+/// Socket passing everything through to `inner` late, out of order, or not at all
+pub struct SimulatedNetwork<S, A> {
+    inner: S,
+    conditions: NetworkConditions,
+    /// Not the match's, just the same cheap generator. Always starting from the
+    /// same seed makes a bad connection reproducible.
+    rng: MatchRng,
+    /// Packets held back, and when to let them go
+    in_flight: Vec<(Instant, A, Message)>,
+}
+
+impl<S, A> SimulatedNetwork<S, A> {
+    pub fn new(inner: S, conditions: NetworkConditions) -> Self {
+        info!("simulating network conditions: {conditions:?}");
+        Self {
+            inner,
+            conditions,
+            rng: MatchRng::default(),
+            in_flight: Vec::new(),
+        }
+    }
+
+    fn delay(&mut self) -> Duration {
+        let jitter = self.conditions.jitter as i64;
+        let offset = if jitter > 0 {
+            self.rng.next_u32() as i64 % (2 * jitter + 1) - jitter
+        } else {
+            0
+        };
+        Duration::from_millis((self.conditions.latency as i64 + offset).max(0) as u64)
+    }
+}
+
+impl<S, A> SimulatedNetwork<S, A>
+where
+    S: NonBlockingSocket<A>,
+    A: Clone + PartialEq + Eq + Hash + Send + Sync,
+{
+    /// Sends everything that has been held back long enough
+    fn deliver(&mut self) {
+        let now = Instant::now();
+        let (due, held): (Vec<_>, Vec<_>) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|(due, ..)| *due <= now);
+        self.in_flight = held;
+
+        for (_, addr, msg) in due {
+            self.inner.send_to(&msg, &addr);
+        }
+    }
+}
+
+impl<S, A> NonBlockingSocket<A> for SimulatedNetwork<S, A>
+where
+    S: NonBlockingSocket<A>,
+    A: Clone + PartialEq + Eq + Hash + Send + Sync,
+{
+    fn send_to(&mut self, msg: &Message, addr: &A) {
+        if !self.rng.chance(self.conditions.packet_loss) {
+            let due = Instant::now() + self.delay();
+            self.in_flight.push((due, addr.clone(), msg.clone()));
+        }
+        self.deliver();
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(A, Message)> {
+        // GGRS polls every frame, which is as good a time as any to send what's due
+        self.deliver();
+        self.inner.receive_all_messages()
+    }
+}
@@ -0,0 +1,171 @@
+//! Live match data for stream overlays.
+//!
+//! Only built with the `overlay` feature, natively. The game answers plain
+//! HTTP on `127.0.0.1:3538` with the state of the match as JSON, which a
+//! browser source in OBS can poll to draw its own scoreboard:
+//!
+//! ```json
+//! { "state": "in_game", "round": 2, "phase": "fighting", "winner": null,
+//!   "players": [{ "handle": 0, "score": 1, "health": 75, "max_health": 100 }] }
+//! ```
+//!
+//! It's whatever is on screen, predictions included, so a hit that gets
+//! rolled back can flicker through for a frame or two.
+
+use std::{
+    io::{Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+use serde::Serialize;
+
+use crate::{
+    components::*,
+    num_players,
+    round::{RoundNumber, RoundPhase, Scores},
+    Config, GameState, MAX_HEALTH,
+};
+
+const OVERLAY_PORT: u16 = 3538;
+/// How long a client gets to send its request before it's answered anyway
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, start_overlay_server).add_systems(
+            Update,
+            update_overlay_feed.run_if(resource_exists::<OverlayFeed>),
+        );
+    }
+}
+
+/// The latest match data, shared with the thread answering requests
+#[derive(Resource, Clone, Default)]
+struct OverlayFeed(Arc<Mutex<String>>);
+
+#[derive(Serialize)]
+struct MatchData {
+    state: &'static str,
+    round: u32,
+    phase: &'static str,
+    winner: Option<usize>,
+    players: Vec<PlayerData>,
+}
+
+#[derive(Serialize)]
+struct PlayerData {
+    handle: usize,
+    score: u32,
+    /// 0 while they're down
+    health: u32,
+    max_health: u32,
+}
+
+fn start_overlay_server(mut commands: Commands) {
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, OVERLAY_PORT)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("can't serve overlay data on port {OVERLAY_PORT}: {err}");
+            return;
+        }
+    };
+
+    let feed = OverlayFeed::default();
+    let latest = feed.0.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let body = latest.lock().unwrap().clone();
+                    respond(stream, &body);
+                }
+                Err(err) => warn!("overlay connection failed: {err}"),
+            }
+        }
+    });
+
+    info!("serving overlay data at http://127.0.0.1:{OVERLAY_PORT}");
+    commands.insert_resource(feed);
+}
+
+/// Whatever was asked for, there's only the one thing to get
+fn respond(mut stream: TcpStream, body: &str) {
+    let mut request = [0; 1024];
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let _ = stream.read(&mut request);
+
+    let response = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: application/json\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Cache-Control: no-store\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if let Err(err) = stream.write_all(response.as_bytes()) {
+        warn!("couldn't send overlay data: {err}");
+    }
+}
+
+fn update_overlay_feed(
+    feed: Res<OverlayFeed>,
+    state: Res<State<GameState>>,
+    session: Option<Res<Session<Config>>>,
+    round: Res<RoundNumber>,
+    phase: Res<RoundPhase>,
+    scores: Res<Scores>,
+    players: Query<(&Player, &Health), Without<Dummy>>,
+) {
+    let in_match = matches!(
+        state.get(),
+        GameState::InGame | GameState::MatchOver | GameState::Replay
+    );
+    let num_players = match &session {
+        Some(session) if in_match => num_players(session),
+        _ => 0,
+    };
+
+    let data = MatchData {
+        state: match state.get() {
+            GameState::AssetLoading | GameState::AssetLoadFailed => "loading",
+            GameState::Matchmaking => "menu",
+            GameState::InGame => "in_game",
+            GameState::MatchOver => "match_over",
+            GameState::Replay => "replay",
+            GameState::Desynced => "desynced",
+        },
+        round: round.0,
+        phase: match *phase {
+            RoundPhase::NewRound | RoundPhase::Countdown { .. } => "countdown",
+            RoundPhase::Fighting => "fighting",
+            RoundPhase::RoundOver { .. } => "round_over",
+            RoundPhase::Intermission { .. } => "intermission",
+            RoundPhase::MatchOver { .. } => "match_over",
+        },
+        winner: match *phase {
+            RoundPhase::MatchOver { winner, .. } => Some(winner),
+            _ => None,
+        },
+        players: (0..num_players)
+            .map(|handle| PlayerData {
+                handle,
+                score: scores.get(handle),
+                health: players
+                    .iter()
+                    .find(|(player, _)| player.handle == handle)
+                    .map_or(0, |(_, health)| health.0),
+                max_health: MAX_HEALTH,
+            })
+            .collect(),
+    };
+
+    *feed.0.lock().unwrap() =
+        serde_json::to_string(&data).expect("serializing to memory can't fail");
+}
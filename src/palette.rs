@@ -0,0 +1,173 @@
+//! Player colors that don't lean on telling red from green, or blue from
+//! yellow.
+//!
+//! The simulation spawns every wizard in [`crate::PLAYER_COLORS`], and the
+//! palette picked in the settings repaints them here, along with their decoys,
+//! so peers on different palettes still simulate the same match. The
+//! colorblind palettes also put a shape beside each wizard's health bar, a
+//! circle for one team and a triangle for the other, which reads without any
+//! color at all.
+
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use serde::{Deserialize, Serialize};
+
+use crate::{components::*, settings::Settings, PLAYER_COLORS};
+
+/// Sized to sit left of the health bar without touching it
+const MARKER_RADIUS: f32 = 0.12;
+/// Same height as the health bar, just off its left end
+const MARKER_POS: Vec3 = Vec3::new(-0.68, 0.7, 0.1);
+const MARKER_COLOR: Color = Color::rgb(0.95, 0.95, 0.95);
+
+/// Blue, orange, sky blue and yellow, from the Okabe-Ito set, so teammates
+/// share a hue and opponents differ in brightness too
+const RED_GREEN_COLORS: [Color; 4] = [
+    Color::rgb(0., 0.45, 0.7),
+    Color::rgb(0.9, 0.62, 0.),
+    Color::rgb(0.34, 0.71, 0.91),
+    Color::rgb(0.94, 0.89, 0.26),
+];
+/// Vermillion, teal, pink and light grey, none of which blue-yellow
+/// colorblindness mixes up
+const BLUE_YELLOW_COLORS: [Color; 4] = [
+    Color::rgb(0.84, 0.37, 0.),
+    Color::rgb(0., 0.6, 0.6),
+    Color::rgb(0.95, 0.6, 0.75),
+    Color::rgb(0.85, 0.85, 0.85),
+];
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_markers).add_systems(
+            Update,
+            (recolor_wizards, add_team_markers, show_team_markers).chain(),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Palette {
+    #[default]
+    Standard,
+    /// For protanopia and deuteranopia
+    RedGreen,
+    /// For tritanopia
+    BlueYellow,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 3] = [Self::Standard, Self::RedGreen, Self::BlueYellow];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Standard => "Standard",
+            Self::RedGreen => "Red-green safe",
+            Self::BlueYellow => "Blue-yellow safe",
+        }
+    }
+
+    /// The player's color, for the wizard and everything that names them
+    pub fn color(self, handle: usize) -> Color {
+        let colors = match self {
+            Self::Standard => &PLAYER_COLORS,
+            Self::RedGreen => &RED_GREEN_COLORS,
+            Self::BlueYellow => &BLUE_YELLOW_COLORS,
+        };
+        colors[handle % colors.len()]
+    }
+
+    /// Only the colorblind palettes show team markers
+    fn marker_visibility(self) -> Visibility {
+        if self == Self::Standard {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        }
+    }
+}
+
+#[derive(Resource)]
+struct MarkerAssets {
+    /// By team
+    meshes: [Handle<Mesh>; 2],
+    material: Handle<ColorMaterial>,
+}
+
+#[derive(Component)]
+struct TeamMarker;
+
+fn setup_markers(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let triangle = Triangle2d::new(
+        Vec2::new(0., MARKER_RADIUS),
+        Vec2::new(-MARKER_RADIUS, -MARKER_RADIUS),
+        Vec2::new(MARKER_RADIUS, -MARKER_RADIUS),
+    );
+    commands.insert_resource(MarkerAssets {
+        meshes: [meshes.add(Circle::new(MARKER_RADIUS)), meshes.add(triangle)],
+        material: materials.add(MARKER_COLOR),
+    });
+}
+
+/// Wizards and decoys spawn in the standard colors, this puts them in the
+/// player's palette as they appear and whenever it changes
+fn recolor_wizards(
+    settings: Res<Settings>,
+    mut wizards: Query<
+        (&mut Sprite, Option<Ref<Player>>, Option<Ref<Decoy>>),
+        Or<(With<Player>, With<Decoy>)>,
+    >,
+) {
+    for (mut sprite, player, decoy) in &mut wizards {
+        let (handle, added) = match (player, decoy) {
+            (Some(player), _) => (player.handle, player.is_added()),
+            (None, Some(decoy)) => (decoy.owner, decoy.is_added()),
+            (None, None) => continue,
+        };
+        if added || settings.is_changed() {
+            sprite.color = settings.palette.color(handle);
+        }
+    }
+}
+
+/// Decoys get one too, or the marker would give them away
+fn add_team_markers(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    assets: Res<MarkerAssets>,
+    wizards: Query<(Entity, &Team), Or<(Added<Player>, Added<Decoy>)>>,
+) {
+    let visibility = settings.palette.marker_visibility();
+    for (wizard, team) in &wizards {
+        commands.entity(wizard).with_children(|parent| {
+            parent.spawn((
+                TeamMarker,
+                MaterialMesh2dBundle {
+                    mesh: assets.meshes[team.0 % assets.meshes.len()].clone().into(),
+                    material: assets.material.clone(),
+                    transform: Transform::from_translation(MARKER_POS),
+                    visibility,
+                    ..default()
+                },
+            ));
+        });
+    }
+}
+
+fn show_team_markers(
+    settings: Res<Settings>,
+    mut markers: Query<&mut Visibility, With<TeamMarker>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let visibility = settings.palette.marker_visibility();
+    for mut marker in &mut markers {
+        marker.set_if_neq(visibility);
+    }
+}
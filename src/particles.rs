@@ -0,0 +1,377 @@
+//! Particles: muzzle flashes, projectile trails, explosions, death poofs and
+//! leaves blowing in the wind.
+//!
+//! Purely visual. Bursts come out of the simulation as [`Burst`] events, which
+//! [`crate::sim_events`] only lets through once their frame is confirmed, so a
+//! rollback never spawns the same burst twice. Trails are left behind by
+//! whatever projectiles are on screen each render frame instead, a projectile
+//! a rollback takes back just stops leaving one. Leaves are blown about by
+//! the round's [`Wind`] the same way.
+//!
+//! Reduced motion leaves out the muzzle flashes and explosion sparks, the
+//! brightest and busiest of them. Trails, smoke and poofs still show where
+//! things are and what happened.
+//!
+//! However busy the arena gets, there are never many more than
+//! [`Settings::max_particles`] at once. Past that the oldest go first, they've
+//! mostly faded out already.
+
+use std::ops::Range;
+
+use bevy::prelude::*;
+
+use crate::{
+    components::*, rng::MatchRng, settings::Settings, sim_events::SimEvents, wind::Wind, GameState,
+    MAP_SIZE,
+};
+
+/// Above the spells, below the explosions
+const BURST_Z: f32 = 2.5;
+/// Under the projectiles leaving them
+const TRAIL_Z: f32 = 1.7;
+/// Seconds between two trail particles from the same projectile
+const TRAIL_INTERVAL: f32 = 0.03;
+/// Over everything, they're blowing past the camera
+const LEAF_Z: f32 = 5.;
+/// Seconds between two leaves, whatever the wind's strength
+const LEAF_INTERVAL: f32 = 0.08;
+/// How much faster than the projectiles' drift leaves fly, so a gentle wind still shows
+const LEAF_SPEED_SCALE: f32 = 4.;
+/// Choices for [`Settings::max_particles`]
+pub const PARTICLE_LIMITS: [u32; 5] = [250, 500, 1000, 2000, 4000];
+
+const MUZZLE_FLASH: ParticleSpec = ParticleSpec {
+    count: 6,
+    color: Color::rgb(2., 2., 1.6),
+    speed: 2.0..5.,
+    spread: 0.6,
+    size: 0.12,
+    lifetime: 0.12..0.2,
+    drag: 8.,
+};
+const EXPLOSION_SPARKS: ParticleSpec = ParticleSpec {
+    count: 32,
+    color: Color::rgb(3., 1.4, 0.3),
+    speed: 2.0..9.,
+    spread: std::f32::consts::PI,
+    size: 0.18,
+    lifetime: 0.3..0.7,
+    drag: 4.,
+};
+const EXPLOSION_SMOKE: ParticleSpec = ParticleSpec {
+    count: 12,
+    color: Color::rgba(0.3, 0.28, 0.26, 0.7),
+    speed: 0.5..2.,
+    spread: std::f32::consts::PI,
+    size: 0.5,
+    lifetime: 0.6..1.,
+    drag: 2.,
+};
+/// Tinted with the player's color when it's spawned
+const DEATH_POOF: ParticleSpec = ParticleSpec {
+    count: 20,
+    color: Color::WHITE,
+    speed: 1.0..4.,
+    spread: std::f32::consts::PI,
+    size: 0.3,
+    lifetime: 0.4..0.8,
+    drag: 5.,
+};
+/// Tinted like the death poof, a smaller puff for a decoy popping
+const DECOY_POP: ParticleSpec = ParticleSpec {
+    count: 10,
+    size: 0.2,
+    lifetime: 0.2..0.4,
+    ..DEATH_POOF
+};
+const BULLET_TRAIL: ParticleSpec = ParticleSpec {
+    count: 1,
+    color: Color::rgba(1.2, 1.2, 1.2, 0.6),
+    speed: 0.0..0.3,
+    spread: std::f32::consts::PI,
+    size: 0.08,
+    lifetime: 0.1..0.15,
+    drag: 0.,
+};
+const CRITICAL_BULLET_TRAIL: ParticleSpec = ParticleSpec {
+    color: Color::rgba(2., 1.2, 0., 0.7),
+    ..BULLET_TRAIL
+};
+/// Sent off along the wind, at a speed that goes with it
+const LEAF: ParticleSpec = ParticleSpec {
+    count: 1,
+    color: Color::rgba(0.45, 0.6, 0.2, 0.8),
+    speed: 0.0..0.,
+    spread: 0.3,
+    size: 0.15,
+    lifetime: 1.5..2.5,
+    drag: 0.,
+};
+const FIREBALL_TRAIL: ParticleSpec = ParticleSpec {
+    count: 2,
+    color: Color::rgba(2.5, 0.8, 0.2, 0.8),
+    speed: 0.2..1.,
+    spread: std::f32::consts::PI,
+    size: 0.2,
+    lifetime: 0.2..0.35,
+    drag: 1.,
+};
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParticleRng>()
+            .add_systems(
+                Update,
+                (spawn_bursts, leave_trails, blow_leaves, update_particles)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), despawn_particles);
+    }
+}
+
+/// Something worth a burst of particles that happened in the simulation
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub enum Burst {
+    /// A bullet leaving its wizard, heading in `dir`
+    Muzzle {
+        pos: Vec2,
+        dir: Vec2,
+    },
+    Explosion {
+        pos: Vec2,
+    },
+    /// Wizard `handle` going down, in their player color
+    Death {
+        pos: Vec2,
+        handle: usize,
+    },
+    /// A decoy of wizard `handle` popping
+    Decoy {
+        pos: Vec2,
+        handle: usize,
+    },
+}
+
+/// What simulation systems send their bursts to
+pub type BurstQueue = SimEvents<Burst>;
+
+/// Randomness for how particles fly. Has nothing to do with the simulation's
+/// [`MatchRng`] and mustn't ever touch it, peers see different particles.
+#[derive(Resource, Default)]
+struct ParticleRng(MatchRng);
+
+impl ParticleRng {
+    fn range(&mut self, range: Range<f32>) -> f32 {
+        let t = self.0.next_u32() as f32 / u32::MAX as f32;
+        range.start + (range.end - range.start) * t
+    }
+}
+
+/// How a group of particles looks and moves
+struct ParticleSpec {
+    count: usize,
+    color: Color,
+    speed: Range<f32>,
+    /// Radians either side of the direction particles are sent in
+    spread: f32,
+    size: f32,
+    lifetime: Range<f32>,
+    /// Fraction of its speed a particle loses per second
+    drag: f32,
+}
+
+#[derive(Component)]
+struct Particle {
+    velocity: Vec2,
+    drag: f32,
+    size: f32,
+    /// Alpha it started with, it fades out from there
+    alpha: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+fn emit(
+    commands: &mut Commands,
+    rng: &mut ParticleRng,
+    spec: &ParticleSpec,
+    pos: Vec2,
+    z: f32,
+    dir: Vec2,
+) {
+    let angle = dir.y.atan2(dir.x);
+    for _ in 0..spec.count {
+        let angle = angle + rng.range(-spec.spread..spec.spread);
+        let velocity = Vec2::from_angle(angle) * rng.range(spec.speed.clone());
+        commands.spawn((
+            Particle {
+                velocity,
+                drag: spec.drag,
+                size: spec.size,
+                alpha: spec.color.a(),
+                age: 0.,
+                lifetime: rng.range(spec.lifetime.clone()),
+            },
+            SpriteBundle {
+                sprite: Sprite {
+                    color: spec.color,
+                    custom_size: Some(Vec2::splat(spec.size)),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos.extend(z)),
+                ..default()
+            },
+        ));
+    }
+}
+
+fn spawn_bursts(
+    mut commands: Commands,
+    mut rng: ResMut<ParticleRng>,
+    settings: Res<Settings>,
+    mut bursts: EventReader<Burst>,
+) {
+    for burst in bursts.read() {
+        match *burst {
+            Burst::Muzzle { .. } if settings.reduced_motion => {}
+            Burst::Muzzle { pos, dir } => {
+                emit(&mut commands, &mut rng, &MUZZLE_FLASH, pos, BURST_Z, dir);
+            }
+            Burst::Explosion { pos } => {
+                let specs: &[&ParticleSpec] = if settings.reduced_motion {
+                    &[&EXPLOSION_SMOKE]
+                } else {
+                    &[&EXPLOSION_SMOKE, &EXPLOSION_SPARKS]
+                };
+                for spec in specs {
+                    emit(&mut commands, &mut rng, spec, pos, BURST_Z, Vec2::X);
+                }
+            }
+            Burst::Death { pos, handle } => {
+                let spec = ParticleSpec {
+                    color: settings.palette.color(handle),
+                    ..DEATH_POOF
+                };
+                emit(&mut commands, &mut rng, &spec, pos, BURST_Z, Vec2::X);
+            }
+            Burst::Decoy { pos, handle } => {
+                let spec = ParticleSpec {
+                    color: settings.palette.color(handle),
+                    ..DECOY_POP
+                };
+                emit(&mut commands, &mut rng, &spec, pos, BURST_Z, Vec2::X);
+            }
+        }
+    }
+}
+
+fn leave_trails(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rng: ResMut<ParticleRng>,
+    bullets: Query<(&GlobalTransform, Has<Critical>), With<Bullet>>,
+    fireballs: Query<&GlobalTransform, With<Fireball>>,
+    mut since_last: Local<f32>,
+) {
+    *since_last += time.delta_seconds();
+    if *since_last < TRAIL_INTERVAL {
+        return;
+    }
+    *since_last = 0.;
+
+    for (transform, critical) in &bullets {
+        let spec = if critical {
+            &CRITICAL_BULLET_TRAIL
+        } else {
+            &BULLET_TRAIL
+        };
+        let pos = transform.translation().xy();
+        emit(&mut commands, &mut rng, spec, pos, TRAIL_Z, Vec2::X);
+    }
+    for transform in &fireballs {
+        let pos = transform.translation().xy();
+        emit(
+            &mut commands,
+            &mut rng,
+            &FIREBALL_TRAIL,
+            pos,
+            TRAIL_Z,
+            Vec2::X,
+        );
+    }
+}
+
+fn blow_leaves(
+    mut commands: Commands,
+    time: Res<Time>,
+    wind: Res<Wind>,
+    mut rng: ResMut<ParticleRng>,
+    mut since_last: Local<f32>,
+) {
+    let wind = wind.0.to_vec2();
+    if wind == Vec2::ZERO {
+        return;
+    }
+    *since_last += time.delta_seconds();
+    if *since_last < LEAF_INTERVAL {
+        return;
+    }
+    *since_last = 0.;
+
+    // the wind is per simulation frame, leaves move per second
+    let speed = wind.length() * 60. * LEAF_SPEED_SCALE;
+    let spec = ParticleSpec {
+        speed: speed * 0.8..speed * 1.2,
+        ..LEAF
+    };
+    let half = MAP_SIZE as f32 / 2.;
+    let pos = Vec2::new(rng.range(-half..half), rng.range(-half..half));
+    emit(&mut commands, &mut rng, &spec, pos, LEAF_Z, wind);
+}
+
+/// Ages particles and clears out the ones past their lifetime or the limit.
+/// Whatever was emitted this frame only counts towards the limit from the
+/// next, so it can be over by a frame's worth.
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let dt = time.delta_seconds();
+    let mut live = Vec::new();
+    for (entity, mut particle, mut transform, mut sprite) in &mut particles {
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        live.push((particle.age, entity));
+
+        let velocity = particle.velocity;
+        transform.translation += (velocity * dt).extend(0.);
+        particle.velocity = velocity * (1. - particle.drag * dt).max(0.);
+
+        // shrink and fade out over their lifetime
+        let left = 1. - particle.age / particle.lifetime;
+        sprite.custom_size = Some(Vec2::splat(particle.size * left));
+        sprite.color.set_a(particle.alpha * left);
+    }
+
+    let max = settings.max_particles as usize;
+    if live.len() > max {
+        // oldest first
+        live.sort_unstable_by(|(a, _), (b, _)| b.total_cmp(a));
+        for (_, entity) in &live[..live.len() - max] {
+            commands.entity(*entity).despawn();
+        }
+    }
+}
+
+fn despawn_particles(mut commands: Commands, particles: Query<Entity, With<Particle>>) {
+    for entity in &particles {
+        commands.entity(entity).despawn();
+    }
+}
@@ -0,0 +1,179 @@
+//! Messages between peers that aren't inputs.
+//!
+//! The socket has three channels: an unreliable one that GGRS gets to itself,
+//! a reliable, ordered one for everything else (the ping handshake, rematch
+//! votes, ...), so none of it has to be squeezed into the input byte, and
+//! another reliable one for [`crate::chat`].
+//! These messages aren't tied to a simulation frame, nothing that gets rolled
+//! back may depend on them.
+
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+use bevy_matchbox::{
+    matchbox_socket::{
+        ChannelConfig, MultipleChannels, PeerId, PeerState, WebRtcChannel, WebRtcSocketBuilder,
+    },
+    MatchboxSocket,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    coach::CoachedPlayers, components::Spell, match_setup::MatchSettings, quick_chat::Emote,
+    rejoin::Rejoin, signaling::IceServers, Config, GameState,
+};
+
+/// Handed to GGRS once the match starts
+pub const GGRS_CHANNEL: usize = 0;
+pub const RELIABLE_CHANNEL: usize = 1;
+pub const CHAT_CHANNEL: usize = 2;
+
+pub struct PeerMessagesPlugin;
+
+impl Plugin for PeerMessagesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PeerMessageReceived>()
+            .add_event::<PeerDisconnected>()
+            .add_systems(
+                PreUpdate,
+                receive_peer_messages
+                    .run_if(not(in_state(GameState::Matchmaking)))
+                    .run_if(resource_exists::<MatchboxSocket<MultipleChannels>>),
+            );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PeerMessage {
+    /// Handshake round trip measurement, answered with a `Pong`
+    Ping(u32),
+    Pong(u32),
+    /// The sender has measured enough and is about to start GGRS
+    HandshakeDone,
+    /// The sender is the coach of a coached room, see [`crate::coach`]
+    Coaching,
+    /// The sender clicked "Ready" in the lobby, see [`crate::ready`]
+    Ready,
+    /// The sender's ban or pick for their turn of the spell draft
+    DraftChoice(Spell),
+    /// What the sender would start the match with, or to a coach, what the
+    /// players agreed on, see [`crate::match_setup`]
+    MatchSettings(MatchSettings),
+    /// The sender clicked "Rematch" on the end screen
+    WantsRematch,
+    /// The sender went back to the menu, there won't be a rematch
+    LeftMatch,
+    /// From the sender's quick chat wheel, see [`crate::quick_chat`]
+    Emote(Emote),
+    /// The sender pinged this spot on the map
+    MapPing(Vec2),
+    /// The sender's browser tab went into the background, or came back, see
+    /// [`crate::background`]
+    TabHidden(bool),
+}
+
+impl PeerMessage {
+    pub fn send(self, channel: &mut WebRtcChannel, peer: PeerId) {
+        let bytes = bincode::serialize(&self).expect("serializing to memory can't fail");
+        channel.send(bytes.into_boxed_slice(), peer);
+    }
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PeerMessageReceived {
+    pub peer: PeerId,
+    pub message: PeerMessage,
+}
+
+/// The signaling server says `peer` is gone, which usually beats GGRS noticing
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PeerDisconnected {
+    pub peer: PeerId,
+}
+
+/// Socket with the game, reliable and chat channels, in that order
+pub fn new_socket(room_url: &str, ice: &IceServers) -> MatchboxSocket<MultipleChannels> {
+    WebRtcSocketBuilder::new(room_url)
+        .ice_server(ice.0.clone())
+        .add_channel(ChannelConfig::unreliable())
+        .add_channel(ChannelConfig::reliable())
+        .add_channel(ChannelConfig::reliable())
+        .into()
+}
+
+/// Every message waiting on the reliable channel
+pub fn receive(channel: &mut WebRtcChannel) -> Vec<(PeerId, PeerMessage)> {
+    channel
+        .receive()
+        .into_iter()
+        .filter_map(|(peer, bytes)| match bincode::deserialize(&bytes) {
+            Ok(message) => Some((peer, message)),
+            Err(err) => {
+                warn!("dropping malformed message from {peer}: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Sends a message to everyone we're connected to
+pub fn send_to_peers(socket: &mut MatchboxSocket<MultipleChannels>, message: PeerMessage) {
+    let peers: Vec<_> = socket.connected_peers().collect();
+    let Ok(channel) = socket.get_channel(RELIABLE_CHANNEL) else {
+        return;
+    };
+    for peer in peers {
+        message.send(channel, peer);
+    }
+}
+
+/// Which player `peer` is, none for the coach. Only the coach tells the players
+/// apart by their peer, GGRS knows which one is the opponent.
+pub fn player_handle(
+    session: &Session<Config>,
+    coached: Option<&CoachedPlayers>,
+    peer: PeerId,
+) -> Option<usize> {
+    match session {
+        // the host also has the coach down as a spectator, past the players' handles
+        Session::P2P(session) => session
+            .handles_by_address(peer)
+            .into_iter()
+            .find(|handle| *handle < session.num_players()),
+        Session::Spectator(_) => coached.and_then(|coached| coached.handle(peer)),
+        Session::SyncTest(_) => None,
+    }
+}
+
+/// Once the match is underway, turns incoming messages into events.
+/// While matchmaking the handshake reads the channel itself.
+fn receive_peer_messages(
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    rejoin: Option<Res<Rejoin>>,
+    mut events: EventWriter<PeerMessageReceived>,
+    mut disconnects: EventWriter<PeerDisconnected>,
+) {
+    // after a rejoin the opponent still goes by the peer the session knows
+    let original = |peer| rejoin.as_ref().map_or(peer, |rejoin| rejoin.original(peer));
+
+    // losing the signaling server doesn't matter anymore, the peers are connected
+    for (peer, state) in socket.try_update_peers().unwrap_or_default() {
+        if state == PeerState::Disconnected {
+            disconnects.send(PeerDisconnected {
+                peer: original(peer),
+            });
+        }
+    }
+    let Ok(channel) = socket.get_channel(RELIABLE_CHANNEL) else {
+        return;
+    };
+    for (peer, message) in receive(channel) {
+        // the opponent may still be measuring the ping after we've started
+        if let PeerMessage::Ping(seq) = message {
+            PeerMessage::Pong(seq).send(channel, peer);
+        }
+        events.send(PeerMessageReceived {
+            peer: original(peer),
+            message,
+        });
+    }
+}
@@ -0,0 +1,460 @@
+//! Private rooms on the matchbox server, to play a friend rather than
+//! whoever turns up.
+//!
+//! Creating one comes up with a short code like `FIRE-TOAD-42`, which is all
+//! the friend has to type into the matchmaking screen to end up in the same
+//! room. The code is the room's name on the server, there's nothing to look
+//! up. Private matches are casual ones without a coach, and the room is kept
+//! for the next match until it's left.
+
+use std::fmt;
+
+use bevy::{input::keyboard::KeyboardInput, prelude::*};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    connect_to_matchbox,
+    matchmaking::{room, CoachSlot, Ranked},
+    signaling::MatchboxUrl,
+    wants_matchmaking, GameState, TextInputFocus,
+};
+
+const FIRST_WORDS: [&str; 16] = [
+    "FIRE", "FROST", "STORM", "STONE", "SHADOW", "SPARK", "MANA", "EMBER", "MOON", "SUN", "MIST",
+    "THORN", "VOID", "RUNE", "ASH", "GALE",
+];
+const SECOND_WORDS: [&str; 16] = [
+    "TOAD", "NEWT", "OWL", "RAVEN", "WOLF", "BAT", "CAT", "WYRM", "IMP", "GOLEM", "SPRITE", "BEAR",
+    "HARE", "CROW", "SNAKE", "FOX",
+];
+/// The number at the end is two digits, from 10 up
+const NUMBERS: u8 = 90;
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const FIELD_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+const FIELD_FOCUSED_COLOR: Color = Color::rgb(0.2, 0.35, 0.6);
+
+pub struct PrivateRoomPlugin;
+
+impl Plugin for PrivateRoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PrivateRoom>()
+            .init_resource::<CodeEntry>()
+            .add_systems(
+                OnEnter(GameState::Matchmaking),
+                spawn_private_room_panel.run_if(wants_matchmaking),
+            )
+            .add_systems(OnExit(GameState::Matchmaking), despawn_private_room_panel)
+            .add_systems(
+                Update,
+                (private_room_buttons, type_code, update_private_room_status)
+                    .chain()
+                    .run_if(in_state(GameState::Matchmaking)),
+            );
+    }
+}
+
+/// A private room's name, as indices into the word lists
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomCode {
+    first: u8,
+    second: u8,
+    number: u8,
+}
+
+impl RoomCode {
+    fn random() -> Self {
+        let random = random_u64();
+        Self {
+            first: (random % FIRST_WORDS.len() as u64) as u8,
+            second: (random / 16 % SECOND_WORDS.len() as u64) as u8,
+            number: (random / 256 % NUMBERS as u64) as u8,
+        }
+    }
+
+    /// Reads a code the way people are likely to type it, in any case and
+    /// with spaces for dashes
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_ascii_uppercase().replace(' ', "-");
+        let mut parts = text.split('-').filter(|part| !part.is_empty());
+        let first = FIRST_WORDS
+            .iter()
+            .position(|word| Some(*word) == parts.next())?;
+        let second = SECOND_WORDS
+            .iter()
+            .position(|word| Some(*word) == parts.next())?;
+        let number: u8 = parts.next()?.parse().ok()?;
+        if parts.next().is_some() || !(10..10 + NUMBERS).contains(&number) {
+            return None;
+        }
+        Some(Self {
+            first: first as u8,
+            second: second as u8,
+            number: number - 10,
+        })
+    }
+}
+
+impl fmt::Display for RoomCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}-{}",
+            FIRST_WORDS[self.first as usize % FIRST_WORDS.len()],
+            SECOND_WORDS[self.second as usize % SECOND_WORDS.len()],
+            self.number as u32 + 10
+        )
+    }
+}
+
+/// Doesn't need to be good, only different between two players creating a room
+#[cfg(not(target_arch = "wasm32"))]
+fn random_u64() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos()),
+    );
+    hasher.finish()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn random_u64() -> u64 {
+    (js_sys::Math::random() * u64::MAX as f64) as u64
+}
+
+/// The private room we're in, if any, instead of the public ones
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct PrivateRoom(pub Option<RoomCode>);
+
+/// What's been typed into the code field
+#[derive(Resource, Default)]
+struct CodeEntry {
+    text: String,
+    focused: bool,
+}
+
+#[derive(Component)]
+struct PrivateRoomPanel;
+
+#[derive(Component)]
+struct CodeField;
+
+#[derive(Component)]
+struct CodeText;
+
+#[derive(Component)]
+struct PrivateRoomStatusText;
+
+#[derive(Component, Clone, Copy)]
+enum PrivateRoomButton {
+    Join,
+    Create,
+    Leave,
+}
+
+fn spawn_private_room_panel(mut commands: Commands, mut entry: ResMut<CodeEntry>) {
+    entry.focused = false;
+
+    commands
+        .spawn((
+            PrivateRoomPanel,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(24.),
+                    width: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Private room:",
+                        TextStyle {
+                            font_size: 22.,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                            ..default()
+                        },
+                    ));
+                    parent
+                        .spawn((
+                            CodeField,
+                            ButtonBundle {
+                                style: Style {
+                                    min_width: Val::Px(200.),
+                                    padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                                    ..default()
+                                },
+                                background_color: FIELD_COLOR.into(),
+                                ..default()
+                            },
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                CodeText,
+                                TextBundle::from_section(
+                                    code_label(&entry),
+                                    TextStyle {
+                                        font_size: 22.,
+                                        ..default()
+                                    },
+                                ),
+                            ));
+                        });
+                    for (button, label) in [
+                        (PrivateRoomButton::Join, "Join"),
+                        (PrivateRoomButton::Create, "Create"),
+                        (PrivateRoomButton::Leave, "Leave"),
+                    ] {
+                        parent
+                            .spawn((
+                                button,
+                                ButtonBundle {
+                                    style: Style {
+                                        padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                                        ..default()
+                                    },
+                                    background_color: BUTTON_COLOR.into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    label,
+                                    TextStyle {
+                                        font_size: 22.,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    }
+                });
+            parent.spawn((
+                PrivateRoomStatusText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.,
+                        color: Color::rgb(0.8, 0.8, 0.8),
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
+
+fn despawn_private_room_panel(
+    mut commands: Commands,
+    panels: Query<Entity, With<PrivateRoomPanel>>,
+    mut entry: ResMut<CodeEntry>,
+    mut focus: ResMut<TextInputFocus>,
+) {
+    for panel in &panels {
+        commands.entity(panel).despawn_recursive();
+    }
+    entry.focused = false;
+    focus.0 = false;
+}
+
+fn code_label(entry: &CodeEntry) -> String {
+    match (entry.text.is_empty(), entry.focused) {
+        (true, false) => "FIRE-TOAD-42".into(),
+        (_, true) => format!("{}_", entry.text),
+        (false, false) => entry.text.clone(),
+    }
+}
+
+/// Moves over to the room `private` says, and looks for a match there if
+/// we're connected to the matchbox server at all
+fn switch_room(
+    commands: &mut Commands,
+    server: &MatchboxUrl,
+    connected: bool,
+    ranked: Ranked,
+    coach: CoachSlot,
+    private: &mut PrivateRoom,
+    code: Option<RoomCode>,
+) {
+    if private.0 == code {
+        return;
+    }
+    private.0 = code;
+    match code {
+        Some(code) => info!("moving to private room {code}"),
+        None => info!("leaving the private room"),
+    }
+    // otherwise it's still coming back on its own, and will pick the right room
+    if connected {
+        connect_to_matchbox(commands, server, room(ranked, coach, *private));
+    }
+}
+
+fn private_room_buttons(
+    mut commands: Commands,
+    server: Res<MatchboxUrl>,
+    socket: Option<Res<MatchboxSocket<MultipleChannels>>>,
+    ranked: Res<Ranked>,
+    coach: Res<CoachSlot>,
+    fields: Query<&Interaction, (Changed<Interaction>, With<CodeField>)>,
+    mut buttons: Query<
+        (&Interaction, &PrivateRoomButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut entry: ResMut<CodeEntry>,
+    mut private: ResMut<PrivateRoom>,
+) {
+    if fields
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        entry.focused = true;
+    }
+
+    for (interaction, button, mut background) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => {
+                entry.focused = false;
+                let code = match button {
+                    PrivateRoomButton::Join => match RoomCode::parse(&entry.text) {
+                        Some(code) => Some(code),
+                        None => {
+                            warn!("\"{}\" isn't a room code", entry.text);
+                            continue;
+                        }
+                    },
+                    PrivateRoomButton::Create => Some(RoomCode::random()),
+                    PrivateRoomButton::Leave => None,
+                };
+                switch_room(
+                    &mut commands,
+                    &server,
+                    socket.is_some(),
+                    *ranked,
+                    *coach,
+                    &mut private,
+                    code,
+                );
+            }
+            Interaction::Hovered => background.0 = BUTTON_HOVER_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+}
+
+fn type_code(
+    mut commands: Commands,
+    server: Res<MatchboxUrl>,
+    socket: Option<Res<MatchboxSocket<MultipleChannels>>>,
+    ranked: Res<Ranked>,
+    coach: Res<CoachSlot>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut keyboard: EventReader<KeyboardInput>,
+    mut entry: ResMut<CodeEntry>,
+    mut private: ResMut<PrivateRoom>,
+    mut focus: ResMut<TextInputFocus>,
+    mut fields: Query<&mut BackgroundColor, With<CodeField>>,
+    mut texts: Query<&mut Text, With<CodeText>>,
+) {
+    if entry.focused {
+        for event in characters.read() {
+            entry.text.extend(
+                event
+                    .char
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == ' ')
+                    .map(|c| c.to_ascii_uppercase()),
+            );
+        }
+        for event in keyboard.read() {
+            if !event.state.is_pressed() {
+                continue;
+            }
+            match event.key_code {
+                KeyCode::Backspace => {
+                    entry.text.pop();
+                }
+                KeyCode::Escape => entry.focused = false,
+                KeyCode::Enter => {
+                    entry.focused = false;
+                    match RoomCode::parse(&entry.text) {
+                        Some(code) => switch_room(
+                            &mut commands,
+                            &server,
+                            socket.is_some(),
+                            *ranked,
+                            *coach,
+                            &mut private,
+                            Some(code),
+                        ),
+                        None => warn!("\"{}\" isn't a room code", entry.text),
+                    }
+                }
+                _ => {}
+            }
+        }
+    } else {
+        characters.clear();
+        keyboard.clear();
+    }
+
+    if !entry.is_changed() {
+        return;
+    }
+    // the chat box has the keyboard the rest of the time
+    focus.0 = entry.focused;
+    for mut background in &mut fields {
+        background.0 = if entry.focused {
+            FIELD_FOCUSED_COLOR
+        } else {
+            FIELD_COLOR
+        };
+    }
+    for mut text in &mut texts {
+        text.sections[0].value = code_label(&entry);
+    }
+}
+
+fn update_private_room_status(
+    private: Res<PrivateRoom>,
+    entry: Res<CodeEntry>,
+    mut statuses: Query<&mut Text, With<PrivateRoomStatusText>>,
+) {
+    let status = match private.0 {
+        Some(code) => format!("In private room {code}, give your friend the code to join you"),
+        None if !entry.text.is_empty()
+            && !entry.focused
+            && RoomCode::parse(&entry.text).is_none() =>
+        {
+            format!(
+                "\"{}\" isn't a room code, they look like FIRE-TOAD-42",
+                entry.text
+            )
+        }
+        None => "Create a room to play a friend, or type in the code they gave you".into(),
+    };
+    for mut text in &mut statuses {
+        if text.sections[0].value != status {
+            text.sections[0].value.clone_from(&status);
+        }
+    }
+}
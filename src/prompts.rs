@@ -0,0 +1,123 @@
+//! Naming the controls of whatever the player is holding.
+//!
+//! Prompts say which key, gamepad button or on-screen control does what, for
+//! the device the player last touched, and switch over the moment they pick up
+//! another one. Gamepads follow the Xbox layout, it's what most of them copy.
+
+use bevy::{input::InputSystem, prelude::*};
+
+use crate::touch::TouchUi;
+
+/// A stick pushed this far counts as picking up the gamepad, resting thumbs
+/// and drifting sticks don't
+const STICK_THRESHOLD: f32 = 0.5;
+
+pub struct PromptsPlugin;
+
+impl Plugin for PromptsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputDevice>()
+            .add_systems(Startup, start_on_touch)
+            .add_systems(PreUpdate, detect_input_device.after(InputSystem));
+    }
+}
+
+/// What the player last played with
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputDevice {
+    #[default]
+    Keyboard,
+    Gamepad,
+    Touch,
+}
+
+/// Something the prompts tell the player how to do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    Move,
+    Fire,
+    Cast,
+    /// Reset the training range
+    Reset,
+    /// Back to the menu, from the training range or a dead match
+    Leave,
+}
+
+impl InputDevice {
+    /// The key or button, short enough for a button label. None on a touch
+    /// screen, the controls are labelled themselves there.
+    pub fn key(self, control: Control) -> Option<&'static str> {
+        let key = match (self, control) {
+            (Self::Touch, _) => return None,
+            (Self::Keyboard, Control::Move) => "WASD",
+            (Self::Keyboard, Control::Fire) => "Space",
+            (Self::Keyboard, Control::Cast) => "E",
+            (Self::Keyboard, Control::Reset) => "R",
+            (Self::Keyboard, Control::Leave) => "Esc",
+            (Self::Gamepad, Control::Move) => "Left stick",
+            (Self::Gamepad, Control::Fire) => "A",
+            (Self::Gamepad, Control::Cast) => "X",
+            (Self::Gamepad, Control::Reset) => "Select",
+            (Self::Gamepad, Control::Leave) => "Start",
+        };
+        Some(key)
+    }
+
+    /// How to refer to the control in a sentence, after "with"
+    pub fn glyph(self, control: Control) -> String {
+        match (self, control) {
+            (Self::Keyboard, Control::Move) => "WASD or the arrow keys".into(),
+            (Self::Gamepad, Control::Move) => "the left stick".into(),
+            (Self::Touch, Control::Move) => "the stick on the left".into(),
+            (Self::Touch, Control::Fire) => "the Fire button".into(),
+            (Self::Touch, Control::Cast) => "the Cast button".into(),
+            (Self::Touch, Control::Reset) => "the Reset button".into(),
+            (Self::Touch, Control::Leave) => "the Leave button".into(),
+            (device, control) => device.key(control).unwrap_or_default().into(),
+        }
+    }
+
+    /// "Reset (R)" on a keyboard, just "Reset" on a touch screen
+    pub fn label(self, text: &str, control: Control) -> String {
+        match self.key(control) {
+            Some(key) => format!("{text} ({key})"),
+            None => text.into(),
+        }
+    }
+}
+
+fn start_on_touch(touch_ui: Res<TouchUi>, mut device: ResMut<InputDevice>) {
+    if touch_ui.0 {
+        *device = InputDevice::Touch;
+    }
+}
+
+fn detect_input_device(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    touches: Res<Touches>,
+    mut device: ResMut<InputDevice>,
+) {
+    let stick_pushed = gamepads.iter().any(|gamepad| {
+        [GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY]
+            .into_iter()
+            .filter_map(|axis| axes.get(GamepadAxis::new(gamepad, axis)))
+            .any(|value| value.abs() > STICK_THRESHOLD)
+    });
+
+    // not clicks, browsers make one up for every tap
+    let used = if touches.any_just_pressed() {
+        Some(InputDevice::Touch)
+    } else if gamepad_buttons.get_just_pressed().next().is_some() || stick_pushed {
+        Some(InputDevice::Gamepad)
+    } else if keys.get_just_pressed().next().is_some() {
+        Some(InputDevice::Keyboard)
+    } else {
+        None
+    };
+    if let Some(used) = used {
+        device.set_if_neq(used);
+    }
+}
@@ -0,0 +1,154 @@
+//! Decorations around the arena (torches, banners, water), read from the
+//! map file `assets/arena.map`. Purely visual, the simulation never sees them.
+//! The map also places barrels and crates, which it does see, those are
+//! spawned by [`crate::barrels`].
+//!
+//! The map is JSON listing each prop's position and the frames of
+//! Dungeon_Objects.png it cycles through, counting tiles left to right, top
+//! to bottom:
+//!
+//! ```json
+//! { "props": [{ "pos": [-21, 6], "frames": [6, 7], "frame_time": 0.2 }] }
+//! ```
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{
+    animation::SpriteAnimation,
+    assets::ArenaAssets,
+    graphics::{YSort, YSORT_Z},
+    GameState,
+};
+
+pub struct PropsPlugin;
+
+impl Plugin for PropsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ArenaMap>()
+            .register_asset_loader(ArenaMapLoader)
+            .add_systems(
+                OnExit(GameState::AssetLoading),
+                // not when loading failed
+                spawn_props.run_if(resource_exists::<ArenaAssets>),
+            );
+    }
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct ArenaMap {
+    #[serde(default)]
+    props: Vec<PropDef>,
+    #[serde(default)]
+    pub barrels: Vec<PlacedDef>,
+    #[serde(default)]
+    pub crates: Vec<PlacedDef>,
+}
+
+/// Something the map only gives a position for
+#[derive(Deserialize, Debug)]
+pub struct PlacedDef {
+    pub pos: [f32; 2],
+}
+
+#[derive(Deserialize, Debug)]
+struct PropDef {
+    pos: [f32; 2],
+    /// Sheet tiles to cycle through, a single one for a still prop
+    frames: Vec<usize>,
+    #[serde(default = "default_frame_time")]
+    frame_time: f32,
+    /// In world units, which is one tile
+    #[serde(default = "default_size")]
+    size: f32,
+}
+
+fn default_frame_time() -> f32 {
+    0.2
+}
+
+fn default_size() -> f32 {
+    1.
+}
+
+#[derive(Default)]
+struct ArenaMapLoader;
+
+#[derive(Debug)]
+pub enum ArenaMapError {
+    Io(std::io::Error),
+    Invalid(serde_json::Error),
+}
+
+impl std::fmt::Display for ArenaMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ArenaMapError::Io(err) => write!(f, "failed to read map: {err}"),
+            ArenaMapError::Invalid(err) => write!(f, "invalid map: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ArenaMapError {}
+
+impl AssetLoader for ArenaMapLoader {
+    type Asset = ArenaMap;
+    type Settings = ();
+    type Error = ArenaMapError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<ArenaMap, ArenaMapError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(ArenaMapError::Io)?;
+            serde_json::from_slice(&bytes).map_err(ArenaMapError::Invalid)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map"]
+    }
+}
+
+fn spawn_props(mut commands: Commands, arena: Res<ArenaAssets>, maps: Res<Assets<ArenaMap>>) {
+    let Some(map) = maps.get(&arena.map) else {
+        warn!("arena map isn't loaded, no props");
+        return;
+    };
+
+    for (i, prop) in map.props.iter().enumerate() {
+        let pos = Vec2::from(prop.pos);
+        let animation = SpriteAnimation::new(prop.frames.clone(), prop.frame_time, true)
+            .with_offset(i as f32 * prop.frame_time / 2.);
+        commands.spawn((
+            YSort {
+                offset: prop.size / 2.,
+            },
+            SpriteSheetBundle {
+                texture: arena.tiles.clone(),
+                atlas: TextureAtlas {
+                    layout: arena.tile_layout.clone(),
+                    index: animation.first_frame(),
+                },
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(prop.size)),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos.extend(YSORT_Z)),
+                ..default()
+            },
+            animation,
+        ));
+    }
+}
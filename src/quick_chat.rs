@@ -0,0 +1,528 @@
+//! Quick chat and map pings, for when there's no time to type.
+//!
+//! Holding C brings up a wheel of canned messages around the cursor, letting
+//! go sends whichever one the cursor has moved towards, and it pops up over
+//! the sender's wizard for a couple of seconds. G, or the middle mouse button,
+//! drops a ping on the map under the cursor instead. Both go over the reliable
+//! channel like any other [`PeerMessage`], and are muted along with the chat.
+//!
+//! A coach has no wizard to emote over, but can ping the map for the player
+//! they're watching, in their own color.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_ggrs::{LocalPlayers, Session};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battery::cursor_in_viewport,
+    bot::Bots,
+    coach::{CoachView, CoachedPlayers},
+    components::*,
+    not_typing,
+    palette::Palette,
+    peer_messages::{
+        player_handle, send_to_peers, PeerMessage, PeerMessageReceived, RELIABLE_CHANNEL,
+    },
+    place_in_arena,
+    settings::Settings,
+    Config, GameState,
+};
+
+/// Between two quick messages of ours, so nobody gets buried in them
+const COOLDOWN_SECS: f32 = 1.;
+const EMOTE_SECS: f32 = 2.;
+const PING_SECS: f32 = 3.;
+/// Pixels from the middle of the wheel to its options
+const WHEEL_RADIUS: f32 = 80.;
+/// The cursor has to move this far from the middle to pick anything
+const WHEEL_DEAD_ZONE: f32 = 25.;
+/// Above the wizard's head, and over the damage numbers
+const EMOTE_OFFSET: Vec2 = Vec2::new(0., 1.1);
+const EMOTE_Z: f32 = 11.;
+const EMOTE_SCALE: f32 = 0.016;
+const PING_Z: f32 = 9.;
+const PING_SIZE: f32 = 0.5;
+/// How far the ping's outline grows past the marker and fades out, over and over
+const PING_RIPPLE: f32 = 2.;
+const PING_RIPPLES_PER_SEC: f32 = 1.5;
+const COACH_COLOR: Color = Color::GOLD;
+
+const WHEEL_COLOR: Color = Color::rgba(0., 0., 0., 0.6);
+const WHEEL_SELECTED_COLOR: Color = Color::rgba(0.3, 0.3, 0.3, 0.9);
+
+pub struct QuickChatPlugin;
+
+impl Plugin for QuickChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<QuickChatCooldown>()
+            .add_systems(OnExit(GameState::InGame), despawn_quick_chat)
+            .add_systems(
+                Update,
+                (
+                    (emote_wheel, ping_map)
+                        .run_if(not_typing)
+                        .run_if(resource_exists::<MatchboxSocket<MultipleChannels>>),
+                    receive_quick_chat,
+                    (follow_emotes, pulse_pings),
+                )
+                    .chain()
+                    .after(place_in_arena)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// The canned messages on the wheel
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emote {
+    GoodGame,
+    NiceShot,
+    Alert,
+    Question,
+}
+
+impl Emote {
+    /// Clockwise from the top, as they sit on the wheel
+    const WHEEL: [Emote; 4] = [
+        Emote::GoodGame,
+        Emote::NiceShot,
+        Emote::Alert,
+        Emote::Question,
+    ];
+
+    fn text(self) -> &'static str {
+        match self {
+            Emote::GoodGame => "GG",
+            Emote::NiceShot => "Nice shot!",
+            Emote::Alert => "!",
+            Emote::Question => "?",
+        }
+    }
+
+    /// Where it sits on the wheel, y up
+    fn direction(self) -> Vec2 {
+        match self {
+            Emote::GoodGame => Vec2::Y,
+            Emote::NiceShot => Vec2::X,
+            Emote::Alert => Vec2::NEG_Y,
+            Emote::Question => Vec2::NEG_X,
+        }
+    }
+}
+
+/// When we last sent anything, in seconds since startup
+#[derive(Resource, Default)]
+struct QuickChatCooldown(Option<f32>);
+
+impl QuickChatCooldown {
+    fn ready(&self, now: f32) -> bool {
+        self.0.is_none_or(|last| now - last >= COOLDOWN_SECS)
+    }
+}
+
+/// Who sent a message: the wizard with this handle, or the coach
+type Sender = Option<usize>;
+
+/// The wheel, open for as long as C is held
+#[derive(Component)]
+struct EmoteWheel {
+    /// Where it was opened, in logical pixels from the window's top left
+    center: Vec2,
+}
+
+#[derive(Component)]
+struct EmoteWheelOption(Emote);
+
+/// A message popped up over the wizard with this handle
+#[derive(Component)]
+struct EmoteBubble {
+    handle: usize,
+    age: f32,
+}
+
+#[derive(Component)]
+struct PingMarker {
+    age: f32,
+}
+
+#[derive(Component)]
+struct PingRipple;
+
+fn despawn_quick_chat(
+    mut commands: Commands,
+    entities: Query<Entity, Or<(With<EmoteWheel>, With<EmoteBubble>, With<PingMarker>)>>,
+) {
+    for entity in &entities {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// The wizard we'd be emoting over, none for the coach
+fn local_handle(local_players: &LocalPlayers, bots: &Bots) -> Option<usize> {
+    local_players
+        .0
+        .iter()
+        .copied()
+        .find(|handle| !bots.contains(*handle))
+}
+
+/// Sends to the opponent and the coach, or from a coach to the player they're watching
+fn send_quick_chat(
+    socket: &mut MatchboxSocket<MultipleChannels>,
+    coached: Option<&CoachedPlayers>,
+    view: &CoachView,
+    message: PeerMessage,
+) {
+    let Some(coached) = coached else {
+        send_to_peers(socket, message);
+        return;
+    };
+    let Some(peer) = coached.0.get(view.0).copied() else {
+        return;
+    };
+    if let Ok(channel) = socket.get_channel(RELIABLE_CHANNEL) {
+        message.send(channel, peer);
+    }
+}
+
+fn sender_color(sender: Sender, palette: Palette) -> Color {
+    match sender {
+        Some(handle) => palette.color(handle),
+        None => COACH_COLOR,
+    }
+}
+
+fn cursor_position(windows: &Query<&Window, With<PrimaryWindow>>) -> Option<Vec2> {
+    windows.get_single().ok()?.cursor_position()
+}
+
+fn emote_wheel(
+    mut commands: Commands,
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    local_players: Res<LocalPlayers>,
+    bots: Res<Bots>,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    mut cooldown: ResMut<QuickChatCooldown>,
+    wheels: Query<(Entity, &EmoteWheel)>,
+    mut options: Query<(&EmoteWheelOption, &mut BackgroundColor)>,
+    wizards: Query<(&Player, &Transform)>,
+    settings: Res<Settings>,
+) {
+    let Some(handle) = local_handle(&local_players, &bots) else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::KeyC) && wheels.is_empty() {
+        let center = cursor_position(&windows).unwrap_or_else(|| {
+            windows.get_single().map_or(Vec2::ZERO, |window| {
+                Vec2::new(window.width(), window.height()) / 2.
+            })
+        });
+        spawn_emote_wheel(&mut commands, center);
+        return;
+    }
+
+    let Ok((wheel_entity, wheel)) = wheels.get_single() else {
+        return;
+    };
+    // window coordinates have y down
+    let selected = cursor_position(&windows)
+        .map(|cursor| (cursor - wheel.center) * Vec2::new(1., -1.))
+        .filter(|offset| offset.length() >= WHEEL_DEAD_ZONE)
+        .and_then(|offset| {
+            Emote::WHEEL.into_iter().max_by(|a, b| {
+                offset
+                    .dot(a.direction())
+                    .total_cmp(&offset.dot(b.direction()))
+            })
+        });
+    for (option, mut background) in &mut options {
+        background.0 = if Some(option.0) == selected {
+            WHEEL_SELECTED_COLOR
+        } else {
+            WHEEL_COLOR
+        };
+    }
+
+    if keys.pressed(KeyCode::KeyC) {
+        return;
+    }
+    commands.entity(wheel_entity).despawn_recursive();
+    let now = time.elapsed_seconds();
+    let Some(emote) = selected.filter(|_| cooldown.ready(now)) else {
+        return;
+    };
+    cooldown.0 = Some(now);
+    send_to_peers(&mut socket, PeerMessage::Emote(emote));
+    show_emote(&mut commands, handle, emote, &wizards, settings.palette);
+}
+
+fn spawn_emote_wheel(commands: &mut Commands, center: Vec2) {
+    commands
+        .spawn((
+            EmoteWheel { center },
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(center.x),
+                    top: Val::Px(center.y),
+                    ..default()
+                },
+                z_index: ZIndex::Global(15),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for emote in Emote::WHEEL {
+                let offset = emote.direction() * WHEEL_RADIUS * Vec2::new(1., -1.);
+                // centered on the spot, whatever the width of the text
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            left: Val::Px(offset.x),
+                            top: Val::Px(offset.y),
+                            width: Val::Px(0.),
+                            height: Val::Px(0.),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent
+                            .spawn((
+                                EmoteWheelOption(emote),
+                                NodeBundle {
+                                    style: Style {
+                                        padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                                        flex_shrink: 0.,
+                                        ..default()
+                                    },
+                                    background_color: WHEEL_COLOR.into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    emote.text(),
+                                    TextStyle {
+                                        font_size: 24.,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    });
+            }
+        });
+}
+
+fn ping_map(
+    mut commands: Commands,
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    local_players: Res<LocalPlayers>,
+    bots: Res<Bots>,
+    coached: Option<Res<CoachedPlayers>>,
+    view: Res<CoachView>,
+    mut socket: ResMut<MatchboxSocket<MultipleChannels>>,
+    mut cooldown: ResMut<QuickChatCooldown>,
+    settings: Res<Settings>,
+) {
+    if !keys.just_pressed(KeyCode::KeyG) && !mouse.just_pressed(MouseButton::Middle) {
+        return;
+    }
+    let now = time.elapsed_seconds();
+    if !cooldown.ready(now) {
+        return;
+    }
+    let (Some(cursor), Ok((camera, camera_transform))) =
+        (cursor_position(&windows), cameras.get_single())
+    else {
+        return;
+    };
+    let Some(pos) = windows
+        .get_single()
+        .ok()
+        .and_then(|window| cursor_in_viewport(camera, window, cursor))
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+
+    cooldown.0 = Some(now);
+    let message = PeerMessage::MapPing(pos);
+    send_quick_chat(&mut socket, coached.as_deref(), &view, message);
+    let sender = match coached {
+        Some(_) => None,
+        None => local_handle(&local_players, &bots),
+    };
+    spawn_map_ping(&mut commands, sender, pos, settings.palette);
+}
+
+fn receive_quick_chat(
+    mut commands: Commands,
+    mut messages: EventReader<PeerMessageReceived>,
+    settings: Res<Settings>,
+    session: Option<Res<Session<Config>>>,
+    coached: Option<Res<CoachedPlayers>>,
+    wizards: Query<(&Player, &Transform)>,
+) {
+    let Some(session) = session else {
+        messages.clear();
+        return;
+    };
+    for received in messages.read() {
+        if settings.mute_chat {
+            continue;
+        }
+        let sender = player_handle(&session, coached.as_deref(), received.peer);
+        match received.message {
+            PeerMessage::Emote(emote) => match sender {
+                Some(handle) => {
+                    show_emote(&mut commands, handle, emote, &wizards, settings.palette)
+                }
+                None => warn!("ignoring emote from {}, who has no wizard", received.peer),
+            },
+            PeerMessage::MapPing(pos) if pos.is_finite() => {
+                spawn_map_ping(&mut commands, sender, pos, settings.palette)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Pops `emote` up over the wizard, in place of whatever they said last
+fn show_emote(
+    commands: &mut Commands,
+    handle: usize,
+    emote: Emote,
+    wizards: &Query<(&Player, &Transform)>,
+    palette: Palette,
+) {
+    let Some((_, transform)) = wizards.iter().find(|(player, _)| player.handle == handle) else {
+        return;
+    };
+    commands.spawn((
+        EmoteBubble { handle, age: 0. },
+        Text2dBundle {
+            text: Text::from_section(
+                emote.text(),
+                TextStyle {
+                    font_size: 40.,
+                    color: sender_color(Some(handle), palette),
+                    ..default()
+                },
+            ),
+            transform: Transform::from_translation(
+                (transform.translation.xy() + EMOTE_OFFSET).extend(EMOTE_Z),
+            )
+            .with_scale(Vec3::splat(EMOTE_SCALE)),
+            ..default()
+        },
+    ));
+}
+
+fn spawn_map_ping(commands: &mut Commands, sender: Sender, pos: Vec2, palette: Palette) {
+    let color = sender_color(sender, palette);
+    // a square turned on its corner, with an outline rippling out of it
+    let rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_4);
+    commands
+        .spawn((
+            PingMarker { age: 0. },
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(PING_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos.extend(PING_Z)).with_rotation(rotation),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                PingRipple,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::splat(PING_SIZE)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0., 0., -0.1),
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// Bubbles ride along with their wizard and go once they're old or replaced
+fn follow_emotes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut bubbles: Query<(Entity, &mut EmoteBubble, &mut Transform, &mut Text), Without<Player>>,
+    wizards: Query<(&Player, &Transform)>,
+) {
+    let dt = time.delta_seconds();
+    let mut newest: Vec<(usize, f32)> = Vec::new();
+    for (_, bubble, ..) in &bubbles {
+        match newest
+            .iter_mut()
+            .find(|(handle, _)| *handle == bubble.handle)
+        {
+            Some((_, age)) => *age = age.min(bubble.age),
+            None => newest.push((bubble.handle, bubble.age)),
+        }
+    }
+
+    for (entity, mut bubble, mut transform, mut text) in &mut bubbles {
+        let replaced = newest
+            .iter()
+            .any(|(handle, age)| *handle == bubble.handle && *age < bubble.age);
+        let wizard = wizards
+            .iter()
+            .find(|(player, _)| player.handle == bubble.handle);
+        bubble.age += dt;
+        let Some((_, wizard_transform)) = wizard.filter(|_| !replaced && bubble.age < EMOTE_SECS)
+        else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        let pos = wizard_transform.translation.xy() + EMOTE_OFFSET;
+        transform.translation = pos.extend(EMOTE_Z);
+        // fading out over the last quarter
+        let alpha = (4. - 4. * bubble.age / EMOTE_SECS).min(1.);
+        text.sections[0].style.color.set_a(alpha);
+    }
+}
+
+fn pulse_pings(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pings: Query<(Entity, &mut PingMarker, &Children)>,
+    mut ripples: Query<(&mut Transform, &mut Sprite), With<PingRipple>>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut ping, children) in &mut pings {
+        ping.age += dt;
+        if ping.age >= PING_SECS {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let ripple = (ping.age * PING_RIPPLES_PER_SEC).fract();
+        let mut ripples = ripples.iter_many_mut(children);
+        while let Some((mut transform, mut sprite)) = ripples.fetch_next() {
+            transform.scale = Vec3::splat(1. + ripple * PING_RIPPLE);
+            sprite.color.set_a(0.8 * (1. - ripple));
+        }
+    }
+}
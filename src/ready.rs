@@ -0,0 +1,239 @@
+//! Readying up once an opponent is found.
+//!
+//! Nobody gets pulled into a match the moment somebody else shows up: both
+//! players click "Ready" first, and see whether the other one has. Being ready
+//! is final, so both peers always agree on when it's done, and the ping
+//! handshake, the draft and everything after it only start then. Somebody who
+//! hasn't readied up after [`READY_SECS`] loses their opponent, and both go
+//! back to looking for a match. A coach isn't asked, they join whatever the
+//! players get ready for.
+
+use bevy::{prelude::*, ui::FocusPolicy};
+use bevy_matchbox::{
+    matchbox_socket::{MultipleChannels, PeerId, WebRtcChannel},
+    MatchboxSocket,
+};
+
+use crate::{
+    peer_messages::{self, PeerMessage, RELIABLE_CHANNEL},
+    GameState,
+};
+
+/// How long both players get to ready up before going their separate ways
+const READY_SECS: f32 = 30.;
+
+const BACKGROUND_COLOR: Color = Color::rgba(0., 0., 0., 0.85);
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const BUTTON_READY_COLOR: Color = Color::rgb(0.15, 0.45, 0.2);
+
+pub struct ReadyPlugin;
+
+impl Plugin for ReadyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::Matchmaking), end_ready_check)
+            .add_systems(
+                Update,
+                (
+                    spawn_ready_screen.run_if(resource_added::<ReadyCheck>),
+                    (ready_button, update_ready_screen).run_if(resource_exists::<ReadyCheck>),
+                    despawn_ready_screen.run_if(ready_check_over),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Matchmaking)),
+            );
+    }
+}
+
+/// Both players readying up, driven by `wait_for_players` every frame until it's done
+#[derive(Resource)]
+pub struct ReadyCheck {
+    peer: PeerId,
+    local_ready: bool,
+    peer_ready: bool,
+    /// In seconds since startup
+    started: f32,
+}
+
+impl ReadyCheck {
+    pub fn new(peer: PeerId, now: f32) -> Self {
+        Self {
+            peer,
+            local_ready: false,
+            peer_ready: false,
+            started: now,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.local_ready && self.peer_ready
+    }
+
+    /// Somebody kept the other waiting too long
+    pub fn timed_out(&self, now: f32) -> bool {
+        !self.is_done() && now - self.started > READY_SECS
+    }
+
+    /// Takes in the opponent's ready, returns true once both players are
+    pub fn update(&mut self, channel: &mut WebRtcChannel) -> bool {
+        for (peer, message) in peer_messages::receive(channel) {
+            match message {
+                // they may be on to measuring the ping already
+                PeerMessage::Ping(seq) => PeerMessage::Pong(seq).send(channel, peer),
+                PeerMessage::Ready if peer == self.peer => self.peer_ready = true,
+                _ => {}
+            }
+        }
+        self.is_done()
+    }
+
+    fn ready(&mut self, channel: &mut WebRtcChannel) {
+        if !self.local_ready {
+            self.local_ready = true;
+            PeerMessage::Ready.send(channel, self.peer);
+        }
+    }
+
+    fn status(&self, now: f32) -> String {
+        let secs_left = (READY_SECS - (now - self.started)).max(0.).ceil();
+        match (self.local_ready, self.peer_ready) {
+            (true, true) => "Both ready, starting the match...".into(),
+            (true, false) => format!("Waiting for your opponent to ready up ({secs_left:.0}s)"),
+            (false, true) => format!("Your opponent is ready ({secs_left:.0}s)"),
+            (false, false) => format!("Neither of you is ready yet ({secs_left:.0}s)"),
+        }
+    }
+}
+
+fn ready_check_over(ready: Option<Res<ReadyCheck>>) -> bool {
+    ready.is_none_or(|ready| ready.is_done())
+}
+
+#[derive(Component)]
+struct ReadyScreen;
+
+#[derive(Component)]
+struct ReadyStatusText;
+
+#[derive(Component)]
+struct ReadyButton;
+
+fn spawn_ready_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            ReadyScreen,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.),
+                    ..default()
+                },
+                background_color: BACKGROUND_COLOR.into(),
+                // over the matchmaking screen, whose buttons mustn't be clicked through this
+                z_index: ZIndex::Global(10),
+                focus_policy: FocusPolicy::Block,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Opponent found!",
+                TextStyle {
+                    font_size: 40.,
+                    ..default()
+                },
+            ));
+            parent.spawn((
+                ReadyStatusText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 24.,
+                        color: Color::ORANGE,
+                        ..default()
+                    },
+                ),
+            ));
+            parent
+                .spawn((
+                    ReadyButton,
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::axes(Val::Px(24.), Val::Px(12.)),
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Ready",
+                        TextStyle {
+                            font_size: 30.,
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn ready_button(
+    mut ready: ResMut<ReadyCheck>,
+    socket: Option<ResMut<MatchboxSocket<MultipleChannels>>>,
+    buttons: Query<&Interaction, (Changed<Interaction>, With<ReadyButton>)>,
+) {
+    let Some(mut socket) = socket else {
+        return;
+    };
+    let Ok(channel) = socket.get_channel(RELIABLE_CHANNEL) else {
+        return;
+    };
+    for interaction in &buttons {
+        if *interaction == Interaction::Pressed {
+            ready.ready(channel);
+        }
+    }
+}
+
+fn update_ready_screen(
+    time: Res<Time>,
+    ready: Res<ReadyCheck>,
+    mut buttons: Query<(&Interaction, &mut BackgroundColor), With<ReadyButton>>,
+    mut statuses: Query<&mut Text, With<ReadyStatusText>>,
+) {
+    let status = ready.status(time.elapsed_seconds());
+    for mut text in &mut statuses {
+        if text.sections[0].value != status {
+            text.sections[0].value = status.clone();
+        }
+    }
+
+    for (interaction, mut background) in &mut buttons {
+        background.0 = if ready.local_ready {
+            BUTTON_READY_COLOR
+        } else if *interaction == Interaction::Hovered {
+            BUTTON_HOVER_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+    }
+}
+
+fn despawn_ready_screen(mut commands: Commands, screens: Query<Entity, With<ReadyScreen>>) {
+    for screen in &screens {
+        commands.entity(screen).despawn_recursive();
+    }
+}
+
+fn end_ready_check(mut commands: Commands, screens: Query<Entity, With<ReadyScreen>>) {
+    commands.remove_resource::<ReadyCheck>();
+    for screen in &screens {
+        commands.entity(screen).despawn_recursive();
+    }
+}
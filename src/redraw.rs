@@ -0,0 +1,92 @@
+//! Draws rollback entities that a rollback brought back.
+//!
+//! When a rollback goes back past something being despawned, bevy_ggrs spawns
+//! it again with nothing but its registered components. Those are all
+//! simulation state, so the sprite, the `Transform` and anything else it was
+//! drawn with are missing. Whatever turns up with a [`Position`] and no
+//! `Transform` gets the look it was spawned with put back, worked out from
+//! what it is. Systems that pick up new wizards and decoys, like the HUD and
+//! the team markers, see the `Player` or `Decoy` come back and do the rest.
+
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+
+use crate::{
+    assets::WizardSprites,
+    barrels::prop_look,
+    bullet_look,
+    components::*,
+    decoy::decoy_look,
+    fixed::SimVec2,
+    settings::Settings,
+    spells::{explosion_look, fireball_look, hook_look, mana_burn_look},
+    wall_look, wizard_look,
+};
+
+type Respawned = (With<Rollback>, Without<Transform>);
+
+pub fn redraw_respawned(
+    mut commands: Commands,
+    sprites: Res<WizardSprites>,
+    settings: Res<Settings>,
+    respawned: Query<
+        (
+            Entity,
+            &Position,
+            Option<&Velocity>,
+            Option<&Player>,
+            Option<&Decoy>,
+            Option<&Wall>,
+            Option<&Explosion>,
+            Has<Bullet>,
+            Has<Critical>,
+            Has<Fireball>,
+            Has<Hook>,
+            Has<ManaBurn>,
+            Has<Barrel>,
+            Has<Destructible>,
+        ),
+        Respawned,
+    >,
+) {
+    for (
+        entity,
+        position,
+        velocity,
+        player,
+        decoy,
+        wall,
+        explosion,
+        bullet,
+        critical,
+        fireball,
+        hook,
+        mana_burn,
+        barrel,
+        destructible,
+    ) in &respawned
+    {
+        let pos = position.0;
+        let mut entity = commands.entity(entity);
+        if let Some(player) = player {
+            entity.insert(wizard_look(pos, settings.palette.color(player.handle)));
+        } else if let Some(decoy) = decoy {
+            entity.insert(decoy_look(pos, settings.palette.color(decoy.owner)));
+        } else if bullet {
+            let velocity = velocity.map_or(SimVec2::ZERO, |velocity| velocity.0);
+            entity.insert(bullet_look(&sprites, pos, velocity, critical));
+        } else if fireball {
+            entity.insert(fireball_look(&sprites, pos));
+        } else if hook {
+            entity.insert(hook_look(&sprites, pos));
+        } else if mana_burn {
+            entity.insert(mana_burn_look(&sprites, pos));
+        } else if let Some(explosion) = explosion {
+            entity.insert(explosion_look(&sprites, pos, explosion.frames_left));
+        } else if barrel || destructible {
+            entity.insert(prop_look(pos, barrel));
+        } else if let Some(wall) = wall {
+            entity.insert(wall_look(pos, wall.half_size));
+        }
+    }
+}
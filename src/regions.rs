@@ -0,0 +1,228 @@
+//! Picking the closest of the regional signaling servers.
+//!
+//! Every server in [`REGIONS`] gets pinged at startup, and matchmaking goes
+//! through whichever answered fastest, unless the player picked a region in
+//! the settings. Players only ever meet others on the same server, so each
+//! region is a pool of its own. A server given any other way, see
+//! [`crate::signaling`], or hosted in-process leaves the regions out of it.
+//!
+//! The server is never switched under a match: not while peers are connected,
+//! and not in a match or on its end screen, where rejoining needs the server
+//! it started on. Nor while on a backup server, the region is what failed.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{prelude::*, utils::Instant};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+
+use crate::{
+    connect_to_matchbox,
+    matchmaking::{RoomChoice, SignalingReconnect},
+    settings::Settings,
+    signaling::MatchboxUrl,
+    GameState,
+};
+
+/// A regional matchbox server
+pub struct Region {
+    /// What the settings remember the region by
+    pub id: &'static str,
+    pub name: &'static str,
+    pub url: &'static str,
+}
+
+pub const REGIONS: [Region; 4] = [
+    Region {
+        id: "eu",
+        name: "Europe",
+        url: "wss://eu.matchbox.wizardbattles.net",
+    },
+    Region {
+        id: "us-east",
+        name: "US East",
+        url: "wss://us-east.matchbox.wizardbattles.net",
+    },
+    Region {
+        id: "us-west",
+        name: "US West",
+        url: "wss://us-west.matchbox.wizardbattles.net",
+    },
+    Region {
+        id: "asia",
+        name: "Asia",
+        url: "wss://asia.matchbox.wizardbattles.net",
+    },
+];
+
+/// Servers that haven't answered by then are as good as down
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub struct RegionsPlugin;
+
+impl Plugin for RegionsPlugin {
+    fn build(&self, app: &mut App) {
+        if MatchboxUrl::configured().is_some() {
+            return;
+        }
+        app.insert_resource(RegionPings::start())
+            .add_systems(Update, pick_region.run_if(resource_exists::<RegionPings>));
+    }
+}
+
+pub fn region(id: &str) -> Option<&'static Region> {
+    REGIONS.iter().find(|region| region.id == id)
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Ping {
+    Waiting,
+    Answered(Duration),
+    Failed,
+}
+
+/// How long each of [`REGIONS`] took to answer, filled in off the main thread
+#[derive(Resource)]
+pub struct RegionPings {
+    started: Instant,
+    pings: Arc<Mutex<Vec<Ping>>>,
+}
+
+impl RegionPings {
+    fn start() -> Self {
+        let pings = Arc::new(Mutex::new(vec![Ping::Waiting; REGIONS.len()]));
+        for (index, region) in REGIONS.iter().enumerate() {
+            ping(region.url, index, pings.clone());
+        }
+        Self {
+            started: Instant::now(),
+            pings,
+        }
+    }
+
+    /// The quickest region to answer, once every region has or has run out
+    /// of time. None if nobody answered at all.
+    fn fastest(&self) -> Option<&'static Region> {
+        let pings = self.pings.lock().unwrap();
+        let waiting = pings.iter().any(|ping| matches!(ping, Ping::Waiting));
+        if waiting && self.started.elapsed() < PING_TIMEOUT {
+            return None;
+        }
+        pings
+            .iter()
+            .zip(&REGIONS)
+            .filter_map(|(ping, region)| match ping {
+                Ping::Answered(time) if *time < PING_TIMEOUT => Some((*time, region)),
+                _ => None,
+            })
+            .min_by_key(|(time, _)| *time)
+            .map(|(_, region)| region)
+    }
+}
+
+/// Records how long it takes to open a connection to the server, natively
+/// the TCP handshake is enough
+#[cfg(not(target_arch = "wasm32"))]
+fn ping(url: &'static str, index: usize, pings: Arc<Mutex<Vec<Ping>>>) {
+    use std::net::{TcpStream, ToSocketAddrs};
+
+    std::thread::spawn(move || {
+        let (secure, rest) = match url.split_once("://") {
+            Some((scheme, rest)) => (scheme == "wss", rest),
+            None => (false, url),
+        };
+        let host = rest.split('/').next().unwrap_or(rest);
+        let address = if host.contains(':') {
+            host.to_socket_addrs()
+        } else {
+            (host, if secure { 443 } else { 80 }).to_socket_addrs()
+        };
+
+        let result = match address.ok().and_then(|mut addresses| addresses.next()) {
+            Some(address) => {
+                let start = Instant::now();
+                match TcpStream::connect_timeout(&address, PING_TIMEOUT) {
+                    Ok(_) => Ping::Answered(start.elapsed()),
+                    Err(err) => {
+                        info!("signaling server {url} didn't answer: {err}");
+                        Ping::Failed
+                    }
+                }
+            }
+            None => {
+                info!("couldn't look up signaling server {url}");
+                Ping::Failed
+            }
+        };
+        pings.lock().unwrap()[index] = result;
+    });
+}
+
+/// Records how long a request to the server takes. The response is opaque
+/// without CORS, but it only has to come back at all.
+#[cfg(target_arch = "wasm32")]
+fn ping(url: &'static str, index: usize, pings: Arc<Mutex<Vec<Ping>>>) {
+    use wasm_bindgen::{closure::Closure, JsValue};
+
+    let Some(window) = web_sys::window() else {
+        pings.lock().unwrap()[index] = Ping::Failed;
+        return;
+    };
+    let http_url = url.replacen("ws", "http", 1);
+    let init = web_sys::RequestInit::new();
+    init.set_mode(web_sys::RequestMode::NoCors);
+
+    let start = Instant::now();
+    let answered = Closure::once({
+        let pings = pings.clone();
+        move |_: JsValue| pings.lock().unwrap()[index] = Ping::Answered(start.elapsed())
+    });
+    let failed = Closure::once(move |_: JsValue| {
+        info!("signaling server {url} didn't answer");
+        pings.lock().unwrap()[index] = Ping::Failed;
+    });
+    let _ = window
+        .fetch_with_str_and_init(&http_url, &init)
+        .then2(&answered, &failed);
+
+    // each runs at most once, and the promise outlives this function
+    answered.forget();
+    failed.forget();
+}
+
+/// Points matchmaking at the region the player picked, or the fastest one,
+/// and moves an idle search over to it
+fn pick_region(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    pings: Res<RegionPings>,
+    state: Res<State<GameState>>,
+    room_choice: RoomChoice,
+    reconnect: Res<SignalingReconnect>,
+    socket: Option<Res<MatchboxSocket<MultipleChannels>>>,
+    mut server: ResMut<MatchboxUrl>,
+) {
+    if reconnect.on_fallback() {
+        return;
+    }
+    let picked = settings.region.as_deref().and_then(region);
+    let Some(region) = picked.or_else(|| pings.fastest()) else {
+        return;
+    };
+    if server.0 == region.url || matches!(state.get(), GameState::InGame | GameState::MatchOver) {
+        return;
+    }
+
+    let searching = *state.get() == GameState::Matchmaking && socket.is_some();
+    if socket.is_some_and(|socket| socket.connected_peers().next().is_some()) {
+        return;
+    }
+
+    info!("matchmaking in {} ({})", region.name, region.url);
+    server.0 = region.url.to_string();
+    if searching {
+        connect_to_matchbox(&mut commands, &server, room_choice.room());
+    }
+}
@@ -0,0 +1,346 @@
+//! Picking a match back up after the connection to the opponent drops.
+//!
+//! GGRS gives a peer that stopped answering [`RECONNECT_SECS`] before calling
+//! them disconnected, and the match stalls at the edge of the prediction window
+//! in the meantime. That covers a bad patch of network on its own. If the
+//! signaling server says the WebRTC connection is gone for good, both players
+//! open a fresh socket to a room of their own on the matchbox server, named
+//! after the two of them, and wait for each other there. The new connection's
+//! GGRS channel is slipped in under the session by [`RejoinableChannel`],
+//! which passes the opponent's new peer off as the old one, so to GGRS the
+//! packets just start arriving again and it carries on from the last frame
+//! both sides confirmed. The new socket takes over the reliable and chat
+//! channels too.
+//!
+//! Direct and LAN matches only get the longer timeout. A coach watching the
+//! match is left behind on the old connection.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use bevy_ggrs::{
+    ggrs::{GgrsEvent, Message, NonBlockingSocket},
+    Session,
+};
+use bevy_matchbox::{
+    matchbox_socket::{MultipleChannels, PeerId, PeerState, WebRtcChannel},
+    MatchboxSocket,
+};
+
+use crate::{
+    peer_messages::{self, PeerDisconnected, GGRS_CHANNEL},
+    signaling::{IceServers, MatchboxUrl},
+    Config, GameState, SessionEvent,
+};
+
+/// How long a peer can go quiet before GGRS calls them disconnected
+pub const RECONNECT_SECS: u64 = 15;
+/// How long a peer has to go quiet before it's worth a word on screen
+pub const INTERRUPTED_NOTIFY_DELAY: Duration = Duration::from_millis(500);
+
+pub struct RejoinPlugin;
+
+impl Plugin for RejoinPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Matchmaking), forget_rejoin)
+            .add_systems(OnEnter(GameState::InGame), spawn_connection_banner)
+            .add_systems(OnExit(GameState::InGame), despawn_connection_banner)
+            .add_systems(
+                Update,
+                (
+                    track_interruptions,
+                    start_rejoin.run_if(resource_exists::<Rejoin>),
+                    poll_rejoin.run_if(resource_exists::<RejoinAttempt>),
+                    update_connection_banner,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// GGRS's socket for a matchbox match, whose connection to the opponent can
+/// be swapped for a new one without the session noticing
+#[derive(Clone)]
+pub struct RejoinableChannel(Arc<Mutex<RejoinableInner>>);
+
+struct RejoinableInner {
+    channel: WebRtcChannel,
+    /// The peer the session knows, and the one they're connected as now
+    alias: Option<(PeerId, PeerId)>,
+}
+
+impl RejoinableInner {
+    fn current(&self, peer: PeerId) -> PeerId {
+        match self.alias {
+            Some((original, current)) if original == peer => current,
+            _ => peer,
+        }
+    }
+
+    fn original(&self, peer: PeerId) -> PeerId {
+        match self.alias {
+            Some((original, current)) if current == peer => original,
+            _ => peer,
+        }
+    }
+}
+
+impl RejoinableChannel {
+    pub fn new(channel: WebRtcChannel) -> Self {
+        Self(Arc::new(Mutex::new(RejoinableInner {
+            channel,
+            alias: None,
+        })))
+    }
+
+    /// The peer the session knows `peer` as
+    pub fn original(&self, peer: PeerId) -> PeerId {
+        self.0.lock().unwrap().original(peer)
+    }
+
+    fn swap(&self, channel: WebRtcChannel, original: PeerId, current: PeerId) {
+        let mut inner = self.0.lock().unwrap();
+        inner.channel = channel;
+        inner.alias = Some((original, current));
+    }
+}
+
+impl NonBlockingSocket<PeerId> for RejoinableChannel {
+    fn send_to(&mut self, msg: &Message, addr: &PeerId) {
+        let mut inner = self.0.lock().unwrap();
+        let to = inner.current(*addr);
+        inner.channel.send_to(msg, &to);
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(PeerId, Message)> {
+        let mut inner = self.0.lock().unwrap();
+        let messages = inner.channel.receive_all_messages();
+        messages
+            .into_iter()
+            .map(|(peer, msg)| (inner.original(peer), msg))
+            .collect()
+    }
+}
+
+/// Everything needed to find the opponent again, for matches found through
+/// the matchbox server
+#[derive(Resource)]
+pub struct Rejoin {
+    /// Both players' peers from when the match started, which is what they
+    /// stay as to the session
+    local: PeerId,
+    opponent: PeerId,
+    channel: RejoinableChannel,
+}
+
+impl Rejoin {
+    pub fn new(local: PeerId, opponent: PeerId, channel: RejoinableChannel) -> Self {
+        Self {
+            local,
+            opponent,
+            channel,
+        }
+    }
+
+    /// The peer the session knows `peer` as
+    pub fn original(&self, peer: PeerId) -> PeerId {
+        self.channel.original(peer)
+    }
+
+    /// The same for both players
+    fn match_id(&self) -> String {
+        let (a, b) = if self.local < self.opponent {
+            (self.local, self.opponent)
+        } else {
+            (self.opponent, self.local)
+        };
+        format!("{a}_{b}")
+    }
+}
+
+/// Waiting for the opponent in the rejoin room
+#[derive(Resource)]
+struct RejoinAttempt {
+    socket: Option<MatchboxSocket<MultipleChannels>>,
+    /// In seconds since startup
+    since: f32,
+}
+
+/// GGRS hasn't heard from the opponent in a while, since then, in seconds since startup
+#[derive(Resource)]
+struct Interrupted {
+    since: f32,
+}
+
+#[derive(Component)]
+struct ConnectionBanner;
+
+fn forget_rejoin(mut commands: Commands) {
+    commands.remove_resource::<Rejoin>();
+    commands.remove_resource::<RejoinAttempt>();
+    commands.remove_resource::<Interrupted>();
+}
+
+fn track_interruptions(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut events: EventReader<SessionEvent>,
+    interrupted: Option<Res<Interrupted>>,
+) {
+    for event in events.read() {
+        match &event.0 {
+            GgrsEvent::NetworkInterrupted { addr, .. } if interrupted.is_none() => {
+                info!("connection to {addr} interrupted");
+                commands.insert_resource(Interrupted {
+                    since: time.elapsed_seconds(),
+                });
+            }
+            GgrsEvent::NetworkResumed { addr } => {
+                info!("connection to {addr} resumed");
+                commands.remove_resource::<Interrupted>();
+                commands.remove_resource::<RejoinAttempt>();
+            }
+            // out of time, that's for the forfeit to handle now
+            GgrsEvent::Disconnected { .. } => {
+                commands.remove_resource::<Interrupted>();
+                commands.remove_resource::<RejoinAttempt>();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The WebRTC connection to the opponent closed, go looking for them
+fn start_rejoin(
+    mut commands: Commands,
+    time: Res<Time>,
+    server: Res<MatchboxUrl>,
+    ice: Res<IceServers>,
+    rejoin: Res<Rejoin>,
+    mut disconnects: EventReader<PeerDisconnected>,
+    attempt: Option<Res<RejoinAttempt>>,
+) {
+    let opponent_gone = disconnects
+        .read()
+        .any(|disconnect| disconnect.peer == rejoin.opponent);
+    if !opponent_gone || attempt.is_some() {
+        return;
+    }
+
+    let room_url = server.rejoin_room_url(&rejoin.match_id());
+    info!("lost the connection to the opponent, waiting for them at {room_url}");
+    commands.insert_resource(RejoinAttempt {
+        socket: Some(peer_messages::new_socket(&room_url, &ice)),
+        since: time.elapsed_seconds(),
+    });
+}
+
+fn poll_rejoin(
+    mut commands: Commands,
+    time: Res<Time>,
+    rejoin: Res<Rejoin>,
+    mut attempt: ResMut<RejoinAttempt>,
+) {
+    if time.elapsed_seconds() - attempt.since > RECONNECT_SECS as f32 {
+        // GGRS is about to give up on them too
+        commands.remove_resource::<RejoinAttempt>();
+        return;
+    }
+    let Some(socket) = attempt.socket.as_mut() else {
+        return;
+    };
+
+    let joined = match socket.try_update_peers() {
+        Ok(changes) => changes
+            .into_iter()
+            .find(|(_, state)| *state == PeerState::Connected)
+            .map(|(peer, _)| peer),
+        Err(err) => {
+            warn!("lost the signaling server while rejoining: {err}");
+            commands.remove_resource::<RejoinAttempt>();
+            return;
+        }
+    };
+    let Some(peer) = joined else {
+        return;
+    };
+
+    info!("the opponent is back as {peer}, picking the match up where it left off");
+    let Some(mut socket) = attempt.socket.take() else {
+        return;
+    };
+    let channel = socket.take_channel(GGRS_CHANNEL).unwrap();
+    rejoin.channel.swap(channel, rejoin.opponent, peer);
+    // the reliable and chat channels come along with it
+    commands.insert_resource(socket);
+    commands.remove_resource::<RejoinAttempt>();
+}
+
+fn spawn_connection_banner(mut commands: Commands) {
+    commands.spawn((
+        ConnectionBanner,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 28.,
+                color: Color::ORANGE,
+                ..default()
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(140.),
+            width: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        }),
+    ));
+}
+
+fn despawn_connection_banner(
+    mut commands: Commands,
+    banners: Query<Entity, With<ConnectionBanner>>,
+) {
+    for banner in &banners {
+        commands.entity(banner).despawn_recursive();
+    }
+    commands.remove_resource::<RejoinAttempt>();
+    commands.remove_resource::<Interrupted>();
+}
+
+fn update_connection_banner(
+    time: Res<Time>,
+    interrupted: Option<Res<Interrupted>>,
+    attempt: Option<Res<RejoinAttempt>>,
+    session: Option<Res<Session<Config>>>,
+    mut banners: Query<&mut Text, With<ConnectionBanner>>,
+) {
+    let since = match (&attempt, &interrupted) {
+        (Some(attempt), _) => Some(attempt.since),
+        (None, Some(interrupted)) => Some(interrupted.since),
+        (None, None) => None,
+    };
+    let status = match since {
+        // only players get a session that waits on the opponent
+        Some(since) if matches!(session.as_deref(), Some(Session::P2P(_))) => {
+            let secs_left = (RECONNECT_SECS as f32 - (time.elapsed_seconds() - since)).max(0.);
+            let what = if attempt.is_some() {
+                "Connection lost, reconnecting"
+            } else {
+                "Connection interrupted"
+            };
+            format!("{what}... {:.0}s", secs_left.ceil())
+        }
+        _ => String::new(),
+    };
+    for mut text in &mut banners {
+        if text.sections[0].value != status {
+            text.sections[0].value.clone_from(&status);
+        }
+    }
+}
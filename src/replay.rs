@@ -0,0 +1,610 @@
+//! Replays (`.wbr` files).
+//!
+//! The simulation is deterministic, so a replay is just the state the match
+//! started from plus every confirmed input after that. Inputs barely change
+//! from frame to frame, so they're stored as runs of identical frames.
+//!
+//! Playback feeds the recorded inputs through a synctest session, with pause,
+//! speed control and frame stepping done through bevy's virtual time.
+
+use std::collections::BTreeMap;
+
+use bevy::{
+    ecs::system::{RunSystemOnce, SystemParam},
+    prelude::*,
+};
+use bevy_ggrs::{
+    ggrs::{PlayerType, SessionBuilder},
+    LocalInputs, PlayerInputs, Session,
+};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    barrels::ArenaLayout,
+    files::{save_file, timestamped_name},
+    input::Input,
+    rng::MatchRng,
+    round::{reset_match, MatchStats, RoundKills, RoundNumber, RoundPhase, Scores, SimFrame},
+    rules::Rules,
+    settings::Settings,
+    Config, GameState, HeadlessPlugin, Health, Player, Position,
+};
+
+/// First bytes of every replay file
+const REPLAY_MAGIC: [u8; 4] = *b"WBR\0";
+/// Bumped whenever the simulation or the file layout changes in a way that
+/// breaks old replays
+const REPLAY_VERSION: u32 = 21;
+const REPLAY_DIR: &str = "replays";
+
+/// Playback speeds to cycle through, as multiples of real time
+const PLAYBACK_SPEEDS: [f32; 5] = [0.25, 0.5, 1., 2., 4.];
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>()
+            .add_systems(Startup, load_replay_on_startup)
+            .add_systems(
+                OnEnter(GameState::Matchmaking),
+                watch_replay.run_if(resource_exists::<ReplayPlayback>),
+            )
+            .add_systems(
+                Update,
+                load_dropped_replay.run_if(in_state(GameState::Matchmaking)),
+            )
+            .add_systems(OnEnter(GameState::MatchOver), save_replay)
+            .add_systems(OnEnter(GameState::Replay), start_replay_session)
+            .add_systems(OnExit(GameState::Replay), restore_map_layout)
+            .add_systems(
+                Update,
+                (
+                    playback_controls,
+                    follow_replay_player,
+                    // headless verification has no settings and nobody to show it to
+                    update_replay_status.run_if(resource_exists::<Settings>),
+                )
+                    .run_if(in_state(GameState::Replay)),
+            );
+    }
+}
+
+/// The same inputs repeated for a number of frames
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct InputRun {
+    frames: u32,
+    /// One per player
+    inputs: Vec<Input>,
+}
+
+impl InputRun {
+    /// Each frame's inputs, with repeats folded into runs
+    fn runs(frames: impl IntoIterator<Item = Vec<Input>>) -> Vec<Self> {
+        let mut runs: Vec<Self> = Vec::new();
+        for inputs in frames {
+            match runs.last_mut() {
+                Some(run) if run.inputs == inputs => run.frames += 1,
+                _ => runs.push(Self { frames: 1, inputs }),
+            }
+        }
+        runs
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replay {
+    /// `MatchRng` state when the match started
+    pub seed: u64,
+    /// `SimFrame` right before the first recorded frame
+    pub start_frame: i32,
+    pub rules: Rules,
+    /// Barrels and crates of the map it was played on
+    pub layout: ArenaLayout,
+    pub num_players: usize,
+    runs: Vec<InputRun>,
+    /// `MatchState::hash` on the final frame, playing the replay back has to
+    /// end up with the same
+    pub end_state: u64,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    NotAReplay,
+    UnsupportedVersion(u32),
+    Corrupt(bincode::Error),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReplayError::NotAReplay => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "made with an incompatible version ({version})")
+            }
+            ReplayError::Corrupt(err) => write!(f, "corrupt replay: {err}"),
+        }
+    }
+}
+
+impl Replay {
+    /// A fresh match on an empty arena with the default rules, played with
+    /// `inputs`, one per player for each frame. It isn't known how it ends
+    /// until it's been played, see [`play_headless`].
+    pub fn new(seed: u64, inputs: impl IntoIterator<Item = Vec<Input>>) -> Self {
+        let runs = InputRun::runs(inputs);
+        Self {
+            seed,
+            start_frame: 0,
+            rules: Rules::default(),
+            layout: ArenaLayout::default(),
+            num_players: runs.first().map_or(0, |run| run.inputs.len()),
+            runs,
+            end_state: 0,
+        }
+    }
+
+    /// Number of recorded frames
+    pub fn num_frames(&self) -> usize {
+        self.runs.iter().map(|run| run.frames as usize).sum()
+    }
+
+    /// Everyone's input on the given frame, counting from the start of the replay
+    pub fn frame(&self, mut frame: usize) -> Option<&[Input]> {
+        for run in &self.runs {
+            if frame < run.frames as usize {
+                return Some(&run.inputs);
+            }
+            frame -= run.frames as usize;
+        }
+        None
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        bytes.extend(REPLAY_VERSION.to_le_bytes());
+        bincode::DefaultOptions::new()
+            .serialize_into(&mut bytes, self)
+            .expect("failed to serialize replay");
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let rest = bytes
+            .strip_prefix(&REPLAY_MAGIC)
+            .filter(|rest| rest.len() >= 4)
+            .ok_or(ReplayError::NotAReplay)?;
+        let (version, rest) = rest.split_at(4);
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        bincode::DefaultOptions::new()
+            .deserialize(rest)
+            .map_err(ReplayError::Corrupt)
+    }
+}
+
+/// The parts of the simulation that decide how a match played out
+#[derive(SystemParam)]
+pub struct MatchState<'w, 's> {
+    scores: Res<'w, Scores>,
+    stats: Res<'w, MatchStats>,
+    round: Res<'w, RoundNumber>,
+    kills: Res<'w, RoundKills>,
+    rng: Res<'w, MatchRng>,
+    players: Query<'w, 's, (&'static Player, &'static Health, &'static Position)>,
+}
+
+impl MatchState<'_, '_> {
+    /// Same state, same hash, on every platform. Everything is fed in as
+    /// fixed-size integers, `usize` and `Hash` impls would differ between
+    /// native and wasm.
+    pub fn hash(&self) -> u64 {
+        // FNV-1a
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut write = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+
+        write(self.round.0 as u64);
+        write(self.rng.state());
+        for score in &self.scores.0 {
+            write(*score as u64);
+        }
+        for stats in &self.stats.0 {
+            write(stats.kills as u64);
+            write(stats.deaths as u64);
+            write(stats.assists as u64);
+        }
+        for kill in &self.kills.0 {
+            write(kill.killer as u64);
+            write(kill.victim as u64);
+            for assist in &kill.assists {
+                write(*assist as u64);
+            }
+        }
+
+        let mut players: Vec<_> = self.players.iter().collect();
+        players.sort_by_key(|(player, ..)| player.handle);
+        for (player, health, position) in players {
+            let pos = position.0.to_vec2();
+            write(player.handle as u64);
+            write(health.0 as u64);
+            write(pos.x.to_bits() as u64);
+            write(pos.y.to_bits() as u64);
+        }
+
+        hash
+    }
+}
+
+/// The replay of the match that just ended. Only the browser build does
+/// anything with it, natively the saved file is all you need.
+#[derive(Resource)]
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub struct LastReplay(pub Replay);
+
+/// Plays `replay` back with [`HeadlessPlugin`] and returns the
+/// `MatchState::hash` it ends with, or `None` if it never finishes
+pub fn play_headless(replay: Replay) -> Option<u64> {
+    // the session may need a couple of updates to get going
+    let max_updates = replay.num_frames() * 2 + 60;
+
+    let mut app = App::new();
+    app.add_plugins(HeadlessPlugin)
+        .insert_resource(ReplayPlayback::new(replay));
+    app.finish();
+    app.cleanup();
+
+    for _ in 0..max_updates {
+        app.update();
+        if app.world.resource::<ReplayPlayback>().finished() {
+            return Some(app.world.run_system_once(|state: MatchState| state.hash()));
+        }
+    }
+    None
+}
+
+/// The replay being watched
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    /// Next frame to feed to the session
+    cursor: usize,
+    speed: usize,
+    /// Advancing a single frame, pause again once it's through
+    stepping: bool,
+    /// Handle of the wizard the camera follows
+    follow: usize,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            cursor: 0,
+            speed: PLAYBACK_SPEEDS
+                .iter()
+                .position(|&speed| speed == 1.)
+                .unwrap(),
+            stepping: false,
+            follow: 0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cursor >= self.replay.num_frames()
+    }
+}
+
+#[derive(Component)]
+struct ReplayStatus;
+
+/// Inputs of the match in progress. Kept out of the rollback state, a
+/// resimulated frame just overwrites what was recorded for it.
+#[derive(Resource, Default, Debug)]
+pub struct ReplayRecorder {
+    seed: u64,
+    start_frame: i32,
+    inputs: BTreeMap<i32, Vec<Input>>,
+}
+
+/// Runs before `start_round`, so it can tell when a fresh match is about to start
+pub fn record_replay_inputs(
+    frame: Res<SimFrame>,
+    phase: Res<RoundPhase>,
+    round: Res<RoundNumber>,
+    rng: Res<MatchRng>,
+    inputs: Res<PlayerInputs<Config>>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    recorder.inputs.split_off(&frame.0);
+
+    if *phase == RoundPhase::NewRound && round.0 == 0 {
+        recorder.inputs.clear();
+        recorder.seed = rng.state();
+        recorder.start_frame = frame.0 - 1;
+    }
+
+    recorder
+        .inputs
+        .insert(frame.0, inputs.iter().map(|(input, _)| *input).collect());
+}
+
+fn save_replay(
+    mut commands: Commands,
+    phase: Res<RoundPhase>,
+    rules: Res<Rules>,
+    layout: Res<ArenaLayout>,
+    recorder: Res<ReplayRecorder>,
+    state: MatchState,
+) {
+    let RoundPhase::MatchOver { frame, .. } = *phase else {
+        return;
+    };
+
+    // everything up to the winning frame is confirmed by now
+    let runs = InputRun::runs(
+        recorder
+            .inputs
+            .range(..=frame)
+            .map(|(_, inputs)| inputs.clone()),
+    );
+
+    let replay = Replay {
+        seed: recorder.seed,
+        start_frame: recorder.start_frame,
+        rules: *rules,
+        layout: layout.clone(),
+        num_players: runs.first().map_or(0, |run| run.inputs.len()),
+        runs,
+        end_state: state.hash(),
+    };
+
+    save_file(
+        REPLAY_DIR,
+        &timestamped_name("match", "wbr"),
+        &replay.to_bytes(),
+        "application/octet-stream",
+    );
+    commands.insert_resource(LastReplay(replay));
+}
+
+/// `--replay <file>` on the command line skips matchmaking and plays the file instead
+#[cfg(not(target_arch = "wasm32"))]
+fn load_replay_on_startup(mut commands: Commands) {
+    let mut args = std::env::args().skip_while(|arg| arg != "--replay").skip(1);
+    let Some(path) = args.next() else {
+        return;
+    };
+
+    match std::fs::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| Replay::from_bytes(&bytes).map_err(|err| err.to_string()))
+    {
+        Ok(replay) => {
+            info!("playing back {path}, {} frames", replay.num_frames());
+            commands.insert_resource(ReplayPlayback::new(replay));
+        }
+        Err(err) => error!("failed to load replay {path}: {err}"),
+    }
+}
+
+/// Same as native, but the replay comes from a shared link
+#[cfg(target_arch = "wasm32")]
+fn load_replay_on_startup(mut commands: Commands) {
+    crate::share::listen_for_dropped_files();
+
+    if let Some(replay) = crate::share::replay_from_url() {
+        info!("playing back shared replay, {} frames", replay.num_frames());
+        commands.insert_resource(ReplayPlayback::new(replay));
+    }
+}
+
+/// Watch any replay file dropped onto the window while waiting for an opponent
+fn load_dropped_replay(
+    mut commands: Commands,
+    mut drops: EventReader<FileDragAndDrop>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let dropped = drops.read().find_map(|event| match event {
+        FileDragAndDrop::DroppedFile { path_buf, .. } => std::fs::read(path_buf).ok(),
+        _ => None,
+    });
+    #[cfg(target_arch = "wasm32")]
+    let dropped = dropped.or_else(crate::share::take_dropped_file);
+    let Some(bytes) = dropped else {
+        return;
+    };
+
+    match Replay::from_bytes(&bytes) {
+        Ok(replay) => {
+            info!(
+                "playing back dropped replay, {} frames",
+                replay.num_frames()
+            );
+            commands.remove_resource::<MatchboxSocket<MultipleChannels>>();
+            commands.insert_resource(ReplayPlayback::new(replay));
+            next_state.set(GameState::Replay);
+        }
+        Err(err) => error!("dropped file isn't a replay we can play: {err}"),
+    }
+}
+
+fn watch_replay(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Replay);
+}
+
+/// The layout of the map that's loaded, while a replay's is in its place
+#[derive(Resource)]
+struct MapLayout(ArenaLayout);
+
+fn start_replay_session(
+    mut commands: Commands,
+    playback: Res<ReplayPlayback>,
+    mut layout: ResMut<ArenaLayout>,
+) {
+    let replay = &playback.replay;
+
+    // put the simulation back exactly where it was when the recording started
+    reset_match(&mut commands);
+    commands.insert_resource(SimFrame(replay.start_frame));
+    commands.insert_resource(MatchRng::new(replay.seed));
+    commands.insert_resource(replay.rules);
+    let map_layout = std::mem::replace(&mut *layout, replay.layout.clone());
+    commands.insert_resource(MapLayout(map_layout));
+
+    // every player is local and nothing is ever mispredicted, so there's no
+    // need to have synctest roll anything back
+    let mut session_builder = SessionBuilder::<Config>::new()
+        .with_num_players(replay.num_players)
+        .with_check_distance(0);
+    for handle in 0..replay.num_players {
+        session_builder = session_builder
+            .add_player(PlayerType::Local, handle)
+            .expect("failed to add player");
+    }
+    let session = session_builder
+        .start_synctest_session()
+        .expect("failed to start replay session");
+    commands.insert_resource(Session::SyncTest(session));
+
+    commands.spawn((
+        ReplayStatus,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 22.,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            left: Val::Px(10.),
+            ..default()
+        }),
+    ));
+}
+
+/// Matches after the replay are played on the map again
+fn restore_map_layout(mut commands: Commands, map_layout: Option<Res<MapLayout>>) {
+    if let Some(map_layout) = map_layout {
+        commands.insert_resource(map_layout.0.clone());
+        commands.remove_resource::<MapLayout>();
+    }
+}
+
+pub fn read_replay_inputs(
+    mut commands: Commands,
+    mut playback: ResMut<ReplayPlayback>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    // past the end, keep the session fed with nothing while we sit paused
+    let inputs = playback
+        .replay
+        .frame(playback.cursor)
+        .map(<[Input]>::to_vec)
+        .unwrap_or_else(|| vec![0; playback.replay.num_players]);
+    playback.cursor += 1;
+
+    if playback.stepping || playback.finished() {
+        playback.stepping = false;
+        time.pause();
+    }
+
+    commands.insert_resource(LocalInputs::<Config>(
+        inputs.into_iter().enumerate().collect(),
+    ));
+}
+
+fn playback_controls(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if keys.just_pressed(KeyCode::Space) && !playback.finished() {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+
+    // one frame at a time, only makes sense while paused
+    if keys.just_pressed(KeyCode::Period) && time.is_paused() && !playback.finished() {
+        playback.stepping = true;
+        time.set_relative_speed(1.);
+        time.unpause();
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::ArrowUp) {
+        playback.speed = (playback.speed + 1).min(PLAYBACK_SPEEDS.len() - 1);
+    }
+    if keys.just_pressed(KeyCode::ArrowDown) {
+        playback.speed = playback.speed.saturating_sub(1);
+    }
+    if keys.just_pressed(KeyCode::Tab) {
+        playback.follow = (playback.follow + 1) % playback.replay.num_players.max(1);
+    }
+
+    if !playback.stepping {
+        time.set_relative_speed(PLAYBACK_SPEEDS[playback.speed]);
+    }
+}
+
+fn follow_replay_player(
+    playback: Res<ReplayPlayback>,
+    players: Query<(&Player, &Position)>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    let Some((_, position)) = players
+        .iter()
+        .find(|(player, _)| player.handle == playback.follow)
+    else {
+        return;
+    };
+
+    let pos = position.0.to_vec2();
+    for mut transform in &mut cameras {
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
+
+fn update_replay_status(
+    playback: Res<ReplayPlayback>,
+    time: Res<Time<Virtual>>,
+    settings: Res<Settings>,
+    mut texts: Query<&mut Text, With<ReplayStatus>>,
+) {
+    let state = if playback.finished() {
+        "finished".to_string()
+    } else if time.is_paused() {
+        "paused".to_string()
+    } else {
+        format!("{}x", PLAYBACK_SPEEDS[playback.speed])
+    };
+    let frames = playback.replay.num_frames();
+    let status = format!(
+        "REPLAY  frame {}/{frames}  {state}  following player {}\n\
+         space: pause  .: step  up/down: speed  tab: switch player",
+        playback.cursor.min(frames),
+        playback.follow + 1,
+    );
+
+    for mut text in &mut texts {
+        if text.sections[0].value != status {
+            text.sections[0].value.clone_from(&status);
+            text.sections[0].style.color = settings.palette.color(playback.follow);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+
+/// Seed used until the peers agree on one of their own
+const DEFAULT_SEED: u64 = 0x5eed_f1a5_c0de_b01d;
+
+/// Random numbers for the simulation.
+///
+/// Rolled back together with the rest of the game state, so as long as both
+/// peers start from the same seed they draw the exact same numbers.
+#[derive(Resource, Clone, Copy, Debug, Hash)]
+pub struct MatchRng(u64);
+
+impl Default for MatchRng {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl MatchRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// The current state, `MatchRng::new(rng.state())` picks up the sequence from here
+    pub fn state(&self) -> u64 {
+        self.0
+    }
+
+    /// splitmix64, small and good enough for gameplay rolls
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((z ^ (z >> 31)) >> 32) as u32
+    }
+
+    /// Returns true `percent` percent of the time
+    pub fn chance(&mut self, percent: u32) -> bool {
+        self.next_u32() % 100 < percent
+    }
+}
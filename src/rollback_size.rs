@@ -0,0 +1,116 @@
+//! How big each frame's rollback snapshot is, for the network overlay.
+//!
+//! bevy_ggrs copies every registered component of every rollback entity and
+//! every registered resource once per simulated frame, and keeps a snapshot
+//! for each frame a rollback could go back to. Registering through
+//! [`MeasuredRollbackApp`] does the same and also lists the type here, so the
+//! overlay can show what a frame costs and what's taking up most of it. Only
+//! what's copied inline counts, not whatever a `Vec` in a resource points to.
+//!
+//! Nothing that's only drawn belongs in there. Sprites, textures and
+//! `Transform`s aren't registered. Entities that stay alive through a rollback
+//! keep theirs, and [`crate::redraw`] draws the ones a rollback respawned from
+//! scratch. What an entity is and whose side it's on, `Player`, `Bullet`,
+//! `Owner`, `Team` and the like, is registered even though it never changes,
+//! since a respawned entity would come back without it.
+
+use std::{any::type_name, mem::size_of};
+
+use bevy::prelude::*;
+use bevy_ggrs::{GgrsApp, Rollback};
+
+/// Bytes of a snapshot of the world as it is now that go to one type
+type Measure = fn(&mut World) -> usize;
+
+/// Everything registered for rollback, by name
+#[derive(Resource, Default)]
+struct RollbackLayout(Vec<(&'static str, Measure)>);
+
+pub trait MeasuredRollbackApp {
+    fn measured_rollback_component_with_copy<T: Component + Copy>(&mut self) -> &mut Self;
+    fn measured_rollback_resource_with_copy<T: Resource + Copy>(&mut self) -> &mut Self;
+    fn measured_rollback_resource_with_clone<T: Resource + Clone>(&mut self) -> &mut Self;
+}
+
+impl MeasuredRollbackApp for App {
+    fn measured_rollback_component_with_copy<T: Component + Copy>(&mut self) -> &mut Self {
+        add_measure(self, name::<T>(), measure_component::<T>);
+        self.rollback_component_with_copy::<T>()
+    }
+
+    fn measured_rollback_resource_with_copy<T: Resource + Copy>(&mut self) -> &mut Self {
+        add_measure(self, name::<T>(), measure_resource::<T>);
+        self.rollback_resource_with_copy::<T>()
+    }
+
+    fn measured_rollback_resource_with_clone<T: Resource + Clone>(&mut self) -> &mut Self {
+        add_measure(self, name::<T>(), measure_resource::<T>);
+        self.rollback_resource_with_clone::<T>()
+    }
+}
+
+fn add_measure(app: &mut App, name: &'static str, measure: Measure) {
+    app.init_resource::<RollbackLayout>();
+    app.world
+        .resource_mut::<RollbackLayout>()
+        .0
+        .push((name, measure));
+}
+
+/// The type's name without its module path
+fn name<T>() -> &'static str {
+    let name = type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Each rollback entity with a `T` has its own copy of it in the snapshot
+fn measure_component<T: Component>(world: &mut World) -> usize {
+    let count = world
+        .query_filtered::<(), (With<T>, With<Rollback>)>()
+        .iter(world)
+        .count();
+    count * size_of::<(Rollback, T)>()
+}
+
+fn measure_resource<T: Resource>(world: &mut World) -> usize {
+    if world.contains_resource::<T>() {
+        size_of::<T>()
+    } else {
+        0
+    }
+}
+
+/// What a snapshot of the world as it is now would take
+pub struct RollbackSize {
+    /// Bytes going to the list of which entities are rolled back
+    pub entities: usize,
+    /// Bytes going to each registered type, biggest first
+    pub by_type: Vec<(&'static str, usize)>,
+}
+
+impl RollbackSize {
+    pub fn total(&self) -> usize {
+        self.entities + self.by_type.iter().map(|(_, bytes)| bytes).sum::<usize>()
+    }
+}
+
+pub fn measure_rollback_size(world: &mut World) -> RollbackSize {
+    let entities = world
+        .query_filtered::<(), With<Rollback>>()
+        .iter(world)
+        .count();
+    let measures = world
+        .get_resource::<RollbackLayout>()
+        .map(|layout| layout.0.clone())
+        .unwrap_or_default();
+
+    let mut by_type: Vec<_> = measures
+        .into_iter()
+        .map(|(name, measure)| (name, measure(world)))
+        .collect();
+    by_type.sort_by_key(|(_, bytes)| std::cmp::Reverse(*bytes));
+    RollbackSize {
+        entities: entities * size_of::<(Rollback, Entity)>(),
+        by_type,
+    }
+}
@@ -0,0 +1,402 @@
+use bevy::prelude::*;
+use bevy_ggrs::{PlayerInputs, Rollback, Session};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    afk::IdleFrames,
+    announcer::{Announcement, AnnouncerQueue},
+    barrels::ArenaLayout,
+    bot::Bots,
+    components::*,
+    final_kill::FinalKill,
+    input::rematch,
+    matchmaking::MatchInputDelay,
+    mutators::RoundMutator,
+    rng::MatchRng,
+    rules::Rules,
+    sim_events::reset_sim_events,
+    spawn_players, spawn_walls,
+    training::{spawn_range, TrainingRange},
+    wind::Wind,
+    Config, GameState,
+};
+
+/// Round wins needed to take the match
+pub const ROUNDS_TO_WIN: u32 = 3;
+
+/// Frames the survivor gets to run around before the next round starts
+const ROUND_END_FRAMES: u32 = 60;
+
+/// Length of the 3-2-1 countdown at the start of each round
+pub const COUNTDOWN_FRAMES: u32 = 3 * 60;
+
+/// How long the score recap between rounds stays up
+const INTERMISSION_FRAMES: u32 = 3 * 60;
+
+/// Time limit of a round before it's decided on health (or goes to overtime)
+pub const ROUND_FRAMES: u32 = 60 * 60;
+
+/// Number of simulation frames advanced since the session started.
+///
+/// Rolled back with everything else, so it always matches the frame GGRS is simulating.
+#[derive(Resource, Clone, Copy, Default, Debug, Serialize, Deserialize)]
+pub struct SimFrame(pub i32);
+
+/// Where we are in the match. Lives in the simulation (and is rolled back) so
+/// both peers agree on when rounds start and end.
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoundPhase {
+    /// Clear the arena and spawn fresh wizards on the next frame
+    #[default]
+    NewRound,
+    /// Wizards are in place but inputs are ignored until this runs out
+    Countdown {
+        frames_left: u32,
+    },
+    Fighting,
+    RoundOver {
+        frames_left: u32,
+    },
+    /// Score recap between rounds, the arena is reset once it runs out
+    Intermission {
+        frames_left: u32,
+    },
+    MatchOver {
+        winner: usize,
+        frame: i32,
+    },
+}
+
+/// Rounds won, indexed by player handle
+#[derive(Resource, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Scores(pub Vec<u32>);
+
+impl Scores {
+    pub fn get(&self, handle: usize) -> u32 {
+        self.0.get(handle).copied().unwrap_or(0)
+    }
+
+    fn add_point(&mut self, handle: usize) -> u32 {
+        if self.0.len() <= handle {
+            self.0.resize(handle + 1, 0);
+        }
+        self.0[handle] += 1;
+        self.0[handle]
+    }
+}
+
+/// Time left in the current round
+#[derive(Resource, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RoundClock {
+    pub frames_left: u32,
+    /// The round timed out with everyone on equal health. Spawn walls are
+    /// gone and cooldowns halved until somebody dies.
+    pub overtime: bool,
+}
+
+impl Default for RoundClock {
+    fn default() -> Self {
+        Self {
+            frames_left: ROUND_FRAMES,
+            overtime: false,
+        }
+    }
+}
+
+/// The round currently being played, starting at 1
+#[derive(Resource, Clone, Copy, Default, Debug, Serialize, Deserialize)]
+pub struct RoundNumber(pub u32);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Kill {
+    /// Same as `victim` if they took themselves out with nobody else to blame
+    pub killer: usize,
+    pub victim: usize,
+    /// Other players who damaged the victim shortly before
+    pub assists: Vec<usize>,
+}
+
+/// Everyone who died this round, and who got them
+#[derive(Resource, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct RoundKills(pub Vec<Kill>);
+
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub kills: u32,
+    pub deaths: u32,
+    pub assists: u32,
+}
+
+/// Kills, deaths and assists over the whole match, indexed by player handle
+#[derive(Resource, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct MatchStats(pub Vec<PlayerStats>);
+
+impl MatchStats {
+    pub fn get(&self, handle: usize) -> PlayerStats {
+        self.0.get(handle).copied().unwrap_or_default()
+    }
+
+    fn entry(&mut self, handle: usize) -> &mut PlayerStats {
+        if self.0.len() <= handle {
+            self.0.resize(handle + 1, PlayerStats::default());
+        }
+        &mut self.0[handle]
+    }
+
+    fn record_round(&mut self, kills: &RoundKills) {
+        for kill in &kills.0 {
+            self.entry(kill.victim).deaths += 1;
+            if kill.killer != kill.victim {
+                self.entry(kill.killer).kills += 1;
+            }
+            for &assist in &kill.assists {
+                self.entry(assist).assists += 1;
+            }
+        }
+    }
+}
+
+/// Puts all match state back the way it was before the session started
+pub fn reset_match(commands: &mut Commands) {
+    commands.insert_resource(SimFrame::default());
+    commands.insert_resource(RoundPhase::default());
+    commands.insert_resource(Scores::default());
+    commands.insert_resource(RoundNumber::default());
+    commands.insert_resource(RoundKills::default());
+    commands.insert_resource(MatchStats::default());
+    commands.insert_resource(RoundClock::default());
+    commands.insert_resource(MatchRng::default());
+    commands.insert_resource(Wind::default());
+    commands.insert_resource(RoundMutator::default());
+    commands.insert_resource(IdleFrames::default());
+    commands.add(reset_sim_events);
+}
+
+/// Leaves whatever match is on for the menu: the arena goes, along with the
+/// session, the connection to the other peers, the bots and the score
+pub fn teardown_match(commands: &mut Commands, rollback_entities: &Query<Entity, With<Rollback>>) {
+    for entity in rollback_entities {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<Session<Config>>();
+    commands.remove_resource::<MatchInputDelay>();
+    commands.remove_resource::<MatchboxSocket<MultipleChannels>>();
+    commands.remove_resource::<TrainingRange>();
+    commands.insert_resource(Bots::default());
+    reset_match(commands);
+}
+
+/// Whether the simulation frame has been confirmed by every peer, i.e. it can
+/// no longer be undone by a rollback.
+pub fn frame_confirmed(session: &Session<Config>, frame: i32) -> bool {
+    match session {
+        Session::P2P(session) => session.confirmed_frame() >= frame,
+        _ => true,
+    }
+}
+
+pub fn in_round(phase: Res<RoundPhase>) -> bool {
+    *phase == RoundPhase::Fighting
+}
+
+pub fn advance_frame(mut frame: ResMut<SimFrame>) {
+    frame.0 += 1;
+}
+
+pub fn start_round(
+    mut commands: Commands,
+    mut phase: ResMut<RoundPhase>,
+    mut round: ResMut<RoundNumber>,
+    mut kills: ResMut<RoundKills>,
+    mut clock: ResMut<RoundClock>,
+    mut wind: ResMut<Wind>,
+    mut mutator: ResMut<RoundMutator>,
+    mut rng: ResMut<MatchRng>,
+    rules: Res<Rules>,
+    training: Option<Res<TrainingRange>>,
+    inputs: Res<PlayerInputs<Config>>,
+    layout: Res<ArenaLayout>,
+    arena: Query<Entity, With<Rollback>>,
+) {
+    if *phase != RoundPhase::NewRound {
+        return;
+    }
+
+    // everything rollback-tracked belongs to the previous round
+    for entity in &arena {
+        commands.entity(entity).despawn_recursive();
+    }
+    if training.is_some() {
+        spawn_range(&mut commands);
+    } else {
+        spawn_players(&mut commands, inputs.len(), &rules);
+        spawn_walls(&mut commands, &layout);
+    }
+    round.0 += 1;
+    kills.0.clear();
+    *clock = RoundClock::default();
+    *wind = Wind::roll(&mut rng, &rules);
+    *mutator = RoundMutator::roll(&mut rng, &rules);
+
+    *phase = RoundPhase::Countdown {
+        frames_left: COUNTDOWN_FRAMES,
+    };
+}
+
+pub fn check_round_end(
+    mut commands: Commands,
+    players: Query<(&Player, &Health)>,
+    // the arena's own walls, props stay put through overtime
+    walls: Query<Entity, (With<Wall>, Without<Barrel>, Without<Destructible>)>,
+    frame: Res<SimFrame>,
+    mut phase: ResMut<RoundPhase>,
+    mut scores: ResMut<Scores>,
+    mut clock: ResMut<RoundClock>,
+    kills: Res<RoundKills>,
+    mut stats: ResMut<MatchStats>,
+    mut announcer: ResMut<AnnouncerQueue>,
+    training: Option<Res<TrainingRange>>,
+) {
+    // the range's dummies don't need telling
+    let announce = training.is_none();
+    match *phase {
+        RoundPhase::Fighting => {
+            let survivors: Vec<(usize, u32)> = players
+                .iter()
+                .map(|(player, health)| (player.handle, health.0))
+                .collect();
+
+            let round_winner = if survivors.len() > 1 {
+                if clock.frames_left > 0 {
+                    clock.frames_left -= 1;
+                    return;
+                }
+                if clock.overtime {
+                    return; // sudden death, keep going until somebody dies
+                }
+
+                let best = survivors.iter().map(|(_, health)| *health).max();
+                let leaders: Vec<usize> = survivors
+                    .iter()
+                    .filter(|(_, health)| Some(*health) == best)
+                    .map(|(handle, _)| *handle)
+                    .collect();
+
+                if leaders.len() == survivors.len() {
+                    info!("round timed out with everyone on equal health, overtime!");
+                    clock.overtime = true;
+                    if announce {
+                        announcer.send(frame.0, Announcement::SuddenDeath);
+                    }
+                    for wall in &walls {
+                        commands.entity(wall).despawn_recursive();
+                    }
+                    return;
+                }
+
+                // whoever has the most health left takes it, unless that's a tie
+                (leaders.len() == 1).then(|| leaders[0])
+            } else {
+                // if both died on the same frame, nobody gets the point
+                survivors.first().map(|(handle, _)| *handle)
+            };
+
+            stats.record_round(&kills);
+
+            if let Some(winner) = round_winner {
+                if scores.add_point(winner) >= ROUNDS_TO_WIN {
+                    info!("player {winner} won the match");
+                    *phase = RoundPhase::MatchOver {
+                        winner,
+                        frame: frame.0,
+                    };
+                    return;
+                }
+            }
+
+            *phase = RoundPhase::RoundOver {
+                frames_left: ROUND_END_FRAMES,
+            };
+        }
+        RoundPhase::Countdown { frames_left: 0 } => {
+            if announce {
+                announcer.send(frame.0, Announcement::Fight);
+            }
+            *phase = RoundPhase::Fighting
+        }
+        RoundPhase::Countdown { frames_left } => {
+            // the countdown's first frame, once per round
+            if announce && frames_left == COUNTDOWN_FRAMES {
+                let match_point = scores.0.iter().any(|score| score + 1 >= ROUNDS_TO_WIN);
+                let announcement = if match_point {
+                    Announcement::FinalRound
+                } else {
+                    Announcement::Round
+                };
+                announcer.send(frame.0, announcement);
+            }
+            *phase = RoundPhase::Countdown {
+                frames_left: frames_left - 1,
+            }
+        }
+        RoundPhase::RoundOver { frames_left: 0 } => {
+            *phase = RoundPhase::Intermission {
+                frames_left: INTERMISSION_FRAMES,
+            }
+        }
+        RoundPhase::RoundOver { frames_left } => {
+            *phase = RoundPhase::RoundOver {
+                frames_left: frames_left - 1,
+            }
+        }
+        RoundPhase::Intermission { frames_left: 0 } => *phase = RoundPhase::NewRound,
+        RoundPhase::Intermission { frames_left } => {
+            *phase = RoundPhase::Intermission {
+                frames_left: frames_left - 1,
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Starts a new match once every player has voted for a rematch on the end screen
+pub fn handle_rematch(
+    inputs: Res<PlayerInputs<Config>>,
+    mut phase: ResMut<RoundPhase>,
+    mut scores: ResMut<Scores>,
+    mut round: ResMut<RoundNumber>,
+    mut stats: ResMut<MatchStats>,
+) {
+    if !matches!(*phase, RoundPhase::MatchOver { .. }) {
+        return;
+    }
+
+    if inputs.iter().all(|(input, _)| rematch(*input)) {
+        scores.0.clear();
+        stats.0.clear();
+        round.0 = 0;
+        *phase = RoundPhase::NewRound;
+    }
+}
+
+pub fn enter_match_over(
+    phase: Res<RoundPhase>,
+    session: Res<Session<Config>>,
+    final_kill: Res<FinalKill>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if let RoundPhase::MatchOver { frame, .. } = *phase {
+        // don't show the end screen for a kill that might still get rolled back,
+        // or before the kill has been shown again
+        if frame_confirmed(&session, frame) && final_kill.is_done() {
+            next_state.set(GameState::MatchOver);
+        }
+    }
+}
+
+pub fn leave_match_over(phase: Res<RoundPhase>, mut next_state: ResMut<NextState<GameState>>) {
+    if !matches!(*phase, RoundPhase::MatchOver { .. }) {
+        next_state.set(GameState::InGame);
+    }
+}
@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{components::SpellSet, mutators::MutatorPool};
+
+/// Match rules both peers play by. Set up before the session starts and never
+/// changed mid-match, so it doesn't need to be rolled back.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rules {
+    /// Whether projectiles hurt wizards on the shooter's own team
+    pub friendly_fire: bool,
+    /// Damage multiplier for friendly hits, in percent
+    pub friendly_fire_percent: u32,
+    /// Whether rounds can have wind blowing projectiles off course, see [`crate::wind`]
+    pub wind: bool,
+    /// What each round's mutator is rolled from, see [`crate::mutators`]
+    pub mutators: MutatorPool,
+    /// Spells each seat can cast, narrowed down by the draft before ranked
+    /// matches, see [`crate::draft`]
+    pub spells: [SpellSet; 4],
+    /// Seconds of fighting without touching anything before a player forfeits,
+    /// 0 lets them idle forever, see [`crate::afk`]
+    pub afk_secs: u32,
+    /// Projectiles a wizard can have flying at once before their oldest go,
+    /// 0 for no cap, see [`crate::caps`]
+    pub max_projectiles: u32,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            friendly_fire: false,
+            friendly_fire_percent: 50,
+            wind: true,
+            mutators: MutatorPool::all(),
+            spells: [SpellSet::default(); 4],
+            afk_secs: 45,
+            max_projectiles: 16,
+        }
+    }
+}
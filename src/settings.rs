@@ -0,0 +1,441 @@
+//! Player preferences, kept between sessions in `settings.json` natively and
+//! in local storage in the browser. F2 opens the settings panel anywhere.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    frame_pacing::FrameLimit,
+    palette::Palette,
+    particles::PARTICLE_LIMITS,
+    regions::{region, REGIONS},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+const SETTINGS_FILE: &str = "settings.json";
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "wizard_battles_settings";
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load()).add_systems(
+            Update,
+            (
+                toggle_settings_panel,
+                setting_buttons,
+                update_setting_labels,
+                save_settings.run_if(resource_changed::<Settings>),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    /// Whole-number zoom and unfiltered sprites, see [`crate::graphics`]
+    pub pixel_perfect: bool,
+    /// How strongly spells glow, 0 turns bloom off
+    pub bloom_intensity: f32,
+    /// Vsync, a frame rate cap or neither, see [`crate::frame_pacing`]
+    pub frame_limit: FrameLimit,
+    /// Half resolution and 30 FPS at most, see [`crate::battery`]
+    pub battery_saver: bool,
+    /// Ambient darkness with spells lighting it up, see [`crate::lighting`]
+    pub lighting: bool,
+    /// Most particles on screen at once, see [`crate::particles`]
+    pub max_particles: u32,
+    pub music_volume: f32,
+    /// The announcer and the wizards' grunts
+    pub voice_volume: f32,
+    /// Camera shake on explosions and kills, see [`crate::shake`]
+    pub screen_shake: bool,
+    /// No shake, hit-stop, hit flashes, bright bursts or bouncing, for anyone
+    /// they make queasy or worse. The match plays out exactly the same.
+    pub reduced_motion: bool,
+    /// Player colors, and team markers on the colorblind ones, see [`crate::palette`]
+    pub palette: Palette,
+    /// Tap fire to start or stop firing rather than mashing it, see
+    /// [`crate::input::FireToggle`]
+    pub toggle_fire: bool,
+    /// Drop everyone else's messages, see [`crate::chat`]
+    pub mute_chat: bool,
+    /// [`crate::regions::Region::id`] of the signaling server to use, the
+    /// fastest one if none
+    pub region: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            pixel_perfect: false,
+            bloom_intensity: 0.3,
+            frame_limit: FrameLimit::Vsync,
+            battery_saver: false,
+            lighting: true,
+            max_particles: 1000,
+            music_volume: 0.5,
+            voice_volume: 0.8,
+            screen_shake: true,
+            reduced_motion: false,
+            palette: Palette::Standard,
+            toggle_fire: false,
+            mute_chat: false,
+            region: None,
+        }
+    }
+}
+
+impl Settings {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load() -> Self {
+        let Ok(contents) = std::fs::read_to_string(SETTINGS_FILE) else {
+            return Self::default();
+        };
+        serde_json::from_str(&contents).unwrap_or_else(|err| {
+            error!("ignoring {SETTINGS_FILE}: {err}");
+            Self::default()
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn load() -> Self {
+        let stored = web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten());
+        stored
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save(&self) {
+        let contents = serde_json::to_string_pretty(self).expect("settings always serialize");
+        if let Err(err) = std::fs::write(SETTINGS_FILE, contents) {
+            error!("failed to save {SETTINGS_FILE}: {err}");
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save(&self) {
+        let contents = serde_json::to_string(self).expect("settings always serialize");
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        if let Some(storage) = storage {
+            let _ = storage.set_item(STORAGE_KEY, &contents);
+        }
+    }
+}
+
+/// A line in the settings panel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Setting {
+    PixelPerfect,
+    Bloom,
+    Lighting,
+    Particles,
+    FrameLimit,
+    BatterySaver,
+    MusicVolume,
+    VoiceVolume,
+    ScreenShake,
+    ReducedMotion,
+    Palette,
+    ToggleFire,
+    Chat,
+    Region,
+}
+
+impl Setting {
+    const ALL: [Setting; 14] = [
+        Self::PixelPerfect,
+        Self::Bloom,
+        Self::Lighting,
+        Self::Particles,
+        Self::FrameLimit,
+        Self::BatterySaver,
+        Self::ScreenShake,
+        Self::ReducedMotion,
+        Self::Palette,
+        Self::ToggleFire,
+        Self::MusicVolume,
+        Self::VoiceVolume,
+        Self::Chat,
+        Self::Region,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::PixelPerfect => "Pixel-perfect",
+            Self::Bloom => "Spell glow",
+            Self::Lighting => "Dungeon lighting",
+            Self::Particles => "Particle limit",
+            Self::FrameLimit => "Frame rate",
+            Self::BatterySaver => "Battery saver",
+            Self::MusicVolume => "Music",
+            Self::VoiceVolume => "Announcer",
+            Self::ScreenShake => "Screen shake",
+            Self::ReducedMotion => "Reduced motion",
+            Self::Palette => "Player colors",
+            Self::ToggleFire => "Toggle fire",
+            Self::Chat => "Chat from others",
+            Self::Region => "Server region",
+        }
+    }
+
+    fn value(self, settings: &Settings) -> String {
+        match self {
+            Self::PixelPerfect => on_off(settings.pixel_perfect),
+            Self::Bloom => percent(settings.bloom_intensity),
+            Self::Lighting => on_off(settings.lighting),
+            Self::Particles => settings.max_particles.to_string(),
+            Self::FrameLimit => FrameLimit::of(settings).name(),
+            Self::BatterySaver => on_off(settings.battery_saver),
+            Self::MusicVolume => percent(settings.music_volume),
+            Self::VoiceVolume => percent(settings.voice_volume),
+            Self::ScreenShake => on_off(settings.screen_shake && !settings.reduced_motion),
+            Self::ReducedMotion => on_off(settings.reduced_motion),
+            Self::Palette => settings.palette.name().into(),
+            Self::ToggleFire => on_off(settings.toggle_fire),
+            Self::Chat => on_off(!settings.mute_chat),
+            Self::Region => match settings.region.as_deref().and_then(region) {
+                Some(region) => region.name.into(),
+                None => "Closest".into(),
+            },
+        }
+    }
+
+    /// Steps the setting up or down, toggles just flip
+    fn change(self, settings: &mut Settings, step: i32) {
+        match self {
+            Self::PixelPerfect => settings.pixel_perfect = !settings.pixel_perfect,
+            Self::Lighting => settings.lighting = !settings.lighting,
+            Self::ScreenShake => settings.screen_shake = !settings.screen_shake,
+            Self::ReducedMotion => settings.reduced_motion = !settings.reduced_motion,
+            Self::ToggleFire => settings.toggle_fire = !settings.toggle_fire,
+            Self::BatterySaver => settings.battery_saver = !settings.battery_saver,
+            Self::Chat => settings.mute_chat = !settings.mute_chat,
+            Self::MusicVolume => {
+                settings.music_volume = (settings.music_volume + step as f32 * 0.1).clamp(0., 1.)
+            }
+            Self::VoiceVolume => {
+                settings.voice_volume = (settings.voice_volume + step as f32 * 0.1).clamp(0., 1.)
+            }
+            Self::Bloom => {
+                settings.bloom_intensity =
+                    (settings.bloom_intensity + step as f32 * 0.05).clamp(0., 1.)
+            }
+            Self::FrameLimit => {
+                let choices = FrameLimit::ALL.len() as i32;
+                let current = FrameLimit::ALL
+                    .iter()
+                    .position(|limit| *limit == settings.frame_limit)
+                    .unwrap_or_default() as i32;
+                settings.frame_limit =
+                    FrameLimit::ALL[(current + step).rem_euclid(choices) as usize];
+            }
+            Self::Particles => {
+                let choices = PARTICLE_LIMITS.len() as i32;
+                // a limit from a hand-edited file steps from the closest choice
+                let current = PARTICLE_LIMITS
+                    .iter()
+                    .position(|limit| *limit >= settings.max_particles)
+                    .unwrap_or(PARTICLE_LIMITS.len() - 1) as i32;
+                settings.max_particles =
+                    PARTICLE_LIMITS[(current + step).clamp(0, choices - 1) as usize];
+            }
+            Self::Palette => {
+                let choices = Palette::ALL.len() as i32;
+                let current = Palette::ALL
+                    .iter()
+                    .position(|palette| *palette == settings.palette)
+                    .unwrap_or_default() as i32;
+                settings.palette = Palette::ALL[(current + step).rem_euclid(choices) as usize];
+            }
+            Self::Region => {
+                // the closest one comes before the first region and after the last
+                let choices = REGIONS.len() as i32 + 1;
+                let current = settings
+                    .region
+                    .as_deref()
+                    .and_then(|id| REGIONS.iter().position(|region| region.id == id))
+                    .map_or(0, |index| index as i32 + 1);
+                settings.region = match (current + step).rem_euclid(choices) {
+                    0 => None,
+                    index => Some(REGIONS[index as usize - 1].id.to_string()),
+                };
+            }
+        }
+    }
+}
+
+fn on_off(on: bool) -> String {
+    if on { "On" } else { "Off" }.into()
+}
+
+fn percent(value: f32) -> String {
+    if value > 0. {
+        format!("{:.0}%", value * 100.)
+    } else {
+        "Off".into()
+    }
+}
+
+#[derive(Component)]
+struct SettingsPanel;
+
+#[derive(Component)]
+struct SettingButton {
+    setting: Setting,
+    step: i32,
+}
+
+#[derive(Component)]
+struct SettingValue(Setting);
+
+fn toggle_settings_panel(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    panels: Query<Entity, With<SettingsPanel>>,
+) {
+    if !keys.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    if !panels.is_empty() {
+        for panel in &panels {
+            commands.entity(panel).despawn_recursive();
+        }
+        return;
+    }
+
+    commands
+        .spawn((
+            SettingsPanel,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(20.),
+                    left: Val::Px(20.),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(8.),
+                    padding: UiRect::all(Val::Px(16.)),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.8).into(),
+                z_index: ZIndex::Global(100),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Settings (F2 to close)",
+                TextStyle {
+                    font_size: 26.,
+                    ..default()
+                },
+            ));
+            for setting in Setting::ALL {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(8.),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(
+                            TextBundle::from_section(
+                                setting.name(),
+                                TextStyle {
+                                    font_size: 20.,
+                                    color: Color::rgb(0.8, 0.8, 0.8),
+                                    ..default()
+                                },
+                            )
+                            .with_style(Style {
+                                width: Val::Px(160.),
+                                ..default()
+                            }),
+                        );
+                        for (step, label) in [(-1, "<"), (1, ">")] {
+                            parent
+                                .spawn((
+                                    SettingButton { setting, step },
+                                    ButtonBundle {
+                                        style: Style {
+                                            padding: UiRect::axes(Val::Px(10.), Val::Px(4.)),
+                                            ..default()
+                                        },
+                                        background_color: BUTTON_COLOR.into(),
+                                        ..default()
+                                    },
+                                ))
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        label,
+                                        TextStyle {
+                                            font_size: 20.,
+                                            ..default()
+                                        },
+                                    ));
+                                });
+                            if step < 0 {
+                                parent.spawn((
+                                    SettingValue(setting),
+                                    TextBundle::from_section(
+                                        setting.value(&settings),
+                                        TextStyle {
+                                            font_size: 20.,
+                                            ..default()
+                                        },
+                                    )
+                                    .with_style(Style {
+                                        min_width: Val::Px(60.),
+                                        justify_content: JustifyContent::Center,
+                                        ..default()
+                                    }),
+                                ));
+                            }
+                        }
+                    });
+            }
+        });
+}
+
+fn setting_buttons(
+    mut buttons: Query<(&Interaction, &SettingButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut settings: ResMut<Settings>,
+) {
+    for (interaction, button, mut background) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => button.setting.change(&mut settings, button.step),
+            Interaction::Hovered => background.0 = BUTTON_HOVER_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+}
+
+fn update_setting_labels(settings: Res<Settings>, mut values: Query<(&SettingValue, &mut Text)>) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (value, mut text) in &mut values {
+        text.sections[0].value = value.0.value(&settings);
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    // loading counts as a change too, nothing to save yet
+    if !settings.is_added() {
+        settings.save();
+    }
+}
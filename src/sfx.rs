@@ -0,0 +1,96 @@
+//! Sound effects for what happens in the arena.
+//!
+//! The simulation sends an [`SfxEvent`] for everything that should be heard,
+//! which only comes out of [`crate::sim_events`] once its frame is confirmed,
+//! so rollbacks neither repeat a sound nor play one that didn't really happen.
+//!
+//! Sounds come from where they happened: the camera is the listener, so
+//! anything off screen is quieter and off to one side.
+
+use bevy::{audio::SpatialScale, prelude::*};
+
+use crate::{assets::SfxAssets, sim_events::SimEvents};
+
+/// Sounds closer to the camera than this play at full volume, further ones
+/// fall off with the square of the distance
+const FULL_VOLUME_DISTANCE: f32 = 6.;
+/// Distance between the listener's ears, the wider the harder sounds pan
+const EAR_GAP: f32 = 4.;
+
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (add_listener, play_sfx.run_if(resource_exists::<SfxAssets>)),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sfx {
+    Fire,
+    Cast,
+    Impact,
+    /// A bullet stopped by a wall
+    Block,
+    Explosion,
+    Death,
+}
+
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct SfxEvent {
+    pub sfx: Sfx,
+    /// Player handle of whoever made the sound
+    pub source: usize,
+    /// Where in the arena it happened
+    pub pos: Vec2,
+}
+
+/// What simulation systems send their sounds to
+pub type SfxQueue = SimEvents<SfxEvent>;
+
+impl SfxQueue {
+    pub fn play(&mut self, frame: i32, sfx: Sfx, source: usize, pos: Vec2) {
+        self.send(frame, SfxEvent { sfx, source, pos });
+    }
+}
+
+/// Plays once, quieter and panned the further it is from the camera. The
+/// sound's entity needs a transform for where it came from.
+pub fn spatial() -> PlaybackSettings {
+    // 2d scale ignores z, the camera sits far above the arena
+    PlaybackSettings::DESPAWN
+        .with_spatial(true)
+        .with_spatial_scale(SpatialScale::new_2d(1. / FULL_VOLUME_DISTANCE))
+}
+
+/// Whatever the camera looks at is what the local player hears
+fn add_listener(mut commands: Commands, cameras: Query<Entity, Added<Camera2d>>) {
+    for camera in &cameras {
+        commands
+            .entity(camera)
+            .insert(SpatialListener::new(EAR_GAP));
+    }
+}
+
+fn play_sfx(mut commands: Commands, assets: Res<SfxAssets>, mut events: EventReader<SfxEvent>) {
+    for event in events.read() {
+        let source = match event.sfx {
+            Sfx::Fire => &assets.fire,
+            Sfx::Cast => &assets.cast,
+            Sfx::Impact => &assets.impact,
+            Sfx::Block => &assets.block,
+            Sfx::Explosion => &assets.explosion,
+            Sfx::Death => &assets.death,
+        };
+        commands.spawn((
+            AudioBundle {
+                source: source.clone(),
+                settings: spatial(),
+            },
+            TransformBundle::from_transform(Transform::from_translation(event.pos.extend(0.))),
+        ));
+    }
+}
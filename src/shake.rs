@@ -0,0 +1,119 @@
+//! Camera shake for explosions and death blows.
+//!
+//! Trauma-based: every big hit adds trauma, which wears off over time, and the
+//! camera is knocked about by the square of it so small hits barely register
+//! and big ones really shake. Driven by the confirmed [`Burst`] events, so a
+//! rollback can't shake the screen for something that didn't happen, and off
+//! entirely when the player turned it, or all motion, down in the settings.
+
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::{
+    graphics::{clamp_camera_to_arena, snap_camera_to_pixels},
+    particles::Burst,
+    settings::Settings,
+};
+
+/// Trauma lost per second
+const TRAUMA_DECAY: f32 = 1.5;
+/// Furthest the camera is ever knocked from where it should be, in world units
+const MAX_OFFSET: f32 = 0.4;
+/// How quickly the shake changes direction
+const SHAKE_FREQUENCY: f32 = 25.;
+const EXPLOSION_TRAUMA: f32 = 0.6;
+const DEATH_TRAUMA: f32 = 0.5;
+/// Hits this far from the camera or further don't shake it at all
+const SHAKE_DISTANCE: f32 = 12.;
+
+pub struct ShakePlugin;
+
+impl Plugin for ShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Shake>()
+            .add_systems(PreUpdate, settle_camera)
+            .add_systems(Update, add_trauma)
+            .add_systems(
+                PostUpdate,
+                shake_camera
+                    .after(clamp_camera_to_arena)
+                    .before(snap_camera_to_pixels)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+struct Shake {
+    /// How shaken up the camera is, 0 to 1
+    trauma: f32,
+    /// Where the camera would be if it weren't shaking
+    steady: Option<Vec2>,
+}
+
+fn add_trauma(
+    settings: Res<Settings>,
+    mut shake: ResMut<Shake>,
+    mut bursts: EventReader<Burst>,
+    cameras: Query<&GlobalTransform, With<Camera2d>>,
+) {
+    let Ok(camera) = cameras.get_single() else {
+        return;
+    };
+    let camera = camera.translation().xy();
+
+    for burst in bursts.read() {
+        let (pos, amount) = match *burst {
+            Burst::Explosion { pos } => (pos, EXPLOSION_TRAUMA),
+            Burst::Death { pos, .. } => (pos, DEATH_TRAUMA),
+            Burst::Muzzle { .. } | Burst::Decoy { .. } => continue,
+        };
+        let falloff = 1. - (pos.distance(camera) / SHAKE_DISTANCE).min(1.);
+        if settings.screen_shake && !settings.reduced_motion {
+            shake.trauma = (shake.trauma + amount * falloff).min(1.);
+        }
+    }
+}
+
+/// Puts the camera back where it was before it was shaken, so whatever
+/// moves it this frame starts from there
+fn settle_camera(mut shake: ResMut<Shake>, mut cameras: Query<&mut Transform, With<Camera2d>>) {
+    let Some(steady) = shake.steady.take() else {
+        return;
+    };
+    for mut transform in &mut cameras {
+        transform.translation.x = steady.x;
+        transform.translation.y = steady.y;
+    }
+}
+
+fn shake_camera(
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    mut shake: ResMut<Shake>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    if !settings.screen_shake {
+        shake.trauma = 0.;
+    }
+    if shake.trauma <= 0. {
+        return;
+    }
+
+    let strength = shake.trauma * shake.trauma;
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.);
+
+    // two sine waves each way that never quite line up, smooth enough to not
+    // look like the camera is teleporting about
+    let t = time.elapsed_seconds() * SHAKE_FREQUENCY;
+    let noise = Vec2::new(
+        (t.sin() + (t * 1.7 + 3.).sin()) / 2.,
+        ((t * 1.3 + 1.).sin() + (t * 2.1 + 5.).sin()) / 2.,
+    );
+
+    for mut transform in &mut cameras {
+        shake.steady = Some(transform.translation.xy());
+        let offset = noise * strength * MAX_OFFSET;
+        transform.translation.x += offset.x;
+        transform.translation.y += offset.y;
+    }
+}
@@ -0,0 +1,131 @@
+//! Sharing replays in the browser, without a backend to upload them to.
+//!
+//! Small replays fit in a link: the compressed file goes in the URL fragment
+//! (`#replay=...`), which never leaves the browser. Anything can also just be
+//! dropped onto the page as a `.wbr` file.
+
+use std::{io::prelude::*, sync::Mutex};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bevy::prelude::*;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+
+use crate::replay::Replay;
+
+const FRAGMENT_PREFIX: &str = "#replay=";
+/// Longest link we'll hand out, some chat apps and browsers choke on more
+const MAX_LINK_LENGTH: usize = 16 * 1024;
+/// Refuse to inflate anything bigger than this, no replay gets anywhere close
+const MAX_REPLAY_BYTES: u64 = 16 * 1024 * 1024;
+
+/// A file dropped onto the page, waiting for bevy to pick it up
+static DROPPED_FILE: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+fn encode(replay: &Replay) -> String {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(&replay.to_bytes())
+        .and_then(|_| encoder.finish())
+        .map(|compressed| URL_SAFE_NO_PAD.encode(compressed))
+        .expect("compressing into memory can't fail")
+}
+
+fn decode(data: &str) -> Result<Replay, String> {
+    let compressed = URL_SAFE_NO_PAD
+        .decode(data)
+        .map_err(|err| err.to_string())?;
+    let mut bytes = Vec::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .take(MAX_REPLAY_BYTES)
+        .read_to_end(&mut bytes)
+        .map_err(|err| err.to_string())?;
+    Replay::from_bytes(&bytes).map_err(|err| err.to_string())
+}
+
+/// The replay in the page's URL fragment, if it has one
+pub fn replay_from_url() -> Option<Replay> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    let data = hash.strip_prefix(FRAGMENT_PREFIX)?;
+
+    decode(data)
+        .map_err(|err| error!("failed to load replay from the link: {err}"))
+        .ok()
+}
+
+/// Shows the player a link to the replay they can copy, or tells them why there isn't one
+pub fn share_replay(replay: &Replay) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let location = window.location();
+    let (Ok(origin), Ok(path)) = (location.origin(), location.pathname()) else {
+        return;
+    };
+
+    let link = format!("{origin}{path}{FRAGMENT_PREFIX}{}", encode(replay));
+    if link.len() > MAX_LINK_LENGTH {
+        let _ = window.alert_with_message(
+            "This match is too long to share as a link, send the saved .wbr file instead",
+        );
+        return;
+    }
+
+    // a prompt is the one way to get text onto the clipboard without extra permissions
+    let _ = window.prompt_with_message_and_default("Copy this link to share the replay", &link);
+}
+
+/// Lets replay files be dropped anywhere on the page
+pub fn listen_for_dropped_files() {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+
+    // the browser would navigate to the file otherwise
+    let dragover = Closure::<dyn FnMut(web_sys::DragEvent)>::new(|event: web_sys::DragEvent| {
+        event.prevent_default();
+    });
+
+    let drop = Closure::<dyn FnMut(web_sys::DragEvent)>::new(|event: web_sys::DragEvent| {
+        event.prevent_default();
+        let file = event
+            .data_transfer()
+            .and_then(|transfer| transfer.files())
+            .and_then(|files| files.get(0));
+        if let Some(file) = file {
+            if let Err(err) = read_dropped_file(&file) {
+                error!("failed to read dropped file: {err:?}");
+            }
+        }
+    });
+
+    for (event, listener) in [("dragover", &dragover), ("drop", &drop)] {
+        let _ = window.add_event_listener_with_callback(event, listener.as_ref().unchecked_ref());
+    }
+
+    // the listeners stay for the lifetime of the page
+    dragover.forget();
+    drop.forget();
+}
+
+fn read_dropped_file(file: &web_sys::File) -> Result<(), JsValue> {
+    let reader = web_sys::FileReader::new()?;
+
+    let onload = Closure::once({
+        let reader = reader.clone();
+        move || {
+            if let Ok(buffer) = reader.result() {
+                *DROPPED_FILE.lock().unwrap() = Some(js_sys::Uint8Array::new(&buffer).to_vec());
+            }
+        }
+    });
+    reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+    onload.forget();
+
+    reader.read_as_array_buffer(file)
+}
+
+/// Contents of the last file dropped onto the page, if bevy hasn't seen it yet
+pub fn take_dropped_file() -> Option<Vec<u8>> {
+    DROPPED_FILE.lock().unwrap().take()
+}
@@ -0,0 +1,301 @@
+//! Where to find the matchbox signaling server.
+//!
+//! Checked in this order:
+//! - `?matchbox=wss://...` in the page URL, on the web
+//! - the `MATCHBOX_URL` environment variable, natively
+//! - `"matchbox_url"` in `wizard_battles.json` in the working directory, natively
+//! - the closest of the regional servers, or the one picked in the settings,
+//!   see [`crate::regions`]
+//! - `MATCHBOX_URL` at build time, so web builds can be published pointing at a real server
+//! - a server on this machine
+//!
+//! The regional servers take over from the last two once one of them answers.
+//!
+//! Backup servers, tried in order whenever the connection to the one in use
+//! fails, come from the same places: `?matchbox_fallbacks=wss://...,wss://...`,
+//! `MATCHBOX_FALLBACKS` at runtime or build time, both comma-separated, or
+//! `"matchbox_fallbacks"` in `wizard_battles.json`.
+//!
+//! So do the STUN and TURN servers the WebRTC connection to the other peers is
+//! set up through, [`IceServers`]. Players behind a symmetric NAT can't reach
+//! anybody directly, only through a TURN server relaying for them:
+//! `?ice=turn:...,stun:...&turn_username=...&turn_credential=...`,
+//! `ICE_SERVERS`, `TURN_USERNAME` and `TURN_CREDENTIAL` at runtime or build
+//! time, or `"ice_servers"`, `"turn_username"` and `"turn_credential"` in
+//! `wizard_battles.json`. The username and credential are taken from wherever
+//! the servers were.
+
+use bevy::prelude::*;
+use bevy_matchbox::matchbox_socket::RtcIceServerConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::private_room::RoomCode;
+
+const DEFAULT_MATCHBOX_URL: &str = "ws://127.0.0.1:3536";
+/// Every match is between two players in the same room
+const ROOM: &str = "my_bevy_wasm_game?next=2";
+/// Ranked players only ever get matched with each other
+const RANKED_ROOM: &str = "my_bevy_wasm_game_ranked?next=2";
+/// Two players and their coach, see [`crate::coach`]
+const COACHED_ROOM: &str = "my_bevy_wasm_game_coached?next=3";
+/// Followed by the room's code, see [`crate::private_room`]
+const PRIVATE_ROOM_PREFIX: &str = "my_bevy_wasm_game_private_";
+/// Followed by the match's id, see [`crate::rejoin`]
+const REJOIN_ROOM_PREFIX: &str = "my_bevy_wasm_game_rejoin_";
+#[cfg(not(target_arch = "wasm32"))]
+const CONFIG_FILE: &str = "wizard_battles.json";
+
+/// Which room on the matchbox server to look for a match in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Room {
+    Casual,
+    Ranked,
+    Coached,
+    /// Only whoever was given the code, see [`crate::private_room`]
+    Private(RoomCode),
+}
+
+impl Room {
+    /// Coached matches aren't ranked, the coach would be left out of the draft
+    pub fn new(ranked: bool, coached: bool) -> Self {
+        match (ranked, coached) {
+            (_, true) => Room::Coached,
+            (true, false) => Room::Ranked,
+            (false, false) => Room::Casual,
+        }
+    }
+
+    /// Peers the matchbox server puts together for a match
+    pub fn size(self) -> usize {
+        match self {
+            Room::Casual | Room::Ranked | Room::Private(_) => 2,
+            Room::Coached => 3,
+        }
+    }
+}
+
+/// Address of the matchbox server, without the room
+#[derive(Resource, Clone, Debug)]
+pub struct MatchboxUrl(pub String);
+
+impl MatchboxUrl {
+    pub fn room_url(&self, room: Room) -> String {
+        let room = match room {
+            Room::Casual => ROOM.to_string(),
+            Room::Ranked => RANKED_ROOM.to_string(),
+            Room::Coached => COACHED_ROOM.to_string(),
+            Room::Private(code) => {
+                format!(
+                    "{PRIVATE_ROOM_PREFIX}{}?next=2",
+                    code.to_string().to_lowercase()
+                )
+            }
+        };
+        format!("{}/{room}", self.0.trim_end_matches('/'))
+    }
+
+    /// Where the two players of a match find each other again after losing
+    /// their connection
+    pub fn rejoin_room_url(&self, match_id: &str) -> String {
+        format!(
+            "{}/{REJOIN_ROOM_PREFIX}{match_id}?next=2",
+            self.0.trim_end_matches('/')
+        )
+    }
+}
+
+impl MatchboxUrl {
+    /// The server the player pointed the game at, if they did
+    pub fn configured() -> Option<String> {
+        from_page_url().or_else(from_env).or_else(from_config_file)
+    }
+}
+
+/// Servers to fall back on when the one in use can't be reached, in the order to try them
+pub fn fallback_urls() -> Vec<String> {
+    fallbacks_from_page_url()
+        .or_else(fallbacks_from_env)
+        .or_else(fallbacks_from_config_file)
+        .or_else(|| option_env!("MATCHBOX_FALLBACKS").map(split_urls))
+        .unwrap_or_default()
+}
+
+/// STUN and TURN servers for the WebRTC connection, matchbox's public STUN
+/// server unless configured otherwise. No `Debug`, it holds the TURN credential.
+#[derive(Resource, Clone)]
+pub struct IceServers(pub RtcIceServerConfig);
+
+impl Default for IceServers {
+    fn default() -> Self {
+        let config = ice_from_page_url()
+            .or_else(ice_from_env)
+            .or_else(ice_from_config_file)
+            .or_else(|| {
+                Some(RtcIceServerConfig {
+                    urls: split_urls(option_env!("ICE_SERVERS")?),
+                    username: option_env!("TURN_USERNAME").map(String::from),
+                    credential: option_env!("TURN_CREDENTIAL").map(String::from),
+                })
+            });
+        let Some(config) = config.filter(|config| !config.urls.is_empty()) else {
+            return Self(RtcIceServerConfig::default());
+        };
+
+        let turn = config.urls.iter().any(|url| url.starts_with("turn"));
+        if turn && (config.username.is_none() || config.credential.is_none()) {
+            warn!("TURN servers usually need a username and credential, none were given");
+        }
+        info!("setting up connections through {:?}", config.urls);
+        Self(config)
+    }
+}
+
+fn split_urls(urls: &str) -> Vec<String> {
+    urls.split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+impl Default for MatchboxUrl {
+    fn default() -> Self {
+        let url = Self::configured().unwrap_or_else(|| {
+            option_env!("MATCHBOX_URL")
+                .unwrap_or(DEFAULT_MATCHBOX_URL)
+                .to_string()
+        });
+        Self(url)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Deserialize)]
+struct ConfigFile {
+    matchbox_url: Option<String>,
+    matchbox_fallbacks: Option<Vec<String>>,
+    ice_servers: Option<Vec<String>>,
+    turn_username: Option<String>,
+    turn_credential: Option<String>,
+}
+
+#[cfg(target_arch = "wasm32")]
+fn page_url_param(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get(name)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn from_page_url() -> Option<String> {
+    page_url_param("matchbox")
+}
+
+#[cfg(target_arch = "wasm32")]
+fn fallbacks_from_page_url() -> Option<Vec<String>> {
+    page_url_param("matchbox_fallbacks").map(|urls| split_urls(&urls))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn ice_from_page_url() -> Option<RtcIceServerConfig> {
+    Some(RtcIceServerConfig {
+        urls: split_urls(&page_url_param("ice")?),
+        username: page_url_param("turn_username"),
+        credential: page_url_param("turn_credential"),
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn from_page_url() -> Option<String> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn fallbacks_from_page_url() -> Option<Vec<String>> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn ice_from_page_url() -> Option<RtcIceServerConfig> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn from_env() -> Option<String> {
+    std::env::var("MATCHBOX_URL").ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn fallbacks_from_env() -> Option<Vec<String>> {
+    std::env::var("MATCHBOX_FALLBACKS")
+        .ok()
+        .map(|urls| split_urls(&urls))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn ice_from_env() -> Option<RtcIceServerConfig> {
+    Some(RtcIceServerConfig {
+        urls: split_urls(&std::env::var("ICE_SERVERS").ok()?),
+        username: std::env::var("TURN_USERNAME").ok(),
+        credential: std::env::var("TURN_CREDENTIAL").ok(),
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn from_env() -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn fallbacks_from_env() -> Option<Vec<String>> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn ice_from_env() -> Option<RtcIceServerConfig> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn config_file() -> Option<ConfigFile> {
+    let contents = std::fs::read_to_string(CONFIG_FILE).ok()?;
+    serde_json::from_str::<ConfigFile>(&contents)
+        .map_err(|err| error!("ignoring {CONFIG_FILE}: {err}"))
+        .ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn from_config_file() -> Option<String> {
+    config_file()?.matchbox_url
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn fallbacks_from_config_file() -> Option<Vec<String>> {
+    config_file()?.matchbox_fallbacks
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn ice_from_config_file() -> Option<RtcIceServerConfig> {
+    let config = config_file()?;
+    Some(RtcIceServerConfig {
+        urls: config.ice_servers?,
+        username: config.turn_username,
+        credential: config.turn_credential,
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn from_config_file() -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn fallbacks_from_config_file() -> Option<Vec<String>> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn ice_from_config_file() -> Option<RtcIceServerConfig> {
+    None
+}
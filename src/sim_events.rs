@@ -0,0 +1,117 @@
+//! Getting things that happened in the simulation out to the rest of the game.
+//!
+//! Simulation systems can't just send bevy events: a rollback resimulates
+//! frames, so a mispredicted hit would still get its sound and a real one
+//! would be heard twice. Instead they send to [`SimEvents`], tagged with the
+//! frame, and the events only go out as regular bevy events once no rollback
+//! can undo that frame anymore. Resimulating a frame throws away what it sent
+//! last time, so events from mispredicted frames never go out at all. A new
+//! match starts them all over with [`reset_sim_events`].
+
+use bevy::prelude::*;
+use bevy_ggrs::{GgrsSchedule, Session};
+
+use crate::{
+    round::{advance_frame, frame_confirmed, start_round, SimFrame},
+    Config,
+};
+
+pub trait SimEventsApp {
+    /// Lets the simulation send `E`, which everything else reads with a
+    /// regular `EventReader<E>`
+    fn add_sim_event<E: Event>(&mut self) -> &mut Self;
+}
+
+impl SimEventsApp for App {
+    fn add_sim_event<E: Event>(&mut self) -> &mut Self {
+        self.init_resource::<SimEventResets>();
+        self.world
+            .resource_mut::<SimEventResets>()
+            .0
+            .push(reset::<E>);
+        self.add_event::<E>()
+            .init_resource::<SimEvents<E>>()
+            // everything that sends runs after start_round
+            .add_systems(
+                GgrsSchedule,
+                start_frame::<E>.after(advance_frame).before(start_round),
+            )
+            .add_systems(Update, send_confirmed::<E>)
+    }
+}
+
+/// Events the simulation sent that might still be rolled back
+#[derive(Resource)]
+pub struct SimEvents<E: Event> {
+    /// In the order they were sent, which is by frame
+    pending: Vec<(i32, E)>,
+    /// Last frame whose events went out. Its events aren't taken again when
+    /// a sync test resimulates it.
+    sent_through: i32,
+}
+
+impl<E: Event> Default for SimEvents<E> {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            sent_through: i32::MIN,
+        }
+    }
+}
+
+impl<E: Event> SimEvents<E> {
+    pub fn send(&mut self, frame: i32, event: E) {
+        if frame > self.sent_through {
+            self.pending.push((frame, event));
+        }
+    }
+}
+
+/// How to empty each kind of [`SimEvents`]
+#[derive(Resource, Default)]
+struct SimEventResets(Vec<fn(&mut World)>);
+
+fn reset<E: Event>(world: &mut World) {
+    world.insert_resource(SimEvents::<E>::default());
+}
+
+/// Throws away everything sent and forgets which frames already went out,
+/// for a new match whose frames count from the start again
+pub fn reset_sim_events(world: &mut World) {
+    let resets = world
+        .get_resource::<SimEventResets>()
+        .map(|resets| resets.0.clone())
+        .unwrap_or_default();
+    for reset in resets {
+        reset(world);
+    }
+}
+
+/// Forgets what the frame about to be simulated sent the last time around
+fn start_frame<E: Event>(frame: Res<SimFrame>, mut events: ResMut<SimEvents<E>>) {
+    events.pending.retain(|(sent_on, _)| *sent_on < frame.0);
+}
+
+fn send_confirmed<E: Event>(
+    session: Option<Res<Session<Config>>>,
+    mut events: ResMut<SimEvents<E>>,
+    mut writer: EventWriter<E>,
+) {
+    let confirmed = events
+        .pending
+        .iter()
+        .take_while(|(frame, _)| {
+            session
+                .as_deref()
+                .is_none_or(|session| frame_confirmed(session, *frame))
+        })
+        .count();
+    if confirmed == 0 {
+        return;
+    }
+
+    events.sent_through = events.pending[confirmed - 1].0;
+    for (_, event) in events.pending.drain(..confirmed) {
+        writer.send(event);
+    }
+}
@@ -0,0 +1,729 @@
+//! Dev tool for reproducing bugs from a single moment of a match.
+//!
+//! F9 writes everything the simulation rolls back (match resources, wizards,
+//! projectiles, walls) to a `.wbs` file. Launching with `--snapshot <file>`
+//! loads it into an offline session with both wizards controlled locally, so
+//! whatever happened next can be poked at. Hit markers are left out, they're
+//! only for show.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    afk::IdleFrames,
+    assets::WizardSprites,
+    barrels::{fling_prop, spawn_barrel, spawn_crate},
+    bot::start_local_session,
+    caps::Fired,
+    components::*,
+    decoy::{spawn_decoy, DECOY_DELAY_FRAMES},
+    files::{save_file, timestamped_name},
+    fixed::SimVec2,
+    in_arena,
+    mutators::RoundMutator,
+    rng::MatchRng,
+    round::{MatchStats, RoundClock, RoundKills, RoundNumber, RoundPhase, Scores, SimFrame},
+    rules::Rules,
+    spawn_bullet, spawn_wall, spawn_wizard,
+    spells::{spawn_explosion, spawn_fireball, spawn_hook, spawn_mana_burn},
+    ultimate::spawn_meteor_storm,
+    wind::Wind,
+    GameState, PLAYER_COLORS,
+};
+
+/// Bumped whenever the layout changes, old snapshots are refused
+const SNAPSHOT_VERSION: u32 = 18;
+const SNAPSHOT_DIR: &str = "snapshots";
+
+pub struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_snapshot_on_startup)
+            .add_systems(
+                OnEnter(GameState::Matchmaking),
+                start_snapshot_session.run_if(resource_exists::<PendingSnapshot>),
+            )
+            .add_systems(
+                OnEnter(GameState::InGame),
+                restore_snapshot.run_if(resource_exists::<PendingSnapshot>),
+            )
+            .add_systems(Update, save_snapshot_on_key_press.run_if(in_arena));
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WizardSnapshot {
+    handle: usize,
+    team: usize,
+    pos: SimVec2,
+    facing: SimVec2,
+    knockback: SimVec2,
+    health: u32,
+    armor: u32,
+    bullet_ready: bool,
+    fire_cooldown: u32,
+    spell_cooldown: u32,
+    last_combat_frame: i32,
+    damage_history: DamageHistory,
+    combo: u32,
+    stun: Stun,
+    spellbook: Spellbook,
+    pulled: Pulled,
+    mana: Mana,
+    ultimate: Ultimate,
+    /// A training range target rather than a player
+    dummy: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProjectileSnapshot {
+    owner: usize,
+    team: usize,
+    pos: SimVec2,
+    velocity: SimVec2,
+    critical: bool,
+    /// Only for fireballs, hooks, mana burns, flung props and explosions
+    frames_left: u32,
+    /// Only for bullets, fireballs, hooks and mana burns
+    fired: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MeteorStormSnapshot {
+    owner: usize,
+    team: usize,
+    frames_left: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DecoySnapshot {
+    owner: usize,
+    team: usize,
+    pos: SimVec2,
+    health: u32,
+    trail: Vec<SimVec2>,
+    followed: SimVec2,
+    frames_left: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WallSnapshot {
+    center: SimVec2,
+    half_size: SimVec2,
+    barrel: bool,
+    destructible: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    frame: SimFrame,
+    phase: RoundPhase,
+    scores: Scores,
+    round: RoundNumber,
+    kills: RoundKills,
+    stats: MatchStats,
+    clock: RoundClock,
+    rng: u64,
+    rules: Rules,
+    wind: Wind,
+    mutator: RoundMutator,
+    idle: IdleFrames,
+    wizards: Vec<WizardSnapshot>,
+    decoys: Vec<DecoySnapshot>,
+    bullets: Vec<ProjectileSnapshot>,
+    fireballs: Vec<ProjectileSnapshot>,
+    hooks: Vec<ProjectileSnapshot>,
+    mana_burns: Vec<ProjectileSnapshot>,
+    flung_barrels: Vec<ProjectileSnapshot>,
+    flung_crates: Vec<ProjectileSnapshot>,
+    meteor_storms: Vec<MeteorStormSnapshot>,
+    explosions: Vec<ProjectileSnapshot>,
+    walls: Vec<WallSnapshot>,
+}
+
+impl Snapshot {
+    fn to_bytes(&self) -> Vec<u8> {
+        bincode::DefaultOptions::new()
+            .serialize(self)
+            .expect("serializing to memory can't fail")
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let snapshot: Snapshot = bincode::DefaultOptions::new()
+            .deserialize(bytes)
+            .map_err(|err| err.to_string())?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "snapshot version {} isn't supported, expected {SNAPSHOT_VERSION}",
+                snapshot.version
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Snapshot to load as soon as the offline session is up
+#[derive(Resource)]
+pub struct PendingSnapshot(Snapshot);
+
+/// Everything a snapshot is taken from
+#[derive(SystemParam)]
+struct SimulationState<'w, 's> {
+    frame: Res<'w, SimFrame>,
+    phase: Res<'w, RoundPhase>,
+    scores: Res<'w, Scores>,
+    round: Res<'w, RoundNumber>,
+    kills: Res<'w, RoundKills>,
+    stats: Res<'w, MatchStats>,
+    clock: Res<'w, RoundClock>,
+    rng: Res<'w, MatchRng>,
+    rules: Res<'w, Rules>,
+    wind: Res<'w, Wind>,
+    mutator: Res<'w, RoundMutator>,
+    idle: Res<'w, IdleFrames>,
+    wizards: Query<
+        'w,
+        's,
+        (
+            &'static Player,
+            &'static Team,
+            &'static Position,
+            &'static Facing,
+            &'static Knockback,
+            &'static Health,
+            &'static Armor,
+            &'static BulletReady,
+            &'static FireCooldown,
+            &'static SpellCooldown,
+            &'static LastCombatFrame,
+            &'static DamageHistory,
+            &'static Combo,
+            (
+                &'static Stun,
+                &'static Spellbook,
+                &'static Pulled,
+                &'static Mana,
+                &'static Ultimate,
+                Has<Dummy>,
+            ),
+        ),
+    >,
+    decoys: Query<
+        'w,
+        's,
+        (
+            &'static Decoy,
+            &'static Team,
+            &'static Position,
+            &'static Health,
+        ),
+    >,
+    bullets: Query<
+        'w,
+        's,
+        (
+            &'static Owner,
+            &'static Team,
+            &'static Position,
+            &'static Velocity,
+            Has<Critical>,
+            Option<&'static Fired>,
+        ),
+        With<Bullet>,
+    >,
+    fireballs: Query<
+        'w,
+        's,
+        (
+            &'static Owner,
+            &'static Team,
+            &'static Position,
+            &'static Velocity,
+            &'static Fireball,
+            Option<&'static Fired>,
+        ),
+    >,
+    hooks: Query<
+        'w,
+        's,
+        (
+            &'static Owner,
+            &'static Team,
+            &'static Position,
+            &'static Velocity,
+            &'static Hook,
+            Option<&'static Fired>,
+        ),
+    >,
+    mana_burns: Query<
+        'w,
+        's,
+        (
+            &'static Owner,
+            &'static Team,
+            &'static Position,
+            &'static Velocity,
+            &'static ManaBurn,
+            Option<&'static Fired>,
+        ),
+    >,
+    flung_props: Query<
+        'w,
+        's,
+        (
+            &'static Owner,
+            &'static Team,
+            &'static Position,
+            &'static Velocity,
+            &'static Flung,
+            Has<Barrel>,
+        ),
+    >,
+    explosions: Query<
+        'w,
+        's,
+        (
+            &'static Owner,
+            &'static Team,
+            &'static Position,
+            &'static Explosion,
+        ),
+    >,
+    meteor_storms: Query<'w, 's, (&'static Owner, &'static Team, &'static MeteorStorm)>,
+    walls: Query<
+        'w,
+        's,
+        (
+            &'static Position,
+            &'static Wall,
+            Has<Barrel>,
+            Has<Destructible>,
+        ),
+    >,
+}
+
+impl SimulationState<'_, '_> {
+    fn snapshot(&self) -> Snapshot {
+        let mut wizards: Vec<WizardSnapshot> = self
+            .wizards
+            .iter()
+            .map(
+                |(
+                    player,
+                    team,
+                    position,
+                    facing,
+                    knockback,
+                    health,
+                    armor,
+                    bullet_ready,
+                    fire_cooldown,
+                    spell_cooldown,
+                    last_combat,
+                    damage_history,
+                    combo,
+                    (stun, spellbook, pulled, mana, ultimate, dummy),
+                )| WizardSnapshot {
+                    handle: player.handle,
+                    team: team.0,
+                    pos: position.0,
+                    facing: facing.0,
+                    knockback: knockback.0,
+                    health: health.0,
+                    armor: armor.0,
+                    bullet_ready: bullet_ready.0,
+                    fire_cooldown: fire_cooldown.0,
+                    spell_cooldown: spell_cooldown.0,
+                    last_combat_frame: last_combat.0,
+                    damage_history: *damage_history,
+                    combo: combo.0,
+                    stun: *stun,
+                    spellbook: *spellbook,
+                    pulled: *pulled,
+                    mana: *mana,
+                    ultimate: *ultimate,
+                    dummy,
+                },
+            )
+            .collect();
+        wizards.sort_by_key(|wizard| wizard.handle);
+
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            frame: *self.frame,
+            phase: *self.phase,
+            scores: self.scores.clone(),
+            round: *self.round,
+            kills: self.kills.clone(),
+            stats: self.stats.clone(),
+            clock: *self.clock,
+            rng: self.rng.state(),
+            rules: *self.rules,
+            wind: *self.wind,
+            mutator: *self.mutator,
+            idle: self.idle.clone(),
+            wizards,
+            decoys: self
+                .decoys
+                .iter()
+                .map(|(decoy, team, position, health)| DecoySnapshot {
+                    owner: decoy.owner,
+                    team: team.0,
+                    pos: position.0,
+                    health: health.0,
+                    trail: decoy.trail.to_vec(),
+                    followed: decoy.followed,
+                    frames_left: decoy.frames_left,
+                })
+                .collect(),
+            bullets: self
+                .bullets
+                .iter()
+                .map(
+                    |(owner, team, position, velocity, critical, fired)| ProjectileSnapshot {
+                        owner: owner.0,
+                        team: team.0,
+                        pos: position.0,
+                        velocity: velocity.0,
+                        critical,
+                        frames_left: 0,
+                        fired: fired.map(|fired| fired.0),
+                    },
+                )
+                .collect(),
+            fireballs: self
+                .fireballs
+                .iter()
+                .map(
+                    |(owner, team, position, velocity, fireball, fired)| ProjectileSnapshot {
+                        owner: owner.0,
+                        team: team.0,
+                        pos: position.0,
+                        velocity: velocity.0,
+                        critical: false,
+                        frames_left: fireball.frames_left,
+                        fired: fired.map(|fired| fired.0),
+                    },
+                )
+                .collect(),
+            hooks: self
+                .hooks
+                .iter()
+                .map(
+                    |(owner, team, position, velocity, hook, fired)| ProjectileSnapshot {
+                        owner: owner.0,
+                        team: team.0,
+                        pos: position.0,
+                        velocity: velocity.0,
+                        critical: false,
+                        frames_left: hook.frames_left,
+                        fired: fired.map(|fired| fired.0),
+                    },
+                )
+                .collect(),
+            mana_burns: self
+                .mana_burns
+                .iter()
+                .map(
+                    |(owner, team, position, velocity, burn, fired)| ProjectileSnapshot {
+                        owner: owner.0,
+                        team: team.0,
+                        pos: position.0,
+                        velocity: velocity.0,
+                        critical: false,
+                        frames_left: burn.frames_left,
+                        fired: fired.map(|fired| fired.0),
+                    },
+                )
+                .collect(),
+            flung_barrels: self.flung_props(true),
+            flung_crates: self.flung_props(false),
+            meteor_storms: self
+                .meteor_storms
+                .iter()
+                .map(|(owner, team, storm)| MeteorStormSnapshot {
+                    owner: owner.0,
+                    team: team.0,
+                    frames_left: storm.frames_left,
+                })
+                .collect(),
+            explosions: self
+                .explosions
+                .iter()
+                .map(|(owner, team, position, explosion)| ProjectileSnapshot {
+                    owner: owner.0,
+                    team: team.0,
+                    pos: position.0,
+                    velocity: SimVec2::ZERO,
+                    critical: false,
+                    frames_left: explosion.frames_left,
+                    fired: None,
+                })
+                .collect(),
+            walls: self
+                .walls
+                .iter()
+                .map(|(position, wall, barrel, destructible)| WallSnapshot {
+                    center: position.0,
+                    half_size: wall.half_size,
+                    barrel,
+                    destructible,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl SimulationState<'_, '_> {
+    fn flung_props(&self, barrels: bool) -> Vec<ProjectileSnapshot> {
+        self.flung_props
+            .iter()
+            .filter(|(.., barrel)| *barrel == barrels)
+            .map(
+                |(owner, team, position, velocity, flung, _)| ProjectileSnapshot {
+                    owner: owner.0,
+                    team: team.0,
+                    pos: position.0,
+                    velocity: velocity.0,
+                    critical: false,
+                    frames_left: flung.frames_left,
+                    fired: None,
+                },
+            )
+            .collect()
+    }
+}
+
+fn save_snapshot_on_key_press(keys: Res<ButtonInput<KeyCode>>, state: SimulationState) {
+    if !keys.just_pressed(KeyCode::F9) {
+        return;
+    }
+
+    let snapshot = state.snapshot();
+    info!("saving snapshot of frame {}", snapshot.frame.0);
+    save_file(
+        SNAPSHOT_DIR,
+        &timestamped_name("snapshot", "wbs"),
+        &snapshot.to_bytes(),
+        "application/octet-stream",
+    );
+}
+
+/// `--snapshot <file>` on the command line skips matchmaking and continues from the snapshot
+fn load_snapshot_on_startup(mut commands: Commands) {
+    let mut args = std::env::args()
+        .skip_while(|arg| arg != "--snapshot")
+        .skip(1);
+    let Some(path) = args.next() else {
+        return;
+    };
+
+    match std::fs::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| Snapshot::from_bytes(&bytes))
+    {
+        Ok(snapshot) => {
+            info!("continuing from {path}, frame {}", snapshot.frame.0);
+            commands.insert_resource(PendingSnapshot(snapshot));
+        }
+        Err(err) => error!("failed to load snapshot {path}: {err}"),
+    }
+}
+
+fn start_snapshot_session(
+    mut commands: Commands,
+    pending: Res<PendingSnapshot>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let num_players = pending
+        .0
+        .wizards
+        .iter()
+        .map(|wizard| wizard.handle + 1)
+        .max()
+        .unwrap_or(2)
+        .max(2);
+    start_local_session(&mut commands, num_players, &mut next_state);
+}
+
+/// Puts the simulation back the way it was when the snapshot was taken,
+/// before the session simulates its first frame
+fn restore_snapshot(
+    mut commands: Commands,
+    pending: Res<PendingSnapshot>,
+    sprites: Res<WizardSprites>,
+) {
+    let snapshot = &pending.0;
+
+    commands.insert_resource(snapshot.frame);
+    commands.insert_resource(snapshot.phase);
+    commands.insert_resource(snapshot.scores.clone());
+    commands.insert_resource(snapshot.round);
+    commands.insert_resource(snapshot.kills.clone());
+    commands.insert_resource(snapshot.stats.clone());
+    commands.insert_resource(snapshot.clock);
+    commands.insert_resource(MatchRng::new(snapshot.rng));
+    commands.insert_resource(snapshot.rules);
+    commands.insert_resource(snapshot.wind);
+    commands.insert_resource(snapshot.mutator);
+    commands.insert_resource(snapshot.idle.clone());
+
+    for wizard in &snapshot.wizards {
+        let entity = spawn_wizard(
+            &mut commands,
+            wizard.handle,
+            wizard.team,
+            wizard.pos,
+            wizard.facing,
+            PLAYER_COLORS[wizard.handle % PLAYER_COLORS.len()],
+        );
+        commands.entity(entity).insert((
+            Knockback(wizard.knockback),
+            Health(wizard.health),
+            Armor(wizard.armor),
+            BulletReady(wizard.bullet_ready),
+            FireCooldown(wizard.fire_cooldown),
+            SpellCooldown(wizard.spell_cooldown),
+            LastCombatFrame(wizard.last_combat_frame),
+            wizard.damage_history,
+            Combo(wizard.combo),
+            wizard.stun,
+            wizard.spellbook,
+            wizard.pulled,
+            wizard.mana,
+            wizard.ultimate,
+        ));
+        if wizard.dummy {
+            commands.entity(entity).insert(Dummy);
+        }
+    }
+    for decoy in &snapshot.decoys {
+        let mut trail = [decoy.followed; DECOY_DELAY_FRAMES];
+        for (slot, pos) in trail.iter_mut().zip(&decoy.trail) {
+            *slot = *pos;
+        }
+        spawn_decoy(
+            &mut commands,
+            Decoy {
+                owner: decoy.owner,
+                trail,
+                followed: decoy.followed,
+                frames_left: decoy.frames_left,
+            },
+            Team(decoy.team),
+            decoy.pos,
+            Health(decoy.health),
+        );
+    }
+    for bullet in &snapshot.bullets {
+        let entity = spawn_bullet(
+            &mut commands,
+            &sprites,
+            Owner(bullet.owner),
+            Team(bullet.team),
+            bullet.pos,
+            bullet.velocity,
+            bullet.critical,
+        );
+        restamp(&mut commands, entity, bullet.fired);
+    }
+    for fireball in &snapshot.fireballs {
+        let entity = spawn_fireball(
+            &mut commands,
+            &sprites,
+            Owner(fireball.owner),
+            Team(fireball.team),
+            fireball.pos,
+            fireball.velocity,
+            fireball.frames_left,
+        );
+        restamp(&mut commands, entity, fireball.fired);
+    }
+    for hook in &snapshot.hooks {
+        let entity = spawn_hook(
+            &mut commands,
+            &sprites,
+            Owner(hook.owner),
+            Team(hook.team),
+            hook.pos,
+            hook.velocity,
+            hook.frames_left,
+        );
+        restamp(&mut commands, entity, hook.fired);
+    }
+    for burn in &snapshot.mana_burns {
+        let entity = spawn_mana_burn(
+            &mut commands,
+            &sprites,
+            Owner(burn.owner),
+            Team(burn.team),
+            burn.pos,
+            burn.velocity,
+            burn.frames_left,
+        );
+        restamp(&mut commands, entity, burn.fired);
+    }
+    for barrel in &snapshot.flung_barrels {
+        let entity = spawn_barrel(&mut commands, barrel.pos);
+        fling_prop(
+            &mut commands,
+            entity,
+            Owner(barrel.owner),
+            Team(barrel.team),
+            barrel.velocity,
+            barrel.frames_left,
+        );
+    }
+    for crate_ in &snapshot.flung_crates {
+        let entity = spawn_crate(&mut commands, crate_.pos);
+        fling_prop(
+            &mut commands,
+            entity,
+            Owner(crate_.owner),
+            Team(crate_.team),
+            crate_.velocity,
+            crate_.frames_left,
+        );
+    }
+    for storm in &snapshot.meteor_storms {
+        spawn_meteor_storm(
+            &mut commands,
+            Owner(storm.owner),
+            Team(storm.team),
+            storm.frames_left,
+        );
+    }
+    for explosion in &snapshot.explosions {
+        spawn_explosion(
+            &mut commands,
+            &sprites,
+            Owner(explosion.owner),
+            Team(explosion.team),
+            explosion.pos,
+            explosion.frames_left,
+        );
+    }
+    for wall in &snapshot.walls {
+        let center = wall.center;
+        if wall.barrel {
+            spawn_barrel(&mut commands, center);
+        } else if wall.destructible {
+            spawn_crate(&mut commands, center);
+        } else {
+            spawn_wall(&mut commands, center, wall.half_size);
+        }
+    }
+
+    commands.remove_resource::<PendingSnapshot>();
+}
+
+/// Puts back the frame a projectile was fired on, so the cap doesn't take it
+/// for one fired on the first frame after loading
+fn restamp(commands: &mut Commands, entity: Entity, fired: Option<i32>) {
+    if let Some(fired) = fired {
+        commands.entity(entity).insert(Fired(fired));
+    }
+}
@@ -0,0 +1,925 @@
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs};
+
+use crate::{
+    assets::{ProjectileSprite, WizardSprites},
+    barrels::{fling_prop, nearest_prop, Shovable, FLUNG_RANGE_FRAMES, FLUNG_SPEED},
+    broadphase::Broadphase,
+    caps::{in_order, FireOrder, Fired},
+    combat::{apply_damage, resolve_damage, ComboTracker, HitQueue, Target},
+    components::*,
+    decoy::{pop_decoy, spawn_decoys},
+    fixed::{Fixed, SimVec2},
+    hits_wall,
+    input::{cast, next_spell},
+    mutators::RoundMutator,
+    muzzle,
+    particles::{Burst, BurstQueue},
+    round::{RoundClock, RoundKills, SimFrame},
+    rules::Rules,
+    sfx::{Sfx, SfxQueue},
+    step_wizard,
+    telemetry::MatchTelemetry,
+    Config, MAP_SIZE, PLAYER_RADIUS,
+};
+
+pub const FIREBALL_RADIUS: f32 = 0.2;
+/// Distance covered each frame
+const FIREBALL_SPEED: Fixed = Fixed::from_f32(0.2);
+const FIREBALL_RANGE_FRAMES: u32 = 60;
+const FIREBALL_COOLDOWN_FRAMES: u32 = 90;
+
+pub const HOOK_RADIUS: f32 = 0.15;
+/// Distance covered each frame, quicker than a fireball but with a shorter reach
+const HOOK_SPEED: Fixed = Fixed::from_f32(0.35);
+const HOOK_RANGE_FRAMES: u32 = 25;
+const HOOK_COOLDOWN_FRAMES: u32 = 120;
+const DECOY_COOLDOWN_FRAMES: u32 = 8 * 60;
+const TELEKINESIS_COOLDOWN_FRAMES: u32 = 90;
+
+pub const MANA_BURN_RADIUS: f32 = 0.12;
+/// Distance covered each frame
+const MANA_BURN_SPEED: Fixed = Fixed::from_f32(0.3);
+const MANA_BURN_RANGE_FRAMES: u32 = 40;
+const MANA_BURN_COOLDOWN_FRAMES: u32 = 60;
+/// Barely a scratch, the mana is what hurts
+const MANA_BURN_DAMAGE: u32 = 8;
+const MANA_BURN_DRAIN: u32 = 40;
+/// How long the target's mana stops coming back for
+const MANA_BURN_REGEN_PAUSE_FRAMES: i32 = 2 * 60;
+/// Distance a hooked wizard is reeled in by each frame
+const PULL_SPEED: Fixed = Fixed::from_f32(0.25);
+/// Longest a pull can go on for, in case the wizard gets stuck on a wall
+const PULL_FRAMES: u32 = 30;
+/// How close to the caster the pull lets go
+const PULL_STOP_DISTANCE: Fixed = Fixed::from_f32(PLAYER_RADIUS * 2. + 0.2);
+
+pub const EXPLOSION_RADIUS: f32 = 2.;
+const EXPLOSION_DAMAGE: u32 = 30;
+/// How long an explosion stuns enemies it catches, before diminishing returns
+const EXPLOSION_STUN_FRAMES: u32 = 30;
+/// Frames the explosion stays visible after dealing its damage
+pub const EXPLOSION_FRAMES: u32 = 20;
+/// Knockback speed at the center of the blast, falling off towards the edge
+const EXPLOSION_KNOCKBACK: Fixed = Fixed::from_f32(0.5);
+/// How much of the damage the caster takes from their own explosions. Low
+/// enough that blasting yourself across the map is a valid way to get around.
+const SELF_DAMAGE_PERCENT: u32 = 40;
+/// Brighter than white, so these bloom
+const FIREBALL_GLOW: Color = Color::rgb(4., 1.2, 0.3);
+const EXPLOSION_GLOW: Color = Color::rgba(2.5, 1.5, 0.25, 0.5);
+const HOOK_COLOR: Color = Color::rgb(0.8, 0.85, 0.9);
+const MANA_BURN_GLOW: Color = Color::rgb(1.6, 0.4, 2.4);
+
+/// A spell drawn out of the projectile atlas. The tiles are unit circles
+/// scaled to `radius`, so explosions can shrink by scaling them down.
+fn spell_sprite(
+    sprites: &WizardSprites,
+    tile: ProjectileSprite,
+    color: Color,
+    pos: Vec3,
+    radius: f32,
+) -> SpriteSheetBundle {
+    let (texture, atlas) = sprites.projectile(tile);
+    SpriteSheetBundle {
+        texture,
+        atlas,
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::splat(2.)),
+            ..default()
+        },
+        transform: Transform::from_translation(pos).with_scale(Vec3::splat(radius)),
+        ..default()
+    }
+}
+
+/// Switches to the next spell when the key goes down, holding it doesn't keep
+/// switching. Only goes through the spells the wizard's seat is allowed.
+pub fn select_spells(
+    inputs: Res<PlayerInputs<Config>>,
+    rules: Res<Rules>,
+    mut players: Query<(&Player, &mut Spellbook)>,
+) {
+    for (player, mut spellbook) in &mut players {
+        let (input, _) = inputs[player.handle];
+        let allowed = rules.spells[player.handle % rules.spells.len()];
+        let pressed = next_spell(input);
+        if (pressed && !spellbook.next_held) || !allowed.contains(spellbook.selected) {
+            spellbook.selected = allowed.next_after(spellbook.selected);
+        }
+        spellbook.next_held = pressed;
+    }
+}
+
+pub fn cast_spells(
+    mut commands: Commands,
+    inputs: Res<PlayerInputs<Config>>,
+    sprites: Res<WizardSprites>,
+    clock: Res<RoundClock>,
+    mutator: Res<RoundMutator>,
+    frame: Res<SimFrame>,
+    mut sfx: ResMut<SfxQueue>,
+    mut players: Query<(
+        &Position,
+        &Player,
+        &Team,
+        &Facing,
+        &Spellbook,
+        &Health,
+        &mut SpellCooldown,
+        &mut Mana,
+    )>,
+    walls: Query<(&Position, &Wall), Without<Player>>,
+    broadphase: Res<Broadphase>,
+    props: Query<(Entity, &Position), (Shovable, Without<Player>)>,
+) {
+    for (position, player, team, facing, spellbook, health, mut cooldown, mut mana) in &mut players
+    {
+        if cooldown.0 > 0 {
+            cooldown.0 -= 1;
+            continue;
+        }
+
+        let (input, _) = inputs[player.handle];
+        if !cast(input) {
+            continue;
+        }
+        let shoved = (spellbook.selected == Spell::Telekinesis)
+            .then(|| nearest_prop(position.0, &props))
+            .flatten();
+        // nothing in reach to shove, so no mana spent on it either
+        if spellbook.selected == Spell::Telekinesis && shoved.is_none() {
+            continue;
+        }
+        if !mana.spend(spellbook.selected.mana_cost()) {
+            continue;
+        }
+
+        let (pos, cooldown_frames) = match spellbook.selected {
+            Spell::Fireball => {
+                let pos = muzzle(position.0, facing.0, FIREBALL_RADIUS);
+                spawn_fireball(
+                    &mut commands,
+                    &sprites,
+                    Owner(player.handle),
+                    *team,
+                    pos,
+                    facing.0 * mutator.projectile_speed(FIREBALL_SPEED),
+                    mutator.projectile_range(FIREBALL_RANGE_FRAMES),
+                );
+                (pos, FIREBALL_COOLDOWN_FRAMES)
+            }
+            Spell::Hook => {
+                let pos = muzzle(position.0, facing.0, HOOK_RADIUS);
+                spawn_hook(
+                    &mut commands,
+                    &sprites,
+                    Owner(player.handle),
+                    *team,
+                    pos,
+                    facing.0 * mutator.projectile_speed(HOOK_SPEED),
+                    mutator.projectile_range(HOOK_RANGE_FRAMES),
+                );
+                (pos, HOOK_COOLDOWN_FRAMES)
+            }
+            Spell::Decoy => {
+                let pos = position.0;
+                spawn_decoys(
+                    &mut commands,
+                    Owner(player.handle),
+                    *team,
+                    pos,
+                    facing.0,
+                    *health,
+                    &broadphase,
+                    &walls,
+                );
+                (pos, DECOY_COOLDOWN_FRAMES)
+            }
+            Spell::ManaBurn => {
+                let pos = muzzle(position.0, facing.0, MANA_BURN_RADIUS);
+                spawn_mana_burn(
+                    &mut commands,
+                    &sprites,
+                    Owner(player.handle),
+                    *team,
+                    pos,
+                    facing.0 * mutator.projectile_speed(MANA_BURN_SPEED),
+                    mutator.projectile_range(MANA_BURN_RANGE_FRAMES),
+                );
+                (pos, MANA_BURN_COOLDOWN_FRAMES)
+            }
+            Spell::Telekinesis => {
+                let Some((prop, pos)) = shoved else {
+                    continue;
+                };
+                let direction = match (pos - position.0).normalize_or_zero() {
+                    SimVec2::ZERO => facing.0,
+                    direction => direction,
+                };
+                fling_prop(
+                    &mut commands,
+                    prop,
+                    Owner(player.handle),
+                    *team,
+                    direction * mutator.projectile_speed(FLUNG_SPEED),
+                    mutator.projectile_range(FLUNG_RANGE_FRAMES),
+                );
+                (pos, TELEKINESIS_COOLDOWN_FRAMES)
+            }
+        };
+        sfx.play(frame.0, Sfx::Cast, player.handle, pos.to_vec2());
+
+        cooldown.0 = mutator.cooldown(if clock.overtime {
+            cooldown_frames / 2
+        } else {
+            cooldown_frames
+        });
+    }
+}
+
+pub fn fireball_look(sprites: &WizardSprites, pos: SimVec2) -> SpriteSheetBundle {
+    spell_sprite(
+        sprites,
+        ProjectileSprite::Orb,
+        FIREBALL_GLOW,
+        pos.to_vec2().extend(2.),
+        FIREBALL_RADIUS,
+    )
+}
+
+pub fn spawn_fireball(
+    commands: &mut Commands,
+    sprites: &WizardSprites,
+    owner: Owner,
+    team: Team,
+    pos: SimVec2,
+    velocity: SimVec2,
+    frames_left: u32,
+) -> Entity {
+    commands
+        .spawn((
+            Fireball { frames_left },
+            owner,
+            team,
+            Position(pos),
+            Velocity(velocity),
+            fireball_look(sprites, pos),
+        ))
+        .add_rollback()
+        .id()
+}
+
+/// What stopped a projectile
+pub enum ProjectileHit {
+    /// The wizard with this handle
+    Wizard(usize),
+    Wall,
+    OutOfBounds,
+}
+
+/// Whether a projectile of `radius` at `pos` has run into anything: a wizard
+/// other than its caster, a wall or the edge of the map. Only `players` and
+/// `walls` are checked, the ones the [`Broadphase`] has near it will do.
+pub fn projectile_hit<'a>(
+    pos: SimVec2,
+    radius: Fixed,
+    mutator: &RoundMutator,
+    owner: Owner,
+    players: impl IntoIterator<Item = (&'a Position, &'a Player)>,
+    walls: impl IntoIterator<Item = (&'a Position, &'a Wall)>,
+) -> Option<ProjectileHit> {
+    let reach = mutator.wizard_radius() + radius;
+    let hit_player = players
+        .into_iter()
+        .find(|(player_pos, player)| player.handle != owner.0 && player_pos.0.within(pos, reach));
+    if let Some((_, player)) = hit_player {
+        return Some(ProjectileHit::Wizard(player.handle));
+    }
+    if walls
+        .into_iter()
+        .any(|(wall_pos, wall)| hits_wall(pos, radius, wall_pos.0, wall))
+    {
+        return Some(ProjectileHit::Wall);
+    }
+    if pos.outside(map_limit()) {
+        return Some(ProjectileHit::OutOfBounds);
+    }
+    None
+}
+
+/// How far from the middle of the arena its edge is
+pub fn map_limit() -> Fixed {
+    Fixed::from_int(MAP_SIZE as i32) / 2
+}
+
+pub fn move_fireballs(
+    mut commands: Commands,
+    sprites: Res<WizardSprites>,
+    mutator: Res<RoundMutator>,
+    mut fireballs: Query<(
+        Entity,
+        &mut Position,
+        &mut Fireball,
+        &Velocity,
+        &Owner,
+        &Team,
+        Option<&Fired>,
+    )>,
+    players: Query<(&Position, &Player), Without<Fireball>>,
+    walls: Query<(&Position, &Wall), Without<Fireball>>,
+    broadphase: Res<Broadphase>,
+) {
+    let order = in_order(fireballs.iter().map(
+        |(entity, position, _, velocity, owner, _, fired)| {
+            (FireOrder::of(fired, owner, position, velocity), entity)
+        },
+    ));
+    for fireball in order {
+        let Ok((entity, mut position, mut fireball, velocity, owner, team, _)) =
+            fireballs.get_mut(fireball)
+        else {
+            continue;
+        };
+        position.0 += velocity.0;
+        let pos = position.0;
+        fireball.frames_left = fireball.frames_left.saturating_sub(1);
+
+        let radius = Fixed::from_f32(FIREBALL_RADIUS);
+        let hit = projectile_hit(
+            pos,
+            radius,
+            &mutator,
+            *owner,
+            broadphase.wizards.near(pos, radius, &players),
+            broadphase.walls.near(pos, radius, &walls),
+        );
+        if hit.is_some() || fireball.frames_left == 0 {
+            commands.entity(entity).despawn_recursive();
+            spawn_explosion(
+                &mut commands,
+                &sprites,
+                *owner,
+                *team,
+                pos,
+                EXPLOSION_FRAMES,
+            );
+        }
+    }
+}
+
+pub fn hook_look(sprites: &WizardSprites, pos: SimVec2) -> SpriteSheetBundle {
+    spell_sprite(
+        sprites,
+        ProjectileSprite::Hook,
+        HOOK_COLOR,
+        pos.to_vec2().extend(2.),
+        HOOK_RADIUS,
+    )
+}
+
+pub fn spawn_hook(
+    commands: &mut Commands,
+    sprites: &WizardSprites,
+    owner: Owner,
+    team: Team,
+    pos: SimVec2,
+    velocity: SimVec2,
+    frames_left: u32,
+) -> Entity {
+    commands
+        .spawn((
+            Hook { frames_left },
+            owner,
+            team,
+            Position(pos),
+            Velocity(velocity),
+            hook_look(sprites, pos),
+        ))
+        .add_rollback()
+        .id()
+}
+
+/// Hooks fly until they run out of range or hit something, and an enemy they
+/// hit gets reeled in towards the caster
+pub fn move_hooks(
+    mut commands: Commands,
+    frame: Res<SimFrame>,
+    mutator: Res<RoundMutator>,
+    mut sfx: ResMut<SfxQueue>,
+    mut hooks: Query<(
+        Entity,
+        &mut Position,
+        &mut Hook,
+        &Velocity,
+        &Owner,
+        &Team,
+        Option<&Fired>,
+    )>,
+    mut players: Query<(&Position, &Player, &Team, &mut Pulled), Without<Hook>>,
+    walls: Query<(&Position, &Wall), Without<Hook>>,
+    broadphase: Res<Broadphase>,
+) {
+    let radius = Fixed::from_f32(HOOK_RADIUS);
+    let reach = mutator.wizard_radius() + radius;
+
+    let order = in_order(
+        hooks
+            .iter()
+            .map(|(entity, position, _, velocity, owner, _, fired)| {
+                (FireOrder::of(fired, owner, position, velocity), entity)
+            }),
+    );
+    for hook in order {
+        let Ok((entity, mut position, mut hook, velocity, owner, hook_team, _)) =
+            hooks.get_mut(hook)
+        else {
+            continue;
+        };
+        position.0 += velocity.0;
+        let pos = position.0;
+        hook.frames_left = hook.frames_left.saturating_sub(1);
+
+        // allies are flown straight past
+        let hooked = broadphase
+            .wizards
+            .entities_near(pos, radius)
+            .into_iter()
+            .find(|&wizard| {
+                players
+                    .get(wizard)
+                    .is_ok_and(|(player_pos, player, team, _)| {
+                        player.handle != owner.0
+                            && team.0 != hook_team.0
+                            && player_pos.0.within(pos, reach)
+                    })
+            });
+        if let Some((player_pos, player, _, mut pulled)) =
+            hooked.and_then(|wizard| players.get_mut(wizard).ok())
+        {
+            *pulled = Pulled {
+                by: owner.0,
+                frames_left: PULL_FRAMES,
+            };
+            sfx.play(frame.0, Sfx::Impact, player.handle, player_pos.0.to_vec2());
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let hit_wall = broadphase
+            .walls
+            .near(pos, radius, &walls)
+            .any(|(wall_pos, wall)| hits_wall(pos, radius, wall_pos.0, wall));
+        if hit_wall || pos.outside(map_limit()) || hook.frames_left == 0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Reels hooked wizards in towards whoever hooked them. Runs before
+/// `move_players`, which leaves a wizard being pulled to it.
+pub fn pull_wizards(
+    mut players: Query<(&mut Position, &Player, &mut Pulled)>,
+    walls: Query<(&Position, &Wall), Without<Player>>,
+    broadphase: Res<Broadphase>,
+) {
+    let casters: Vec<(usize, SimVec2)> = players
+        .iter()
+        .map(|(position, player, _)| (player.handle, position.0))
+        .collect();
+    let radius = Fixed::from_f32(PLAYER_RADIUS);
+
+    for (mut position, _, mut pulled) in &mut players {
+        if pulled.frames_left == 0 {
+            continue;
+        }
+        pulled.frames_left -= 1;
+
+        // the caster died on the way, nothing left to pull towards
+        let Some(&(_, target)) = casters.iter().find(|(handle, _)| *handle == pulled.by) else {
+            pulled.frames_left = 0;
+            continue;
+        };
+
+        let offset = target - position.0;
+        let distance = offset.length();
+        if distance <= PULL_STOP_DISTANCE {
+            pulled.frames_left = 0;
+            continue;
+        }
+
+        let step = offset / distance * PULL_SPEED.min(distance - PULL_STOP_DISTANCE);
+        position.0 = step_wizard(position.0, step, |pos| {
+            broadphase
+                .walls
+                .near(pos, radius, &walls)
+                .any(|(wall_pos, wall)| hits_wall(pos, radius, wall_pos.0, wall))
+        });
+    }
+}
+
+pub fn mana_burn_look(sprites: &WizardSprites, pos: SimVec2) -> SpriteSheetBundle {
+    spell_sprite(
+        sprites,
+        ProjectileSprite::Orb,
+        MANA_BURN_GLOW,
+        pos.to_vec2().extend(2.),
+        MANA_BURN_RADIUS,
+    )
+}
+
+pub fn spawn_mana_burn(
+    commands: &mut Commands,
+    sprites: &WizardSprites,
+    owner: Owner,
+    team: Team,
+    pos: SimVec2,
+    velocity: SimVec2,
+    frames_left: u32,
+) -> Entity {
+    commands
+        .spawn((
+            ManaBurn { frames_left },
+            owner,
+            team,
+            Position(pos),
+            Velocity(velocity),
+            mana_burn_look(sprites, pos),
+        ))
+        .add_rollback()
+        .id()
+}
+
+/// Mana burns fly straight past allies, and on the first enemy they hit deal a
+/// little damage and drain a chunk of their mana
+pub fn move_mana_burns(
+    mut commands: Commands,
+    mut kills: ResMut<RoundKills>,
+    mut telemetry: ResMut<MatchTelemetry>,
+    mut hits: ResMut<HitQueue>,
+    mut sfx: ResMut<SfxQueue>,
+    mut bursts: ResMut<BurstQueue>,
+    frame: Res<SimFrame>,
+    mutator: Res<RoundMutator>,
+    mut burns: Query<(
+        Entity,
+        &mut Position,
+        &mut ManaBurn,
+        &Velocity,
+        &Owner,
+        &Team,
+        Option<&Fired>,
+    )>,
+    mut players: Query<
+        (
+            Entity,
+            &Position,
+            &Player,
+            &Team,
+            &mut Health,
+            &Armor,
+            &mut DamageHistory,
+            &mut LastCombatFrame,
+            &mut Combo,
+            &mut Mana,
+        ),
+        Without<ManaBurn>,
+    >,
+    walls: Query<(&Position, &Wall), Without<ManaBurn>>,
+    broadphase: Res<Broadphase>,
+) {
+    let radius = Fixed::from_f32(MANA_BURN_RADIUS);
+    let reach = mutator.wizard_radius() + radius;
+    let mut attackers = Vec::new();
+    let mut combos = ComboTracker::new(
+        players
+            .iter()
+            .map(|(_, _, player, .., combo, _)| (player.handle, *combo)),
+    );
+
+    let order = in_order(
+        burns
+            .iter()
+            .map(|(entity, position, _, velocity, owner, _, fired)| {
+                (FireOrder::of(fired, owner, position, velocity), entity)
+            }),
+    );
+    for burn in order {
+        let Ok((entity, mut position, mut burn, velocity, owner, burn_team, _)) =
+            burns.get_mut(burn)
+        else {
+            continue;
+        };
+        position.0 += velocity.0;
+        let pos = position.0;
+        burn.frames_left = burn.frames_left.saturating_sub(1);
+
+        let target = broadphase
+            .wizards
+            .entities_near(pos, radius)
+            .into_iter()
+            .find(|&wizard| {
+                players
+                    .get(wizard)
+                    .is_ok_and(|(_, player_pos, _, team, ..)| {
+                        team.0 != burn_team.0 && player_pos.0.within(pos, reach)
+                    })
+            });
+        if let Some((
+            player_entity,
+            player_pos,
+            player,
+            _,
+            mut health,
+            armor,
+            mut history,
+            mut last_combat,
+            _,
+            mut mana,
+        )) = target.and_then(|wizard| players.get_mut(wizard).ok())
+        {
+            commands.entity(entity).despawn_recursive();
+            attackers.push(owner.0);
+            last_combat.0 = frame.0;
+            mana.burn(frame.0, MANA_BURN_DRAIN, MANA_BURN_REGEN_PAUSE_FRAMES);
+
+            let modifiers = [combos.bonus_percent(owner.0)];
+            let damage = resolve_damage(MANA_BURN_DAMAGE, *armor, &modifiers);
+            combos.record(owner.0, player.handle, true);
+            let pos = player_pos.0.to_vec2();
+            let target = Target {
+                entity: player_entity,
+                player,
+                health: &mut health,
+                history: &mut history,
+                pos,
+            };
+            sfx.play(frame.0, Sfx::Impact, player.handle, pos);
+            let died = apply_damage(
+                &mut commands,
+                &mut kills,
+                &mut telemetry,
+                &mut hits,
+                frame.0,
+                target,
+                Some(owner.0),
+                damage,
+                false,
+            );
+            if died {
+                sfx.play(frame.0, Sfx::Death, player.handle, pos);
+                bursts.send(
+                    frame.0,
+                    Burst::Death {
+                        pos,
+                        handle: player.handle,
+                    },
+                );
+            }
+            continue;
+        }
+
+        let hit_wall = broadphase
+            .walls
+            .near(pos, radius, &walls)
+            .any(|(wall_pos, wall)| hits_wall(pos, radius, wall_pos.0, wall));
+        if hit_wall || pos.outside(map_limit()) || burn.frames_left == 0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    // dealing damage also counts as being in combat
+    for (.., player, _, _, _, _, mut last_combat, mut combo, _) in &mut players {
+        if attackers.contains(&player.handle) {
+            last_combat.0 = frame.0;
+        }
+        combos.update(player.handle, &mut combo);
+    }
+}
+
+pub fn explosion_look(
+    sprites: &WizardSprites,
+    pos: SimVec2,
+    frames_left: u32,
+) -> SpriteSheetBundle {
+    spell_sprite(
+        sprites,
+        ProjectileSprite::Explosion,
+        EXPLOSION_GLOW,
+        pos.to_vec2().extend(3.),
+        explosion_scale(frames_left),
+    )
+}
+
+/// An explosion that has `frames_left` to go, `EXPLOSION_FRAMES` for a fresh one
+pub fn spawn_explosion(
+    commands: &mut Commands,
+    sprites: &WizardSprites,
+    owner: Owner,
+    team: Team,
+    pos: SimVec2,
+    frames_left: u32,
+) {
+    commands
+        .spawn((
+            Explosion { frames_left },
+            owner,
+            team,
+            Position(pos),
+            explosion_look(sprites, pos, frames_left),
+        ))
+        .add_rollback();
+}
+
+/// Explosions shrink away as they fade out
+fn explosion_scale(frames_left: u32) -> f32 {
+    let t = frames_left as f32 / EXPLOSION_FRAMES as f32;
+    EXPLOSION_RADIUS * t
+}
+
+pub fn shrink_explosions(mut explosions: Query<(&Explosion, &mut Transform), Changed<Explosion>>) {
+    for (explosion, mut transform) in &mut explosions {
+        transform.scale = Vec3::splat(explosion_scale(explosion.frames_left));
+    }
+}
+
+pub fn resolve_explosions(
+    mut commands: Commands,
+    mut kills: ResMut<RoundKills>,
+    mut telemetry: ResMut<MatchTelemetry>,
+    mut hits: ResMut<HitQueue>,
+    mut sfx: ResMut<SfxQueue>,
+    mut bursts: ResMut<BurstQueue>,
+    frame: Res<SimFrame>,
+    rules: Res<Rules>,
+    mutator: Res<RoundMutator>,
+    mut explosions: Query<(Entity, &mut Explosion, &Position, &Owner, &Team)>,
+    mut players: Query<
+        (
+            Entity,
+            &Position,
+            &Player,
+            &Team,
+            &mut Health,
+            &Armor,
+            &mut DamageHistory,
+            &mut Knockback,
+            &mut LastCombatFrame,
+            &mut Combo,
+            &mut Stun,
+        ),
+        Without<Explosion>,
+    >,
+    decoys: Query<(Entity, &Decoy, &Position, &Team), Without<Player>>,
+    broadphase: Res<Broadphase>,
+) {
+    let mut combos = ComboTracker::new(
+        players
+            .iter()
+            .map(|(_, _, player, .., combo, _)| (player.handle, *combo)),
+    );
+
+    let order = in_order(
+        explosions
+            .iter()
+            .map(|(entity, explosion, position, owner, _)| {
+                let pos = position.0;
+                ((owner.0, pos.x, pos.y, explosion.frames_left), entity)
+            }),
+    );
+    for explosion in order {
+        let Ok((entity, mut explosion, position, owner, explosion_team)) =
+            explosions.get_mut(explosion)
+        else {
+            continue;
+        };
+        if explosion.frames_left == 0 {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        // the blast only does anything on its first frame, after that it just fades out
+        if explosion.frames_left == EXPLOSION_FRAMES {
+            let center = position.0;
+            let reach = Fixed::from_f32(EXPLOSION_RADIUS) + mutator.wizard_radius();
+            sfx.play(frame.0, Sfx::Explosion, owner.0, center.to_vec2());
+            bursts.send(
+                frame.0,
+                Burst::Explosion {
+                    pos: center.to_vec2(),
+                },
+            );
+            for (decoy_entity, decoy, decoy_pos, team) in &decoys {
+                let pos = decoy_pos.0;
+                if team != explosion_team && pos.distance_squared(center) <= reach * reach {
+                    pop_decoy(
+                        &mut commands,
+                        &mut bursts,
+                        frame.0,
+                        decoy_entity,
+                        decoy,
+                        pos.to_vec2(),
+                    );
+                }
+            }
+            let mut hurt_someone_else = false;
+            let caught = broadphase
+                .wizards
+                .entities_near(center, Fixed::from_f32(EXPLOSION_RADIUS));
+            for wizard in caught {
+                let Ok((
+                    player_entity,
+                    player_pos,
+                    player,
+                    team,
+                    mut health,
+                    armor,
+                    mut history,
+                    mut knockback,
+                    mut last_combat,
+                    _,
+                    mut stun,
+                )) = players.get_mut(wizard)
+                else {
+                    continue;
+                };
+                let offset = player_pos.0 - center;
+                let distance = offset.length();
+                if distance > reach || health.0 == 0 {
+                    continue;
+                }
+
+                // everybody gets pushed, even allies and the caster
+                let falloff = Fixed::ONE - distance / reach;
+                knockback.0 += offset.normalize_or_zero() * EXPLOSION_KNOCKBACK * falloff;
+                let pos = player_pos.0.to_vec2();
+
+                let mut modifiers = Vec::new();
+                let enemy = team != explosion_team;
+                if player.handle == owner.0 {
+                    modifiers.push(SELF_DAMAGE_PERCENT);
+                } else if !enemy {
+                    if !rules.friendly_fire {
+                        continue;
+                    }
+                    modifiers.push(rules.friendly_fire_percent);
+                } else {
+                    modifiers.push(combos.bonus_percent(owner.0));
+                }
+
+                last_combat.0 = frame.0;
+                hurt_someone_else |= player.handle != owner.0;
+                let damage = resolve_damage(EXPLOSION_DAMAGE, *armor, &modifiers);
+                combos.record(owner.0, player.handle, enemy);
+                if enemy {
+                    stun.apply(frame.0, EXPLOSION_STUN_FRAMES);
+                }
+                let target = Target {
+                    entity: player_entity,
+                    player,
+                    health: &mut health,
+                    history: &mut history,
+                    pos,
+                };
+                let attacker = Some(owner.0);
+                sfx.play(frame.0, Sfx::Impact, player.handle, pos);
+                let died = apply_damage(
+                    &mut commands,
+                    &mut kills,
+                    &mut telemetry,
+                    &mut hits,
+                    frame.0,
+                    target,
+                    attacker,
+                    damage,
+                    false,
+                );
+                if died {
+                    sfx.play(frame.0, Sfx::Death, player.handle, pos);
+                    bursts.send(
+                        frame.0,
+                        Burst::Death {
+                            pos,
+                            handle: player.handle,
+                        },
+                    );
+                }
+            }
+
+            if hurt_someone_else {
+                for (.., player, _, _, _, _, _, mut last_combat, _, _) in &mut players {
+                    if player.handle == owner.0 {
+                        last_combat.0 = frame.0;
+                    }
+                }
+            }
+        }
+
+        explosion.frames_left -= 1;
+    }
+
+    for (.., player, _, _, _, _, _, _, mut combo, _) in &mut players {
+        combos.update(player.handle, &mut combo);
+    }
+}
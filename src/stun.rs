@@ -0,0 +1,46 @@
+//! Stuns: a stunned wizard's inputs are ignored until it wears off.
+//!
+//! Stuns landing soon after each other are shorter every time, and past a
+//! few the wizard shrugs them off entirely, so nobody can be stun-locked.
+//! Everything lives in the rolled-back [`Stun`] component.
+
+use bevy::prelude::*;
+use bevy_ggrs::PlayerInputs;
+
+use crate::{components::*, Config};
+
+/// Stuns this soon after the last one are shortened
+const DIMINISHING_WINDOW_FRAMES: i32 = 4 * 60;
+/// Stuns within the window after which the wizard is immune until it's over
+const MAX_DIMINISHED_STUNS: u32 = 3;
+
+impl Stun {
+    /// Stuns the wizard for `frames`, halved for every other stun within the window
+    pub fn apply(&mut self, frame: i32, frames: u32) {
+        if frame - self.last_frame > DIMINISHING_WINDOW_FRAMES {
+            self.recent = 0;
+        }
+        if self.recent >= MAX_DIMINISHED_STUNS {
+            return;
+        }
+
+        self.frames_left = self.frames_left.max(frames >> self.recent);
+        self.recent += 1;
+        self.last_frame = frame;
+    }
+}
+
+/// Swaps the inputs of stunned wizards for nothing at all, for every system
+/// after it this frame. Runs after inputs are recorded, replays get the real ones.
+pub fn lock_stunned_inputs(
+    mut inputs: ResMut<PlayerInputs<Config>>,
+    mut players: Query<(&Player, &mut Stun)>,
+) {
+    for (player, mut stun) in &mut players {
+        if stun.frames_left == 0 {
+            continue;
+        }
+        stun.frames_left -= 1;
+        inputs[player.handle].0 = 0;
+    }
+}
@@ -0,0 +1,164 @@
+//! Per-match telemetry: everyone's inputs, every hit and the final result,
+//! exported as JSON when the match ends.
+//!
+//! The log lives outside the rollback state. Instead of snapshotting it, every
+//! simulated frame first throws away whatever was recorded for that frame and
+//! later, so a resimulated frame simply overwrites its mispredicted past.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_ggrs::PlayerInputs;
+use serde::Serialize;
+
+use crate::{
+    files::{save_file, timestamped_name},
+    input::Input,
+    round::{MatchStats, RoundNumber, RoundPhase, Scores, SimFrame},
+    Config, GameState,
+};
+
+/// Bumped whenever the layout of the exported file changes
+const TELEMETRY_VERSION: u32 = 2;
+const TELEMETRY_DIR: &str = "telemetry";
+
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchTelemetry>()
+            .add_systems(OnEnter(GameState::MatchOver), export_telemetry);
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct DamageEvent {
+    pub frame: i32,
+    /// `None` for damage from the environment
+    pub attacker: Option<usize>,
+    pub victim: usize,
+    pub amount: u32,
+    pub critical: bool,
+    pub fatal: bool,
+}
+
+/// Everything recorded about the match in progress
+#[derive(Resource, Default, Debug)]
+pub struct MatchTelemetry {
+    /// Raw inputs of every player, by simulation frame
+    inputs: BTreeMap<i32, Vec<Input>>,
+    damage: Vec<DamageEvent>,
+}
+
+impl MatchTelemetry {
+    pub fn record_damage(&mut self, event: DamageEvent) {
+        self.damage.push(event);
+    }
+
+    /// Every hit so far, oldest first
+    pub fn damage(&self) -> &[DamageEvent] {
+        &self.damage
+    }
+
+    /// Forgets everything from `frame` on, it's about to be simulated (again)
+    fn truncate(&mut self, frame: i32) {
+        self.inputs.split_off(&frame);
+        self.damage.retain(|event| event.frame < frame);
+    }
+}
+
+#[derive(Serialize)]
+struct MatchReport {
+    version: u32,
+    winner: usize,
+    rounds: u32,
+    /// Length of the match in simulation frames
+    frames: usize,
+    players: Vec<PlayerReport>,
+    /// Frames are counted from the start of the match
+    damage: Vec<DamageEvent>,
+}
+
+#[derive(Serialize)]
+struct PlayerReport {
+    handle: usize,
+    score: u32,
+    kills: u32,
+    deaths: u32,
+    assists: u32,
+    /// One input per frame, see `input.rs` for the bit layout
+    inputs: Vec<Input>,
+}
+
+pub fn record_inputs(
+    frame: Res<SimFrame>,
+    inputs: Res<PlayerInputs<Config>>,
+    mut telemetry: ResMut<MatchTelemetry>,
+) {
+    telemetry.truncate(frame.0);
+    telemetry
+        .inputs
+        .insert(frame.0, inputs.iter().map(|(input, _)| *input).collect());
+}
+
+fn export_telemetry(
+    phase: Res<RoundPhase>,
+    scores: Res<Scores>,
+    stats: Res<MatchStats>,
+    round: Res<RoundNumber>,
+    mut telemetry: ResMut<MatchTelemetry>,
+) {
+    let RoundPhase::MatchOver { winner, frame } = *phase else {
+        return;
+    };
+
+    // a rematch starts recording from scratch
+    let telemetry = std::mem::take(&mut *telemetry);
+    let start = telemetry.inputs.keys().next().copied().unwrap_or(frame);
+    let frames: Vec<&Vec<Input>> = telemetry.inputs.range(..=frame).map(|(_, i)| i).collect();
+    let num_players = frames.first().map_or(0, |inputs| inputs.len());
+
+    let report = MatchReport {
+        version: TELEMETRY_VERSION,
+        winner,
+        rounds: round.0,
+        frames: frames.len(),
+        players: (0..num_players)
+            .map(|handle| {
+                let stats = stats.get(handle);
+                PlayerReport {
+                    handle,
+                    score: scores.get(handle),
+                    kills: stats.kills,
+                    deaths: stats.deaths,
+                    assists: stats.assists,
+                    inputs: frames.iter().map(|inputs| inputs[handle]).collect(),
+                }
+            })
+            .collect(),
+        damage: telemetry
+            .damage
+            .iter()
+            .filter(|event| event.frame <= frame)
+            .map(|event| DamageEvent {
+                frame: event.frame - start,
+                ..*event
+            })
+            .collect(),
+    };
+
+    let json = match serde_json::to_string_pretty(&report) {
+        Ok(json) => json,
+        Err(err) => {
+            error!("failed to serialize match telemetry: {err}");
+            return;
+        }
+    };
+
+    save_file(
+        TELEMETRY_DIR,
+        &timestamped_name("match", "json"),
+        json.as_bytes(),
+        "application/json",
+    );
+}
@@ -0,0 +1,171 @@
+//! The arena floor and the wall around it, drawn as a tilemap.
+//!
+//! Tiles are batched into one mesh per chunk and layer, so the whole map is a
+//! handful of entities instead of one sprite per tile (or grid line). Purely
+//! visual, the simulation only knows the arena's size.
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    sprite::MaterialMesh2dBundle,
+};
+
+use crate::{assets::ArenaAssets, GameState, MAP_SIZE};
+
+/// Tiles along each side of a chunk
+const CHUNK_TILES: i32 = 16;
+/// Width of the gaps between floor tiles, where the grid color shows through
+const GRID_WIDTH: f32 = 0.05;
+const FLOOR_COLOR: Color = Color::rgb(0.53, 0.53, 0.53);
+const GRID_COLOR: Color = Color::rgb(0.27, 0.27, 0.27);
+/// Wooden planks from the middle of the door in the top left of the sheet
+const WALL_TILE: UVec2 = UVec2::new(1, 1);
+/// Size of a tile in Dungeon_Objects.png
+const SHEET_TILE_PIXELS: u32 = 16;
+const SHEET_PIXELS: u32 = 176;
+
+pub struct TilemapPlugin;
+
+impl Plugin for TilemapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnExit(GameState::AssetLoading),
+            // not when loading failed
+            spawn_tilemap.run_if(resource_exists::<ArenaAssets>),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Tile {
+    Floor,
+    Wall,
+}
+
+/// Tiles are a world unit across, centered on whole coordinates. The arena
+/// has a ring of wall around it, just outside where wizards can go.
+fn arena_tile(pos: IVec2) -> Option<Tile> {
+    let half = MAP_SIZE as i32 / 2;
+    match pos.x.abs().max(pos.y.abs()) {
+        distance if distance <= half => Some(Tile::Floor),
+        distance if distance == half + 1 => Some(Tile::Wall),
+        _ => None,
+    }
+}
+
+/// Quads batched into one mesh
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn add_quad(&mut self, center: Vec2, half_size: f32, uv_min: Vec2, uv_max: Vec2, color: Color) {
+        let first = self.positions.len() as u32;
+        for (corner, uv) in [
+            (Vec2::new(-1., -1.), Vec2::new(uv_min.x, uv_max.y)),
+            (Vec2::new(1., -1.), Vec2::new(uv_max.x, uv_max.y)),
+            (Vec2::new(1., 1.), Vec2::new(uv_max.x, uv_min.y)),
+            (Vec2::new(-1., 1.), Vec2::new(uv_min.x, uv_min.y)),
+        ] {
+            self.positions
+                .push((center + corner * half_size).extend(0.).to_array());
+            self.uvs.push(uv.to_array());
+            self.colors.push(color.as_linear_rgba_f32());
+        }
+        self.indices
+            .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    fn build(self) -> Option<Mesh> {
+        if self.indices.is_empty() {
+            return None;
+        }
+        let mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, self.colors)
+        .with_inserted_indices(Indices::U32(self.indices));
+        Some(mesh)
+    }
+}
+
+/// Where `tile` is in the sheet, pulled in half a texel so neighbors don't bleed in
+fn sheet_uvs(tile: UVec2) -> (Vec2, Vec2) {
+    let min = (tile * SHEET_TILE_PIXELS).as_vec2() + 0.5;
+    let max = ((tile + 1) * SHEET_TILE_PIXELS).as_vec2() - 0.5;
+    (min / SHEET_PIXELS as f32, max / SHEET_PIXELS as f32)
+}
+
+fn spawn_tilemap(
+    mut commands: Commands,
+    arena: Res<ArenaAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let floor_material = materials.add(ColorMaterial::default());
+    let wall_material = materials.add(arena.tiles.clone());
+    let (wall_uv_min, wall_uv_max) = sheet_uvs(WALL_TILE);
+
+    // the grid lines are what's left of this between the floor tiles
+    commands.spawn(SpriteBundle {
+        sprite: Sprite {
+            color: GRID_COLOR,
+            custom_size: Some(Vec2::splat(MAP_SIZE as f32)),
+            ..default()
+        },
+        transform: Transform::from_xyz(0., 0., -0.1),
+        ..default()
+    });
+
+    let extent = MAP_SIZE as i32 / 2 + 1;
+    let chunks = (-extent..=extent).step_by(CHUNK_TILES as usize);
+    for chunk_y in chunks.clone() {
+        for chunk_x in chunks.clone() {
+            let mut floor = MeshBuilder::default();
+            let mut walls = MeshBuilder::default();
+
+            for y in chunk_y..(chunk_y + CHUNK_TILES).min(extent + 1) {
+                for x in chunk_x..(chunk_x + CHUNK_TILES).min(extent + 1) {
+                    let center = Vec2::new(x as f32, y as f32);
+                    match arena_tile(IVec2::new(x, y)) {
+                        Some(Tile::Floor) => floor.add_quad(
+                            center,
+                            0.5 - GRID_WIDTH / 2.,
+                            Vec2::ZERO,
+                            Vec2::ONE,
+                            FLOOR_COLOR,
+                        ),
+                        Some(Tile::Wall) => {
+                            walls.add_quad(center, 0.5, wall_uv_min, wall_uv_max, Color::WHITE)
+                        }
+                        None => {}
+                    }
+                }
+            }
+
+            for (mesh, material) in [
+                (floor.build(), &floor_material),
+                (walls.build(), &wall_material),
+            ] {
+                let Some(mesh) = mesh else {
+                    continue;
+                };
+                commands.spawn(MaterialMesh2dBundle {
+                    mesh: meshes.add(mesh).into(),
+                    material: material.clone(),
+                    ..default()
+                });
+            }
+        }
+    }
+}
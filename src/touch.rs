@@ -0,0 +1,399 @@
+//! Playing on a phone or tablet.
+//!
+//! Browsers with a coarse pointer or a touch screen get on-screen controls
+//! without having to ask for them: a stick that appears wherever the left half
+//! of the screen is touched, buttons for the spells on the right, a bigger UI
+//! to hit with a finger, and a prompt to turn the device sideways while it's
+//! held upright. The first touch anywhere turns it all on too, for devices
+//! that didn't say they have a touch screen.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::GameState;
+
+/// Everything is hard to hit with a finger at its usual size
+const TOUCH_UI_SCALE: f32 = 1.5;
+/// How far the stick has to be pulled, in UI pixels, to run at full speed
+const STICK_RADIUS: f32 = 60.;
+/// Share of [`STICK_RADIUS`] a resting thumb may wander without moving the wizard
+const STICK_DEADZONE: f32 = 0.25;
+const KNOB_SIZE: f32 = 50.;
+const BUTTON_SIZE: f32 = 80.;
+
+const STICK_COLOR: Color = Color::rgba(1., 1., 1., 0.15);
+const KNOB_COLOR: Color = Color::rgba(1., 1., 1., 0.5);
+const BUTTON_COLOR: Color = Color::rgba(0.15, 0.15, 0.15, 0.6);
+const BUTTON_PRESSED_COLOR: Color = Color::rgba(0.35, 0.35, 0.35, 0.8);
+
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TouchUi(has_touch_screen()))
+            .init_resource::<TouchControls>()
+            .add_systems(Startup, (spawn_touch_controls, spawn_orientation_prompt))
+            .add_systems(OnExit(GameState::InGame), release_touch_controls)
+            .add_systems(
+                Update,
+                (
+                    enable_on_touch,
+                    apply_ui_scale.run_if(resource_changed::<TouchUi>),
+                    read_touches
+                        .run_if(touch_ui_on)
+                        .run_if(in_state(GameState::InGame)),
+                    update_touch_controls,
+                    update_orientation_prompt,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Whether the touch controls and the bigger UI are on
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TouchUi(pub bool);
+
+fn touch_ui_on(touch_ui: Res<TouchUi>) -> bool {
+    touch_ui.0
+}
+
+/// What the on-screen controls are being held at, merged into the keyboard's
+/// input by `read_local_inputs`
+#[derive(Resource, Default, Debug)]
+pub struct TouchControls {
+    /// Up is positive, at most 1 long
+    pub direction: Vec2,
+    pub fire: bool,
+    pub cast: bool,
+    pub next_spell: bool,
+    pub ultimate: bool,
+    /// The finger on the stick, and where it first touched down, in UI pixels
+    stick: Option<(u64, Vec2)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+fn has_touch_screen() -> bool {
+    let Some(window) = web_sys::window() else {
+        return false;
+    };
+    let coarse_pointer = window
+        .match_media("(pointer: coarse)")
+        .ok()
+        .flatten()
+        .is_some_and(|query| query.matches());
+    coarse_pointer || window.navigator().max_touch_points() > 0
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn has_touch_screen() -> bool {
+    false
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TouchAction {
+    Fire,
+    Cast,
+    NextSpell,
+    Ultimate,
+}
+
+impl TouchAction {
+    const ALL: [TouchAction; 4] = [Self::NextSpell, Self::Ultimate, Self::Cast, Self::Fire];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Fire => "Fire",
+            Self::Cast => "Cast",
+            Self::NextSpell => "Next",
+            Self::Ultimate => "Ult",
+        }
+    }
+
+    fn pressed(self, controls: &TouchControls) -> bool {
+        match self {
+            Self::Fire => controls.fire,
+            Self::Cast => controls.cast,
+            Self::NextSpell => controls.next_spell,
+            Self::Ultimate => controls.ultimate,
+        }
+    }
+}
+
+#[derive(Component)]
+struct TouchControlsRoot;
+
+#[derive(Component)]
+struct StickBase;
+
+#[derive(Component)]
+struct StickKnob;
+
+#[derive(Component)]
+struct TouchButton(TouchAction);
+
+#[derive(Component)]
+struct OrientationPrompt;
+
+fn spawn_touch_controls(mut commands: Commands) {
+    commands
+        .spawn((
+            TouchControlsRoot,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    StickBase,
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            width: Val::Px(STICK_RADIUS * 2.),
+                            height: Val::Px(STICK_RADIUS * 2.),
+                            ..default()
+                        },
+                        background_color: STICK_COLOR.into(),
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        StickKnob,
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                width: Val::Px(KNOB_SIZE),
+                                height: Val::Px(KNOB_SIZE),
+                                ..default()
+                            },
+                            background_color: KNOB_COLOR.into(),
+                            ..default()
+                        },
+                    ));
+                });
+
+            // two by two in the bottom right corner, fire closest to the thumb
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        right: Val::Px(24.),
+                        bottom: Val::Px(24.),
+                        width: Val::Px(BUTTON_SIZE * 2. + 16.),
+                        flex_wrap: FlexWrap::Wrap,
+                        column_gap: Val::Px(16.),
+                        row_gap: Val::Px(16.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for action in TouchAction::ALL {
+                        parent
+                            .spawn((
+                                TouchButton(action),
+                                NodeBundle {
+                                    style: Style {
+                                        width: Val::Px(BUTTON_SIZE),
+                                        height: Val::Px(BUTTON_SIZE),
+                                        align_items: AlignItems::Center,
+                                        justify_content: JustifyContent::Center,
+                                        ..default()
+                                    },
+                                    background_color: BUTTON_COLOR.into(),
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    action.label(),
+                                    TextStyle {
+                                        font_size: 22.,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    }
+                });
+        });
+}
+
+fn spawn_orientation_prompt(mut commands: Commands) {
+    commands
+        .spawn((
+            OrientationPrompt,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.9).into(),
+                // over everything, nothing's playable upright anyway
+                z_index: ZIndex::Global(200),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "Turn your device sideways to play",
+                    TextStyle {
+                        font_size: 30.,
+                        ..default()
+                    },
+                )
+                .with_text_justify(JustifyText::Center),
+            );
+        });
+}
+
+fn enable_on_touch(touches: Res<Touches>, mut touch_ui: ResMut<TouchUi>) {
+    if !touch_ui.0 && touches.any_just_pressed() {
+        info!("touched the screen, switching to touch controls");
+        touch_ui.0 = true;
+    }
+}
+
+fn apply_ui_scale(touch_ui: Res<TouchUi>, mut ui_scale: ResMut<UiScale>) {
+    ui_scale.0 = if touch_ui.0 { TOUCH_UI_SCALE } else { 1. };
+}
+
+/// Works out what every finger on the screen is holding. Each one is checked
+/// on its own, bevy's buttons only ever follow the first.
+fn read_touches(
+    touches: Res<Touches>,
+    ui_scale: Res<UiScale>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    buttons: Query<(&TouchButton, &Node, &GlobalTransform)>,
+    mut controls: ResMut<TouchControls>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    if controls
+        .stick
+        .is_some_and(|(id, _)| touches.get_pressed(id).is_none())
+    {
+        controls.stick = None;
+    }
+
+    let (mut fire, mut cast, mut next_spell, mut ultimate) = (false, false, false, false);
+    for touch in touches.iter() {
+        if controls.stick.is_some_and(|(id, _)| id == touch.id()) {
+            continue;
+        }
+        // touches are in window pixels, the UI is laid out scaled down by this
+        let position = touch.position() / ui_scale.0;
+        let button = buttons
+            .iter()
+            .find(|(_, node, transform)| node.logical_rect(transform).contains(position));
+        match button.map(|(button, ..)| button.0) {
+            Some(TouchAction::Fire) => fire = true,
+            Some(TouchAction::Cast) => cast = true,
+            Some(TouchAction::NextSpell) => next_spell = true,
+            Some(TouchAction::Ultimate) => ultimate = true,
+            None if controls.stick.is_none()
+                && touches.just_pressed(touch.id())
+                && touch.position().x < window.width() / 2. =>
+            {
+                controls.stick = Some((touch.id(), position));
+            }
+            None => {}
+        }
+    }
+
+    let direction = controls.stick.and_then(|(id, start)| {
+        let offset = (touches.get_pressed(id)?.position() / ui_scale.0 - start) / STICK_RADIUS;
+        // screen y goes down
+        Some(Vec2::new(offset.x, -offset.y).clamp_length_max(1.))
+    });
+    controls.direction = direction
+        .filter(|direction| direction.length() >= STICK_DEADZONE)
+        .unwrap_or(Vec2::ZERO);
+    controls.fire = fire;
+    controls.cast = cast;
+    controls.next_spell = next_spell;
+    controls.ultimate = ultimate;
+}
+
+/// Nobody's holding anything once the match is over
+fn release_touch_controls(mut controls: ResMut<TouchControls>) {
+    *controls = TouchControls::default();
+}
+
+fn update_touch_controls(
+    touch_ui: Res<TouchUi>,
+    state: Res<State<GameState>>,
+    controls: Res<TouchControls>,
+    mut roots: Query<&mut Visibility, (With<TouchControlsRoot>, Without<StickBase>)>,
+    mut bases: Query<(&mut Style, &mut Visibility), (With<StickBase>, Without<StickKnob>)>,
+    mut knobs: Query<&mut Style, (With<StickKnob>, Without<StickBase>)>,
+    mut buttons: Query<(&TouchButton, &mut BackgroundColor)>,
+) {
+    let shown = touch_ui.0 && *state.get() == GameState::InGame;
+    for mut visibility in &mut roots {
+        visibility.set_if_neq(if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+    if !shown {
+        return;
+    }
+
+    for (mut style, mut visibility) in &mut bases {
+        let Some((_, start)) = controls.stick else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+        style.left = Val::Px(start.x - STICK_RADIUS);
+        style.top = Val::Px(start.y - STICK_RADIUS);
+    }
+    let knob = Vec2::splat(STICK_RADIUS - KNOB_SIZE / 2.)
+        + Vec2::new(controls.direction.x, -controls.direction.y) * STICK_RADIUS;
+    for mut style in &mut knobs {
+        style.left = Val::Px(knob.x);
+        style.top = Val::Px(knob.y);
+    }
+
+    for (button, mut background) in &mut buttons {
+        background.0 = if button.0.pressed(&controls) {
+            BUTTON_PRESSED_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+    }
+}
+
+fn update_orientation_prompt(
+    touch_ui: Res<TouchUi>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut prompts: Query<&mut Visibility, With<OrientationPrompt>>,
+) {
+    let upright = windows
+        .get_single()
+        .is_ok_and(|window| window.height() > window.width());
+    for mut visibility in &mut prompts {
+        visibility.set_if_neq(if touch_ui.0 && upright {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
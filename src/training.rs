@@ -0,0 +1,424 @@
+//! Training range: an endless round against target dummies, for trying out
+//! spells and combos without anybody shooting back.
+//!
+//! Dummies are wizards like any other, driven by a fixed input pattern the way
+//! bots are, so every hit goes through the regular damage pipeline. The range
+//! runs in a local session, and its reset button travels through the input bits
+//! like everything else that changes the simulation.
+
+use bevy::prelude::*;
+use bevy_ggrs::{LocalInputs, PlayerInputs, Rollback};
+use bevy_matchbox::{matchbox_socket::MultipleChannels, MatchboxSocket};
+
+use crate::{
+    bot::start_local_session,
+    components::*,
+    fixed::SimVec2,
+    input::{encode_input, gamepad_just_pressed, read_local_inputs, rematch},
+    not_typing,
+    prompts::{Control, InputDevice},
+    round::{teardown_match, RoundKills, RoundPhase, SimFrame},
+    spawn_wizard,
+    telemetry::MatchTelemetry,
+    Config, GameState, MAX_HEALTH, PLAYER_COLORS,
+};
+
+const PLAYER_SPAWN: Vec2 = Vec2::new(-2., 0.);
+const DUMMIES: [(Vec2, DummyKind); 3] = [
+    (Vec2::new(3., 2.), DummyKind::Stationary),
+    (Vec2::new(3., -2.), DummyKind::Stationary),
+    (Vec2::new(7., 0.), DummyKind::Moving),
+];
+/// The player, and a handle for each dummy after that
+const NUM_PLAYERS: usize = 1 + DUMMIES.len();
+const DUMMY_COLOR: Color = Color::rgb(0.6, 0.45, 0.3);
+/// Frames a dummy has to be left alone before it's back to full health
+const DUMMY_HEAL_DELAY_FRAMES: i32 = 2 * 60;
+/// Frames a moving dummy walks in one direction before turning around
+const DUMMY_STRAFE_FRAMES: i32 = 45;
+/// How far back the DPS meter looks
+const DPS_WINDOW_FRAMES: i32 = 5 * 60;
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+
+#[derive(Clone, Copy)]
+enum DummyKind {
+    Stationary,
+    /// Walks up and down in front of where it spawned
+    Moving,
+}
+
+pub struct TrainingPlugin;
+
+impl Plugin for TrainingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ResetRequested>()
+            .add_systems(Startup, training_from_args)
+            .add_systems(
+                OnEnter(GameState::Matchmaking),
+                start_training_session.run_if(resource_exists::<TrainingRange>),
+            )
+            .add_systems(
+                OnEnter(GameState::InGame),
+                spawn_training_hud.run_if(resource_exists::<TrainingRange>),
+            )
+            .add_systems(OnExit(GameState::InGame), despawn_training_hud)
+            .add_systems(
+                Update,
+                (
+                    training_on_key_press
+                        .run_if(in_state(GameState::Matchmaking))
+                        .run_if(not_typing),
+                    (update_dps_meter, reset_range_button, leave_training_range)
+                        .run_if(in_state(GameState::InGame))
+                        .run_if(resource_exists::<TrainingRange>),
+                    update_button_labels.run_if(resource_changed::<InputDevice>),
+                ),
+            )
+            .add_systems(
+                bevy_ggrs::ReadInputs,
+                read_training_inputs
+                    .after(read_local_inputs)
+                    .run_if(resource_exists::<TrainingRange>),
+            );
+    }
+}
+
+/// Present while playing in the training range instead of a match
+#[derive(Resource, Clone, Copy, Default, Debug)]
+pub struct TrainingRange {
+    /// Simulation frame the range was last reset on, the DPS meter starts over from there
+    reset_frame: i32,
+}
+
+/// Set when the player asks for a reset, sent with their next input
+#[derive(Resource, Default)]
+struct ResetRequested(bool);
+
+#[derive(Component)]
+struct TrainingHud;
+
+#[derive(Component)]
+struct DpsText;
+
+#[derive(Component)]
+struct ResetButton;
+
+#[derive(Component)]
+struct LeaveButton;
+
+/// A button's text, followed by its key for the device in use
+#[derive(Component)]
+struct ButtonLabel(&'static str, Control);
+
+/// `--training` skips matchmaking and goes straight to the training range
+fn training_from_args(mut commands: Commands) {
+    if std::env::args().any(|arg| arg == "--training") {
+        commands.insert_resource(TrainingRange::default());
+    }
+}
+
+/// Pressing T while waiting for an opponent goes to the training range instead
+fn training_on_key_press(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    training: Option<Res<TrainingRange>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keys.just_pressed(KeyCode::KeyT) && training.is_none() {
+        enter_training_range(&mut commands, &mut next_state);
+    }
+}
+
+/// Gives up on matchmaking and goes to the training range right away
+pub fn enter_training_range(commands: &mut Commands, next_state: &mut NextState<GameState>) {
+    commands.remove_resource::<MatchboxSocket<MultipleChannels>>();
+    commands.insert_resource(TrainingRange::default());
+    start_training(commands, next_state);
+}
+
+fn start_training_session(mut commands: Commands, mut next_state: ResMut<NextState<GameState>>) {
+    start_training(&mut commands, &mut next_state);
+}
+
+fn start_training(commands: &mut Commands, next_state: &mut NextState<GameState>) {
+    info!("entering the training range");
+    start_local_session(commands, NUM_PLAYERS, next_state);
+}
+
+/// Spawns the player and every dummy, called by `start_round` instead of the usual arena
+pub fn spawn_range(commands: &mut Commands) {
+    for handle in 0..NUM_PLAYERS {
+        spawn_range_wizard(commands, handle);
+    }
+}
+
+fn spawn_range_wizard(commands: &mut Commands, handle: usize) {
+    if handle == 0 {
+        spawn_wizard(
+            commands,
+            0,
+            0,
+            PLAYER_SPAWN.into(),
+            SimVec2::X,
+            PLAYER_COLORS[0],
+        );
+        return;
+    }
+
+    let (pos, _) = DUMMIES[handle - 1];
+    let dummy = spawn_wizard(commands, handle, 1, pos.into(), -SimVec2::X, DUMMY_COLOR);
+    commands.entity(dummy).insert(Dummy);
+}
+
+/// Keeps the range going: no countdown, no round end, dummies heal up when
+/// left alone and anyone who dies comes right back
+pub fn run_training_range(
+    mut commands: Commands,
+    inputs: Res<PlayerInputs<Config>>,
+    frame: Res<SimFrame>,
+    mut training: ResMut<TrainingRange>,
+    mut phase: ResMut<RoundPhase>,
+    mut kills: ResMut<RoundKills>,
+    mut players: Query<(Entity, &Player, &mut Health, &LastCombatFrame)>,
+    projectiles: Query<Entity, (With<Rollback>, Without<Player>, Without<Wall>)>,
+) {
+    if matches!(*phase, RoundPhase::Countdown { .. }) {
+        *phase = RoundPhase::Fighting;
+    }
+
+    // the rematch bit doubles as the reset button, there's no match to rematch
+    let (input, _) = inputs[0];
+    if rematch(input) {
+        for (entity, ..) in &players {
+            commands.entity(entity).despawn_recursive();
+        }
+        for entity in &projectiles {
+            commands.entity(entity).despawn_recursive();
+        }
+        spawn_range(&mut commands);
+        kills.0.clear();
+        training.reset_frame = frame.0;
+        return;
+    }
+
+    let mut alive = [false; NUM_PLAYERS];
+    for (_, player, mut health, last_combat) in &mut players {
+        alive[player.handle] = true;
+        if player.handle != 0
+            && health.0 < MAX_HEALTH
+            && frame.0 - last_combat.0 >= DUMMY_HEAL_DELAY_FRAMES
+        {
+            health.0 = MAX_HEALTH;
+        }
+    }
+
+    for (handle, _) in alive.iter().enumerate().filter(|(_, alive)| !**alive) {
+        spawn_range_wizard(&mut commands, handle);
+    }
+}
+
+/// Replaces the keyboard input `read_local_inputs` gave the dummies' handles,
+/// and adds the player's reset request
+fn read_training_inputs(
+    mut local_inputs: ResMut<LocalInputs<Config>>,
+    mut reset: ResMut<ResetRequested>,
+    frame: Res<SimFrame>,
+) {
+    if std::mem::take(&mut reset.0) {
+        *local_inputs.0.entry(0).or_default() |= encode_input(Vec2::ZERO, false, false, true);
+    }
+
+    for (i, (_, kind)) in DUMMIES.iter().enumerate() {
+        let input = match kind {
+            DummyKind::Stationary => 0,
+            DummyKind::Moving => {
+                let direction = if (frame.0 / DUMMY_STRAFE_FRAMES) % 2 == 0 {
+                    Vec2::Y
+                } else {
+                    -Vec2::Y
+                };
+                encode_input(direction, false, false, false)
+            }
+        };
+        local_inputs.0.insert(i + 1, input);
+    }
+}
+
+fn spawn_training_hud(mut commands: Commands, device: Res<InputDevice>) {
+    commands
+        .spawn((
+            TrainingHud,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.),
+                    left: Val::Px(10.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::FlexStart,
+                    row_gap: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                DpsText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 28.,
+                        ..default()
+                    },
+                ),
+            ));
+            parent
+                .spawn((
+                    ResetButton,
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::axes(Val::Px(16.), Val::Px(8.)),
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        ButtonLabel("Reset", Control::Reset),
+                        TextBundle::from_section(
+                            device.label("Reset", Control::Reset),
+                            TextStyle {
+                                font_size: 22.,
+                                ..default()
+                            },
+                        ),
+                    ));
+                });
+            // a button too, touch screens have no Esc
+            parent
+                .spawn((
+                    LeaveButton,
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::axes(Val::Px(12.), Val::Px(6.)),
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOR.into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        ButtonLabel("Leave", Control::Leave),
+                        TextBundle::from_section(
+                            device.label("Leave", Control::Leave),
+                            TextStyle {
+                                font_size: 18.,
+                                color: Color::rgb(0.8, 0.8, 0.8),
+                                ..default()
+                            },
+                        ),
+                    ));
+                });
+        });
+}
+
+fn update_button_labels(device: Res<InputDevice>, mut labels: Query<(&ButtonLabel, &mut Text)>) {
+    for (label, mut text) in &mut labels {
+        text.sections[0].value = device.label(label.0, label.1);
+    }
+}
+
+fn despawn_training_hud(mut commands: Commands, huds: Query<Entity, With<TrainingHud>>) {
+    for hud in &huds {
+        commands.entity(hud).despawn_recursive();
+    }
+}
+
+/// Damage the player dealt to dummies over the last few seconds, and since the last reset
+fn update_dps_meter(
+    telemetry: Res<MatchTelemetry>,
+    training: Res<TrainingRange>,
+    frame: Res<SimFrame>,
+    mut texts: Query<&mut Text, With<DpsText>>,
+) {
+    let dealt = || {
+        telemetry
+            .damage()
+            .iter()
+            .filter(|event| event.attacker == Some(0) && event.victim != 0)
+            .filter(|event| event.frame > training.reset_frame)
+    };
+
+    let window_start = (frame.0 - DPS_WINDOW_FRAMES).max(training.reset_frame);
+    let recent: u32 = dealt()
+        .filter(|event| event.frame > window_start)
+        .map(|event| event.amount)
+        .sum();
+    // don't let the first hit after a reset read as a huge spike
+    let seconds = (frame.0 - window_start).max(60) as f32 / 60.;
+    let total: u32 = dealt().map(|event| event.amount).sum();
+
+    for mut text in &mut texts {
+        text.sections[0].value = format!("DPS: {:.1}\nTotal: {total}", recent as f32 / seconds);
+    }
+}
+
+fn reset_range_button(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut buttons: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<ResetButton>),
+    >,
+    mut reset: ResMut<ResetRequested>,
+) {
+    if keys.just_pressed(KeyCode::KeyR)
+        || gamepad_just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::Select)
+    {
+        reset.0 = true;
+    }
+
+    for (interaction, mut background) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => reset.0 = true,
+            Interaction::Hovered => background.0 = BUTTON_HOVER_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+}
+
+/// Esc goes back to the menu, same as leaving a match from the end screen
+fn leave_training_range(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    mut buttons: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<LeaveButton>),
+    >,
+    rollback_entities: Query<Entity, With<Rollback>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let mut leave = keys.just_pressed(KeyCode::Escape)
+        || gamepad_just_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::Start);
+    for (interaction, mut background) in &mut buttons {
+        match *interaction {
+            Interaction::Pressed => leave = true,
+            Interaction::Hovered => background.0 = BUTTON_HOVER_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+    if !leave {
+        return;
+    }
+
+    teardown_match(&mut commands, &rollback_entities);
+    next_state.set(GameState::Matchmaking);
+}
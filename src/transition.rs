@@ -0,0 +1,131 @@
+//! Screen transitions: a black overlay that fades or wipes away to reveal
+//! the new screen. Game state changes and arena resets between rounds play
+//! one on their own, anything else can send a [`ScreenTransition`].
+
+use bevy::prelude::*;
+
+use crate::{round::RoundPhase, GameState};
+
+const TRANSITION_SECS: f32 = 0.4;
+const OVERLAY_COLOR: Color = Color::BLACK;
+
+pub struct TransitionPlugin;
+
+impl Plugin for TransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ScreenTransition>().add_systems(
+            Update,
+            (
+                transition_on_state_change,
+                transition_on_new_round.run_if(in_state(GameState::InGame)),
+                start_transitions,
+                update_transitions,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransitionStyle {
+    /// The overlay turns transparent
+    Fade,
+    /// The overlay slides off to the right
+    Wipe,
+}
+
+/// Covers the screen right away and then reveals it
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ScreenTransition(pub TransitionStyle);
+
+#[derive(Component)]
+struct TransitionOverlay {
+    style: TransitionStyle,
+    elapsed: f32,
+}
+
+fn transition_on_state_change(
+    mut state_changes: EventReader<StateTransitionEvent<GameState>>,
+    mut transitions: EventWriter<ScreenTransition>,
+) {
+    for change in state_changes.read() {
+        let style = match (&change.before, &change.after) {
+            (GameState::Matchmaking | GameState::MatchOver, GameState::InGame) => {
+                TransitionStyle::Wipe
+            }
+            _ => TransitionStyle::Fade,
+        };
+        transitions.send(ScreenTransition(style));
+    }
+}
+
+/// The arena is cleared and the wizards put back once the intermission ends
+fn transition_on_new_round(
+    phase: Res<RoundPhase>,
+    mut transitions: EventWriter<ScreenTransition>,
+    mut in_intermission: Local<bool>,
+) {
+    let intermission = matches!(*phase, RoundPhase::Intermission { .. });
+    if *in_intermission && matches!(*phase, RoundPhase::NewRound | RoundPhase::Countdown { .. }) {
+        transitions.send(ScreenTransition(TransitionStyle::Fade));
+    }
+    *in_intermission = intermission;
+}
+
+fn start_transitions(
+    mut commands: Commands,
+    mut transitions: EventReader<ScreenTransition>,
+    overlays: Query<Entity, With<TransitionOverlay>>,
+) {
+    // only the latest one matters if several came in at once
+    let Some(&ScreenTransition(style)) = transitions.read().last() else {
+        return;
+    };
+
+    for overlay in &overlays {
+        commands.entity(overlay).despawn_recursive();
+    }
+    commands.spawn((
+        TransitionOverlay { style, elapsed: 0. },
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            background_color: OVERLAY_COLOR.into(),
+            // over everything but the settings panel
+            z_index: ZIndex::Global(90),
+            ..default()
+        },
+    ));
+}
+
+fn update_transitions(
+    mut commands: Commands,
+    // real time, replays speed up and pause virtual time
+    time: Res<Time<Real>>,
+    mut overlays: Query<(
+        Entity,
+        &mut TransitionOverlay,
+        &mut Style,
+        &mut BackgroundColor,
+    )>,
+) {
+    for (entity, mut overlay, mut style, mut background) in &mut overlays {
+        overlay.elapsed += time.delta_seconds();
+        let t = overlay.elapsed / TRANSITION_SECS;
+        if t >= 1. {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        // ease out, the new screen shows up quickly and settles in
+        let eased = 1. - (1. - t).powi(2);
+        match overlay.style {
+            TransitionStyle::Fade => background.0 = OVERLAY_COLOR.with_a(1. - eased),
+            TransitionStyle::Wipe => style.left = Val::Percent(eased * 100.),
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Guided first steps on the training range: walk somewhere, shoot a dummy,
+//! cast a fireball and finish one off.
+//!
+//! The tutorial only watches. It plays on a regular training range session and
+//! checks the simulation after every update to see if the current step is done.
+
+use bevy::prelude::*;
+
+use crate::{
+    components::*,
+    not_typing,
+    prompts::{Control, InputDevice},
+    round::{RoundKills, SimFrame},
+    telemetry::MatchTelemetry,
+    training::{enter_training_range, TrainingRange},
+    GameState,
+};
+
+/// Where the player has to walk in the first step
+const MARKER_POS: Vec2 = Vec2::new(-2., 3.);
+const MARKER_SIZE: f32 = 1.2;
+
+pub struct TutorialPlugin;
+
+impl Plugin for TutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, tutorial_from_args)
+            .add_systems(
+                OnEnter(GameState::InGame),
+                spawn_tutorial.run_if(resource_exists::<Tutorial>),
+            )
+            .add_systems(OnExit(GameState::InGame), end_tutorial)
+            .add_systems(
+                Update,
+                (
+                    tutorial_on_key_press
+                        .run_if(in_state(GameState::Matchmaking))
+                        .run_if(not_typing),
+                    (advance_tutorial, update_tutorial_prompt)
+                        .chain()
+                        .run_if(in_state(GameState::InGame))
+                        .run_if(resource_exists::<Tutorial>),
+                ),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TutorialStep {
+    Move,
+    Fire,
+    Cast,
+    Finish,
+    Done,
+}
+
+impl TutorialStep {
+    /// Steps the player has to get through, `Done` isn't one of them
+    const COUNT: usize = 4;
+
+    fn prompt(self, device: InputDevice) -> String {
+        match self {
+            Self::Move => format!("Walk onto the marker with {}", device.glyph(Control::Move)),
+            Self::Fire => format!(
+                "Face a dummy and fire at it with {}",
+                device.glyph(Control::Fire)
+            ),
+            Self::Cast => format!(
+                "Cast a fireball with {}, mind the blast",
+                device.glyph(Control::Cast)
+            ),
+            Self::Finish => "Finish off a dummy".into(),
+            Self::Done => format!(
+                "Tutorial complete! Keep practicing, or leave with {}",
+                device.glyph(Control::Leave)
+            ),
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::Move => Self::Fire,
+            Self::Fire => Self::Cast,
+            Self::Cast => Self::Finish,
+            Self::Finish | Self::Done => Self::Done,
+        }
+    }
+}
+
+/// Present while the tutorial is running, on top of the training range
+#[derive(Resource, Debug)]
+struct Tutorial {
+    step: TutorialStep,
+    /// Simulation frame the current step started on, only what happens after counts
+    step_started: i32,
+}
+
+impl Default for Tutorial {
+    fn default() -> Self {
+        Self {
+            step: TutorialStep::Move,
+            step_started: 0,
+        }
+    }
+}
+
+#[derive(Component)]
+struct TutorialPrompt;
+
+#[derive(Component)]
+struct TutorialMarker;
+
+/// `--tutorial` skips matchmaking and goes straight to the tutorial
+fn tutorial_from_args(mut commands: Commands) {
+    if std::env::args().any(|arg| arg == "--tutorial") {
+        commands.insert_resource(TrainingRange::default());
+        commands.insert_resource(Tutorial::default());
+    }
+}
+
+/// Pressing H while waiting for an opponent starts the tutorial instead
+fn tutorial_on_key_press(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    training: Option<Res<TrainingRange>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keys.just_pressed(KeyCode::KeyH) && training.is_none() {
+        info!("starting the tutorial");
+        commands.insert_resource(Tutorial::default());
+        enter_training_range(&mut commands, &mut next_state);
+    }
+}
+
+fn spawn_tutorial(mut commands: Commands) {
+    commands.spawn((
+        TutorialMarker,
+        SpriteBundle {
+            transform: Transform::from_translation(MARKER_POS.extend(0.5)),
+            sprite: Sprite {
+                color: Color::rgba(1., 0.9, 0.2, 0.5),
+                custom_size: Some(Vec2::splat(MARKER_SIZE)),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+
+    commands
+        .spawn((
+            TutorialPrompt,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(40.),
+                    width: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 30.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn end_tutorial(
+    mut commands: Commands,
+    prompts: Query<Entity, Or<(With<TutorialPrompt>, With<TutorialMarker>)>>,
+) {
+    for entity in &prompts {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<Tutorial>();
+}
+
+/// Moves on to the next step once the player did what the current one asks
+fn advance_tutorial(
+    mut commands: Commands,
+    mut tutorial: ResMut<Tutorial>,
+    frame: Res<SimFrame>,
+    telemetry: Res<MatchTelemetry>,
+    kills: Res<RoundKills>,
+    players: Query<(&Player, &Position)>,
+    fireballs: Query<&Owner, With<Fireball>>,
+    markers: Query<Entity, With<TutorialMarker>>,
+) {
+    let since = tutorial.step_started;
+    let done = match tutorial.step {
+        TutorialStep::Move => players.iter().any(|(player, position)| {
+            player.handle == 0 && position.0.to_vec2().distance(MARKER_POS) < MARKER_SIZE / 2.
+        }),
+        TutorialStep::Fire => telemetry
+            .damage()
+            .iter()
+            .any(|event| event.frame >= since && event.attacker == Some(0) && event.victim != 0),
+        TutorialStep::Cast => fireballs.iter().any(|owner| owner.0 == 0),
+        TutorialStep::Finish => kills
+            .0
+            .iter()
+            .any(|kill| kill.killer == 0 && kill.victim != 0),
+        TutorialStep::Done => false,
+    };
+    if !done {
+        return;
+    }
+
+    if tutorial.step == TutorialStep::Move {
+        for marker in &markers {
+            commands.entity(marker).despawn_recursive();
+        }
+    }
+    tutorial.step = tutorial.step.next();
+    tutorial.step_started = frame.0;
+    info!("tutorial step {:?}", tutorial.step);
+}
+
+fn update_tutorial_prompt(
+    tutorial: Res<Tutorial>,
+    device: Res<InputDevice>,
+    prompts: Query<&Children, With<TutorialPrompt>>,
+    mut texts: Query<&mut Text>,
+) {
+    let label = match tutorial.step {
+        TutorialStep::Done => tutorial.step.prompt(*device),
+        step => format!(
+            "Step {}/{}: {}",
+            step as usize + 1,
+            TutorialStep::COUNT,
+            step.prompt(*device)
+        ),
+    };
+
+    for children in &prompts {
+        for &child in children {
+            if let Ok(mut text) = texts.get_mut(child) {
+                if text.sections[0].value != label {
+                    text.sections[0].value = label.clone();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Ultimates: a meter that fills up from dealing and taking damage, and once
+//! it's full the ultimate key lets loose the wizard's super.
+//!
+//! Every wizard has one of two: a meteor storm that rains explosions down on
+//! the enemies, or a time freeze that locks them in place. Which one goes by
+//! seat, so both turn up in a 1v1. The meter is part of the rolled-back
+//! [`Ultimate`] component, charged from the damage the telemetry recorded this
+//! frame, which a resimulated frame records afresh.
+
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs};
+
+use crate::{
+    assets::WizardSprites,
+    components::*,
+    input::ultimate,
+    rng::MatchRng,
+    round::SimFrame,
+    sfx::{Sfx, SfxQueue},
+    spells::{spawn_explosion, EXPLOSION_FRAMES},
+    telemetry::MatchTelemetry,
+    Config,
+};
+
+/// Charge a full meter holds, a bit more than a kill's worth of damage
+pub const MAX_ULTIMATE_CHARGE: u32 = 150;
+/// Charge per point of damage taken, as a percentage of the damage dealt rate
+const TAKEN_CHARGE_PERCENT: u32 = 50;
+
+const METEOR_COUNT: u32 = 6;
+/// Frames between two meteors
+const METEOR_INTERVAL_FRAMES: u32 = 8;
+/// Furthest from their target a meteor lands
+const METEOR_SPREAD: f32 = 1.5;
+const TIME_FREEZE_FRAMES: u32 = 90;
+
+impl UltimateKind {
+    pub fn for_handle(handle: usize) -> Self {
+        if handle.is_multiple_of(2) {
+            UltimateKind::MeteorStorm
+        } else {
+            UltimateKind::TimeFreeze
+        }
+    }
+}
+
+impl Ultimate {
+    pub fn new(kind: UltimateKind) -> Self {
+        Self { kind, charge: 0 }
+    }
+
+    pub fn ready(&self) -> bool {
+        self.charge >= MAX_ULTIMATE_CHARGE
+    }
+
+    fn add_charge(&mut self, amount: u32) {
+        self.charge = (self.charge + amount).min(MAX_ULTIMATE_CHARGE);
+    }
+}
+
+pub fn use_ultimates(
+    mut commands: Commands,
+    inputs: Res<PlayerInputs<Config>>,
+    frame: Res<SimFrame>,
+    mut sfx: ResMut<SfxQueue>,
+    mut casters: Query<(&Player, &Team, &Position, &mut Ultimate)>,
+    mut enemies: Query<(&Team, &mut Stun), With<Player>>,
+) {
+    for (player, team, position, mut meter) in &mut casters {
+        let (input, _) = inputs[player.handle];
+        if !ultimate(input) || !meter.ready() {
+            continue;
+        }
+        meter.charge = 0;
+        sfx.play(frame.0, Sfx::Cast, player.handle, position.0.to_vec2());
+
+        match meter.kind {
+            UltimateKind::MeteorStorm => {
+                spawn_meteor_storm(
+                    &mut commands,
+                    Owner(player.handle),
+                    *team,
+                    METEOR_COUNT * METEOR_INTERVAL_FRAMES,
+                );
+            }
+            UltimateKind::TimeFreeze => {
+                // goes around diminishing returns, it's an ultimate after all
+                for (enemy_team, mut stun) in &mut enemies {
+                    if enemy_team != team {
+                        stun.frames_left = stun.frames_left.max(TIME_FREEZE_FRAMES);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn spawn_meteor_storm(commands: &mut Commands, owner: Owner, team: Team, frames_left: u32) {
+    commands
+        .spawn((MeteorStorm { frames_left }, owner, team))
+        .add_rollback();
+}
+
+/// Drops a meteor on a random enemy every so often, which goes off like a fireball would
+pub fn rain_meteors(
+    mut commands: Commands,
+    sprites: Res<WizardSprites>,
+    mut rng: ResMut<MatchRng>,
+    mut storms: Query<(Entity, &mut MeteorStorm, &Owner, &Team)>,
+    players: Query<(&Player, &Team, &Position)>,
+) {
+    for (entity, mut storm, owner, storm_team) in &mut storms {
+        if storm.frames_left == 0 {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        storm.frames_left -= 1;
+        if storm.frames_left % METEOR_INTERVAL_FRAMES != 0 {
+            continue;
+        }
+
+        // sorted, so which one gets picked doesn't hang on query order
+        let mut targets: Vec<(usize, Vec2)> = players
+            .iter()
+            .filter(|(_, team, _)| *team != storm_team)
+            .map(|(player, _, position)| (player.handle, position.0.to_vec2()))
+            .collect();
+        if targets.is_empty() {
+            continue;
+        }
+        targets.sort_by_key(|(handle, _)| *handle);
+
+        let (_, target) = targets[rng.next_u32() as usize % targets.len()];
+        let angle = rng.next_u32() as f32 / u32::MAX as f32 * std::f32::consts::TAU;
+        let distance = rng.next_u32() as f32 / u32::MAX as f32 * METEOR_SPREAD;
+        let pos = target + Vec2::from_angle(angle) * distance;
+        spawn_explosion(
+            &mut commands,
+            &sprites,
+            *owner,
+            *storm_team,
+            pos.into(),
+            EXPLOSION_FRAMES,
+        );
+    }
+}
+
+/// Fills meters from the damage dealt this frame, runs after everything that deals it
+pub fn charge_ultimates(
+    frame: Res<SimFrame>,
+    telemetry: Res<MatchTelemetry>,
+    mut players: Query<(&Player, &mut Ultimate)>,
+) {
+    let this_frame = telemetry
+        .damage()
+        .iter()
+        .rev()
+        .take_while(|event| event.frame == frame.0);
+    for event in this_frame {
+        for (player, mut meter) in &mut players {
+            if player.handle == event.victim {
+                meter.add_charge(event.amount * TAKEN_CHARGE_PERCENT / 100);
+            } else if event.attacker == Some(player.handle) {
+                meter.add_charge(event.amount);
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Wind: some rounds a wind blows across the arena and pushes every
+//! projectile sideways a little each frame, so long shots have to be aimed
+//! off. Which rounds, and how hard it blows, is rolled from the [`MatchRng`]
+//! when the round starts. The particles blow leaves across the screen so it
+//! can be read at a glance.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{components::*, fixed::SimVec2, rng::MatchRng, rules::Rules};
+
+/// Chance of a round having wind, if the rules allow it at all
+const WIND_CHANCE_PERCENT: u32 = 50;
+/// Drift per frame of the gentlest and the strongest wind
+const MIN_WIND: f32 = 0.01;
+const MAX_WIND: f32 = 0.03;
+
+/// Drift added to every projectile each frame this round, zero when it's calm
+#[derive(Resource, Clone, Copy, Default, Debug, Serialize, Deserialize)]
+pub struct Wind(pub SimVec2);
+
+impl Wind {
+    pub fn roll(rng: &mut MatchRng, rules: &Rules) -> Self {
+        if !rules.wind || !rng.chance(WIND_CHANCE_PERCENT) {
+            return Self::default();
+        }
+        let angle = rng.next_u32() as f32 / u32::MAX as f32 * std::f32::consts::TAU;
+        let t = rng.next_u32() as f32 / u32::MAX as f32;
+        let strength = MIN_WIND + (MAX_WIND - MIN_WIND) * t;
+        // rounded once here, the drift itself is added up in fixed point
+        Self(SimVec2::from(Vec2::from_angle(angle) * strength))
+    }
+}
+
+pub fn drift_projectiles(
+    wind: Res<Wind>,
+    mut projectiles: Query<
+        &mut Position,
+        Or<(
+            With<Bullet>,
+            With<Fireball>,
+            With<Hook>,
+            With<ManaBurn>,
+            With<Flung>,
+        )>,
+    >,
+) {
+    if wind.0 == SimVec2::ZERO {
+        return;
+    }
+    for mut position in &mut projectiles {
+        position.0 += wind.0;
+    }
+}
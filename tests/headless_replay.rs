@@ -0,0 +1,33 @@
+//! Plays a short scripted match with the headless app, the same way
+//! `verify_replay` does, and checks it ends the same way again after going
+//! through a `.wbr` file. Catches the headless app missing anything the
+//! simulation needs, as well as the simulation no longer being deterministic.
+
+use bevy_wizard_battles::replay::{play_headless, Replay};
+
+// the bits from src/input.rs
+const UP: u16 = 1 << 0;
+const LEFT: u16 = 1 << 2;
+const RIGHT: u16 = 1 << 3;
+const FIRE: u16 = 1 << 4;
+const CAST: u16 = 1 << 6;
+
+/// Twenty seconds of both wizards walking at each other, firing and casting
+fn scripted_match() -> Replay {
+    let frames = (0..1200u32).map(|frame| {
+        let fire = if frame % 20 < 10 { FIRE } else { 0 };
+        let cast = if frame % 90 == 0 { CAST } else { 0 };
+        let up = if frame / 60 % 2 == 0 { UP } else { 0 };
+        vec![RIGHT | fire | cast, LEFT | up | fire]
+    });
+    Replay::new(0x5eed, frames)
+}
+
+#[test]
+fn replay_ends_the_way_it_was_recorded() {
+    let mut replay = scripted_match();
+    replay.end_state = play_headless(replay.clone()).expect("replay never finished");
+
+    let saved = Replay::from_bytes(&replay.to_bytes()).expect("replay didn't load");
+    assert_eq!(play_headless(saved), Some(replay.end_state));
+}